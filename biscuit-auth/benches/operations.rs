@@ -0,0 +1,237 @@
+//! token operations benchmarked with criterion, using the fixtures from
+//! `biscuit_auth::testing`
+//!
+//! run with `cargo bench --features testing --bench operations`. Use
+//! `-- --save-baseline <name>` before a change and `-- --baseline <name>`
+//! after it to compare.
+//!
+//! baseline on a x86_64 Linux VM, release build:
+//!
+//! | benchmark                  | time     |
+//! |----------------------------|----------|
+//! | build_authority/1          | 42 µs    |
+//! | build_authority/10         | 52 µs    |
+//! | build_authority/100        | 180 µs   |
+//! | append_block               | 51 µs    |
+//! | parse_and_verify/1         | 88 µs    |
+//! | parse_and_verify/5         | 445 µs   |
+//! | parse_and_verify/20        | 1.35 ms  |
+//! | authorize/10               | 73 µs    |
+//! | authorize/100              | 139 µs   |
+//! | authorize/1000             | 1.80 ms  |
+//! | transitive_closure/10      | 232 µs   |
+//! | transitive_closure/30      | 4.12 ms  |
+//! | transitive_closure/60      | 35.7 ms  |
+//! | world_copy/clone           | 449 µs   |
+//! | world_copy/fork            | 269 µs   |
+extern crate biscuit_auth as biscuit;
+
+use std::time::Duration;
+
+use biscuit::{
+    builder::{fact, int, string, BlockBuilder},
+    builder_ext::BuilderExt,
+    datalog,
+    testing::{sample_token, ArbitraryBiscuit},
+    Authorizer, AuthorizerLimits, Biscuit, KeyPair,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+fn build_authority(c: &mut Criterion) {
+    let root = ArbitraryBiscuit::root();
+    let mut group = c.benchmark_group("build_authority");
+
+    for rights in [1, 10, 100] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(rights),
+            &rights,
+            |b, &rights| {
+                b.iter(|| {
+                    let mut builder = Biscuit::builder();
+                    for i in 0..rights {
+                        builder
+                            .add_fact(fact(
+                                "right",
+                                &[string(&format!("file{}", i)), string("read")],
+                            ))
+                            .unwrap();
+                    }
+                    builder.build(&root).unwrap().to_vec().unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn append_block(c: &mut Criterion) {
+    let token = sample_token(1, 10);
+    let keypair = KeyPair::new();
+
+    c.bench_function("append_block", |b| {
+        b.iter(|| {
+            let mut block = BlockBuilder::new();
+            block.check_resource("file0");
+            block.check_operation("read");
+            token
+                .append_with_keypair(&keypair, block)
+                .unwrap()
+                .to_vec()
+                .unwrap()
+        })
+    });
+}
+
+fn parse_and_verify(c: &mut Criterion) {
+    let root = ArbitraryBiscuit::root().public();
+    let mut group = c.benchmark_group("parse_and_verify");
+
+    for blocks in [1, 5, 20] {
+        let data = sample_token(blocks, 10).to_vec().unwrap();
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(blocks), &data, |b, data| {
+            b.iter(|| Biscuit::from(black_box(data), root).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn authorize(c: &mut Criterion) {
+    let token = sample_token(5, 10);
+    let mut group = c.benchmark_group("authorize");
+
+    for facts in [10, 100, 1000] {
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer.add_fact(r#"resource("file0")"#).unwrap();
+        authorizer.add_fact(r#"operation("read")"#).unwrap();
+        authorizer.set_time();
+        // the default limits are too low for the largest case
+        authorizer.set_limits(
+            AuthorizerLimits::default()
+                .with_max_facts(10_000)
+                .with_max_time(Duration::from_secs(1)),
+        );
+        for i in 0..facts {
+            authorizer
+                .add_fact(fact("ambient", &[string(&format!("key{}", i)), int(i)]))
+                .unwrap();
+        }
+        authorizer
+            .add_code(
+                r#"check if ambient("key0", $v), $v >= 0;
+                allow if right($r, "read"), resource($r);
+                deny if true;"#,
+            )
+            .unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(facts),
+            &authorizer,
+            |b, authorizer| b.iter(|| authorizer.clone().authorize().unwrap()),
+        );
+    }
+    group.finish();
+}
+
+// recursive rules over a chain of `n` nodes: generates n * (n - 1) / 2 facts
+// in n iterations
+fn transitive_closure(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transitive_closure");
+
+    for nodes in [10, 30, 60] {
+        let mut authorizer = Authorizer::new();
+        authorizer.set_limits(
+            AuthorizerLimits::default()
+                .with_max_facts(10_000)
+                .with_max_iterations(1000)
+                .with_max_time(Duration::from_secs(10)),
+        );
+        for i in 0..nodes {
+            authorizer
+                .add_fact(fact("edge", &[int(i), int(i + 1)]))
+                .unwrap();
+        }
+        authorizer
+            .add_code(
+                r#"path($x, $y) <- edge($x, $y);
+                path($x, $z) <- path($x, $y), edge($y, $z);
+                allow if path(0, $n);"#,
+            )
+            .unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(nodes),
+            &authorizer,
+            |b, authorizer| b.iter(|| authorizer.clone().authorize().unwrap()),
+        );
+    }
+    group.finish();
+}
+
+// per request copies of a world holding 5000 facts, each adding one fact
+// and running the rules
+fn world_copy(c: &mut Criterion) {
+    let mut symbols = datalog::SymbolTable::new();
+    let mut world = datalog::World::new();
+    let origin: datalog::Origin = [0].iter().collect();
+    let scope: datalog::TrustedOrigins = [0].iter().collect();
+
+    let resource = symbols.insert("resource");
+    let request = symbols.insert("request");
+    let allowed = symbols.insert("allowed");
+    let id = symbols.insert("id");
+    for i in 0..5000 {
+        world.add_fact(
+            &origin,
+            datalog::Fact::new(resource, &[datalog::Term::Integer(i)]),
+        );
+    }
+    world.add_rule(
+        0,
+        &scope,
+        datalog::Rule {
+            head: datalog::Predicate::new(allowed, &[datalog::Term::Variable(id as u32)]),
+            body: vec![
+                datalog::Predicate::new(request, &[datalog::Term::Variable(id as u32)]),
+                datalog::Predicate::new(resource, &[datalog::Term::Variable(id as u32)]),
+            ],
+            expressions: vec![],
+            scopes: vec![],
+        },
+    );
+    world.freeze();
+    let request_fact = datalog::Fact::new(request, &[datalog::Term::Integer(42)]);
+    let limits = datalog::RunLimits::default()
+        .with_max_facts(10_000)
+        .with_max_time(Duration::from_secs(1));
+
+    let mut group = c.benchmark_group("world_copy");
+    group.bench_function("clone", |b| {
+        b.iter(|| {
+            let mut copy = world.clone();
+            copy.add_fact(&origin, request_fact.clone());
+            copy.run_with_limits(&symbols, limits.clone()).unwrap();
+            black_box(copy)
+        })
+    });
+    group.bench_function("fork", |b| {
+        b.iter(|| {
+            let mut copy = world.fork();
+            copy.add_fact(&origin, request_fact.clone());
+            copy.run_with_limits(&symbols, limits.clone()).unwrap();
+            black_box(copy)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    build_authority,
+    append_block,
+    parse_and_verify,
+    authorize,
+    transitive_closure,
+    world_copy
+);
+criterion_main!(benches);
@@ -5,9 +5,11 @@ extern crate biscuit_auth as biscuit;
 
 use bencher::Bencher;
 use biscuit::{
-    builder::*, builder_ext::BuilderExt, datalog::SymbolTable, Biscuit, KeyPair, UnverifiedBiscuit,
+    builder::*, builder_ext::BuilderExt, datalog::SymbolTable, Authorizer, AuthorizerLimits,
+    Biscuit, KeyPair, UnverifiedBiscuit,
 };
 use rand::rngs::OsRng;
+use std::time::Duration;
 
 fn create_block_1(b: &mut Bencher) {
     let mut rng = OsRng;
@@ -548,6 +550,181 @@ fn checks_block_verify_only2(b: &mut Bencher) {
     });
 }
 
+fn verify_block_5_many_facts(b: &mut Bencher) {
+    let mut rng: OsRng = OsRng;
+    let root = KeyPair::new_with_rng(&mut rng);
+
+    let data = {
+        let mut builder = Biscuit::builder();
+        for i in 0..50 {
+            builder
+                .add_fact(fact(
+                    "right",
+                    &[string(&format!("file{}", i)), string("read")],
+                ))
+                .unwrap();
+        }
+
+        let mut token = builder
+            .build_with_rng(&root, SymbolTable::default(), &mut rng)
+            .unwrap();
+
+        for i in 0..4 {
+            let mut block_builder = BlockBuilder::new();
+            for j in 0..10 {
+                block_builder
+                    .add_fact(fact(
+                        "attr",
+                        &[string(&format!("file{}", j)), string(&format!("attr{}", i))],
+                    ))
+                    .unwrap();
+            }
+            block_builder.check_operation("read");
+
+            let keypair = KeyPair::new_with_rng(&mut rng);
+            token = token.append_with_keypair(&keypair, block_builder).unwrap();
+        }
+        token.to_vec().unwrap()
+    };
+
+    let token = Biscuit::from(&data, root.public()).unwrap();
+    let mut verifier = token.authorizer().unwrap();
+    verifier.add_fact("resource(\"file1\")").unwrap();
+    verifier.add_fact("operation(\"read\")").unwrap();
+    verifier
+        .add_check("check if resource($f), operation($op), right($f, $op)")
+        .unwrap();
+    verifier.allow().unwrap();
+    verifier.authorize().unwrap();
+
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        let token = Biscuit::from(&data, root.public()).unwrap();
+        let mut verifier = token.authorizer().unwrap();
+        verifier.add_fact("resource(\"file1\")").unwrap();
+        verifier.add_fact("operation(\"read\")").unwrap();
+        verifier
+            .add_check("check if resource($f), operation($op), right($f, $op)")
+            .unwrap();
+        verifier.allow().unwrap();
+        verifier.authorize().unwrap();
+    });
+}
+
+// the authority block's first check fails, and the following blocks have
+// expensive checks on many facts
+fn failing_checks_authorizer(fail_fast: bool) -> Authorizer {
+    let mut rng: OsRng = OsRng;
+    let root = KeyPair::new_with_rng(&mut rng);
+
+    let mut builder = Biscuit::builder();
+    builder.check_operation("write");
+    for i in 0..50 {
+        builder
+            .add_fact(fact(
+                "right",
+                &[string(&format!("file{}", i)), string("read")],
+            ))
+            .unwrap();
+    }
+    let mut token = builder
+        .build_with_rng(&root, SymbolTable::default(), &mut rng)
+        .unwrap();
+
+    for _ in 0..4 {
+        let mut block_builder = BlockBuilder::new();
+        block_builder
+            .add_check(r#"check all right($f, $op), $f.matches("^file[0-9]+$"), $op != "write""#)
+            .unwrap();
+        let keypair = KeyPair::new_with_rng(&mut rng);
+        token = token.append_with_keypair(&keypair, block_builder).unwrap();
+    }
+
+    let mut authorizer = token.authorizer().unwrap();
+    authorizer.add_fact("resource(\"file1\")").unwrap();
+    authorizer.add_fact("operation(\"read\")").unwrap();
+    authorizer.set_fail_fast(fail_fast);
+    authorizer.allow().unwrap();
+    authorizer
+}
+
+fn checks_collect_all(b: &mut Bencher) {
+    let authorizer = failing_checks_authorizer(false);
+    authorizer.clone().authorize().unwrap_err();
+
+    b.iter(|| {
+        let mut authorizer = authorizer.clone();
+        authorizer.authorize().unwrap_err();
+    });
+}
+
+fn checks_fail_fast(b: &mut Bencher) {
+    let authorizer = failing_checks_authorizer(true);
+    authorizer.clone().authorize().unwrap_err();
+
+    b.iter(|| {
+        let mut authorizer = authorizer.clone();
+        authorizer.authorize().unwrap_err();
+    });
+}
+
+// an authorizer configuration with 100 rules and facts that do not depend on
+// the request
+fn config_100_rules() -> (Biscuit, String) {
+    let mut rng: OsRng = OsRng;
+    let root = KeyPair::new_with_rng(&mut rng);
+
+    let mut builder = Biscuit::builder();
+    builder.add_fact(fact("user", &[string("alice")])).unwrap();
+    let token = builder
+        .build_with_rng(&root, SymbolTable::default(), &mut rng)
+        .unwrap();
+
+    let mut code = String::new();
+    for i in 0..100 {
+        code.push_str(&format!(
+            "role(\"alice\", \"role{i}\");\nright{i}($f) <- user($u), role($u, \"role{i}\"), resource($f), operation(\"read{i}\");\n",
+            i = i
+        ));
+    }
+    code.push_str("allow if user($u), resource($f);\n");
+    (token, code)
+}
+
+fn limits() -> AuthorizerLimits {
+    AuthorizerLimits::default().with_max_time(Duration::from_secs(1))
+}
+
+fn config_100_rules_uncached(b: &mut Bencher) {
+    let (token, code) = config_100_rules();
+
+    b.iter(|| {
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer.add_code(&code).unwrap();
+        authorizer.set_limits(limits());
+        authorizer.add_fact("resource(\"file1\")").unwrap();
+        authorizer.add_fact("operation(\"read\")").unwrap();
+        authorizer.authorize().unwrap();
+    });
+}
+
+fn config_100_rules_cached(b: &mut Bencher) {
+    let (token, code) = config_100_rules();
+    let mut builder = Authorizer::builder();
+    builder.add_code(&code).unwrap();
+    builder.set_limits(limits());
+    let config = builder.build().unwrap();
+
+    b.iter(|| {
+        config
+            .authorize(&token, |authorizer| {
+                authorizer.add_fact("resource(\"file1\")").unwrap();
+                authorizer.add_fact("operation(\"read\")").unwrap();
+            })
+            .unwrap();
+    });
+}
+
 benchmark_group!(
     benchmarks,
     create_block_1,
@@ -557,10 +734,15 @@ benchmark_group!(
     unverified_append_block_5,
     verify_block_2,
     verify_block_5,
+    verify_block_5_many_facts,
     check_signature_2,
     check_signature_5,
     checks_block_2,
     checks_block_create_verifier2,
-    checks_block_verify_only2
+    checks_block_verify_only2,
+    checks_collect_all,
+    checks_fail_fast,
+    config_100_rules_uncached,
+    config_100_rules_cached
 );
 benchmark_main!(benchmarks);
@@ -19,6 +19,27 @@ use std::{
     time::*,
 };
 
+/// the creation time of blocks is not part of the specification, and would
+/// change the samples each time they are generated, so it is only written in
+/// the sample dedicated to it
+trait OmitCreatedAt {
+    fn omit_created_at(self) -> Self;
+}
+
+impl OmitCreatedAt for BiscuitBuilder {
+    fn omit_created_at(mut self) -> Self {
+        self.set_created_at(None);
+        self
+    }
+}
+
+impl OmitCreatedAt for BlockBuilder {
+    fn omit_created_at(mut self) -> Self {
+        self.set_created_at(None);
+        self
+    }
+}
+
 fn main() {
     let mut args = std::env::args();
     args.next();
@@ -112,6 +133,10 @@ fn main() {
 
     results.push(integer_wraparound(&mut rng, &target, &root, test));
 
+    results.push(oversized_symbols(&mut rng, &target, &root, test));
+
+    results.push(block_created_at(&mut rng, &target, &root, test));
+
     if json {
         let s = serde_json::to_string_pretty(&TestCases {
             root_private_key: hex::encode(root.private().to_bytes()),
@@ -324,6 +349,7 @@ fn basic_token<T: Rng + CryptoRng>(
         right("file1", "write");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -335,7 +361,8 @@ fn basic_token<T: Rng + CryptoRng>(
                 r#"
             check if resource($0), operation("read"), right($0, "read")
             "#
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
 
@@ -393,6 +420,7 @@ fn different_root_key<T: Rng + CryptoRng>(
         right("file1", "read");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root2, SymbolTable::default(), rng)
     .unwrap();
 
@@ -404,7 +432,8 @@ fn different_root_key<T: Rng + CryptoRng>(
                 r#"
                 check if resource($0), operation("read"), right($0, "read")
             "#
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
 
@@ -457,6 +486,7 @@ fn invalid_signature_format<T: Rng + CryptoRng>(
         right("file1", "write");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -464,7 +494,8 @@ fn invalid_signature_format<T: Rng + CryptoRng>(
     let biscuit2 = biscuit1
         .append_with_keypair(
             &keypair2,
-            block!(r#"check if resource($0), operation("read"), right($0, "read")"#),
+            block!(r#"check if resource($0), operation("read"), right($0, "read")"#)
+                .omit_created_at(),
         )
         .unwrap();
     token = print_blocks(&biscuit2);
@@ -513,6 +544,7 @@ fn random_block<T: Rng + CryptoRng>(
         right("file1", "write");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -520,7 +552,8 @@ fn random_block<T: Rng + CryptoRng>(
     let biscuit2 = biscuit1
         .append_with_keypair(
             &keypair2,
-            block!(r#"check if resource($0), operation("read"), right($0, "read")"#),
+            block!(r#"check if resource($0), operation("read"), right($0, "read")"#)
+                .omit_created_at(),
         )
         .unwrap();
 
@@ -572,6 +605,7 @@ fn invalid_signature<T: Rng + CryptoRng>(
         right("file1", "write");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -579,7 +613,8 @@ fn invalid_signature<T: Rng + CryptoRng>(
     let biscuit2 = biscuit1
         .append_with_keypair(
             &keypair2,
-            block!(r#"check if resource($0), operation("read"), right($0, "read")"#),
+            block!(r#"check if resource($0), operation("read"), right($0, "read")"#)
+                .omit_created_at(),
         )
         .unwrap();
     token = print_blocks(&biscuit2);
@@ -630,6 +665,7 @@ fn reordered_blocks<T: Rng + CryptoRng>(
         right("file1", "write");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -637,13 +673,17 @@ fn reordered_blocks<T: Rng + CryptoRng>(
     let biscuit2 = biscuit1
         .append_with_keypair(
             &keypair2,
-            block!(r#"check if resource($0), operation("read"), right($0, "read")"#),
+            block!(r#"check if resource($0), operation("read"), right($0, "read")"#)
+                .omit_created_at(),
         )
         .unwrap();
 
     let keypair3 = KeyPair::new_with_rng(rng);
     let biscuit3 = biscuit2
-        .append_with_keypair(&keypair3, block!(r#"check if resource("file1")"#))
+        .append_with_keypair(
+            &keypair3,
+            block!(r#"check if resource("file1")"#).omit_created_at(),
+        )
         .unwrap();
     token = print_blocks(&biscuit3);
 
@@ -692,6 +732,7 @@ fn scoped_rules<T: Rng + CryptoRng>(
         owner("alice", "file1");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -704,11 +745,12 @@ fn scoped_rules<T: Rng + CryptoRng>(
             right($0, "read") <- resource($0), user_id($1), owner($1, $0);
             check if resource($0), operation("read"), right($0, "read");
         "#
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
 
-    let mut block3 = BlockBuilder::new();
+    let mut block3 = BlockBuilder::new().omit_created_at();
 
     block3.add_fact(r#"owner("alice", "file2")"#).unwrap();
 
@@ -765,6 +807,7 @@ fn scoped_checks<T: Rng + CryptoRng>(
         right("file1", "read");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -772,13 +815,17 @@ fn scoped_checks<T: Rng + CryptoRng>(
     let biscuit2 = biscuit1
         .append_with_keypair(
             &keypair2,
-            block!(r#"check if resource($0), operation("read"), right($0, "read")"#),
+            block!(r#"check if resource($0), operation("read"), right($0, "read")"#)
+                .omit_created_at(),
         )
         .unwrap();
 
     let keypair3 = KeyPair::new_with_rng(rng);
     let biscuit3 = biscuit2
-        .append_with_keypair(&keypair3, block!(r#"right("file2", "read")"#))
+        .append_with_keypair(
+            &keypair3,
+            block!(r#"right("file2", "read")"#).omit_created_at(),
+        )
         .unwrap();
     token = print_blocks(&biscuit3);
 
@@ -826,12 +873,12 @@ fn expired_token<T: Rng + CryptoRng>(
     let filename = "test009_expired_token.bc".to_string();
     let token;
 
-    let builder = Biscuit::builder();
+    let builder = Biscuit::builder().omit_created_at();
     let biscuit1 = builder
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
 
-    let mut block2 = block!(r#"check if resource("file1");"#);
+    let mut block2 = block!(r#"check if resource("file1");"#).omit_created_at();
 
     // January 1 2019
     block2.check_expiration_date(
@@ -894,12 +941,16 @@ fn authorizer_scope<T: Rng + CryptoRng>(
         right("file1", "read");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
     let keypair2 = KeyPair::new_with_rng(rng);
     let biscuit2 = biscuit1
-        .append_with_keypair(&keypair2, block!(r#"right("file2", "read")"#))
+        .append_with_keypair(
+            &keypair2,
+            block!(r#"right("file2", "read")"#).omit_created_at(),
+        )
         .unwrap();
     token = print_blocks(&biscuit2);
 
@@ -952,6 +1003,7 @@ fn authorizer_authority_checks<T: Rng + CryptoRng>(
         right("file1", "read");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
     token = print_blocks(&biscuit1);
@@ -1001,6 +1053,7 @@ fn authority_checks<T: Rng + CryptoRng>(
     let token;
 
     let biscuit1 = biscuit!(r#"check if resource("file1")"#)
+        .omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
     token = print_blocks(&biscuit1);
@@ -1067,6 +1120,7 @@ fn block_rules<T: Rng + CryptoRng>(
         right("file2", "read");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -1079,7 +1133,7 @@ fn block_rules<T: Rng + CryptoRng>(
         valid_date($1) <- time($0), resource($1), $0 <= 1999-12-31T12:59:59Z, !["file1"].contains($1);
 
         check if valid_date($0), resource($0);
-    "#)).unwrap();
+    "#).omit_created_at()).unwrap();
 
     token = print_blocks(&biscuit2);
 
@@ -1141,6 +1195,7 @@ fn regex_constraint<T: Rng + CryptoRng>(
     let token;
 
     let biscuit1 = biscuit!(r#"check if resource($0), $0.matches("file[0-9]+.txt")"#)
+        .omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
     token = print_blocks(&biscuit1);
@@ -1185,6 +1240,7 @@ fn multi_queries_checks<T: Rng + CryptoRng>(
     let token;
 
     let biscuit1 = biscuit!(r#"must_be_present("hello")"#)
+        .omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
     token = print_blocks(&biscuit1);
@@ -1229,12 +1285,13 @@ fn check_head_name<T: Rng + CryptoRng>(
     let token;
 
     let biscuit1 = biscuit!(r#"check if resource("hello")"#)
+        .omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
 
     let keypair2 = KeyPair::new_with_rng(rng);
     let biscuit2 = biscuit1
-        .append_with_keypair(&keypair2, block!(r#"query("test")"#))
+        .append_with_keypair(&keypair2, block!(r#"query("test")"#).omit_created_at())
         .unwrap();
     token = print_blocks(&biscuit2);
 
@@ -1354,7 +1411,7 @@ fn expressions<T: Rng + CryptoRng>(
         check if [1, 2].intersection([2, 3]) == [2];
         // set union
         check if [1, 2].union([2, 3]) == [1, 2, 3];
-    "#)
+    "#).omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
     token = print_blocks(&biscuit);
@@ -1395,10 +1452,11 @@ fn unbound_variables_in_rule<T: Rng + CryptoRng>(
     let token;
 
     let biscuit1 = biscuit!(r#"check if operation("read")"#)
+        .omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
 
-    let mut block2 = BlockBuilder::new();
+    let mut block2 = BlockBuilder::new().omit_created_at();
 
     // this one does not go through the parser because it checks for unused variables
     block2
@@ -1449,12 +1507,16 @@ fn generating_ambient_from_variables<T: Rng + CryptoRng>(
     let token;
 
     let biscuit1 = biscuit!(r#"check if operation("read")"#)
+        .omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
 
     let keypair2 = KeyPair::new_with_rng(rng);
     let biscuit2 = biscuit1
-        .append_with_keypair(&keypair2, block!(r#"operation("read") <- operation($any)"#))
+        .append_with_keypair(
+            &keypair2,
+            block!(r#"operation("read") <- operation($any)"#).omit_created_at(),
+        )
         .unwrap();
     token = print_blocks(&biscuit2);
 
@@ -1503,6 +1565,7 @@ fn sealed_token<T: Rng + CryptoRng>(
         right("file1", "write");
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -1510,7 +1573,8 @@ fn sealed_token<T: Rng + CryptoRng>(
     let biscuit2 = biscuit1
         .append_with_keypair(
             &keypair2,
-            block!(r#"check if resource($0), operation("read"), right($0, "read")"#),
+            block!(r#"check if resource($0), operation("read"), right($0, "read")"#)
+                .omit_created_at(),
         )
         .unwrap();
 
@@ -1559,6 +1623,7 @@ fn parsing<T: Rng + CryptoRng>(
     let token;
 
     let biscuit1 = biscuit!("ns::fact_123(\"hello é\t😁\")")
+        .omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
     token = print_blocks(&biscuit1);
@@ -1609,6 +1674,7 @@ fn default_symbols<T: Rng + CryptoRng>(
     ip_address(17);client(18);client_ip(19);domain(20);path(21);
     version(22);cluster(23);node(24);hostname(25);nonce(26);query(27)"#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
     token = print_blocks(&biscuit1);
@@ -1660,12 +1726,13 @@ fn execution_scope<T: Rng + CryptoRng>(
     let token;
 
     let biscuit1 = biscuit!("authority_fact(1)")
+        .omit_created_at()
         .build_with_rng(&root, SymbolTable::default(), rng)
         .unwrap();
 
     let keypair2 = KeyPair::new_with_rng(rng);
     let biscuit2 = biscuit1
-        .append_with_keypair(&keypair2, block!("block1_fact(1)"))
+        .append_with_keypair(&keypair2, block!("block1_fact(1)").omit_created_at())
         .unwrap();
 
     let keypair3 = KeyPair::new_with_rng(rng);
@@ -1677,7 +1744,8 @@ fn execution_scope<T: Rng + CryptoRng>(
                 check if authority_fact($var);
                 check if block1_fact($var);
             "#
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
     token = print_blocks(&biscuit3);
@@ -1726,6 +1794,7 @@ fn third_party<T: Rng + CryptoRng>(
     "#,
         external_pub = external.public()
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -1739,7 +1808,8 @@ fn third_party<T: Rng + CryptoRng>(
                 group("admin");
                 check if right("read");
             "#
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
     let keypair2 = KeyPair::new_with_rng(rng);
@@ -1791,6 +1861,7 @@ fn check_all<T: Rng + CryptoRng>(
         check all operation($op), allowed_operations($allowed), $allowed.contains($op);
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -1864,6 +1935,7 @@ fn public_keys_interning<T: Rng + CryptoRng>(
     "#,
         k1 = external1.public()
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -1881,7 +1953,8 @@ fn public_keys_interning<T: Rng + CryptoRng>(
         "#,
                 k1 = external1.public(),
                 k2 = external2.public(),
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
 
@@ -1901,7 +1974,8 @@ fn public_keys_interning<T: Rng + CryptoRng>(
         "#,
                 k1 = external1.public(),
                 k2 = external2.public(),
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
 
@@ -1921,7 +1995,8 @@ fn public_keys_interning<T: Rng + CryptoRng>(
         "#,
                 k1 = external1.public(),
                 k2 = external2.public(),
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
 
@@ -1940,7 +2015,8 @@ fn public_keys_interning<T: Rng + CryptoRng>(
             "#,
                 k2 = external2.public(),
                 k3 = external3.public(),
-            ),
+            )
+            .omit_created_at(),
         )
         .unwrap();
 
@@ -2006,6 +2082,7 @@ fn integer_wraparound<T: Rng + CryptoRng>(
           check if true || -9223372036854775808 - 1 != 0;
     "#
     )
+    .omit_created_at()
     .build_with_rng(&root, SymbolTable::default(), rng)
     .unwrap();
 
@@ -2037,6 +2114,122 @@ fn integer_wraparound<T: Rng + CryptoRng>(
     }
 }
 
+fn oversized_symbols<T: Rng + CryptoRng>(
+    rng: &mut T,
+    target: &str,
+    root: &KeyPair,
+    test: bool,
+) -> TestResult {
+    let title = "oversized symbols".to_string();
+    let filename = "test028_oversized_symbols.bc".to_string();
+    let token;
+
+    // a large symbol table and a string a lot longer than the usual symbols,
+    // to check the varint encoding of lengths and symbol indexes
+    let mut builder = Biscuit::builder().omit_created_at();
+    for i in 0..256 {
+        builder.add_fact(fact("symbol", &[string(&format!("symbol{}", i))]));
+    }
+    builder.add_fact(fact("long", &[string(&"a".repeat(4096))]));
+
+    let biscuit1 = builder
+        .build_with_rng(&root, SymbolTable::default(), rng)
+        .unwrap();
+
+    let block2 =
+        block!(r#"check if symbol("symbol255"), long($s), $s.length() == 4096;"#).omit_created_at();
+
+    let keypair2 = KeyPair::new_with_rng(rng);
+    let biscuit2 = biscuit1.append_with_keypair(&keypair2, block2).unwrap();
+
+    token = print_blocks(&biscuit2);
+
+    let data = if test {
+        let v = load_testcase(target, "test028_oversized_symbols");
+        let expected = Biscuit::from(&v[..], root.public()).unwrap();
+        print_diff(&biscuit2.print(), &expected.print());
+        v
+    } else {
+        let data = biscuit2.to_vec().unwrap();
+        write_testcase(target, "test028_oversized_symbols", &data[..]);
+
+        data
+    };
+
+    let mut validations = BTreeMap::new();
+    validations.insert(
+        "".to_string(),
+        validate_token(root, &data[..], r#"allow if symbol("symbol0");"#),
+    );
+
+    TestResult {
+        title,
+        filename,
+        token,
+        validations,
+    }
+}
+
+fn block_created_at<T: Rng + CryptoRng>(
+    rng: &mut T,
+    target: &str,
+    root: &KeyPair,
+    test: bool,
+) -> TestResult {
+    let title = "block creation time".to_string();
+    let filename = "test029_block_created_at.bc".to_string();
+    let token;
+
+    // the creation time is optional: it is written in the first two blocks,
+    // at fixed times so the sample does not change, and omitted in the last one
+    let created_at = UNIX_EPOCH + Duration::from_secs(1_608_542_592);
+    let mut builder = biscuit!(r#"right("file1", "read");"#);
+    builder.set_created_at(Some(created_at));
+    let biscuit1 = builder
+        .build_with_rng(&root, SymbolTable::default(), rng)
+        .unwrap();
+
+    let mut block2 = block!(r#"check if resource("file1");"#);
+    block2.set_created_at(Some(created_at + Duration::from_secs(3600)));
+    let keypair2 = KeyPair::new_with_rng(rng);
+    let biscuit2 = biscuit1.append_with_keypair(&keypair2, block2).unwrap();
+
+    let block3 = block!(r#"check if operation("read");"#).omit_created_at();
+    let keypair3 = KeyPair::new_with_rng(rng);
+    let biscuit3 = biscuit2.append_with_keypair(&keypair3, block3).unwrap();
+
+    token = print_blocks(&biscuit3);
+
+    let data = if test {
+        let v = load_testcase(target, "test029_block_created_at");
+        let expected = Biscuit::from(&v[..], root.public()).unwrap();
+        print_diff(&biscuit3.print(), &expected.print());
+        v
+    } else {
+        let data = biscuit3.to_vec().unwrap();
+        write_testcase(target, "test029_block_created_at", &data[..]);
+
+        data
+    };
+
+    let mut validations = BTreeMap::new();
+    validations.insert(
+        "".to_string(),
+        validate_token(
+            root,
+            &data[..],
+            r#"resource("file1"); operation("read"); allow if true;"#,
+        ),
+    );
+
+    TestResult {
+        title,
+        filename,
+        token,
+        validations,
+    }
+}
+
 fn print_blocks(token: &Biscuit) -> Vec<BlockContent> {
     let mut v = Vec::new();
 
@@ -0,0 +1,47 @@
+#![no_main]
+use biscuit_auth::{
+    builder_ext::AuthorizerExt, format::SerializedBiscuit, Authorizer, Biscuit, KeyPair,
+};
+use libfuzzer_sys::fuzz_target;
+use rand::{prelude::StdRng, SeedableRng};
+
+// appends the input as a correctly signed block, then evaluates it: this
+// is what an attacker holding a valid token can do
+fuzz_target!(|data: &[u8]| {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+    let root = KeyPair::new_with_rng(&mut rng);
+    let next = KeyPair::new_with_rng(&mut rng);
+
+    let mut builder = Biscuit::builder();
+    builder.add_code(r#"right("file1", "read");"#).unwrap();
+    let token = builder
+        .build_with_rng(&root, biscuit_auth::datalog::SymbolTable::default(), &mut rng)
+        .unwrap();
+
+    let serialized = SerializedBiscuit::from_slice(&token.to_vec().unwrap(), root.public())
+        .unwrap()
+        .append_serialized(&next, data.to_vec(), None)
+        .unwrap()
+        .to_vec()
+        .unwrap();
+
+    let token = match Biscuit::from(&serialized, root.public()) {
+        Ok(token) => token,
+        Err(_) => return,
+    };
+
+    let _ = token.print();
+
+    let mut authorizer = Authorizer::new();
+    authorizer
+        .add_code(r#"resource("file1"); operation("read"); time(2020-12-21T09:23:12Z);"#)
+        .unwrap();
+    authorizer.add_allow_all();
+    if authorizer.add_token(&token).is_ok() {
+        let _ = authorizer.authorize();
+        let _ = authorizer.print_world();
+        if let Ok(snapshot) = authorizer.to_raw_snapshot() {
+            let _ = Authorizer::from_raw_snapshot(&snapshot);
+        }
+    }
+});
@@ -0,0 +1,31 @@
+#![no_main]
+use biscuit_auth::{Authorizer, Biscuit, KeyPair, UnverifiedBiscuit};
+use libfuzzer_sys::fuzz_target;
+use rand::{prelude::StdRng, SeedableRng};
+
+// deserializes arbitrary tokens, authorizer policies and snapshots. Tokens
+// will almost never pass signature verification, see `block_checks` for
+// the Datalog evaluation
+fuzz_target!(|data: &[u8]| {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+    let root = KeyPair::new_with_rng(&mut rng);
+
+    if let Ok(token) = Biscuit::from(data, root.public()) {
+        let _ = token.print();
+    }
+
+    if let Ok(token) = UnverifiedBiscuit::from(data) {
+        for i in 0..token.block_count() {
+            let _ = token.print_block_source(i);
+        }
+    }
+
+    if let Ok(authorizer) = Authorizer::from(data) {
+        let _ = authorizer.dump_code();
+    }
+
+    if let Ok(mut authorizer) = Authorizer::from_raw_snapshot(data) {
+        let _ = authorizer.print_world();
+        let _ = authorizer.authorize();
+    }
+});
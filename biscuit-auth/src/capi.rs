@@ -97,6 +97,25 @@ pub enum ErrorKind {
     FormatSignatureInvalidSignatureGeneration,
     AlreadySealed,
     Execution,
+    UnknownPredicates,
+    FormatSymbolTableMismatch,
+    LogicRevoked,
+    LogicUnboundExpressionVariable,
+    FormatInvalidName,
+    InvalidCidr,
+    SchemaViolations,
+    FormatEmptyRuleBody,
+    LogicMissingNonce,
+    LogicReplayedNonce,
+    OperationCycle,
+    LogicMissingTimestamp,
+    LogicTimestampOutsideWindow,
+    PolicySyntax,
+    LogicTokenTooOld,
+    TooManyResolvedFacts,
+    InvalidConfiguration,
+    EvaluationSemantics,
+    NotSealed,
 }
 
 #[no_mangle]
@@ -158,8 +177,14 @@ pub extern "C" fn error_kind() -> ErrorKind {
                         ErrorKind::FormatUnknownExternalKey
                     }
                     Token::Format(Format::UnknownSymbol(_)) => ErrorKind::FormatUnknownSymbol,
+                    Token::Format(Format::SymbolTableMismatch) => {
+                        ErrorKind::FormatSymbolTableMismatch
+                    }
+                    Token::Format(Format::InvalidName(_)) => ErrorKind::FormatInvalidName,
+                    Token::Format(Format::EmptyRuleBody(_)) => ErrorKind::FormatEmptyRuleBody,
                     Token::AppendOnSealed => ErrorKind::AppendOnSealed,
                     Token::AlreadySealed => ErrorKind::AlreadySealed,
+                    Token::NotSealed => ErrorKind::NotSealed,
                     Token::Language(_) => ErrorKind::LanguageError,
                     Token::FailedLogic(Logic::InvalidBlockRule(_, _)) => {
                         ErrorKind::LogicInvalidBlockRule
@@ -171,12 +196,33 @@ pub extern "C" fn error_kind() -> ErrorKind {
                     Token::FailedLogic(Logic::NoMatchingPolicy { .. }) => {
                         ErrorKind::LogicNoMatchingPolicy
                     }
+                    Token::FailedLogic(Logic::Revoked(_)) => ErrorKind::LogicRevoked,
+                    Token::FailedLogic(Logic::UnboundExpressionVariable { .. }) => {
+                        ErrorKind::LogicUnboundExpressionVariable
+                    }
+                    Token::FailedLogic(Logic::MissingNonce) => ErrorKind::LogicMissingNonce,
+                    Token::FailedLogic(Logic::ReplayedNonce(_)) => ErrorKind::LogicReplayedNonce,
+                    Token::FailedLogic(Logic::MissingTimestamp) => ErrorKind::LogicMissingTimestamp,
+                    Token::FailedLogic(Logic::TimestampOutsideWindow(_)) => {
+                        ErrorKind::LogicTimestampOutsideWindow
+                    }
+                    Token::FailedLogic(Logic::TokenTooOld(_)) => ErrorKind::LogicTokenTooOld,
                     Token::RunLimit(RunLimit::TooManyFacts) => ErrorKind::TooManyFacts,
                     Token::RunLimit(RunLimit::TooManyIterations) => ErrorKind::TooManyIterations,
                     Token::RunLimit(RunLimit::Timeout) => ErrorKind::Timeout,
+                    Token::RunLimit(RunLimit::TooManyResolvedFacts) => {
+                        ErrorKind::TooManyResolvedFacts
+                    }
                     Token::ConversionError(_) => ErrorKind::ConversionError,
                     Token::Base64(_) => ErrorKind::FormatDeserializationError,
                     Token::Execution(_) => ErrorKind::Execution,
+                    Token::UnknownPredicates(_) => ErrorKind::UnknownPredicates,
+                    Token::InvalidCidr(_) => ErrorKind::InvalidCidr,
+                    Token::SchemaViolations(_) => ErrorKind::SchemaViolations,
+                    Token::OperationCycle(_) => ErrorKind::OperationCycle,
+                    Token::PolicySyntax(_) => ErrorKind::PolicySyntax,
+                    Token::InvalidConfiguration(_) => ErrorKind::InvalidConfiguration,
+                    Token::EvaluationSemantics { .. } => ErrorKind::EvaluationSemantics,
                 }
             }
         },
@@ -236,6 +282,25 @@ pub extern "C" fn error_check_block_id(check_index: u64) -> u64 {
     })
 }
 
+#[no_mangle]
+pub extern "C" fn error_check_token_id(check_index: u64) -> u64 {
+    use crate::error::*;
+    LAST_ERROR.with(|prev| match *prev.borrow() {
+        Some(Error::Biscuit(Token::FailedLogic(Logic::Unauthorized { ref checks, .. })))
+        | Some(Error::Biscuit(Token::FailedLogic(Logic::NoMatchingPolicy { ref checks }))) => {
+            if check_index >= checks.len() as u64 {
+                u64::MAX
+            } else {
+                match checks[check_index as usize] {
+                    FailedCheck::Block(FailedBlockCheck { token_id, .. }) => token_id as u64,
+                    _ => u64::MAX,
+                }
+            }
+        }
+        _ => u64::MAX,
+    })
+}
+
 /// deallocation is handled by Biscuit
 /// the string is overwritten on each call
 #[no_mangle]
@@ -269,6 +334,70 @@ pub extern "C" fn error_check_rule(check_index: u64) -> *const c_char {
     })
 }
 
+/// returns NULL if the check has no name. Only the authorizer's checks
+/// can have one
+///
+/// deallocation is handled by Biscuit
+/// the string is overwritten on each call
+#[no_mangle]
+pub extern "C" fn error_check_name(check_index: u64) -> *const c_char {
+    use crate::error::*;
+    thread_local! {
+        static CHECK_NAME: RefCell<Option<CString>> = RefCell::new(None);
+    }
+
+    LAST_ERROR.with(|prev| match *prev.borrow() {
+        Some(Error::Biscuit(Token::FailedLogic(Logic::Unauthorized { ref checks, .. })))
+        | Some(Error::Biscuit(Token::FailedLogic(Logic::NoMatchingPolicy { ref checks }))) => {
+            let name = match checks.get(check_index as usize) {
+                Some(FailedCheck::Authorizer(FailedAuthorizerCheck { name, .. })) => name,
+                _ => return std::ptr::null(),
+            };
+            let name = name
+                .as_ref()
+                .and_then(|name| CString::new(name.clone()).ok());
+            CHECK_NAME.with(|ret| {
+                *ret.borrow_mut() = name;
+                ret.borrow()
+                    .as_ref()
+                    .map(|x| x.as_ptr())
+                    .unwrap_or(std::ptr::null())
+            })
+        }
+        _ => std::ptr::null(),
+    })
+}
+
+/// returns NULL if the authorizer had no denial reasons
+///
+/// deallocation is handled by Biscuit
+/// the string is overwritten on each call
+#[no_mangle]
+pub extern "C" fn error_check_reason(check_index: u64) -> *const c_char {
+    use crate::error::*;
+    thread_local! {
+        static CHECK_REASON: RefCell<Option<CString>> = RefCell::new(None);
+    }
+
+    LAST_ERROR.with(|prev| match *prev.borrow() {
+        Some(Error::Biscuit(Token::FailedLogic(Logic::Unauthorized { ref checks, .. })))
+        | Some(Error::Biscuit(Token::FailedLogic(Logic::NoMatchingPolicy { ref checks }))) => {
+            let reason = checks
+                .get(check_index as usize)
+                .and_then(FailedCheck::reason)
+                .and_then(|reason| CString::new(reason).ok());
+            CHECK_REASON.with(|ret| {
+                *ret.borrow_mut() = reason;
+                ret.borrow()
+                    .as_ref()
+                    .map(|x| x.as_ptr())
+                    .unwrap_or(std::ptr::null())
+            })
+        }
+        _ => std::ptr::null(),
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn error_check_is_authorizer(check_index: u64) -> bool {
     use crate::error::*;
@@ -519,6 +648,33 @@ pub unsafe extern "C" fn biscuit_builder_add_check(
         .is_ok()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn biscuit_builder_add_code(
+    builder: Option<&mut BiscuitBuilder>,
+    code: *const c_char,
+) -> bool {
+    if builder.is_none() {
+        update_last_error(Error::InvalidArgument);
+        return false;
+    }
+    let builder = builder.unwrap();
+
+    let code = CStr::from_ptr(code);
+    let s = code.to_str();
+    if s.is_err() {
+        update_last_error(Error::InvalidArgument);
+        return false;
+    }
+
+    builder
+        .0
+        .add_code(s.unwrap())
+        .map_err(|e| {
+            update_last_error(Error::Biscuit(e));
+        })
+        .is_ok()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn biscuit_builder_build(
     builder: Option<&BiscuitBuilder>,
@@ -657,7 +813,7 @@ pub unsafe extern "C" fn biscuit_serialize_sealed(
 
     let biscuit = biscuit.unwrap();
 
-    match (*biscuit).0.seal() {
+    match (*biscuit).0.clone().seal() {
         Ok(b) => match b.to_vec() {
             Ok(v) => {
                 let size = match biscuit.0.serialized_size() {
@@ -1072,6 +1228,33 @@ pub unsafe extern "C" fn block_builder_add_check(
         .is_ok()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn block_builder_add_code(
+    builder: Option<&mut BlockBuilder>,
+    code: *const c_char,
+) -> bool {
+    if builder.is_none() {
+        update_last_error(Error::InvalidArgument);
+        return false;
+    }
+    let builder = builder.unwrap();
+
+    let code = CStr::from_ptr(code);
+    let s = code.to_str();
+    if s.is_err() {
+        update_last_error(Error::InvalidArgument);
+        return false;
+    }
+
+    builder
+        .0
+        .add_code(s.unwrap())
+        .map_err(|e| {
+            update_last_error(Error::Biscuit(e));
+        })
+        .is_ok()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn block_builder_free(_builder: Option<Box<BlockBuilder>>) {}
 
@@ -1183,6 +1366,71 @@ pub unsafe extern "C" fn authorizer_add_policy(
         .is_ok()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn authorizer_add_code(
+    authorizer: Option<&mut Authorizer>,
+    code: *const c_char,
+) -> bool {
+    if authorizer.is_none() {
+        update_last_error(Error::InvalidArgument);
+        return false;
+    }
+    let authorizer = authorizer.unwrap();
+
+    let code = CStr::from_ptr(code);
+    let s = code.to_str();
+    if s.is_err() {
+        update_last_error(Error::InvalidArgument);
+        return false;
+    }
+
+    authorizer
+        .0
+        .add_code(s.unwrap())
+        .map_err(|e| {
+            update_last_error(Error::Biscuit(e));
+        })
+        .is_ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn authorizer_set_time(authorizer: Option<&mut Authorizer>) -> bool {
+    if authorizer.is_none() {
+        update_last_error(Error::InvalidArgument);
+        return false;
+    }
+    let authorizer = authorizer.unwrap();
+
+    authorizer.0.set_time();
+    true
+}
+
+/// adds a `time` fact, from a number of seconds since the UNIX epoch
+///
+/// returns false if the date cannot be represented
+#[no_mangle]
+pub unsafe extern "C" fn authorizer_set_time_at(
+    authorizer: Option<&mut Authorizer>,
+    seconds: u64,
+) -> bool {
+    if authorizer.is_none() {
+        update_last_error(Error::InvalidArgument);
+        return false;
+    }
+    let authorizer = authorizer.unwrap();
+
+    match std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(seconds)) {
+        Some(time) => {
+            authorizer.0.set_time_at(time);
+            true
+        }
+        None => {
+            update_last_error(Error::InvalidArgument);
+            false
+        }
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn authorizer_authorize(authorizer: Option<&mut Authorizer>) -> bool {
     if authorizer.is_none() {
@@ -0,0 +1,250 @@
+//! loading keys from environment variables and files
+//!
+//! the content can be hex encoded (as produced by `to_bytes_hex`), base64
+//! encoded, or a PEM file (PKCS#8 private key or SubjectPublicKeyInfo public
+//! key, as generated by `openssl genpkey -algorithm ed25519`).
+//! Error messages name the source of the key but never include its content
+use std::{env::VarError, fs::File, io::Read, path::Path};
+
+use super::{KeyPair, PrivateKey, PublicKey};
+use crate::error::Format;
+
+// DER prefixes of Ed25519 keys, followed by the 32 bytes of the key
+const PKCS8_PREFIX: &[u8] = &[
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+const SPKI_PREFIX: &[u8] = &[
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+impl KeyPair {
+    /// loads the private key from the environment variable `var`
+    pub fn from_env(var: &str) -> Result<Self, Format> {
+        let source = format!("environment variable {}", var);
+        // the error for non unicode content would contain the content
+        let content = std::env::var(var).map_err(|e| match e {
+            VarError::NotPresent => Format::InvalidKey(format!("{} is not set", source)),
+            VarError::NotUnicode(_) => {
+                Format::InvalidKey(format!("{} is not valid unicode", source))
+            }
+        })?;
+
+        private_key(&content, &source).map(|key| KeyPair::from(&key))
+    }
+
+    /// loads the private key from a file
+    ///
+    /// on unix, files readable by all users are refused, see
+    /// [`KeyPair::from_pem_file_allow_world_readable`]
+    pub fn from_pem_file<P: AsRef<Path>>(path: P) -> Result<Self, Format> {
+        Self::load_file(path.as_ref(), false)
+    }
+
+    /// loads the private key from a file, even if it is readable by all users
+    pub fn from_pem_file_allow_world_readable<P: AsRef<Path>>(path: P) -> Result<Self, Format> {
+        Self::load_file(path.as_ref(), true)
+    }
+
+    fn load_file(path: &Path, allow_world_readable: bool) -> Result<Self, Format> {
+        let source = format!("file {}", path.display());
+        // the permissions are checked on the opened file, so it cannot be
+        // replaced before it is read
+        let mut file = open_file(path, &source)?;
+
+        #[cfg(unix)]
+        if !allow_world_readable {
+            use std::os::unix::fs::PermissionsExt;
+
+            let metadata = file
+                .metadata()
+                .map_err(|e| Format::InvalidKey(format!("cannot read {}: {}", source, e)))?;
+            if metadata.permissions().mode() & 0o004 != 0 {
+                return Err(Format::InvalidKey(format!(
+                    "{} is readable by all users",
+                    source
+                )));
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = allow_world_readable;
+
+        let content = read_file(&mut file, &source)?;
+        private_key(&content, &source).map(|key| KeyPair::from(&key))
+    }
+}
+
+impl PublicKey {
+    /// loads the public key from a file
+    pub fn from_pem_file<P: AsRef<Path>>(path: P) -> Result<Self, Format> {
+        let path = path.as_ref();
+        let source = format!("file {}", path.display());
+        let content = read_file(&mut open_file(path, &source)?, &source)?;
+
+        let bytes =
+            decode(&content, "PUBLIC KEY", SPKI_PREFIX).ok_or_else(|| invalid_encoding(&source))?;
+        PublicKey::from_bytes(&bytes)
+            .map_err(|_| Format::InvalidKey(format!("invalid Ed25519 public key in {}", source)))
+    }
+}
+
+fn open_file(path: &Path, source: &str) -> Result<File, Format> {
+    File::open(path).map_err(|e| Format::InvalidKey(format!("cannot read {}: {}", source, e)))
+}
+
+fn read_file(file: &mut File, source: &str) -> Result<String, Format> {
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .map_err(|e| Format::InvalidKey(format!("cannot read {}: {}", source, e)))?;
+    Ok(content)
+}
+
+fn private_key(content: &str, source: &str) -> Result<PrivateKey, Format> {
+    let bytes =
+        decode(content, "PRIVATE KEY", PKCS8_PREFIX).ok_or_else(|| invalid_encoding(source))?;
+    PrivateKey::from_bytes(&bytes)
+        .map_err(|_| Format::InvalidKey(format!("invalid Ed25519 private key in {}", source)))
+}
+
+fn invalid_encoding(source: &str) -> Format {
+    Format::InvalidKey(format!(
+        "{} does not contain a hex, base64 or PEM encoded Ed25519 key",
+        source
+    ))
+}
+
+// returns the 32 bytes of the key
+fn decode(content: &str, pem_label: &str, der_prefix: &[u8]) -> Option<Vec<u8>> {
+    let content = content.trim();
+
+    if content.starts_with("-----BEGIN ") {
+        let body = content
+            .strip_prefix(&format!("-----BEGIN {}-----", pem_label))?
+            .trim_end()
+            .strip_suffix(&format!("-----END {}-----", pem_label))?;
+        let body: String = body.split_whitespace().collect();
+        let der = base64::decode(body).ok()?;
+
+        return der
+            .strip_prefix(der_prefix)
+            .filter(|key| key.len() == 32)
+            .map(|key| key.to_vec());
+    }
+
+    if content.len() == 64 {
+        if let Ok(bytes) = hex::decode(content) {
+            return Some(bytes);
+        }
+    }
+
+    base64::decode(content)
+        .or_else(|_| base64::decode_config(content, base64::URL_SAFE))
+        .ok()
+        .filter(|bytes| bytes.len() == 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pem(label: &str, prefix: &[u8], key: &[u8]) -> String {
+        format!(
+            "-----BEGIN {}-----\n{}\n-----END {}-----\n",
+            label,
+            base64::encode([prefix, key].concat()),
+            label
+        )
+    }
+
+    fn write(name: &str, content: &str, mode: u32) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("biscuit-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        path
+    }
+
+    #[test]
+    fn encodings() {
+        let keypair = KeyPair::new();
+        let private_pem = pem("PRIVATE KEY", PKCS8_PREFIX, &keypair.private().to_bytes());
+        let public_pem = pem("PUBLIC KEY", SPKI_PREFIX, &keypair.public().to_bytes());
+
+        let private = decode(&private_pem, "PRIVATE KEY", PKCS8_PREFIX).unwrap();
+        let public = decode(&public_pem, "PUBLIC KEY", SPKI_PREFIX).unwrap();
+        assert_eq!(keypair.private().to_bytes().to_vec(), private);
+        assert_eq!(keypair.public().to_bytes().to_vec(), public);
+
+        let hex = keypair.private().to_bytes_hex();
+        assert_eq!(
+            private_key(&hex, "test").unwrap().to_bytes().to_vec(),
+            private
+        );
+        let b64 = base64::encode(&private);
+        assert_eq!(
+            private_key(&b64, "test").unwrap().to_bytes().to_vec(),
+            private
+        );
+
+        // a public key is not accepted as a private key
+        assert!(private_key(&public_pem, "test").is_err());
+    }
+
+    #[test]
+    fn env() {
+        let var = "BISCUIT_TEST_PRIVATE_KEY";
+        std::env::set_var(var, "not a key");
+        let error = KeyPair::from_env(var).unwrap_err().to_string();
+        assert!(error.contains(var));
+        assert!(!error.contains("not a key"));
+
+        let keypair = KeyPair::new();
+        std::env::set_var(var, keypair.private().to_bytes_hex());
+        assert_eq!(KeyPair::from_env(var).unwrap().public(), keypair.public());
+        std::env::remove_var(var);
+        assert!(KeyPair::from_env(var).is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+
+            let content = std::ffi::OsString::from_vec(b"secret\xff".to_vec());
+            std::env::set_var(var, content);
+            let error = KeyPair::from_env(var).unwrap_err().to_string();
+            assert!(error.contains(var));
+            assert!(!error.contains("secret"));
+            std::env::remove_var(var);
+        }
+    }
+
+    #[test]
+    fn files() {
+        let keypair = KeyPair::new();
+        let private_pem = pem("PRIVATE KEY", PKCS8_PREFIX, &keypair.private().to_bytes());
+        let public_pem = pem("PUBLIC KEY", SPKI_PREFIX, &keypair.public().to_bytes());
+
+        let private = write("private.pem", &private_pem, 0o600);
+        let public = write("public.pem", &public_pem, 0o644);
+        let loaded = KeyPair::from_pem_file(&private).unwrap();
+        assert_eq!(loaded.public(), keypair.public());
+        assert_eq!(PublicKey::from_pem_file(&public).unwrap(), keypair.public());
+
+        let readable = write("readable.pem", &private_pem, 0o644);
+        #[cfg(unix)]
+        {
+            let error = KeyPair::from_pem_file(&readable).unwrap_err().to_string();
+            assert!(error.contains(&readable.display().to_string()));
+        }
+        assert!(KeyPair::from_pem_file_allow_world_readable(&readable).is_ok());
+
+        assert!(KeyPair::from_pem_file(std::env::temp_dir().join("biscuit-missing.pem")).is_err());
+
+        for path in [private, public, readable] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -16,8 +16,15 @@ use rand_core::{CryptoRng, RngCore};
 use std::{convert::TryInto, fmt::Display, hash::Hash, ops::Drop, str::FromStr};
 use zeroize::Zeroize;
 
+mod load;
+
 /// pair of cryptographic keys used to sign a token's block
-#[derive(Debug)]
+///
+/// the `Debug` implementation only shows the public key, unless the
+/// `unsafe_debug` feature is activated
+///
+/// it is `Send` and `Sync`, and signing only needs a shared reference, so
+/// it can be shared between threads with an `Arc`
 pub struct KeyPair {
     pub kp: ed25519_dalek::Keypair,
 }
@@ -65,8 +72,27 @@ impl Drop for KeyPair {
     }
 }
 
+#[cfg(not(feature = "unsafe_debug"))]
+impl std::fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("public", &format_args!("{}", self.public()))
+            .field("private", &format_args!("<private key redacted>"))
+            .finish()
+    }
+}
+
+#[cfg(feature = "unsafe_debug")]
+impl std::fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyPair").field("kp", &self.kp).finish()
+    }
+}
+
 /// the private part of a [KeyPair]
-#[derive(Debug)]
+///
+/// the `Debug` implementation only shows the matching public key, unless the
+/// `unsafe_debug` feature is activated
 pub struct PrivateKey(pub(crate) ed25519_dalek::SecretKey);
 
 impl PrivateKey {
@@ -115,6 +141,23 @@ impl Drop for PrivateKey {
     }
 }
 
+#[cfg(not(feature = "unsafe_debug"))]
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("public", &format_args!("{}", self.public()))
+            .field("private", &format_args!("<private key redacted>"))
+            .finish()
+    }
+}
+
+#[cfg(feature = "unsafe_debug")]
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PrivateKey").field(&self.0).finish()
+    }
+}
+
 /// the public part of a [KeyPair]
 #[derive(Debug, Clone, Copy, Eq)]
 pub struct PublicKey(pub(crate) ed25519_dalek::PublicKey);
@@ -199,6 +242,8 @@ impl Display for PublicKey {
 
 #[derive(Clone, Debug)]
 pub struct Block {
+    /// serialized block, as received or generated. It is signed as is, and
+    /// never encoded again
     pub(crate) data: Vec<u8>,
     pub(crate) next_key: PublicKey,
     pub signature: ed25519_dalek::Signature,
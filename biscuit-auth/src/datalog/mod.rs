@@ -4,9 +4,11 @@ use crate::error::Execution;
 use crate::time::Instant;
 use crate::token::{Scope, MIN_SCHEMA_VERSION};
 use crate::{builder, error};
+use std::cell::Cell;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::convert::AsRef;
 use std::fmt;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 mod expression;
@@ -85,6 +87,10 @@ impl Fact {
 pub struct Rule {
     pub head: Predicate,
     pub body: Vec<Predicate>,
+    /// all expressions must evaluate to true for a set of variables to match,
+    /// even if they refer to the same variable. They are evaluated in order
+    /// and evaluation stops at the first one returning false, so the following
+    /// ones are not evaluated and cannot produce an error
     pub expressions: Vec<Expression>,
     pub scopes: Vec<Scope>,
 }
@@ -99,6 +105,8 @@ impl AsRef<Expression> for Expression {
 pub struct Check {
     pub queries: Vec<Rule>,
     pub kind: CheckKind,
+    /// label of the check, not used in evaluation
+    pub name: Option<String>,
 }
 
 impl fmt::Display for Fact {
@@ -130,24 +138,72 @@ impl Rule {
     where
         IT: Iterator<Item = (&'a Origin, &'a Fact)> + Clone + 'a,
     {
-        let head = self.head.clone();
         let variables = MatchedVariables::new(self.variables_set());
+        let combinations = CombineIt::new(variables, &self.body, facts, symbols);
+        self.generate(combinations, rule_origin, symbols, None)
+    }
+
+    /// applies the rule on combinations where the body predicate at `position`
+    /// matches a fact from `delta`, and the other ones match any fact from `facts`
+    ///
+    /// this is used by semi-naive evaluation: once the facts of a previous
+    /// iteration have been used, only combinations including at least one of
+    /// the facts generated by the last iteration can produce new facts
+    pub fn apply_delta<'a, IT>(
+        &'a self,
+        facts: IT,
+        delta: IT,
+        position: usize,
+        rule_origin: usize,
+        symbols: &'a SymbolTable,
+    ) -> impl Iterator<Item = Result<(Origin, Fact), error::Expression>> + 'a
+    where
+        IT: Iterator<Item = (&'a Origin, &'a Fact)> + Clone + 'a,
+    {
+        let variables = MatchedVariables::new(self.variables_set());
+        let combinations =
+            CombineIt::with_delta(variables, &self.body, facts, (position, delta), symbols);
+        self.generate(combinations, rule_origin, symbols, None)
+    }
 
-        CombineIt::new(variables, &self.body, facts, symbols)
+    // same as `apply` or `apply_delta`, adding the number of combinations
+    // matching the body to `explored`
+    fn apply_counted<'a, IT>(
+        &'a self,
+        facts: IT,
+        delta: Option<(usize, IT)>,
+        rule_origin: usize,
+        symbols: &'a SymbolTable,
+        explored: &'a Cell<u64>,
+    ) -> impl Iterator<Item = Result<(Origin, Fact), error::Expression>> + 'a
+    where
+        IT: Iterator<Item = (&'a Origin, &'a Fact)> + Clone + 'a,
+    {
+        let variables = MatchedVariables::new(self.variables_set());
+        let combinations = CombineIt::build(variables, &self.body, facts, delta, symbols);
+        self.generate(combinations, rule_origin, symbols, Some(explored))
+    }
+
+    // generates facts from the combinations of variables that pass the expressions
+    fn generate<'a, IT>(
+        &'a self,
+        combinations: CombineIt<'a, IT>,
+        rule_origin: usize,
+        symbols: &'a SymbolTable,
+        explored: Option<&'a Cell<u64>>,
+    ) -> impl Iterator<Item = Result<(Origin, Fact), error::Expression>> + 'a
+    where
+        IT: Iterator<Item = (&'a Origin, &'a Fact)> + Clone + 'a,
+    {
+        let head = self.head.clone();
+
+        combinations
         .map(move |(origin, variables)| {
-                    let mut temporary_symbols = TemporarySymbolTable::new(&symbols);
-                    for e in self.expressions.iter() {
-                        match e.evaluate(&variables, &mut temporary_symbols) {
-                            Ok(Term::Bool(true)) => {}
-                            Ok(Term::Bool(false)) => return Ok((origin, variables, false)),
-                            Ok(_) => return Err(error::Expression::InvalidType.into()),
-                            Err(e) => {
-                                //println!("expr returned {:?}", res);
-                                return Err(e);
-                            }
-                        }
+                    if let Some(explored) = explored {
+                        explored.set(explored.get() + 1);
                     }
-            Ok((origin, variables, true))
+                    let res = self.evaluate_expressions(&variables, symbols)?;
+            Ok((origin, variables, res))
         }).filter_map(move |res/*(mut origin,h, expression_res)*/| {
             match res {
                 Ok((mut origin,h , expression_res)) => {
@@ -207,23 +263,112 @@ impl Rule {
         for (_, variables) in CombineIt::new(variables, &self.body, fact_it, symbols) {
             found = true;
 
-            let mut temporary_symbols = TemporarySymbolTable::new(&symbols);
-            for e in self.expressions.iter() {
-                match e.evaluate(&variables, &mut temporary_symbols) {
-                    Ok(Term::Bool(true)) => {}
-                    Ok(Term::Bool(false)) => {
-                        //println!("expr returned {:?}", res);
-                        return Ok(false);
-                    },
-                    Ok(_) => return Err(error::Execution::Expression(error::Expression::InvalidType)),
-                    Err(e) => {
-                        return Err(error::Execution::Expression(e));
+            if !self
+                .evaluate_expressions(&variables, symbols)
+                .map_err(error::Execution::Expression)?
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// returns up to `limit` distinct sets of variables matching the body
+    /// and the expressions
+    pub fn bindings(
+        &self,
+        facts: &FactSet,
+        scope: &TrustedOrigins,
+        symbols: &SymbolTable,
+        limit: usize,
+    ) -> Result<Bindings, Execution> {
+        let fact_it = facts.iterator(scope);
+        let variables = MatchedVariables::new(self.variables_set());
+        let mut bindings = Bindings::default();
+
+        for (_, variables) in CombineIt::new(variables, &self.body, fact_it, symbols) {
+            if !self
+                .evaluate_expressions(&variables, symbols)
+                .map_err(error::Execution::Expression)?
+                || bindings.sets.contains(&variables)
+            {
+                continue;
+            }
+
+            if bindings.sets.len() >= limit {
+                bindings.truncated = true;
+                break;
+            }
+            bindings.sets.push(variables);
+        }
+
+        Ok(bindings)
+    }
+
+    /// counts the facts matching each predicate of the body on its own, the
+    /// combinations of facts matching the whole body, and the combinations
+    /// rejected by each expression
+    pub fn diagnostics(
+        &self,
+        facts: &FactSet,
+        scope: &TrustedOrigins,
+        symbols: &SymbolTable,
+    ) -> Result<Diagnostics, Execution> {
+        let mut diagnostics = Diagnostics {
+            predicate_matches: self
+                .body
+                .iter()
+                .map(|predicate| {
+                    facts
+                        .iterator(scope)
+                        .filter(|(_, fact)| match_preds(predicate, &fact.predicate))
+                        .count()
+                })
+                .collect(),
+            candidates: 0,
+            rejected: vec![0; self.expressions.len()],
+        };
+
+        let fact_it = facts.iterator(scope);
+        let variables = MatchedVariables::new(self.variables_set());
+        for (_, variables) in CombineIt::new(variables, &self.body, fact_it, symbols) {
+            diagnostics.candidates += 1;
+            let mut temporary_symbols = TemporarySymbolTable::new(symbols);
+            for (i, e) in self.expressions.iter().enumerate() {
+                match e
+                    .evaluate(&variables, &mut temporary_symbols)
+                    .map_err(Execution::Expression)?
+                {
+                    Term::Bool(true) => {}
+                    Term::Bool(false) => {
+                        diagnostics.rejected[i] += 1;
+                        break;
                     }
+                    _ => return Err(Execution::Expression(error::Expression::InvalidType)),
                 }
             }
         }
 
-        Ok(found)
+        Ok(diagnostics)
+    }
+
+    // evaluates the expressions in order, stopping at the first one returning false
+    fn evaluate_expressions(
+        &self,
+        variables: &HashMap<u32, Term>,
+        symbols: &SymbolTable,
+    ) -> Result<bool, error::Expression> {
+        let mut temporary_symbols = TemporarySymbolTable::new(symbols);
+        for e in self.expressions.iter() {
+            match e.evaluate(variables, &mut temporary_symbols)? {
+                Term::Bool(true) => {}
+                Term::Bool(false) => return Ok(false),
+                _ => return Err(error::Expression::InvalidType),
+            }
+        }
+
+        Ok(true)
     }
 
     // use this to translate rules and checks from token to authorizer world without translating
@@ -300,6 +445,24 @@ impl Rule {
                     ))
         }
     }
+
+    /// finds the first expression using a variable that is not bound by the body,
+    /// returning its index and the variable
+    ///
+    /// such an expression would fail with [`error::Expression::UnknownVariable`]
+    /// whenever the body matches, so rules from tokens are rejected upfront
+    pub fn unbound_expression_variable(&self) -> Option<(usize, u32)> {
+        let variables = self.variables_set();
+        self.expressions
+            .iter()
+            .enumerate()
+            .find_map(|(index, expression)| {
+                expression.ops.iter().find_map(|op| match op {
+                    Op::Value(Term::Variable(v)) if !variables.contains(v) => Some((index, *v)),
+                    _ => None,
+                })
+            })
+    }
 }
 
 /// recursive iterator for rule application
@@ -307,6 +470,8 @@ pub struct CombineIt<'a, IT> {
     variables: MatchedVariables,
     predicates: &'a [Predicate],
     all_facts: IT,
+    // facts used for the predicate at this position instead of `all_facts`
+    delta: Option<(usize, IT)>,
     symbols: &'a SymbolTable,
     current_facts: Box<dyn Iterator<Item = (&'a Origin, &'a Fact)> + 'a>,
     current_it: Option<Box<dyn Iterator<Item = (Origin, HashMap<u32, Term>)> + 'a>>,
@@ -322,22 +487,44 @@ where
         facts: IT,
         symbols: &'a SymbolTable,
     ) -> Self {
+        Self::build(variables, predicates, facts, None, symbols)
+    }
+
+    /// the predicate at `delta.0` only matches facts from `delta.1`
+    pub fn with_delta(
+        variables: MatchedVariables,
+        predicates: &'a [Predicate],
+        facts: IT,
+        delta: (usize, IT),
+        symbols: &'a SymbolTable,
+    ) -> Self {
+        Self::build(variables, predicates, facts, Some(delta), symbols)
+    }
+
+    fn build(
+        variables: MatchedVariables,
+        predicates: &'a [Predicate],
+        facts: IT,
+        delta: Option<(usize, IT)>,
+        symbols: &'a SymbolTable,
+    ) -> Self {
+        let first_facts = match &delta {
+            Some((0, delta_facts)) => delta_facts.clone(),
+            _ => facts.clone(),
+        };
         let current_facts: Box<dyn Iterator<Item = (&'a Origin, &'a Fact)> + 'a> =
             if predicates.is_empty() {
-                Box::new(facts.clone())
+                Box::new(first_facts)
             } else {
                 let p = predicates[0].clone();
-                Box::new(
-                    facts
-                        .clone()
-                        .filter(move |fact| match_preds(&p, &fact.1.predicate)),
-                )
+                Box::new(first_facts.filter(move |fact| match_preds(&p, &fact.1.predicate)))
             };
 
         CombineIt {
             variables,
             predicates,
             all_facts: facts,
+            delta,
             symbols,
             current_facts,
             current_it: None,
@@ -410,11 +597,18 @@ where
                         } else {
                             // create a new iterator with the matched variables, the rest of the predicates,
                             // and all of the facts
+                            let delta = match &self.delta {
+                                Some((position, delta_facts)) if *position > 0 => {
+                                    Some((position - 1, delta_facts.clone()))
+                                }
+                                _ => None,
+                            };
                             self.current_it = Some(Box::new(
-                                CombineIt::new(
+                                CombineIt::build(
                                     vars,
                                     &self.predicates[1..],
                                     self.all_facts.clone(),
+                                    delta,
                                     self.symbols,
                                 )
                                 .map(move |(origin, variables)| {
@@ -533,7 +727,7 @@ pub fn int(i: i64) -> Term {
 }*/
 
 pub fn date(t: &SystemTime) -> Term {
-    let dur = t.duration_since(UNIX_EPOCH).unwrap();
+    let dur = t.duration_since(UNIX_EPOCH).unwrap_or_default();
     Term::Date(dur.as_secs())
 }
 
@@ -568,6 +762,12 @@ pub struct World {
     pub facts: FactSet,
     pub rules: RuleSet,
     pub iterations: u64,
+    /// set when [`RunLimits::max_derivation_depth`] prevented the generation of facts
+    pub derivation_depth_reached: bool,
+    // facts generated at depth `i + 1`, only recorded when the depth is limited
+    depths: Vec<FactSet>,
+    // block of the rule that generated each fact, if enabled
+    derivations: Option<HashMap<(Origin, Fact), usize>>,
 }
 
 impl World {
@@ -583,52 +783,154 @@ impl World {
         self.rules.insert(origin, scope, rule);
     }
 
-    pub fn run(&mut self, symbols: &SymbolTable) -> Result<(), crate::error::Execution> {
+    /// records which rule generated each fact in the next runs, see [`World::derived_by`]
+    ///
+    /// this only adds bookkeeping, the generated facts are the same
+    pub fn record_derivations(&mut self, enabled: bool) {
+        if !enabled {
+            self.derivations = None;
+        } else if self.derivations.is_none() {
+            self.derivations = Some(HashMap::new());
+        }
+    }
+
+    pub fn records_derivations(&self) -> bool {
+        self.derivations.is_some()
+    }
+
+    /// block id of the rule that generated a fact (`usize::MAX` for the
+    /// authorizer), or `None` if the fact was not generated by a rule
+    /// while [`World::record_derivations`] was enabled
+    pub fn derived_by(&self, origin: &Origin, fact: &Fact) -> Option<usize> {
+        self.derivations
+            .as_ref()
+            .and_then(|derivations| derivations.get(&(origin.clone(), fact.clone())))
+            .copied()
+    }
+
+    /// moves the current facts to an immutable base behind an `Arc`, shared
+    /// with the forks of this world, see [`World::fork`]
+    ///
+    /// this is meant to be called once the shared state is built, since the
+    /// base is copied if facts were added after the last call
+    pub fn freeze(&mut self) {
+        self.facts.freeze();
+    }
+
+    /// creates a world sharing the frozen facts and the rules with this one
+    ///
+    /// both worlds write new facts to their own overlay, so running or
+    /// dropping the fork never modifies this world. Only the facts added
+    /// since the last call to [`World::freeze`] are copied, and the rules are
+    /// copied once a rule is added to either world
+    pub fn fork(&self) -> World {
+        self.clone()
+    }
+
+    pub fn run(&mut self, symbols: &SymbolTable) -> Result<RunStats, crate::error::Execution> {
         self.run_with_limits(symbols, RunLimits::default())
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "world_run",
+            level = "debug",
+            skip_all,
+            fields(facts = self.facts.len(), iterations = tracing::field::Empty)
+        )
+    )]
     pub fn run_with_limits(
         &mut self,
         symbols: &SymbolTable,
         limits: RunLimits,
-    ) -> Result<(), crate::error::Execution> {
+    ) -> Result<RunStats, crate::error::Execution> {
         let start = Instant::now();
         let time_limit = start + limits.max_time;
         let mut index = 0;
+        let mut stats = RunStats {
+            facts_before: self.facts.len(),
+            ..Default::default()
+        };
 
-        let res = loop {
-            let mut new_facts = FactSet::default();
+        // semi-naive evaluation: the first iteration applies rules on all facts,
+        // the following ones only on combinations including at least one fact
+        // generated by the previous iteration. Facts derivable only from older
+        // facts were already generated, so this reaches the same fixpoint in the
+        // same number of iterations, and iteration `n` generates the facts with
+        // a derivation depth of `n`.
+        //
+        // When the depth is limited, facts derived by previous runs are held
+        // back until the evaluation reaches their depth
+        let track_depth = limits.max_derivation_depth.is_some();
+        let deeper = if track_depth {
+            std::mem::take(&mut self.depths)
+        } else {
+            Vec::new()
+        };
+        let mut known = if deeper.is_empty() {
+            None
+        } else {
+            let mut known = self.facts.clone();
+            for layer in deeper.iter() {
+                known.remove_existing(layer);
+            }
+            Some(known)
+        };
+        let mut deeper = deeper.into_iter();
+        let mut delta: Option<FactSet> = None;
+        let mut depth = 0;
+        let mut derivations = self.derivations.take();
 
-            for (scope, rules) in self.rules.inner.iter() {
-                let it = self.facts.iterator(scope);
-                for (origin, rule) in rules {
-                    for res in rule.apply(it.clone(), *origin, symbols) {
-                        match res {
-                            Ok((origin,fact)) => {
-                                new_facts.insert(&origin, fact);
+        let res = loop {
+            depth += 1;
+            let facts = known.as_ref().unwrap_or(&self.facts);
+            stats.iterations += 1;
+            let derived =
+                self.derive(facts, delta.as_ref(), derivations.as_mut(), &mut stats, symbols);
+            let mut new_facts = match derived {
+                Ok(new_facts) => new_facts,
+                Err(e) => break Err(e),
+            };
+            new_facts.remove_existing(facts);
 
-                            },
-                            Err(e)  => {
-                                return Err(Execution::Expression(e));
-                            }
-                        }
+            if let Some(max) = limits.max_derivation_depth {
+                if depth > max {
+                    if !new_facts.is_empty() {
+                        self.derivation_depth_reached = true;
                     }
-                    //println!("new_facts after applying {:?}:\n{:#?}", rule, new_facts);
+                    break Ok(());
                 }
             }
 
-            let len = self.facts.len();
-            self.facts.merge(new_facts);
-            if self.facts.len() == len {
+            let previous = deeper.next();
+            if new_facts.is_empty() && previous.is_none() {
                 break Ok(());
             }
+            let has_new_facts = !new_facts.is_empty();
+
+            self.facts.merge(new_facts.clone());
+            if let Some(previous) = previous {
+                new_facts.merge(previous);
+            }
+            if let Some(known) = known.as_mut() {
+                known.merge(new_facts.clone());
+            }
+            if track_depth {
+                self.depths.push(new_facts.clone());
+            }
+            delta = Some(new_facts);
+
+            if !has_new_facts {
+                continue;
+            }
 
             index += 1;
             if index == limits.max_iterations {
                 break Err(Execution::RunLimit( crate::error::RunLimit::TooManyIterations));
             }
 
-            if self.facts.len() >= limits.max_facts as usize {
+            if self.facts.len() as u64 >= limits.max_facts {
                 break Err(Execution::RunLimit(crate::error::RunLimit::TooManyFacts));
             }
 
@@ -637,10 +939,163 @@ impl World {
                 break Err(Execution::RunLimit(crate::error::RunLimit::Timeout));
             }
         };
+        // layers that were not reached keep their depth
+        self.depths.extend(deeper);
+        self.derivations = derivations;
 
         self.iterations += index;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("iterations", index);
 
-        res
+        stats.facts_after = self.facts.len();
+        stats.elapsed = start.elapsed();
+        res.map(|()| stats)
+    }
+
+    /// runs the rules like [`World::run_with_limits`], then asks `resolver` for
+    /// the facts of the predicates used in rule bodies or in `queries` that
+    /// have no facts, and runs the rules again if it provided new ones
+    ///
+    /// the resolver is called at most once per predicate, with the constant
+    /// terms of the first use of the predicate as hints. Its facts get the
+    /// origin `usize::MAX`, like the authorizer's facts, and their number is
+    /// limited by [`RunLimits::max_resolved_facts`]
+    pub fn run_with_resolver(
+        &mut self,
+        symbols: &mut SymbolTable,
+        mut limits: RunLimits,
+        queries: &[Rule],
+        resolver: &mut dyn Resolver,
+    ) -> Result<RunStats, Execution> {
+        let time_limit = Instant::now() + limits.max_time;
+        let mut origin = Origin::default();
+        origin.insert(usize::MAX);
+        let mut resolved = HashSet::new();
+        let mut resolved_facts = 0;
+        let mut stats = self.run_with_limits(symbols, limits.clone())?;
+
+        loop {
+            let mut missing: Vec<(SymbolIndex, Vec<Option<Term>>)> = Vec::new();
+            let bodies = self
+                .rules
+                .inner
+                .values()
+                .flatten()
+                .map(|(_, rule)| rule)
+                .chain(queries.iter())
+                .flat_map(|rule| rule.body.iter());
+            for predicate in bodies {
+                if resolved.contains(&predicate.name)
+                    || missing.iter().any(|(name, _)| *name == predicate.name)
+                    || self
+                        .facts
+                        .iter_all()
+                        .any(|(_, fact)| fact.predicate.name == predicate.name)
+                {
+                    continue;
+                }
+
+                let hints = predicate
+                    .terms
+                    .iter()
+                    .map(|term| match term {
+                        Term::Variable(_) => None,
+                        term => Some(term.clone()),
+                    })
+                    .collect();
+                missing.push((predicate.name, hints));
+            }
+
+            let mut has_new_facts = false;
+            for (name, hints) in missing {
+                resolved.insert(name);
+                let facts = match resolver.resolve(name, &hints, symbols) {
+                    Some(facts) => facts,
+                    None => continue,
+                };
+
+                resolved_facts += facts.len() as u64;
+                if resolved_facts > limits.max_resolved_facts {
+                    return Err(Execution::RunLimit(
+                        crate::error::RunLimit::TooManyResolvedFacts,
+                    ));
+                }
+                // a resolver only provides the facts of its predicate
+                for fact in facts.into_iter().filter(|f| f.predicate.name == name) {
+                    has_new_facts |= !self.facts.contains(&origin, &fact);
+                    self.facts.insert(&origin, fact);
+                }
+            }
+
+            if !has_new_facts {
+                return Ok(stats);
+            }
+
+            let now = Instant::now();
+            if now >= time_limit {
+                return Err(Execution::RunLimit(crate::error::RunLimit::Timeout));
+            }
+            limits.max_time = time_limit - now;
+            stats.accumulate(&self.run_with_limits(symbols, limits.clone())?);
+        }
+    }
+
+    // applies the rules on `facts`, or only on combinations including a fact from `delta`
+    fn derive(
+        &self,
+        facts: &FactSet,
+        delta: Option<&FactSet>,
+        mut derivations: Option<&mut HashMap<(Origin, Fact), usize>>,
+        stats: &mut RunStats,
+        symbols: &SymbolTable,
+    ) -> Result<FactSet, Execution> {
+        let mut new_facts = FactSet::default();
+        let explored = Cell::new(0);
+
+        for (scope, rules) in self.rules.inner.iter() {
+            let it = facts.iterator(scope);
+            for (rule_origin, rule) in rules {
+                stats.rules_evaluated += 1;
+                let mut insert = |(origin, fact): (Origin, Fact)| {
+                    if let Some(derivations) = derivations.as_mut() {
+                        // facts that are already in the world keep their provenance
+                        if !self.facts.contains(&origin, &fact) {
+                            derivations
+                                .entry((origin.clone(), fact.clone()))
+                                .or_insert(*rule_origin);
+                        }
+                    }
+                    new_facts.insert(&origin, fact);
+                };
+
+                match delta {
+                    None => {
+                        for res in
+                            rule.apply_counted(it.clone(), None, *rule_origin, symbols, &explored)
+                        {
+                            insert(res.map_err(Execution::Expression)?);
+                        }
+                    }
+                    Some(delta) => {
+                        let delta_it = delta.iterator(scope);
+                        for position in 0..rule.body.len() {
+                            for res in rule.apply_counted(
+                                it.clone(),
+                                Some((position, delta_it.clone())),
+                                *rule_origin,
+                                symbols,
+                                &explored,
+                            ) {
+                                insert(res.map_err(Execution::Expression)?);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        stats.bindings_explored += explored.get();
+        Ok(new_facts)
     }
 
     /*pub fn query(&self, pred: Predicate) -> Vec<&Fact> {
@@ -697,6 +1152,17 @@ impl World {
         origin: usize,
         scope: &TrustedOrigins,
         symbols: &SymbolTable,
+    ) -> Result<bool, Execution> {
+        self.query_match_ref(&rule, origin, scope, symbols)
+    }
+
+    /// same as [`World::query_match`], without taking ownership of the rule
+    pub fn query_match_ref(
+        &self,
+        rule: &Rule,
+        origin: usize,
+        scope: &TrustedOrigins,
+        symbols: &SymbolTable,
     ) -> Result<bool, Execution> {
         rule.find_match(&self.facts, origin, scope, symbols)
     }
@@ -706,13 +1172,97 @@ impl World {
         rule: Rule,
         scope: &TrustedOrigins,
         symbols: &SymbolTable,
+    ) -> Result<bool, Execution> {
+        self.query_match_all_ref(&rule, scope, symbols)
+    }
+
+    /// same as [`World::query_match_all`], without taking ownership of the rule
+    pub fn query_match_all_ref(
+        &self,
+        rule: &Rule,
+        scope: &TrustedOrigins,
+        symbols: &SymbolTable,
     ) -> Result<bool, Execution> {
         rule.check_match_all(&self.facts, scope, symbols)
     }
+
+    /// returns the variable bindings satisfying the rule, without generating facts
+    ///
+    /// at most `limit` sets are returned, [`Bindings::truncated`] indicates
+    /// if there were more
+    pub fn query_bindings(
+        &self,
+        rule: &Rule,
+        scope: &TrustedOrigins,
+        symbols: &SymbolTable,
+        limit: usize,
+    ) -> Result<Bindings, Execution> {
+        rule.bindings(&self.facts, scope, symbols, limit)
+    }
+
+    /// counts the matches of each part of the rule, see [`Rule::diagnostics`]
+    pub fn query_diagnostics(
+        &self,
+        rule: &Rule,
+        scope: &TrustedOrigins,
+        symbols: &SymbolTable,
+    ) -> Result<Diagnostics, Execution> {
+        rule.diagnostics(&self.facts, scope, symbols)
+    }
+}
+
+/// provides facts during [`World::run_with_resolver`]
+pub trait Resolver {
+    /// returns the facts of the predicate `name`, or `None` if it is not
+    /// handled. `hints` has the constant terms of a use of the predicate, and
+    /// `None` for its variables
+    fn resolve(
+        &mut self,
+        name: SymbolIndex,
+        hints: &[Option<Term>],
+        symbols: &mut SymbolTable,
+    ) -> Option<Vec<Fact>>;
+}
+
+/// variable bindings found by [`World::query_bindings`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bindings {
+    /// each set maps variable ids to their values
+    pub sets: Vec<HashMap<u32, Term>>,
+    /// true if more sets matched than the requested limit
+    pub truncated: bool,
+}
+
+/// match counts of a rule, returned by [`World::query_diagnostics`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// for each predicate of the body, number of facts matching it on its own
+    pub predicate_matches: Vec<usize>,
+    /// number of combinations of facts matching the whole body
+    pub candidates: usize,
+    /// for each expression, number of combinations it rejected. Expressions
+    /// are evaluated in order, so a combination is only counted for the first
+    /// expression rejecting it
+    pub rejected: Vec<usize>,
 }
 
 /// runtime limits for the Datalog engine
-#[derive(Debug, Clone)]
+///
+/// new limits can be added in minor versions, so this is built from
+/// [`RunLimits::default`] with the `with_*` methods:
+///
+/// ```rust
+/// use biscuit_auth::datalog::RunLimits;
+/// use std::time::Duration;
+///
+/// let limits = RunLimits::default()
+///     .with_max_facts(10_000)
+///     .with_max_time(Duration::from_millis(5));
+/// assert_eq!(limits.max_facts, 10_000);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct RunLimits {
     /// maximum number of Datalog facts (memory usage)
     pub max_facts: u64,
@@ -720,6 +1270,46 @@ pub struct RunLimits {
     pub max_iterations: u64,
     /// maximum execution time
     pub max_time: Duration,
+    /// maximum length of derivation chains, if set
+    ///
+    /// facts from tokens and from the authorizer have a depth of 0, and a
+    /// generated fact has the maximum depth of the facts it was generated
+    /// from, plus one. Facts that would exceed this depth are not generated,
+    /// without error, and [`World::derivation_depth_reached`] is set
+    pub max_derivation_depth: Option<u32>,
+    /// maximum number of facts provided by a [`Resolver`] during a run
+    pub max_resolved_facts: u64,
+}
+
+/// statistics of a call to [`World::run_with_limits`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunStats {
+    /// number of times the rules were applied, including the last one
+    /// that did not generate new facts
+    pub iterations: u64,
+    /// number of facts before the run
+    pub facts_before: usize,
+    /// number of facts after the run
+    pub facts_after: usize,
+    /// number of rule applications, summed over iterations
+    pub rules_evaluated: u64,
+    /// number of combinations of facts matching a rule body, before the
+    /// expressions are evaluated
+    pub bindings_explored: u64,
+    /// time spent in the run
+    pub elapsed: Duration,
+}
+
+impl RunStats {
+    /// adds the counters of a following run
+    pub fn accumulate(&mut self, other: &RunStats) {
+        self.iterations += other.iterations;
+        self.facts_after = other.facts_after;
+        self.rules_evaluated += other.rules_evaluated;
+        self.bindings_explored += other.bindings_explored;
+        self.elapsed += other.elapsed;
+    }
 }
 
 impl std::default::Default for RunLimits {
@@ -728,17 +1318,60 @@ impl std::default::Default for RunLimits {
             max_facts: 1000,
             max_iterations: 100,
             max_time: Duration::from_millis(1),
+            max_derivation_depth: None,
+            max_resolved_facts: 100,
         }
     }
 }
 
+impl RunLimits {
+    /// sets [`RunLimits::max_facts`]
+    pub fn with_max_facts(mut self, max_facts: u64) -> Self {
+        self.max_facts = max_facts;
+        self
+    }
+
+    /// sets [`RunLimits::max_iterations`]
+    pub fn with_max_iterations(mut self, max_iterations: u64) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// sets [`RunLimits::max_time`]
+    pub fn with_max_time(mut self, max_time: Duration) -> Self {
+        self.max_time = max_time;
+        self
+    }
+
+    /// sets [`RunLimits::max_derivation_depth`]
+    pub fn with_max_derivation_depth(mut self, max_derivation_depth: Option<u32>) -> Self {
+        self.max_derivation_depth = max_derivation_depth;
+        self
+    }
+
+    /// sets [`RunLimits::max_resolved_facts`]
+    pub fn with_max_resolved_facts(mut self, max_resolved_facts: u64) -> Self {
+        self.max_resolved_facts = max_resolved_facts;
+        self
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct FactSet {
     pub(crate) inner: HashMap<Origin, HashSet<Fact>>,
+    // immutable facts shared between forks, see [`World::fork`]. The base never
+    // has a base of its own
+    base: Option<Arc<FactSet>>,
 }
 
 impl FactSet {
     pub fn insert(&mut self, origin: &Origin, fact: Fact) {
+        if let Some(base) = &self.base {
+            if base.contains(origin, &fact) {
+                return;
+            }
+        }
+
         match self.inner.get_mut(origin) {
             None => {
                 let mut set = HashSet::new();
@@ -753,18 +1386,30 @@ impl FactSet {
 
     pub fn len(&self) -> usize {
         self.inner.values().fold(0, |acc, set| acc + set.len())
+            + self.base.as_ref().map(|base| base.len()).unwrap_or(0)
     }
 
     pub fn is_empty(&self) -> bool {
         self.inner.values().all(|set| set.is_empty())
+            && self
+                .base
+                .as_ref()
+                .map(|base| base.is_empty())
+                .unwrap_or(true)
+    }
+
+    // the sets of facts of the overlay, then those of the base
+    fn layers(&self) -> impl Iterator<Item = (&Origin, &HashSet<Fact>)> + Clone {
+        self.inner
+            .iter()
+            .chain(self.base.iter().flat_map(|base| base.inner.iter()))
     }
 
     pub fn iterator<'a>(
         &'a self,
         block_ids: &'a TrustedOrigins,
     ) -> impl Iterator<Item = (&Origin, &Fact)> + Clone {
-        self.inner
-            .iter()
+        self.layers()
             .filter_map(move |(ids, facts)| {
                 if block_ids.contains(ids) {
                     Some(facts.iter().map(move |fact| (ids, fact)))
@@ -776,24 +1421,75 @@ impl FactSet {
     }
 
     pub fn iter_all<'a>(&'a self) -> impl Iterator<Item = (&Origin, &Fact)> + Clone {
-        self.inner
-            .iter()
+        self.layers()
             .flat_map(move |(ids, facts)| facts.iter().map(move |fact| (ids, fact)))
     }
 
+    /// facts grouped by origin, including those of the shared base
+    pub fn by_origin(&self) -> HashMap<&Origin, Vec<&Fact>> {
+        let mut res: HashMap<&Origin, Vec<&Fact>> = HashMap::new();
+        for (origin, facts) in self.layers() {
+            res.entry(origin).or_default().extend(facts.iter());
+        }
+        res
+    }
+
+    pub fn contains(&self, origin: &Origin, fact: &Fact) -> bool {
+        let in_overlay = match self.inner.get(origin) {
+            Some(facts) => facts.contains(fact),
+            None => false,
+        };
+
+        in_overlay
+            || self
+                .base
+                .as_ref()
+                .map(|base| base.contains(origin, fact))
+                .unwrap_or(false)
+    }
+
+    /// removes the facts already present in `other`
+    pub fn remove_existing(&mut self, other: &FactSet) {
+        for (origin, facts) in self.inner.iter_mut() {
+            facts.retain(|fact| !other.contains(origin, fact));
+        }
+        self.inner.retain(|_, facts| !facts.is_empty());
+    }
+
     pub fn merge(&mut self, other: FactSet) {
-        for (origin, facts) in other.inner {
-            let entry = self.inner.entry(origin).or_default();
-            entry.extend(facts.into_iter());
+        if self.base.is_none() && other.base.is_none() {
+            for (origin, facts) in other.inner {
+                let entry = self.inner.entry(origin).or_default();
+                entry.extend(facts);
+            }
+        } else {
+            self.extend(other);
+        }
+    }
+
+    /// moves the facts to a shared base, so that clones of this set only
+    /// copy the facts added afterwards
+    fn freeze(&mut self) {
+        if self.inner.is_empty() {
+            return;
+        }
+
+        let mut base = match self.base.take() {
+            None => FactSet::default(),
+            // the previous base may still be shared with other forks
+            Some(base) => Arc::try_unwrap(base).unwrap_or_else(|base| (*base).clone()),
+        };
+        for (origin, facts) in std::mem::take(&mut self.inner) {
+            base.inner.entry(origin).or_default().extend(facts);
         }
+        self.base = Some(Arc::new(base));
     }
 }
 
 impl Extend<(Origin, Fact)> for FactSet {
     fn extend<T: IntoIterator<Item = (Origin, Fact)>>(&mut self, iter: T) {
         for (origin, fact) in iter {
-            let entry = self.inner.entry(origin).or_default();
-            entry.insert(fact);
+            self.insert(&origin, fact);
         }
     }
 }
@@ -804,24 +1500,33 @@ impl IntoIterator for FactSet {
     type IntoIter = Box<dyn Iterator<Item = (Origin, Fact)>>;
 
     fn into_iter(self) -> Self::IntoIter {
+        let base = self
+            .base
+            .map(|base| Arc::try_unwrap(base).unwrap_or_else(|base| (*base).clone()));
+
         Box::new(
-            self.inner.into_iter().flat_map(move |(ids, facts)| {
-                facts.into_iter().map(move |fact| (ids.clone(), fact))
-            }),
+            self.inner
+                .into_iter()
+                .chain(base.into_iter().flat_map(|base| base.inner.into_iter()))
+                .flat_map(move |(ids, facts)| {
+                    facts.into_iter().map(move |fact| (ids.clone(), fact))
+                }),
         )
     }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct RuleSet {
-    pub inner: HashMap<TrustedOrigins, Vec<(usize, Rule)>>,
+    // shared between forks, see [`World::fork`], and copied on insertion
+    pub(crate) inner: Arc<HashMap<TrustedOrigins, Vec<(usize, Rule)>>>,
 }
 
 impl RuleSet {
     pub fn insert(&mut self, origin: usize, scope: &TrustedOrigins, rule: Rule) {
-        match self.inner.get_mut(scope) {
+        let inner = Arc::make_mut(&mut self.inner);
+        match inner.get_mut(scope) {
             None => {
-                self.inner.insert(scope.clone(), vec![(origin, rule)]);
+                inner.insert(scope.clone(), vec![(origin, rule)]);
             }
             Some(set) => {
                 set.push((origin, rule));
@@ -1691,4 +2396,504 @@ mod tests {
         }
         assert!(res.len() == 0);
     }
+
+    #[test]
+    fn large_indexes() {
+        let mut syms = SymbolTable::new();
+        let a = syms.insert("a");
+        assert_eq!(syms.get_symbol(a), Some("a"));
+        // on 32 bit targets, truncating these would alias existing entries
+        assert_eq!(syms.get_symbol(a + (1 << 32)), None);
+        assert_eq!(syms.get_symbol(u64::MAX), None);
+        assert_eq!(syms.public_keys.get_key(1 << 32), None);
+
+        let mut public_key_to_block_id = HashMap::new();
+        public_key_to_block_id.insert(0, vec![2]);
+        let origins = TrustedOrigins::from_scopes(
+            &[Scope::PublicKey(1 << 32)],
+            &TrustedOrigins::default(),
+            1,
+            &public_key_to_block_id,
+        );
+        assert!(!origins.contains(&[2].iter().collect()));
+        let origins = TrustedOrigins::from_scopes(
+            &[Scope::PublicKey(0)],
+            &TrustedOrigins::default(),
+            1,
+            &public_key_to_block_id,
+        );
+        assert!(origins.contains(&[2].iter().collect()));
+    }
+
+    #[test]
+    fn default_symbols() {
+        // part of the serialization format, see DEFAULT_SYMBOLS
+        assert_eq!(
+            DEFAULT_SYMBOLS,
+            &[
+                "read",
+                "write",
+                "resource",
+                "operation",
+                "right",
+                "time",
+                "role",
+                "owner",
+                "tenant",
+                "namespace",
+                "user",
+                "team",
+                "service",
+                "admin",
+                "email",
+                "group",
+                "member",
+                "ip_address",
+                "client",
+                "client_ip",
+                "domain",
+                "path",
+                "version",
+                "cluster",
+                "node",
+                "hostname",
+                "nonce",
+                "query",
+            ]
+        );
+
+        let mut syms = SymbolTable::default_table();
+        assert_eq!(syms.current_offset(), 0);
+        for (index, name) in [
+            (SYM_READ, "read"),
+            (SYM_WRITE, "write"),
+            (SYM_RESOURCE, "resource"),
+            (SYM_OPERATION, "operation"),
+            (SYM_RIGHT, "right"),
+            (SYM_TIME, "time"),
+        ] {
+            assert_eq!(syms.insert(name), index);
+        }
+
+        assert_eq!(
+            SymbolTable::from(vec!["custom".to_string(), "read".to_string()]),
+            Err(error::Format::SymbolTableOverlap)
+        );
+    }
+
+    #[test]
+    fn multiple_expressions() {
+        let mut w = World::new();
+        let mut syms = SymbolTable::new();
+
+        let x = syms.insert("x");
+        let res = syms.insert("res");
+        for i in &[-1, 5, 20] {
+            w.add_fact(&[0].iter().collect(), fact(x, &[&int(*i)]));
+        }
+
+        let nb = var(&mut syms, "nb");
+        let compare = |op: Binary, value: i64| Expression {
+            ops: vec![
+                Op::Value(nb.clone()),
+                Op::Value(Term::Integer(value)),
+                Op::Binary(op),
+            ],
+        };
+        // fails on integers
+        let length = Expression {
+            ops: vec![
+                Op::Value(nb.clone()),
+                Op::Unary(Unary::Length),
+                Op::Value(Term::Integer(0)),
+                Op::Binary(Binary::GreaterThan),
+            ],
+        };
+
+        // every expression on $nb must pass, whatever their order
+        for expressions in &[
+            vec![
+                compare(Binary::GreaterThan, 0),
+                compare(Binary::LessThan, 10),
+            ],
+            vec![
+                compare(Binary::LessThan, 10),
+                compare(Binary::GreaterThan, 0),
+            ],
+        ] {
+            let r = expressed_rule(res, &[&nb], &[pred(x, &[&nb])], expressions);
+            let facts = w.query_rule(r, 0, &[0].iter().collect(), &syms).unwrap();
+            let facts = facts.iter_all().map(|(_, f)| f.clone()).collect::<Vec<_>>();
+            assert_eq!(facts, vec![fact(res, &[&int(5)])]);
+        }
+
+        // evaluation stops at the first expression returning false
+        let r = expressed_rule(
+            res,
+            &[&nb],
+            &[pred(x, &[&nb])],
+            &[compare(Binary::GreaterThan, 100), length.clone()],
+        );
+        let facts = w.query_rule(r, 0, &[0].iter().collect(), &syms).unwrap();
+        assert_eq!(facts.len(), 0);
+
+        let r = expressed_rule(
+            res,
+            &[&nb],
+            &[pred(x, &[&nb])],
+            &[length, compare(Binary::GreaterThan, 100)],
+        );
+        assert!(w.query_rule(r, 0, &[0].iter().collect(), &syms).is_err());
+    }
+
+    // reference implementation: applies every rule on all facts until no new fact appears
+    fn run_naive(world: &mut World, symbols: &SymbolTable) -> Result<(), Execution> {
+        loop {
+            let mut new_facts = FactSet::default();
+            for (scope, rules) in world.rules.inner.iter() {
+                for (origin, rule) in rules {
+                    for res in rule.apply(world.facts.iterator(scope), *origin, symbols) {
+                        let (origin, fact) = res.map_err(Execution::Expression)?;
+                        new_facts.insert(&origin, fact);
+                    }
+                }
+            }
+
+            let len = world.facts.len();
+            world.facts.merge(new_facts);
+            if world.facts.len() == len {
+                return Ok(());
+            }
+            world.iterations += 1;
+        }
+    }
+
+    #[test]
+    fn semi_naive_evaluation() {
+        use rand::{prelude::StdRng, Rng, SeedableRng};
+
+        let mut syms = SymbolTable::new();
+        let edge = syms.insert("edge");
+        let path = syms.insert("path");
+        let short = syms.insert("short");
+        let same = syms.insert("same");
+        let x = var(&mut syms, "x");
+        let y = var(&mut syms, "y");
+        let z = var(&mut syms, "z");
+
+        let limits = RunLimits {
+            max_facts: 100_000,
+            max_iterations: 1000,
+            max_time: Duration::from_secs(10),
+            max_derivation_depth: None,
+            max_resolved_facts: 1000,
+        };
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..30 {
+            let mut w = World::new();
+            let nodes = rng.gen_range(1, 12);
+            for _ in 0..rng.gen_range(0, 25) {
+                let origin = rng.gen_range(0, 3);
+                w.add_fact(
+                    &[origin].iter().collect(),
+                    fact(
+                        edge,
+                        &[&int(rng.gen_range(0, nodes)), &int(rng.gen_range(0, nodes))],
+                    ),
+                );
+            }
+
+            // rules in different blocks see different facts
+            let all: TrustedOrigins = [0, 1, 2, usize::MAX].iter().collect();
+            let authority: TrustedOrigins = [0, usize::MAX].iter().collect();
+            w.add_rule(0, &authority, rule(path, &[&x, &y], &[pred(edge, &[&x, &y])]));
+            w.add_rule(
+                1,
+                &all,
+                rule(
+                    path,
+                    &[&x, &z],
+                    &[pred(edge, &[&x, &y]), pred(path, &[&y, &z])],
+                ),
+            );
+            w.add_rule(
+                2,
+                &all,
+                rule(
+                    path,
+                    &[&x, &z],
+                    &[pred(path, &[&x, &y]), pred(path, &[&y, &z])],
+                ),
+            );
+            w.add_rule(
+                usize::MAX,
+                &authority,
+                expressed_rule(
+                    short,
+                    &[&x, &y],
+                    &[pred(path, &[&x, &y])],
+                    &[Expression {
+                        ops: vec![
+                            Op::Value(y.clone()),
+                            Op::Value(x.clone()),
+                            Op::Binary(Binary::Sub),
+                            Op::Value(Term::Integer(3)),
+                            Op::Binary(Binary::LessThan),
+                        ],
+                    }],
+                ),
+            );
+            w.add_rule(
+                usize::MAX,
+                &all,
+                rule(same, &[&x], &[pred(short, &[&x, &x])]),
+            );
+
+            let mut naive = w.clone();
+            run_naive(&mut naive, &syms).unwrap();
+            let mut depth_tracking = w.clone();
+            let mut provenance = w.clone();
+            provenance.record_derivations(true);
+            let initial = w.facts.clone();
+            w.run_with_limits(&syms, limits.clone()).unwrap();
+
+            assert_eq!(w.facts.inner, naive.facts.inner);
+            assert_eq!(w.iterations, naive.iterations);
+
+            depth_tracking
+                .run_with_limits(
+                    &syms,
+                    RunLimits {
+                        max_derivation_depth: Some(1000),
+                        ..limits.clone()
+                    },
+                )
+                .unwrap();
+            assert_eq!(depth_tracking.facts.inner, naive.facts.inner);
+
+            provenance.run_with_limits(&syms, limits.clone()).unwrap();
+            assert_eq!(provenance.facts.inner, naive.facts.inner);
+            for (origin, fact) in provenance.facts.iter_all() {
+                let rule_block = provenance.derived_by(origin, fact);
+                if initial.contains(origin, fact) {
+                    assert_eq!(rule_block, None);
+                } else {
+                    assert!(origin.inner.contains(&rule_block.unwrap()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn run_stats() {
+        let mut w = World::new();
+        let mut syms = SymbolTable::new();
+        let parent = syms.insert("parent");
+        let grandparent = syms.insert("grandparent");
+        let x = var(&mut syms, "x");
+        let y = var(&mut syms, "y");
+        let z = var(&mut syms, "z");
+
+        let origin: Origin = [0].iter().collect();
+        let scope: TrustedOrigins = [0].iter().collect();
+        w.add_fact(&origin, fact(parent, &[&int(0), &int(1)]));
+        w.add_fact(&origin, fact(parent, &[&int(1), &int(2)]));
+        w.add_rule(
+            0,
+            &scope,
+            rule(
+                grandparent,
+                &[&x, &z],
+                &[pred(parent, &[&x, &y]), pred(parent, &[&y, &z])],
+            ),
+        );
+
+        // the second iteration only sees the new grandparent fact, which
+        // does not match the body
+        let stats = w.run(&syms).unwrap();
+        assert_eq!(stats.iterations, 2);
+        assert_eq!(stats.facts_before, 2);
+        assert_eq!(stats.facts_after, 3);
+        assert_eq!(stats.rules_evaluated, 2);
+        assert_eq!(stats.bindings_explored, 1);
+
+        // nothing new to generate
+        let stats = w.run(&syms).unwrap();
+        assert_eq!(stats.iterations, 1);
+        assert_eq!(stats.facts_before, stats.facts_after);
+        assert_eq!(stats.bindings_explored, 1);
+    }
+
+    #[test]
+    fn fork() {
+        let mut w = World::new();
+        let mut syms = SymbolTable::new();
+        let parent = syms.insert("parent");
+        let grandparent = syms.insert("grandparent");
+        let x = var(&mut syms, "x");
+        let y = var(&mut syms, "y");
+        let z = var(&mut syms, "z");
+
+        let origin: Origin = [0].iter().collect();
+        let scope: TrustedOrigins = [0].iter().collect();
+        w.add_fact(&origin, fact(parent, &[&int(0), &int(1)]));
+        w.add_fact(&origin, fact(parent, &[&int(1), &int(2)]));
+        w.add_rule(
+            0,
+            &scope,
+            rule(
+                grandparent,
+                &[&x, &z],
+                &[pred(parent, &[&x, &y]), pred(parent, &[&y, &z])],
+            ),
+        );
+
+        let count = |w: &World, name: u64| {
+            w.facts
+                .iter_all()
+                .filter(|(_, fact)| fact.predicate.name == name)
+                .count()
+        };
+
+        w.freeze();
+        let mut fork = w.fork();
+        assert!(Arc::ptr_eq(&w.rules.inner, &fork.rules.inner));
+        fork.add_fact(&origin, fact(parent, &[&int(2), &int(3)]));
+        // already in the base
+        fork.add_fact(&origin, fact(parent, &[&int(0), &int(1)]));
+        fork.run(&syms).unwrap();
+
+        assert_eq!(count(&fork, parent), 3);
+        assert_eq!(count(&fork, grandparent), 2);
+        // only the new facts are stored in the overlay
+        assert_eq!(
+            fork.facts
+                .inner
+                .values()
+                .map(|set| set.len())
+                .sum::<usize>(),
+            3
+        );
+        assert_eq!(fork.facts.by_origin()[&origin].len(), 5);
+
+        assert_eq!(w.facts.len(), 2);
+        assert_eq!(count(&w, grandparent), 0);
+
+        let second = w.fork();
+        drop(fork);
+        assert_eq!(count(&second, parent), 2);
+
+        w.run(&syms).unwrap();
+        assert_eq!(count(&w, grandparent), 1);
+        assert_eq!(count(&second, grandparent), 0);
+
+        // adding a rule to a fork copies the rules
+        let mut third = w.fork();
+        third.add_rule(
+            0,
+            &scope,
+            rule(parent, &[&x, &x], &[pred(parent, &[&x, &y])]),
+        );
+        assert!(!Arc::ptr_eq(&w.rules.inner, &third.rules.inner));
+        third.run(&syms).unwrap();
+        assert_eq!(count(&third, parent), 4);
+        assert_eq!(count(&w, parent), 2);
+        assert_eq!(w.rules.inner.values().flatten().count(), 1);
+    }
+
+    #[test]
+    fn derivation_depth() {
+        let mut w = World::new();
+        let mut syms = SymbolTable::new();
+        let edge = syms.insert("edge");
+        let path = syms.insert("path");
+        let x = var(&mut syms, "x");
+        let y = var(&mut syms, "y");
+        let z = var(&mut syms, "z");
+
+        let origin: Origin = [0].iter().collect();
+        let scope: TrustedOrigins = [0].iter().collect();
+        w.add_fact(&origin, fact(edge, &[&int(0), &int(1)]));
+        w.add_fact(&origin, fact(edge, &[&int(1), &int(2)]));
+        w.add_rule(0, &scope, rule(path, &[&x, &y], &[pred(edge, &[&x, &y])]));
+        w.add_rule(
+            0,
+            &scope,
+            rule(
+                path,
+                &[&x, &z],
+                &[pred(path, &[&x, &y]), pred(edge, &[&y, &z])],
+            ),
+        );
+
+        let limits = |depth| RunLimits {
+            max_derivation_depth: depth,
+            ..Default::default()
+        };
+        let has_path = |w: &World, from: i64, to: i64| {
+            w.facts
+                .iter_all()
+                .any(|(_, f)| f == &fact(path, &[&int(from), &int(to)]))
+        };
+
+        w.run_with_limits(&syms, limits(Some(1))).unwrap();
+        assert!(has_path(&w, 1, 2));
+        assert!(!has_path(&w, 0, 2));
+        assert!(w.derivation_depth_reached);
+
+        // path(0, 2) has exactly the maximum depth
+        let mut exact = w.clone();
+        exact.derivation_depth_reached = false;
+        exact.run_with_limits(&syms, limits(Some(2))).unwrap();
+        assert!(has_path(&exact, 0, 2));
+        assert!(!exact.derivation_depth_reached);
+
+        // facts generated by a previous run keep their depth: path(0, 3)
+        // has a depth of 3, even though path(0, 2) already exists
+        exact.add_fact(&origin, fact(edge, &[&int(2), &int(3)]));
+        exact.run_with_limits(&syms, limits(Some(2))).unwrap();
+        assert!(has_path(&exact, 2, 3));
+        assert!(has_path(&exact, 1, 3));
+        assert!(!has_path(&exact, 0, 3));
+        assert!(exact.derivation_depth_reached);
+
+        exact.run_with_limits(&syms, limits(None)).unwrap();
+        assert!(has_path(&exact, 0, 3));
+    }
+
+    #[test]
+    fn symbol_table_lookup() {
+        let mut syms = SymbolTable::new();
+        assert!(syms.is_empty());
+        assert_eq!(syms.len(), DEFAULT_SYMBOLS.len());
+
+        let a = syms.insert("a");
+        let b = syms.insert("b");
+        assert_eq!(syms.len(), DEFAULT_SYMBOLS.len() + 2);
+        assert_eq!(syms.get("b"), Some(b));
+        assert_eq!(syms.get("write"), Some(SYM_WRITE));
+        assert_eq!(syms.get("c"), None);
+        assert_eq!(syms.get_symbol(a), Some("a"));
+        assert_eq!(syms.get_symbol(SYM_READ), Some("read"));
+
+        // out of range indexes, between the default symbols and the offset,
+        // after the last symbol, or too large for a usize
+        assert_eq!(syms.get_symbol(DEFAULT_SYMBOLS.len() as u64), None);
+        assert_eq!(syms.get_symbol(a - 1), None);
+        assert_eq!(syms.get_symbol(b + 1), None);
+        assert_eq!(syms.get_symbol(u64::MAX), None);
+        let temporary = TemporarySymbolTable::new(&syms);
+        assert_eq!(temporary.get_symbol(b), Some("b"));
+        assert_eq!(temporary.get_symbol(b + 1), None);
+
+        let all = syms.iter().collect::<Vec<_>>();
+        assert_eq!(all.len(), syms.len());
+        assert_eq!(all[0], (SYM_READ, "read"));
+        assert_eq!(&all[all.len() - 2..], &[(a, "a"), (b, "b")]);
+
+        let printed = syms.to_string();
+        assert!(printed.starts_with("0: read\n1: write\n"));
+        assert!(printed.ends_with(&format!("{}: a\n{}: b\n", a, b)));
+    }
 }
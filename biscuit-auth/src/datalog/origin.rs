@@ -1,11 +1,18 @@
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt::Display;
 use std::hash::Hash;
 use std::iter::FromIterator;
 
 use crate::token::Scope;
 
+/// origin of the facts imported from the authority blocks of secondary tokens
+///
+/// only the authorizer's rules, checks and policies trust it, the blocks of
+/// the token do not
+pub const SECONDARY_TOKENS_ORIGIN: usize = usize::MAX - 1;
+
 #[derive(Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Origin {
     pub(crate) inner: BTreeSet<usize>,
@@ -62,6 +69,8 @@ impl Display for Origin {
         if let Some(i) = it.next() {
             if *i == usize::MAX {
                 write!(f, "authorizer")?;
+            } else if *i == SECONDARY_TOKENS_ORIGIN {
+                write!(f, "secondary tokens")?;
             } else {
                 write!(f, "{i}")?;
             }
@@ -70,6 +79,8 @@ impl Display for Origin {
         for i in it {
             if *i == usize::MAX {
                 write!(f, ", authorizer")?;
+            } else if *i == SECONDARY_TOKENS_ORIGIN {
+                write!(f, ", secondary tokens")?;
             } else {
                 write!(f, ", {i}")?;
             }
@@ -100,12 +111,18 @@ impl TrustedOrigins {
             let mut origins = default_origins.clone();
             origins.0.insert(current_block);
             origins.0.insert(usize::MAX);
+            if current_block == usize::MAX {
+                origins.0.insert(SECONDARY_TOKENS_ORIGIN);
+            }
             return origins;
         }
 
         let mut origins = Origin::default();
         origins.insert(usize::MAX);
         origins.insert(current_block);
+        if current_block == usize::MAX {
+            origins.insert(SECONDARY_TOKENS_ORIGIN);
+        }
 
         for scope in rule_scopes {
             match scope {
@@ -118,7 +135,11 @@ impl TrustedOrigins {
                     }
                 }
                 Scope::PublicKey(key_id) => {
-                    if let Some(block_ids) = public_key_to_block_id.get(&(*key_id as usize)) {
+                    // a key id that does not fit in usize cannot match any block
+                    if let Some(block_ids) = usize::try_from(*key_id)
+                        .ok()
+                        .and_then(|key_id| public_key_to_block_id.get(&key_id))
+                    {
                         origins.extend(block_ids.iter())
                     }
                 }
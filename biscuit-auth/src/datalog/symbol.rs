@@ -1,5 +1,7 @@
 //! Symbol table implementation
 use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 pub type SymbolIndex = u64;
@@ -14,7 +16,12 @@ pub struct SymbolTable {
     pub(crate) public_keys: PublicKeys,
 }
 
-const DEFAULT_SYMBOLS: [&str; 28] = [
+/// symbols predefined in every implementation, they are not transmitted in tokens
+///
+/// a symbol's position in this list is its index in every symbol table, and
+/// blocks cannot redefine them ([`error::Format::SymbolTableOverlap`]). This list
+/// is part of the serialization format: changing it breaks existing tokens
+pub const DEFAULT_SYMBOLS: &[&str] = &[
     "read",
     "write",
     "resource",
@@ -45,6 +52,19 @@ const DEFAULT_SYMBOLS: [&str; 28] = [
     "query",
 ];
 
+/// index of `read` in [`DEFAULT_SYMBOLS`]
+pub const SYM_READ: SymbolIndex = 0;
+/// index of `write` in [`DEFAULT_SYMBOLS`]
+pub const SYM_WRITE: SymbolIndex = 1;
+/// index of `resource` in [`DEFAULT_SYMBOLS`]
+pub const SYM_RESOURCE: SymbolIndex = 2;
+/// index of `operation` in [`DEFAULT_SYMBOLS`]
+pub const SYM_OPERATION: SymbolIndex = 3;
+/// index of `right` in [`DEFAULT_SYMBOLS`]
+pub const SYM_RIGHT: SymbolIndex = 4;
+/// index of `time` in [`DEFAULT_SYMBOLS`]
+pub const SYM_TIME: SymbolIndex = 5;
+
 const OFFSET: usize = 1024;
 
 impl SymbolTable {
@@ -55,6 +75,11 @@ impl SymbolTable {
         }
     }
 
+    /// symbol table containing only the [`DEFAULT_SYMBOLS`]
+    pub fn default_table() -> Self {
+        SymbolTable::new()
+    }
+
     pub fn from(symbols: Vec<String>) -> Result<Self, error::Format> {
         let h1 = DEFAULT_SYMBOLS.iter().copied().collect::<HashSet<_>>();
         let h2 = symbols.iter().map(|s| s.as_str()).collect::<HashSet<_>>();
@@ -97,6 +122,9 @@ impl SymbolTable {
         Term::Str(term)
     }
 
+    /// returns the index of a symbol, if it is a default symbol or in the table
+    ///
+    /// see [`SymbolTable::get_symbol`] for the reverse lookup
     pub fn get(&self, s: &str) -> Option<SymbolIndex> {
         if let Some(index) = DEFAULT_SYMBOLS.iter().position(|sym| *sym == s) {
             return Some(index as u64);
@@ -112,6 +140,30 @@ impl SymbolTable {
         self.symbols.clone()
     }
 
+    /// number of symbols, including the [`DEFAULT_SYMBOLS`]
+    pub fn len(&self) -> usize {
+        DEFAULT_SYMBOLS.len() + self.symbols.len()
+    }
+
+    /// returns true if no symbols were added to the [`DEFAULT_SYMBOLS`]
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// iterates over the symbols and their indexes, starting with the [`DEFAULT_SYMBOLS`]
+    pub fn iter(&self) -> impl Iterator<Item = (SymbolIndex, &str)> + '_ {
+        DEFAULT_SYMBOLS
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (i as SymbolIndex, *s))
+            .chain(
+                self.symbols
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| ((OFFSET + i) as SymbolIndex, s.as_str())),
+            )
+    }
+
     pub fn current_offset(&self) -> usize {
         self.symbols.len()
     }
@@ -122,6 +174,24 @@ impl SymbolTable {
         table
     }
 
+    /// SHA-256 hash of the symbols added on top of the default ones
+    ///
+    /// returns `None` if there are no custom symbols
+    pub fn digest(&self) -> Option<Vec<u8>> {
+        use sha2::{Digest, Sha256};
+
+        if self.symbols.is_empty() {
+            return None;
+        }
+
+        let mut hasher = Sha256::new();
+        for symbol in &self.symbols {
+            hasher.update((symbol.len() as u64).to_le_bytes());
+            hasher.update(symbol.as_bytes());
+        }
+        Some(hasher.finalize().to_vec())
+    }
+
     pub fn is_disjoint(&self, other: &SymbolTable) -> bool {
         let h1 = self.symbols.iter().collect::<HashSet<_>>();
         let h2 = other.symbols.iter().collect::<HashSet<_>>();
@@ -129,13 +199,14 @@ impl SymbolTable {
         h1.is_disjoint(&h2)
     }
 
+    /// returns the symbol at this index, or `None` if the index is out of range
     pub fn get_symbol(&self, i: SymbolIndex) -> Option<&str> {
-        if i >= OFFSET as u64 {
-            self.symbols
-                .get((i - OFFSET as u64) as usize)
-                .map(|s| s.as_str())
+        // indexes come from the wire format and may not fit in a 32 bit usize
+        let i = usize::try_from(i).ok()?;
+        if i >= OFFSET {
+            self.symbols.get(i - OFFSET).map(|s| s.as_str())
         } else {
-            DEFAULT_SYMBOLS.get(i as usize).copied()
+            DEFAULT_SYMBOLS.get(i).copied()
         }
     }
 
@@ -282,12 +353,23 @@ impl SymbolTable {
     }
 }
 
+/// the standard base table, see [`SymbolTable::default_table`]
 impl Default for SymbolTable {
     fn default() -> Self {
         default_symbol_table()
     }
 }
 
+/// prints one symbol per line, prefixed by its index
+impl fmt::Display for SymbolTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, symbol) in self.iter() {
+            writeln!(f, "{}: {}", index, symbol)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TemporarySymbolTable<'a> {
     base: &'a SymbolTable,
@@ -307,10 +389,9 @@ impl<'a> TemporarySymbolTable<'a> {
     }
 
     pub fn get_symbol(&self, i: SymbolIndex) -> Option<&str> {
-        if i as usize >= self.offset {
-            self.symbols
-                .get(i as usize - self.offset)
-                .map(|s| s.as_str())
+        let index = usize::try_from(i).ok()?;
+        if index >= self.offset {
+            self.symbols.get(index - self.offset).map(|s| s.as_str())
         } else {
             self.base.get_symbol(i)
         }
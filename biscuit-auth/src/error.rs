@@ -7,27 +7,251 @@ use thiserror::Error;
 /// the global error type for Biscuit
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Token {
     #[error("internal error")]
     InternalError,
     #[error("error deserializing or verifying the token")]
-    Format(Format),
+    Format(#[source] Format),
     #[error("tried to append a block to a sealed token")]
     AppendOnSealed,
     #[error("tried to seal an already sealed token")]
     AlreadySealed,
+    #[error("the token is not sealed")]
+    NotSealed,
     #[error("authorization failed")]
-    FailedLogic(Logic),
+    FailedLogic(#[source] Logic),
     #[error("error generating Datalog: {0}")]
     Language(biscuit_parser::error::LanguageError),
     #[error("Reached Datalog execution limits")]
-    RunLimit(RunLimit),
+    RunLimit(#[source] RunLimit),
     #[error("Cannot convert from Term: {0}")]
     ConversionError(String),
     #[error("Cannot decode base64 token: {0}")]
     Base64(Base64Error),
     #[error("Datalog  execution failure: {0}")]
     Execution(Expression),
+    #[error("checks reference undefined predicates: {}", display_unknown_predicates(.0))]
+    UnknownPredicates(Vec<UnknownPredicate>),
+    #[error("invalid CIDR block: {0}")]
+    InvalidCidr(String),
+    #[error("blocks do not match the schema: {}", display_schema_violations(.0))]
+    SchemaViolations(Vec<SchemaViolation>),
+    #[error("the operation hierarchy contains a cycle: {}", .0.join(" -> "))]
+    OperationCycle(Vec<String>),
+    #[error("invalid policy document: {}", display_policy_syntax_errors(.0))]
+    PolicySyntax(Vec<PolicySyntaxError>),
+    #[error("invalid authorizer configuration: {}", .0.join(", "))]
+    InvalidConfiguration(Vec<String>),
+    #[error("the bundle was recorded with evaluation semantics version {actual}, this library implements version {expected}")]
+    EvaluationSemantics { expected: u32, actual: u32 },
+}
+
+/// broad categories of errors, as an example to map them to HTTP status codes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// the token or an input could not be deserialized or is inconsistent
+    Format,
+    /// a signature could not be verified, or the root key is unknown
+    Signature,
+    /// the token was valid but authorization failed or could not complete
+    Authorization,
+    /// the library was used incorrectly, like invalid Datalog code, policies
+    /// or configuration, or attenuating a sealed token
+    Usage,
+    /// an unexpected error in the library
+    Internal,
+}
+
+impl Token {
+    /// returns the category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Token::Format(
+                Format::Signature(_) | Format::SealedSignature | Format::UnknownPublicKey,
+            ) => ErrorCategory::Signature,
+            Token::Format(_)
+            | Token::Base64(_)
+            | Token::NotSealed
+            | Token::SchemaViolations(_)
+            | Token::EvaluationSemantics { .. } => ErrorCategory::Format,
+            Token::FailedLogic(_) | Token::RunLimit(_) | Token::Execution(_) => {
+                ErrorCategory::Authorization
+            }
+            Token::AppendOnSealed
+            | Token::AlreadySealed
+            | Token::Language(_)
+            | Token::ConversionError(_)
+            | Token::UnknownPredicates(_)
+            | Token::InvalidCidr(_)
+            | Token::OperationCycle(_)
+            | Token::PolicySyntax(_)
+            | Token::InvalidConfiguration(_) => ErrorCategory::Usage,
+            Token::InternalError => ErrorCategory::Internal,
+        }
+    }
+
+    /// the token or an input is malformed
+    pub fn is_format_error(&self) -> bool {
+        self.category() == ErrorCategory::Format
+    }
+
+    /// a signature was invalid or the root key was not recognized
+    pub fn is_signature_error(&self) -> bool {
+        self.category() == ErrorCategory::Signature
+    }
+
+    /// checks or policies failed, or the Datalog execution could not complete
+    pub fn is_authorization_error(&self) -> bool {
+        self.category() == ErrorCategory::Authorization
+    }
+
+    /// the library was used incorrectly, like invalid Datalog code or
+    /// authorizer configuration
+    pub fn is_usage_error(&self) -> bool {
+        self.category() == ErrorCategory::Usage
+    }
+
+    /// returns a stable identifier for this error
+    ///
+    /// codes will not change across versions of the crate, so they can be used
+    /// in logs, metrics or API responses. Nested errors expose the code of the
+    /// most specific variant:
+    ///
+    /// | code | error |
+    /// |------|-------|
+    /// | `internal_error` | [`Token::InternalError`] |
+    /// | `token.append_on_sealed` | [`Token::AppendOnSealed`] |
+    /// | `token.already_sealed` | [`Token::AlreadySealed`] |
+    /// | `token.not_sealed` | [`Token::NotSealed`] |
+    /// | `token.conversion_error` | [`Token::ConversionError`] |
+    /// | `token.unknown_predicates` | [`Token::UnknownPredicates`] |
+    /// | `token.invalid_cidr` | [`Token::InvalidCidr`] |
+    /// | `token.schema_violations` | [`Token::SchemaViolations`] |
+    /// | `token.operation_cycle` | [`Token::OperationCycle`] |
+    /// | `token.policy_syntax` | [`Token::PolicySyntax`] |
+    /// | `token.invalid_configuration` | [`Token::InvalidConfiguration`] |
+    /// | `token.evaluation_semantics` | [`Token::EvaluationSemantics`] |
+    /// | `language.parse_error` | [`LanguageError::ParseError`](biscuit_parser::error::LanguageError::ParseError) |
+    /// | `language.parameters` | [`LanguageError::Parameters`](biscuit_parser::error::LanguageError::Parameters) |
+    /// | `language.invalid_name` | [`LanguageError::InvalidName`](biscuit_parser::error::LanguageError::InvalidName) |
+    /// | `language.error` | other [`LanguageError`](biscuit_parser::error::LanguageError) variants |
+    /// | `format.base64` | [`Token::Base64`] |
+    /// | `format.*` | see [`Format::code`] |
+    /// | `logic.*` | see [`Logic::code`] |
+    /// | `run_limit.*` | see [`RunLimit::code`] |
+    /// | `execution.*` | see [`Expression::code`] |
+    pub fn code(&self) -> &'static str {
+        match self {
+            Token::InternalError => "internal_error",
+            Token::Format(e) => e.code(),
+            Token::AppendOnSealed => "token.append_on_sealed",
+            Token::AlreadySealed => "token.already_sealed",
+            Token::NotSealed => "token.not_sealed",
+            Token::FailedLogic(e) => e.code(),
+            Token::Language(biscuit_parser::error::LanguageError::ParseError(_)) => {
+                "language.parse_error"
+            }
+            Token::Language(biscuit_parser::error::LanguageError::Parameters { .. }) => {
+                "language.parameters"
+            }
+            Token::Language(biscuit_parser::error::LanguageError::InvalidName(_)) => {
+                "language.invalid_name"
+            }
+            Token::Language(_) => "language.error",
+            Token::RunLimit(e) => e.code(),
+            Token::ConversionError(_) => "token.conversion_error",
+            Token::Base64(_) => "format.base64",
+            Token::Execution(e) => e.code(),
+            Token::UnknownPredicates(_) => "token.unknown_predicates",
+            Token::InvalidCidr(_) => "token.invalid_cidr",
+            Token::SchemaViolations(_) => "token.schema_violations",
+            Token::OperationCycle(_) => "token.operation_cycle",
+            Token::PolicySyntax(_) => "token.policy_syntax",
+            Token::InvalidConfiguration(_) => "token.invalid_configuration",
+            Token::EvaluationSemantics { .. } => "token.evaluation_semantics",
+        }
+    }
+
+    /// creates a summary of this error that can be sent to clients
+    pub fn to_report(&self) -> ErrorReport {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(e) = source {
+            message.push_str(": ");
+            message.push_str(&e.to_string());
+            source = e.source();
+        }
+
+        let checks = match self {
+            Token::FailedLogic(Logic::Unauthorized { checks, .. })
+            | Token::FailedLogic(Logic::NoMatchingPolicy { checks }) => checks.as_slice(),
+            _ => &[],
+        };
+
+        ErrorReport {
+            category: self.category(),
+            message,
+            failed_checks: checks.iter().map(FailedCheckReport::from).collect(),
+        }
+    }
+}
+
+/// summary of an error, created by [`Token::to_report`]
+///
+/// it only contains the error message and the failed checks, never key material.
+/// With the `serde-error` feature, it serializes to the following JSON schema:
+///
+/// ```text
+/// {
+///   "category": "Format" | "Signature" | "Authorization" | "Usage" | "Internal",
+///   "message": string,
+///   "failed_checks": [
+///     { "block_id": number | null, "check_id": number, "rule": string }
+///   ]
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ErrorReport {
+    pub category: ErrorCategory,
+    /// error message, followed by the messages of the underlying errors
+    pub message: String,
+    /// checks that failed, for authorization errors
+    pub failed_checks: Vec<FailedCheckReport>,
+}
+
+/// a failed check in an [`ErrorReport`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct FailedCheckReport {
+    /// index of the block containing the check, `None` for authorizer checks
+    pub block_id: Option<u32>,
+    /// index of the check in the block or authorizer
+    pub check_id: u32,
+    /// pretty print of the rule that failed
+    pub rule: String,
+}
+
+impl From<&FailedCheck> for FailedCheckReport {
+    fn from(check: &FailedCheck) -> Self {
+        match check {
+            FailedCheck::Block(c) => FailedCheckReport {
+                block_id: Some(c.block_id),
+                check_id: c.check_id,
+                rule: c.rule.clone(),
+            },
+            FailedCheck::Authorizer(c) => FailedCheckReport {
+                block_id: None,
+                check_id: c.check_id,
+                rule: c.rule.clone(),
+            },
+        }
+    }
 }
 
 impl From<Infallible> for Token {
@@ -81,6 +305,7 @@ impl From<Execution> for Token {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Base64Error {
     InvalidByte(usize, u8),
     InvalidLength,
@@ -105,42 +330,43 @@ impl std::fmt::Display for Base64Error {
 /// signature
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Format {
     #[error("failed verifying the signature")]
-    Signature(Signature),
+    Signature(#[source] Signature),
     #[error("failed verifying the signature of a sealed token")]
     SealedSignature,
     #[error("the token does not provide intermediate public keys")]
     EmptyKeys,
     #[error("the root public key was not recognized")]
     UnknownPublicKey,
-    #[error("could not deserialize the wrapper object")]
+    #[error("could not deserialize the wrapper object: {0}")]
     DeserializationError(String),
-    #[error("could not serialize the wrapper object")]
+    #[error("could not serialize the wrapper object: {0}")]
     SerializationError(String),
-    #[error("could not deserialize the block")]
+    #[error("could not deserialize the block: {0}")]
     BlockDeserializationError(String),
-    #[error("could not serialize the block")]
+    #[error("could not serialize the block: {0}")]
     BlockSerializationError(String),
-    #[error("Block format version is higher than supported")]
+    #[error("Block format version {actual} is not supported (expected {minimum} to {maximum})")]
     Version {
         maximum: u32,
         minimum: u32,
         actual: u32,
     },
-    #[error("invalid key size")]
+    #[error("invalid key size: {0} bytes")]
     InvalidKeySize(usize),
-    #[error("invalid signature size")]
+    #[error("invalid signature size: {0} bytes")]
     InvalidSignatureSize(usize),
-    #[error("invalid key")]
+    #[error("invalid key: {0}")]
     InvalidKey(String),
-    #[error("could not deserialize signature")]
+    #[error("could not deserialize signature: {0}")]
     SignatureDeserializationError(String),
-    #[error("could not deserialize the block signature")]
+    #[error("could not deserialize the block signature: {0}")]
     BlockSignatureDeserializationError(String),
-    #[error("invalid block id")]
+    #[error("invalid block id: {0}")]
     InvalidBlockId(usize),
-    #[error("the public key is already present in previous blocks")]
+    #[error("the public key is already present in previous blocks: {0}")]
     ExistingPublicKey(String),
     #[error("multiple blocks declare the same symbols")]
     SymbolTableOverlap,
@@ -148,29 +374,115 @@ pub enum Format {
     PublicKeyTableOverlap,
     #[error("the external public key was not recognized")]
     UnknownExternalKey,
-    #[error("the symbol id was not in the table")]
+    #[error("the symbol id was not in the table: {0}")]
     UnknownSymbol(u64),
+    #[error("the token was built with a different symbol table")]
+    SymbolTableMismatch,
+    #[error("invalid predicate name: {0}")]
+    InvalidName(biscuit_parser::error::NameError),
+    #[error("rules and checks must contain at least one predicate or expression: {0}")]
+    EmptyRuleBody(String),
+}
+
+impl Format {
+    /// returns a stable identifier for this error
+    ///
+    /// | code | error |
+    /// |------|-------|
+    /// | `format.signature.*` | see [`Signature::code`] |
+    /// | `format.sealed_signature` | [`Format::SealedSignature`] |
+    /// | `format.empty_keys` | [`Format::EmptyKeys`] |
+    /// | `format.unknown_public_key` | [`Format::UnknownPublicKey`] |
+    /// | `format.deserialization` | [`Format::DeserializationError`] |
+    /// | `format.serialization` | [`Format::SerializationError`] |
+    /// | `format.block_deserialization` | [`Format::BlockDeserializationError`] |
+    /// | `format.block_serialization` | [`Format::BlockSerializationError`] |
+    /// | `format.version` | [`Format::Version`] |
+    /// | `format.invalid_key_size` | [`Format::InvalidKeySize`] |
+    /// | `format.invalid_signature_size` | [`Format::InvalidSignatureSize`] |
+    /// | `format.invalid_key` | [`Format::InvalidKey`] |
+    /// | `format.signature_deserialization` | [`Format::SignatureDeserializationError`] |
+    /// | `format.block_signature_deserialization` | [`Format::BlockSignatureDeserializationError`] |
+    /// | `format.invalid_block_id` | [`Format::InvalidBlockId`] |
+    /// | `format.existing_public_key` | [`Format::ExistingPublicKey`] |
+    /// | `format.symbol_table_overlap` | [`Format::SymbolTableOverlap`] |
+    /// | `format.public_key_table_overlap` | [`Format::PublicKeyTableOverlap`] |
+    /// | `format.unknown_external_key` | [`Format::UnknownExternalKey`] |
+    /// | `format.unknown_symbol` | [`Format::UnknownSymbol`] |
+    /// | `format.symbol_table_mismatch` | [`Format::SymbolTableMismatch`] |
+    /// | `format.invalid_name` | [`Format::InvalidName`] |
+    /// | `format.empty_rule_body` | [`Format::EmptyRuleBody`] |
+    pub fn code(&self) -> &'static str {
+        match self {
+            Format::Signature(e) => e.code(),
+            Format::SealedSignature => "format.sealed_signature",
+            Format::EmptyKeys => "format.empty_keys",
+            Format::UnknownPublicKey => "format.unknown_public_key",
+            Format::DeserializationError(_) => "format.deserialization",
+            Format::SerializationError(_) => "format.serialization",
+            Format::BlockDeserializationError(_) => "format.block_deserialization",
+            Format::BlockSerializationError(_) => "format.block_serialization",
+            Format::Version { .. } => "format.version",
+            Format::InvalidKeySize(_) => "format.invalid_key_size",
+            Format::InvalidSignatureSize(_) => "format.invalid_signature_size",
+            Format::InvalidKey(_) => "format.invalid_key",
+            Format::SignatureDeserializationError(_) => "format.signature_deserialization",
+            Format::BlockSignatureDeserializationError(_) => {
+                "format.block_signature_deserialization"
+            }
+            Format::InvalidBlockId(_) => "format.invalid_block_id",
+            Format::ExistingPublicKey(_) => "format.existing_public_key",
+            Format::SymbolTableOverlap => "format.symbol_table_overlap",
+            Format::PublicKeyTableOverlap => "format.public_key_table_overlap",
+            Format::UnknownExternalKey => "format.unknown_external_key",
+            Format::UnknownSymbol(_) => "format.unknown_symbol",
+            Format::SymbolTableMismatch => "format.symbol_table_mismatch",
+            Format::InvalidName(_) => "format.invalid_name",
+            Format::EmptyRuleBody(_) => "format.empty_rule_body",
+        }
+    }
 }
 
 /// Signature errors
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Signature {
     #[error("could not parse the signature elements")]
     InvalidFormat,
-    #[error("the signature did not match")]
+    #[error("the signature did not match: {0}")]
     InvalidSignature(String),
-    #[error("could not sign")]
+    #[error("could not sign: {0}")]
     InvalidSignatureGeneration(String),
 }
 
+impl Signature {
+    /// returns a stable identifier for this error
+    ///
+    /// | code | error |
+    /// |------|-------|
+    /// | `format.signature.invalid_format` | [`Signature::InvalidFormat`] |
+    /// | `format.signature.invalid_signature` | [`Signature::InvalidSignature`] |
+    /// | `format.signature.invalid_signature_generation` | [`Signature::InvalidSignatureGeneration`] |
+    pub fn code(&self) -> &'static str {
+        match self {
+            Signature::InvalidFormat => "format.signature.invalid_format",
+            Signature::InvalidSignature(_) => "format.signature.invalid_signature",
+            Signature::InvalidSignatureGeneration(_) => {
+                "format.signature.invalid_signature_generation"
+            }
+        }
+    }
+}
+
 /// errors in the Datalog evaluation
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Logic {
-    #[error("a rule provided by a block is generating facts with the authority or ambient tag, or has head variables not used in its body")]
+    #[error("a rule provided by a block is generating facts with the authority or ambient tag, or has head variables not used in its body (block {0}: {1})")]
     InvalidBlockRule(u32, String),
-    #[error("authorization failed")]
+    #[error("{policy}{}", display_failed_checks(checks))]
     Unauthorized {
         /// the policy that matched
         policy: MatchedPolicy,
@@ -179,66 +491,278 @@ pub enum Logic {
     },
     #[error("the authorizer already contains a token")]
     AuthorizerNotEmpty,
-    #[error("no matching policy was found")]
+    #[error("no matching policy was found{}", display_failed_checks(checks))]
     NoMatchingPolicy {
         /// list of checks that failed validation
         checks: Vec<FailedCheck>,
     },
+    #[error("the token carries revoked ids: {}", .0.join(", "))]
+    Revoked(Vec<String>),
+    #[error("expression {expression} of a rule provided by block {block} uses ${variable}, which is not bound by the rule's body: {rule}")]
+    UnboundExpressionVariable {
+        /// index of the block containing the rule or check
+        block: u32,
+        /// the rule or check query, as Datalog
+        rule: String,
+        /// index of the expression in the rule
+        expression: usize,
+        /// name of the unbound variable
+        variable: String,
+    },
+    #[error("the token does not carry a nonce")]
+    MissingNonce,
+    #[error("the token's nonce was already used: {0}")]
+    ReplayedNonce(String),
+    #[error("the token does not carry an issue timestamp")]
+    MissingTimestamp,
+    #[error("the token's issue timestamp is outside of the accepted window: {0}")]
+    TimestampOutsideWindow(String),
+    #[error("the token was issued at {0}, it is older than the maximum token age")]
+    TokenTooOld(String),
+}
+
+impl Logic {
+    /// returns a stable identifier for this error
+    ///
+    /// | code | error |
+    /// |------|-------|
+    /// | `logic.invalid_block_rule` | [`Logic::InvalidBlockRule`] |
+    /// | `logic.unauthorized` | [`Logic::Unauthorized`] |
+    /// | `logic.authorizer_not_empty` | [`Logic::AuthorizerNotEmpty`] |
+    /// | `logic.no_matching_policy` | [`Logic::NoMatchingPolicy`] |
+    /// | `logic.revoked` | [`Logic::Revoked`] |
+    /// | `logic.unbound_expression_variable` | [`Logic::UnboundExpressionVariable`] |
+    /// | `logic.missing_nonce` | [`Logic::MissingNonce`] |
+    /// | `logic.replayed_nonce` | [`Logic::ReplayedNonce`] |
+    /// | `logic.missing_timestamp` | [`Logic::MissingTimestamp`] |
+    /// | `logic.timestamp_outside_window` | [`Logic::TimestampOutsideWindow`] |
+    /// | `logic.token_too_old` | [`Logic::TokenTooOld`] |
+    pub fn code(&self) -> &'static str {
+        match self {
+            Logic::InvalidBlockRule(_, _) => "logic.invalid_block_rule",
+            Logic::Unauthorized { .. } => "logic.unauthorized",
+            Logic::AuthorizerNotEmpty => "logic.authorizer_not_empty",
+            Logic::NoMatchingPolicy { .. } => "logic.no_matching_policy",
+            Logic::Revoked(_) => "logic.revoked",
+            Logic::UnboundExpressionVariable { .. } => "logic.unbound_expression_variable",
+            Logic::MissingNonce => "logic.missing_nonce",
+            Logic::ReplayedNonce(_) => "logic.replayed_nonce",
+            Logic::MissingTimestamp => "logic.missing_timestamp",
+            Logic::TimestampOutsideWindow(_) => "logic.timestamp_outside_window",
+            Logic::TokenTooOld(_) => "logic.token_too_old",
+        }
+    }
 }
 
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum MatchedPolicy {
-    #[error("an allow policy matched")]
+    #[error("an allow policy matched (policy index: {0})")]
     Allow(usize),
-    #[error("a deny policy matched")]
+    #[error("a deny policy matched (policy index: {0})")]
     Deny(usize),
 }
 
 /// check errors
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum FailedCheck {
-    #[error("a check failed in a block")]
+    #[error("check {} of block {}{} failed: {}", .0.check_id, .0.block_id, display_token_id(.0.token_id), .0.rule)]
     Block(FailedBlockCheck),
-    #[error("a check provided by the authorizer failed")]
+    #[error("authorizer check {}{} failed: {}", .0.check_id, display_check_name(&.0.name), .0.rule)]
     Authorizer(FailedAuthorizerCheck),
 }
 
+impl FailedCheck {
+    /// application reason code of the check, if the authorizer had
+    /// [`DenialReasons`](crate::DenialReasons)
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            FailedCheck::Block(check) => check.reason.as_deref(),
+            FailedCheck::Authorizer(check) => check.reason.as_deref(),
+        }
+    }
+}
+
+/// a check provided by a block of the token that failed
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct FailedBlockCheck {
+    /// index of the block (the authority block has index 0)
     pub block_id: u32,
+    /// index of the check in the block
     pub check_id: u32,
     /// pretty print of the rule that failed
     pub rule: String,
+    /// index of the token containing the block: 0 for the token added with
+    /// [`Authorizer::add_token`](crate::Authorizer::add_token), then the ones
+    /// added with [`Authorizer::add_secondary_token`](crate::Authorizer::add_secondary_token)
+    #[cfg_attr(
+        feature = "serde-error",
+        serde(default, skip_serializing_if = "is_zero")
+    )]
+    pub token_id: u32,
+    /// application reason code, see [`DenialReasons`](crate::DenialReasons)
+    #[cfg_attr(
+        feature = "serde-error",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub reason: Option<String>,
 }
 
+/// a check provided by the authorizer that failed
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct FailedAuthorizerCheck {
+    /// index of the check in the authorizer
     pub check_id: u32,
     /// pretty print of the rule that failed
     pub rule: String,
+    /// name of the check, if it was added with
+    /// [`Authorizer::add_check_named`](crate::Authorizer::add_check_named)
+    #[cfg_attr(
+        feature = "serde-error",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub name: Option<String>,
+    /// application reason code, see [`DenialReasons`](crate::DenialReasons)
+    #[cfg_attr(
+        feature = "serde-error",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub reason: Option<String>,
+}
+
+/// a check referencing a predicate that is not defined by the token
+/// or provided by the authorizer
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct UnknownPredicate {
+    /// pretty print of the check
+    pub check: String,
+    /// name of the undefined predicate
+    pub predicate: String,
+}
+
+/// a fact, rule or check using a predicate that does not match a
+/// [`Schema`](crate::Schema)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct SchemaViolation {
+    /// index of the block, when checking a token
+    pub block_id: Option<usize>,
+    /// pretty print of the fact, rule or check
+    pub element: String,
+    /// name of the predicate
+    pub predicate: String,
+    pub mismatch: SchemaMismatch,
+}
+
+/// how a predicate differs from its schema
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum SchemaMismatch {
+    Arity {
+        expected: usize,
+        actual: usize,
+    },
+    /// the kinds are printed like [`TermKind`](crate::TermKind)
+    TermKind {
+        position: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(block_id) = self.block_id {
+            write!(f, "block {}: ", block_id)?;
+        }
+        match &self.mismatch {
+            SchemaMismatch::Arity { expected, actual } => write!(
+                f,
+                "{} has {} terms instead of {} in `{}`",
+                self.predicate, actual, expected, self.element
+            ),
+            SchemaMismatch::TermKind {
+                position,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} has a {} instead of a {} at position {} in `{}`",
+                self.predicate, actual, expected, position, self.element
+            ),
+        }
+    }
+}
+
+/// a statement of a policy document that could not be parsed, see
+/// [`Authorizer::add_policy_source`](crate::Authorizer::add_policy_source)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct PolicySyntaxError {
+    /// name of the document, like its file path
+    pub source: String,
+    /// line of the error, starting at 1
+    pub line: usize,
+    /// column of the error in characters, starting at 1
+    pub column: usize,
+    /// text of the statement from the error position
+    pub input: String,
+    pub message: Option<String>,
+}
+
+impl std::fmt::Display for PolicySyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: ", self.source, self.line, self.column)?;
+        if let Some(message) = &self.message {
+            write!(f, "{} ", message)?;
+        }
+        write!(f, "at `{}`", self.input)
+    }
 }
 
 /// Datalog execution errors
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Execution {
     #[error("Reached Datalog execution limits")]
-    RunLimit(RunLimit),
+    RunLimit(#[source] RunLimit),
     #[error("Expression execution failure")]
-    Expression(Expression),
+    Expression(#[source] Expression),
+}
+
+impl Execution {
+    /// returns a stable identifier for this error
+    ///
+    /// see [`RunLimit::code`] and [`Expression::code`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Execution::RunLimit(e) => e.code(),
+            Execution::Expression(e) => e.code(),
+        }
+    }
 }
 
 /// Datalog expression execution failure
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Expression {
-    #[error("Unknown symbol")]
+    #[error("Unknown symbol {0}")]
     UnknownSymbol(u64),
-    #[error("Unknown variable")]
+    #[error("Unknown variable {0}")]
     UnknownVariable(u32),
     #[error("Invalid type")]
     InvalidType,
@@ -250,9 +774,33 @@ pub enum Expression {
     InvalidStack,
 }
 
+impl Expression {
+    /// returns a stable identifier for this error
+    ///
+    /// | code | error |
+    /// |------|-------|
+    /// | `execution.unknown_symbol` | [`Expression::UnknownSymbol`] |
+    /// | `execution.unknown_variable` | [`Expression::UnknownVariable`] |
+    /// | `execution.invalid_type` | [`Expression::InvalidType`] |
+    /// | `execution.overflow` | [`Expression::Overflow`] |
+    /// | `execution.divide_by_zero` | [`Expression::DivideByZero`] |
+    /// | `execution.invalid_stack` | [`Expression::InvalidStack`] |
+    pub fn code(&self) -> &'static str {
+        match self {
+            Expression::UnknownSymbol(_) => "execution.unknown_symbol",
+            Expression::UnknownVariable(_) => "execution.unknown_variable",
+            Expression::InvalidType => "execution.invalid_type",
+            Expression::Overflow => "execution.overflow",
+            Expression::DivideByZero => "execution.divide_by_zero",
+            Expression::InvalidStack => "execution.invalid_stack",
+        }
+    }
+}
+
 /// runtime limits errors
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum RunLimit {
     #[error("too many facts generated")]
     TooManyFacts,
@@ -260,11 +808,119 @@ pub enum RunLimit {
     TooManyIterations,
     #[error("spent too much time verifying")]
     Timeout,
+    #[error("too many facts provided by resolvers")]
+    TooManyResolvedFacts,
+}
+
+impl RunLimit {
+    /// returns a stable identifier for this error
+    ///
+    /// | code | error |
+    /// |------|-------|
+    /// | `run_limit.too_many_facts` | [`RunLimit::TooManyFacts`] |
+    /// | `run_limit.too_many_iterations` | [`RunLimit::TooManyIterations`] |
+    /// | `run_limit.timeout` | [`RunLimit::Timeout`] |
+    /// | `run_limit.too_many_resolved_facts` | [`RunLimit::TooManyResolvedFacts`] |
+    pub fn code(&self) -> &'static str {
+        match self {
+            RunLimit::TooManyFacts => "run_limit.too_many_facts",
+            RunLimit::TooManyIterations => "run_limit.too_many_iterations",
+            RunLimit::Timeout => "run_limit.timeout",
+            RunLimit::TooManyResolvedFacts => "run_limit.too_many_resolved_facts",
+        }
+    }
+}
+
+/// errors when extracting a token from HTTP headers
+///
+/// a missing token usually maps to a 401 status, an invalid one to a 403
+#[cfg(feature = "http")]
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Request {
+    #[error("no token found in the request")]
+    MissingToken,
+    #[error("invalid token in the request: {0}")]
+    InvalidToken(#[source] Token),
+}
+
+#[cfg(feature = "http")]
+impl Request {
+    /// returns a stable identifier for this error
+    ///
+    /// | code | error |
+    /// |------|-------|
+    /// | `request.missing_token` | [`Request::MissingToken`] |
+    /// | `request.invalid_token` | [`Request::InvalidToken`] |
+    pub fn code(&self) -> &'static str {
+        match self {
+            Request::MissingToken => "request.missing_token",
+            Request::InvalidToken(_) => "request.invalid_token",
+        }
+    }
+}
+
+fn display_failed_checks(checks: &[FailedCheck]) -> String {
+    if checks.is_empty() {
+        return String::new();
+    }
+
+    let checks = checks
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(", failed checks: [{}]", checks)
+}
+
+fn display_check_name(name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!(" ({})", name),
+        None => String::new(),
+    }
+}
+
+fn display_token_id(token_id: u32) -> String {
+    match token_id {
+        0 => String::new(),
+        id => format!(" of token {}", id),
+    }
+}
+
+#[cfg(feature = "serde-error")]
+fn is_zero(id: &u32) -> bool {
+    *id == 0
+}
+
+fn display_schema_violations(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn display_policy_syntax_errors(errors: &[PolicySyntaxError]) -> String {
+    errors
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn display_unknown_predicates(predicates: &[UnknownPredicate]) -> String {
+    predicates
+        .iter()
+        .map(|p| format!("{} in `{}`", p.predicate, p.check))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
 
     #[test]
     fn error_format_strings() {
@@ -278,4 +934,285 @@ mod tests {
             "Cannot decode base64 token: Encoded text cannot have a 6-bit remainder."
         );
     }
+
+    fn error_chain(e: &dyn std::error::Error) -> String {
+        let mut res = e.to_string();
+        let mut source = e.source();
+        while let Some(e) = source {
+            res.push_str(" -> ");
+            res.push_str(&e.to_string());
+            source = e.source();
+        }
+        res
+    }
+
+    #[test]
+    fn error_messages() {
+        let signature = Token::Format(Format::Signature(Signature::InvalidSignature(
+            "signature error".to_string(),
+        )));
+        assert_eq!(
+            error_chain(&signature),
+            "error deserializing or verifying the token -> failed verifying the signature -> the signature did not match: signature error"
+        );
+
+        let failed_check = Token::FailedLogic(Logic::Unauthorized {
+            policy: MatchedPolicy::Allow(0),
+            checks: vec![
+                FailedCheck::Block(FailedBlockCheck {
+                    block_id: 1,
+                    check_id: 0,
+                    rule: "check if operation(\"read\")".to_string(),
+                    token_id: 0,
+                    reason: None,
+                }),
+                FailedCheck::Authorizer(FailedAuthorizerCheck {
+                    check_id: 2,
+                    rule: "check if right(\"read\")".to_string(),
+                    name: None,
+                    reason: None,
+                }),
+            ],
+        });
+        assert_eq!(failed_check.to_string(), "authorization failed");
+        assert_eq!(
+            error_chain(&failed_check),
+            "authorization failed -> an allow policy matched (policy index: 0), failed checks: [check 0 of block 1 failed: check if operation(\"read\"), authorizer check 2 failed: check if right(\"read\")]"
+        );
+
+        let no_policy = Token::FailedLogic(Logic::NoMatchingPolicy { checks: vec![] });
+        assert_eq!(
+            error_chain(&no_policy),
+            "authorization failed -> no matching policy was found"
+        );
+
+        let decode = Token::Format(Format::DeserializationError(
+            "deserialization error: invalid wire type".to_string(),
+        ));
+        assert_eq!(
+            error_chain(&decode),
+            "error deserializing or verifying the token -> could not deserialize the wrapper object: deserialization error: invalid wire type"
+        );
+
+        let limit = Token::RunLimit(RunLimit::Timeout);
+        assert_eq!(
+            error_chain(&limit),
+            "Reached Datalog execution limits -> spent too much time verifying"
+        );
+    }
+
+    #[test]
+    fn error_categories() {
+        let errors = vec![
+            (Token::InternalError, ErrorCategory::Internal),
+            (
+                Token::Format(Format::DeserializationError(String::new())),
+                ErrorCategory::Format,
+            ),
+            (
+                Token::Format(Format::Signature(Signature::InvalidFormat)),
+                ErrorCategory::Signature,
+            ),
+            (
+                Token::Format(Format::SealedSignature),
+                ErrorCategory::Signature,
+            ),
+            (
+                Token::Format(Format::UnknownPublicKey),
+                ErrorCategory::Signature,
+            ),
+            (Token::Format(Format::EmptyKeys), ErrorCategory::Format),
+            (Token::AppendOnSealed, ErrorCategory::Usage),
+            (Token::AlreadySealed, ErrorCategory::Usage),
+            (Token::NotSealed, ErrorCategory::Format),
+            (
+                Token::FailedLogic(Logic::NoMatchingPolicy { checks: vec![] }),
+                ErrorCategory::Authorization,
+            ),
+            (
+                Token::Language(biscuit_parser::error::LanguageError::ParseError(
+                    biscuit_parser::error::ParseErrors::default(),
+                )),
+                ErrorCategory::Usage,
+            ),
+            (
+                Token::RunLimit(RunLimit::TooManyFacts),
+                ErrorCategory::Authorization,
+            ),
+            (Token::ConversionError(String::new()), ErrorCategory::Usage),
+            (
+                Token::Base64(Base64Error::InvalidLength),
+                ErrorCategory::Format,
+            ),
+            (
+                Token::Execution(Expression::Overflow),
+                ErrorCategory::Authorization,
+            ),
+            (Token::UnknownPredicates(vec![]), ErrorCategory::Usage),
+            (Token::InvalidCidr(String::new()), ErrorCategory::Usage),
+            (Token::SchemaViolations(vec![]), ErrorCategory::Format),
+            (Token::OperationCycle(vec![]), ErrorCategory::Usage),
+            (Token::PolicySyntax(vec![]), ErrorCategory::Usage),
+            (Token::InvalidConfiguration(vec![]), ErrorCategory::Usage),
+            (
+                Token::EvaluationSemantics {
+                    expected: 0,
+                    actual: 0,
+                },
+                ErrorCategory::Format,
+            ),
+        ];
+
+        // every variant is listed, the match fails to compile if one is added
+        let variant = |error: &Token| match error {
+            Token::InternalError => 0,
+            Token::Format(_) => 1,
+            Token::AppendOnSealed => 2,
+            Token::AlreadySealed => 3,
+            Token::NotSealed => 4,
+            Token::FailedLogic(_) => 5,
+            Token::Language(_) => 6,
+            Token::RunLimit(_) => 7,
+            Token::ConversionError(_) => 8,
+            Token::Base64(_) => 9,
+            Token::Execution(_) => 10,
+            Token::UnknownPredicates(_) => 11,
+            Token::InvalidCidr(_) => 12,
+            Token::SchemaViolations(_) => 13,
+            Token::OperationCycle(_) => 14,
+            Token::PolicySyntax(_) => 15,
+            Token::InvalidConfiguration(_) => 16,
+            Token::EvaluationSemantics { .. } => 17,
+        };
+        let listed = errors
+            .iter()
+            .map(|(error, _)| variant(error))
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(listed, (0..18).collect());
+
+        for (error, category) in errors {
+            assert_eq!(error.category(), category, "{:?}", error);
+            let flags = [
+                error.is_format_error(),
+                error.is_signature_error(),
+                error.is_authorization_error(),
+                error.is_usage_error(),
+            ];
+            let expected = match category {
+                ErrorCategory::Format => [true, false, false, false],
+                ErrorCategory::Signature => [false, true, false, false],
+                ErrorCategory::Authorization => [false, false, true, false],
+                ErrorCategory::Usage => [false, false, false, true],
+                ErrorCategory::Internal => [false, false, false, false],
+            };
+            assert_eq!(flags, expected, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn error_codes() {
+        use biscuit_parser::error::{LanguageError, NameError, ParseErrors};
+
+        let errors = vec![
+            Token::InternalError,
+            Token::AppendOnSealed,
+            Token::AlreadySealed,
+            Token::NotSealed,
+            Token::ConversionError(String::new()),
+            Token::UnknownPredicates(vec![]),
+            Token::InvalidCidr(String::new()),
+            Token::SchemaViolations(vec![]),
+            Token::OperationCycle(vec![]),
+            Token::PolicySyntax(vec![]),
+            Token::InvalidConfiguration(vec![]),
+            Token::EvaluationSemantics {
+                expected: 0,
+                actual: 0,
+            },
+            Token::Language(LanguageError::ParseError(ParseErrors::default())),
+            Token::Language(LanguageError::Parameters {
+                missing_parameters: vec![],
+                unused_parameters: vec![],
+            }),
+            Token::Language(LanguageError::InvalidName(NameError::Empty)),
+            Token::Base64(Base64Error::InvalidLength),
+            Token::Format(Format::Signature(Signature::InvalidFormat)),
+            Token::Format(Format::Signature(
+                Signature::InvalidSignature(String::new()),
+            )),
+            Token::Format(Format::Signature(Signature::InvalidSignatureGeneration(
+                String::new(),
+            ))),
+            Token::Format(Format::SealedSignature),
+            Token::Format(Format::EmptyKeys),
+            Token::Format(Format::UnknownPublicKey),
+            Token::Format(Format::DeserializationError(String::new())),
+            Token::Format(Format::SerializationError(String::new())),
+            Token::Format(Format::BlockDeserializationError(String::new())),
+            Token::Format(Format::BlockSerializationError(String::new())),
+            Token::Format(Format::Version {
+                maximum: 0,
+                minimum: 0,
+                actual: 0,
+            }),
+            Token::Format(Format::InvalidKeySize(0)),
+            Token::Format(Format::InvalidSignatureSize(0)),
+            Token::Format(Format::InvalidKey(String::new())),
+            Token::Format(Format::SignatureDeserializationError(String::new())),
+            Token::Format(Format::BlockSignatureDeserializationError(String::new())),
+            Token::Format(Format::InvalidBlockId(0)),
+            Token::Format(Format::ExistingPublicKey(String::new())),
+            Token::Format(Format::SymbolTableOverlap),
+            Token::Format(Format::PublicKeyTableOverlap),
+            Token::Format(Format::UnknownExternalKey),
+            Token::Format(Format::UnknownSymbol(0)),
+            Token::Format(Format::SymbolTableMismatch),
+            Token::Format(Format::InvalidName(NameError::Empty)),
+            Token::Format(Format::EmptyRuleBody(String::new())),
+            Token::FailedLogic(Logic::InvalidBlockRule(0, String::new())),
+            Token::FailedLogic(Logic::Unauthorized {
+                policy: MatchedPolicy::Allow(0),
+                checks: vec![],
+            }),
+            Token::FailedLogic(Logic::AuthorizerNotEmpty),
+            Token::FailedLogic(Logic::NoMatchingPolicy { checks: vec![] }),
+            Token::FailedLogic(Logic::Revoked(vec![])),
+            Token::FailedLogic(Logic::UnboundExpressionVariable {
+                block: 0,
+                rule: String::new(),
+                expression: 0,
+                variable: String::new(),
+            }),
+            Token::FailedLogic(Logic::MissingNonce),
+            Token::FailedLogic(Logic::ReplayedNonce(String::new())),
+            Token::FailedLogic(Logic::MissingTimestamp),
+            Token::FailedLogic(Logic::TimestampOutsideWindow(String::new())),
+            Token::FailedLogic(Logic::TokenTooOld(String::new())),
+            Token::RunLimit(RunLimit::TooManyFacts),
+            Token::RunLimit(RunLimit::TooManyIterations),
+            Token::RunLimit(RunLimit::Timeout),
+            Token::RunLimit(RunLimit::TooManyResolvedFacts),
+            Token::Execution(Expression::UnknownSymbol(0)),
+            Token::Execution(Expression::UnknownVariable(0)),
+            Token::Execution(Expression::InvalidType),
+            Token::Execution(Expression::Overflow),
+            Token::Execution(Expression::DivideByZero),
+            Token::Execution(Expression::InvalidStack),
+        ];
+
+        let codes = errors.iter().map(|e| e.code()).collect::<HashSet<_>>();
+        assert_eq!(codes.len(), errors.len());
+
+        assert_eq!(
+            Token::Format(Format::Signature(
+                Signature::InvalidSignature(String::new())
+            ))
+            .code(),
+            "format.signature.invalid_signature"
+        );
+        assert_eq!(
+            Token::from(Execution::RunLimit(RunLimit::Timeout)).code(),
+            Execution::RunLimit(RunLimit::Timeout).code()
+        );
+    }
 }
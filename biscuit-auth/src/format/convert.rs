@@ -43,6 +43,8 @@ pub fn token_block_to_proto_block(input: &Block) -> schema::Block {
             .iter()
             .map(|key| key.to_proto())
             .collect(),
+        created_at: input.created_at,
+        symbol_table_digest: None,
     }
 }
 
@@ -110,9 +112,65 @@ pub fn proto_block_to_token_block(
         external_key,
         public_keys,
         scopes: scopes?,
+        created_at: input.created_at,
     })
 }
 
+/// checks the names of the predicates used in a block, with the rules of
+/// [`validate_name`](crate::builder::validate_name)
+pub fn check_predicate_names(
+    input: &schema::Block,
+    symbols: &SymbolTable,
+) -> Result<(), error::Format> {
+    let rules = input.rules_v2.iter().chain(
+        input
+            .checks_v2
+            .iter()
+            .flat_map(|check| check.queries.iter()),
+    );
+    let predicates = input
+        .facts_v2
+        .iter()
+        .map(|fact| &fact.predicate)
+        .chain(rules.flat_map(|rule| std::iter::once(&rule.head).chain(rule.body.iter())));
+
+    for predicate in predicates {
+        let name = symbols
+            .get_symbol(predicate.name)
+            .ok_or(error::Format::UnknownSymbol(predicate.name))?;
+        crate::builder::validate_name(name, crate::builder::MAX_NAME_LENGTH)
+            .map_err(error::Format::InvalidName)?;
+    }
+
+    Ok(())
+}
+
+/// rejects rules and check queries without predicates or expressions, see
+/// [`Rule::validate_body`](crate::builder::Rule::validate_body)
+pub fn check_rule_bodies(
+    input: &schema::Block,
+    symbols: &SymbolTable,
+) -> Result<(), error::Format> {
+    let is_empty = |rule: &schema::RuleV2| rule.body.is_empty() && rule.expressions.is_empty();
+    let head = |rule: &schema::RuleV2| {
+        symbols
+            .get_symbol(rule.head.name)
+            .unwrap_or("<unknown>")
+            .to_string()
+    };
+
+    if let Some(rule) = input.rules_v2.iter().find(|rule| is_empty(rule)) {
+        return Err(error::Format::EmptyRuleBody(format!("rule {}", head(rule))));
+    }
+    for (i, check) in input.checks_v2.iter().enumerate() {
+        if check.queries.iter().any(is_empty) {
+            return Err(error::Format::EmptyRuleBody(format!("check {}", i)));
+        }
+    }
+
+    Ok(())
+}
+
 pub fn token_block_to_proto_snapshot_block(input: &Block) -> schema::SnapshotBlock {
     schema::SnapshotBlock {
         context: input.context.clone(),
@@ -202,6 +260,7 @@ pub fn proto_snapshot_block_to_token_block(
         external_key,
         public_keys: PublicKeys::default(),
         scopes: scopes?,
+        created_at: None,
     })
 }
 pub fn authorizer_to_proto_authorizer(input: &AuthorizerPolicies) -> schema::AuthorizerPolicies {
@@ -350,7 +409,11 @@ pub mod v2 {
             }
         };
 
-        Ok(Check { queries, kind })
+        Ok(Check {
+            queries,
+            kind,
+            name: None,
+        })
     }
 
     pub fn policy_to_proto_policy(
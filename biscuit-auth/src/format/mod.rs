@@ -31,12 +31,35 @@ use self::convert::*;
 ///
 /// This structure contains the blocks serialized to byte arrays. Those arrays
 /// will be used for the signature
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct SerializedBiscuit {
     pub root_key_id: Option<u32>,
     pub authority: crypto::Block,
+    /// blocks added after the authority block
+    ///
+    /// block indices are not part of the format: the index of a block is its
+    /// position in this list plus one, so they are always contiguous and ordered
     pub blocks: Vec<crypto::Block>,
     pub proof: crypto::TokenNext,
+    /// serialized form of the whole container, as received or generated
+    pub(crate) bytes: Vec<u8>,
+}
+
+/// the proof and the serialized form contain the next private key if the
+/// token is not sealed, so they are not shown
+impl std::fmt::Debug for SerializedBiscuit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SerializedBiscuit")
+            .field("root_key_id", &self.root_key_id)
+            .field("authority", &self.authority)
+            .field("blocks", &self.blocks)
+            .field("proof", &format_args!("<proof redacted>"))
+            .field(
+                "bytes",
+                &format_args!("<{} bytes redacted>", self.bytes.len()),
+            )
+            .finish()
+    }
 }
 
 impl SerializedBiscuit {
@@ -156,6 +179,7 @@ impl SerializedBiscuit {
             authority,
             blocks,
             proof,
+            bytes: slice.to_vec(),
         };
 
         Ok(deser)
@@ -182,6 +206,8 @@ impl SerializedBiscuit {
         })?;
 
         symbols.extend(&SymbolTable::from(authority.symbols.clone())?)?;
+        check_predicate_names(&authority, symbols)?;
+        check_rule_bodies(&authority, symbols)?;
 
         for pk in &authority.public_keys {
             symbols
@@ -205,9 +231,15 @@ impl SerializedBiscuit {
             if let Some(external_signature) = &block.external_signature {
                 symbols.public_keys.insert(&external_signature.public_key);
                 block_external_keys.push(Some(external_signature.public_key));
+                // third party blocks use their own symbol table
+                let block_symbols = SymbolTable::from(deser.symbols.clone())?;
+                check_predicate_names(&deser, &block_symbols)?;
+                check_rule_bodies(&deser, &block_symbols)?;
             } else {
                 block_external_keys.push(None);
                 symbols.extend(&SymbolTable::from(deser.symbols.clone())?)?;
+                check_predicate_names(&deser, symbols)?;
+                check_rule_bodies(&deser, symbols)?;
             }
 
             for pk in &deser.public_keys {
@@ -292,22 +324,26 @@ impl SerializedBiscuit {
     }
 
     /// creates a new token
+    ///
+    /// the digest of the custom symbols (see [`SymbolTable::digest`]) is stored
+    /// in the authority block, so it is covered by the root signature
     pub fn new(
         root_key_id: Option<u32>,
+        symbol_table_digest: Option<Vec<u8>>,
         root_keypair: &KeyPair,
         next_keypair: &KeyPair,
         authority: &Block,
     ) -> Result<Self, error::Token> {
         let mut v = Vec::new();
-        token_block_to_proto_block(authority)
-            .encode(&mut v)
-            .map_err(|e| {
-                error::Format::SerializationError(format!("serialization error: {:?}", e))
-            })?;
+        let mut block = token_block_to_proto_block(authority);
+        block.symbol_table_digest = symbol_table_digest;
+        block.encode(&mut v).map_err(|e| {
+            error::Format::SerializationError(format!("serialization error: {:?}", e))
+        })?;
 
         let signature = crypto::sign(root_keypair, next_keypair, &v)?;
 
-        Ok(SerializedBiscuit {
+        SerializedBiscuit {
             root_key_id,
             authority: crypto::Block {
                 data: v,
@@ -317,10 +353,15 @@ impl SerializedBiscuit {
             },
             blocks: vec![],
             proof: TokenNext::Secret(next_keypair.private()),
-        })
+            bytes: Vec::new(),
+        }
+        .encoded()
     }
 
     /// adds a new block, serializes it and sign a new token
+    ///
+    /// the existing blocks are copied in their serialized form, only the new
+    /// block is encoded
     pub fn append(
         &self,
         next_keypair: &KeyPair,
@@ -350,12 +391,14 @@ impl SerializedBiscuit {
             external_signature,
         });
 
-        Ok(SerializedBiscuit {
+        SerializedBiscuit {
             root_key_id: self.root_key_id,
             authority: self.authority.clone(),
             blocks,
             proof: TokenNext::Secret(next_keypair.private()),
-        })
+            bytes: Vec::new(),
+        }
+        .encoded()
     }
 
     /// adds a new block, serializes it and sign a new token
@@ -383,15 +426,21 @@ impl SerializedBiscuit {
             external_signature,
         });
 
-        Ok(SerializedBiscuit {
+        SerializedBiscuit {
             root_key_id: self.root_key_id,
             authority: self.authority.clone(),
             blocks,
             proof: TokenNext::Secret(next_keypair.private()),
-        })
+            bytes: Vec::new(),
+        }
+        .encoded()
     }
 
     /// checks the signature on a deserialized token
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(blocks = self.blocks.len() + 1))
+    )]
     pub fn verify(&self, root: &PublicKey) -> Result<(), error::Format> {
         //FIXME: try batched signature verification
         let mut current_pub = root;
@@ -465,12 +514,41 @@ impl SerializedBiscuit {
             .map_err(error::Signature::InvalidSignatureGeneration)
             .map_err(error::Format::Signature)?;
 
-        Ok(SerializedBiscuit {
+        SerializedBiscuit {
             root_key_id: self.root_key_id,
             authority: self.authority.clone(),
             blocks: self.blocks.clone(),
             proof: TokenNext::Seal(signature),
-        })
+            bytes: Vec::new(),
+        }
+        .encoded()
+    }
+
+    /// verifies that the symbol table used to parse the token is the one
+    /// it was built with
+    pub(crate) fn check_symbol_table(&self, symbols: &SymbolTable) -> Result<(), error::Format> {
+        let authority = schema::Block::decode(&self.authority.data[..]).map_err(|e| {
+            error::Format::BlockDeserializationError(format!("error deserializing block: {:?}", e))
+        })?;
+
+        if authority.symbol_table_digest == symbols.digest() {
+            Ok(())
+        } else {
+            Err(error::Format::SymbolTableMismatch)
+        }
+    }
+
+    /// returns the serialized form of the container
+    ///
+    /// for a deserialized token, these are the bytes it was parsed from
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// stores the serialized form of a newly generated container
+    fn encoded(mut self) -> Result<Self, error::Token> {
+        self.bytes = self.to_vec()?;
+        Ok(self)
     }
 }
 
@@ -75,6 +75,10 @@ pub struct Block {
     pub scope: ::prost::alloc::vec::Vec<Scope>,
     #[prost(message, repeated, tag="8")]
     pub public_keys: ::prost::alloc::vec::Vec<PublicKey>,
+    #[prost(uint64, optional, tag="9")]
+    pub created_at: ::core::option::Option<u64>,
+    #[prost(bytes="vec", optional, tag="10")]
+    pub symbol_table_digest: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Scope {
@@ -298,6 +302,8 @@ pub struct RunLimits {
     pub max_iterations: u64,
     #[prost(uint64, required, tag="3")]
     pub max_time: u64,
+    #[prost(uint32, optional, tag="4")]
+    pub max_derivation_depth: ::core::option::Option<u32>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct AuthorizerWorld {
@@ -0,0 +1,234 @@
+//! extracting tokens from HTTP headers, available with the `http` feature
+//!
+//! the token is read from the `Authorization` header, with the `Bearer` or
+//! `Biscuit` scheme, then from an optional [`Fallback`] header or cookie.
+//! Its content is the URL safe base64 encoding produced by [`Biscuit::to_base64`]
+use std::convert::TryFrom;
+
+use ::http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE};
+
+use crate::{error, Biscuit, PublicKey};
+
+/// where to look for the token if the `Authorization` header does not contain one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fallback {
+    /// a header containing the token without a scheme
+    Header(HeaderName),
+    /// the name of a cookie containing the token
+    Cookie(String),
+}
+
+impl Biscuit {
+    /// deserializes the token from the `Authorization` header, and
+    /// validates its signature with the root public key
+    ///
+    /// [`error::Request::MissingToken`] is returned if no header has a
+    /// `Bearer` or `Biscuit` scheme, [`error::Request::InvalidToken`] if
+    /// the token cannot be decoded or verified
+    pub fn from_request_parts(
+        headers: &HeaderMap,
+        root: PublicKey,
+    ) -> Result<Self, error::Request> {
+        Biscuit::from_request_parts_with_fallback(headers, root, None)
+    }
+
+    /// same as [`Biscuit::from_request_parts`], also looking for the
+    /// token in `fallback` when the `Authorization` header does not contain one
+    pub fn from_request_parts_with_fallback(
+        headers: &HeaderMap,
+        root: PublicKey,
+        fallback: Option<&Fallback>,
+    ) -> Result<Self, error::Request> {
+        let token = authorization_token(headers)
+            .or_else(|| fallback.and_then(|fallback| fallback_token(headers, fallback)))
+            .ok_or(error::Request::MissingToken)?;
+
+        Biscuit::from_base64(token, root).map_err(error::Request::InvalidToken)
+    }
+
+    /// sets the `Authorization` header to `Bearer <token>`, replacing
+    /// any existing value
+    pub fn insert_into_headers(&self, headers: &mut HeaderMap) -> Result<(), error::Token> {
+        let value = HeaderValue::try_from(format!("Bearer {}", self.to_base64()?))
+            .map_err(|_| error::Token::InternalError)?;
+        headers.insert(AUTHORIZATION, value);
+        Ok(())
+    }
+}
+
+// first `Authorization` value with the `Bearer` or `Biscuit` scheme.
+// Other schemes (like `Basic`) are ignored: they do not carry a token
+fn authorization_token(headers: &HeaderMap) -> Option<&[u8]> {
+    headers.get_all(AUTHORIZATION).iter().find_map(|value| {
+        let value = value.as_bytes();
+        let position = value.iter().position(|c| *c == b' ')?;
+        let (scheme, token) = value.split_at(position);
+
+        if scheme.eq_ignore_ascii_case(b"bearer") || scheme.eq_ignore_ascii_case(b"biscuit") {
+            Some(trim(token))
+        } else {
+            None
+        }
+    })
+}
+
+fn fallback_token<'a>(headers: &'a HeaderMap, fallback: &Fallback) -> Option<&'a [u8]> {
+    match fallback {
+        Fallback::Header(name) => headers.get(name).map(|value| trim(value.as_bytes())),
+        Fallback::Cookie(name) => headers
+            .get_all(COOKIE)
+            .iter()
+            .flat_map(|value| value.as_bytes().split(|c| *c == b';'))
+            .find_map(|cookie| {
+                let cookie = trim(cookie);
+                let position = cookie.iter().position(|c| *c == b'=')?;
+                let (cookie_name, token) = cookie.split_at(position);
+                if cookie_name == name.as_bytes() {
+                    Some(&token[1..])
+                } else {
+                    None
+                }
+            }),
+    }
+}
+
+fn trim(mut s: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = s {
+        s = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = s {
+        s = rest;
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyPair;
+
+    fn token(root: &KeyPair) -> Biscuit {
+        let mut builder = Biscuit::builder();
+        builder.add_fact("user(1234)").unwrap();
+        builder.build(root).unwrap()
+    }
+
+    #[test]
+    fn authorization_header() {
+        let root = KeyPair::new();
+        let token = token(&root);
+
+        let mut headers = HeaderMap::new();
+        token.insert_into_headers(&mut headers).unwrap();
+        let parsed = Biscuit::from_request_parts(&headers, root.public()).unwrap();
+        assert_eq!(parsed.to_base64(), token.to_base64());
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            AUTHORIZATION,
+            HeaderValue::from_static("Basic dXNlcjpwYXNz"),
+        );
+        headers.append(
+            AUTHORIZATION,
+            HeaderValue::try_from(format!("biscuit  {} ", token.to_base64().unwrap())).unwrap(),
+        );
+        assert!(Biscuit::from_request_parts(&headers, root.public()).is_ok());
+
+        // the header is replaced
+        token.insert_into_headers(&mut headers).unwrap();
+        assert_eq!(headers.get_all(AUTHORIZATION).iter().count(), 1);
+    }
+
+    #[test]
+    fn missing_or_invalid() {
+        let root = KeyPair::new();
+        let token = token(&root);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            Biscuit::from_request_parts(&headers, root.public()).unwrap_err(),
+            error::Request::MissingToken
+        );
+
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Basic dXNlcjpwYXNz"),
+        );
+        assert_eq!(
+            Biscuit::from_request_parts(&headers, root.public()).unwrap_err(),
+            error::Request::MissingToken
+        );
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer"));
+        assert_eq!(
+            Biscuit::from_request_parts(&headers, root.public()).unwrap_err(),
+            error::Request::MissingToken
+        );
+
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Bearer not a token"),
+        );
+        assert!(matches!(
+            Biscuit::from_request_parts(&headers, root.public()),
+            Err(error::Request::InvalidToken(error::Token::Base64(_)))
+        ));
+
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_bytes(b"Bearer \xff\xfe").unwrap(),
+        );
+        assert!(matches!(
+            Biscuit::from_request_parts(&headers, root.public()),
+            Err(error::Request::InvalidToken(_))
+        ));
+
+        // valid token, signed by another root key
+        token.insert_into_headers(&mut headers).unwrap();
+        assert!(matches!(
+            Biscuit::from_request_parts(&headers, KeyPair::new().public()),
+            Err(error::Request::InvalidToken(error::Token::Format(_)))
+        ));
+    }
+
+    #[test]
+    fn fallback() {
+        let root = KeyPair::new();
+        let token = token(&root);
+        let encoded = token.to_base64().unwrap();
+
+        let header = Fallback::Header(HeaderName::from_static("x-biscuit"));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-biscuit",
+            HeaderValue::try_from(encoded.as_str()).unwrap(),
+        );
+        assert_eq!(
+            Biscuit::from_request_parts(&headers, root.public()).unwrap_err(),
+            error::Request::MissingToken
+        );
+        assert!(
+            Biscuit::from_request_parts_with_fallback(&headers, root.public(), Some(&header))
+                .is_ok()
+        );
+
+        let cookie = Fallback::Cookie("biscuit".to_string());
+        let mut headers = HeaderMap::new();
+        headers.append(COOKIE, HeaderValue::from_static("theme=dark"));
+        headers.append(
+            COOKIE,
+            HeaderValue::try_from(format!("session=1; biscuit={}; lang=en", encoded)).unwrap(),
+        );
+        assert!(
+            Biscuit::from_request_parts_with_fallback(&headers, root.public(), Some(&cookie))
+                .is_ok()
+        );
+
+        headers.insert(COOKIE, HeaderValue::from_static("biscuits=1; malformed"));
+        assert_eq!(
+            Biscuit::from_request_parts_with_fallback(&headers, root.public(), Some(&cookie))
+                .unwrap_err(),
+            error::Request::MissingToken
+        );
+    }
+}
@@ -52,8 +52,8 @@
 //!     biscuit.to_vec()?
 //!   };
 //!
-//!   // this token is only 249 bytes, holding the authority data and the signature
-//!   assert_eq!(token1.len(), 249);
+//!   // this token is only 255 bytes, holding the authority data and the signature
+//!   assert_eq!(token1.len(), 255);
 //!
 //!   // now let's add some restrictions to this token
 //!   // we want to limit access to `/a/file1.txt` and to read operations
@@ -75,8 +75,8 @@
 //!     biscuit.to_vec()?
 //!   };
 //!
-//!   // this new token fits in 385 bytes
-//!   assert_eq!(token2.len(), 385);
+//!   // this new token fits in 397 bytes
+//!   assert_eq!(token2.len(), 397);
 //!
 //!   /************** VERIFICATION ****************/
 //!
@@ -227,11 +227,19 @@ pub mod parser;
 mod token;
 
 pub use crypto::{KeyPair, PrivateKey, PublicKey};
-pub use token::authorizer::{Authorizer, AuthorizerLimits};
+pub use token::authorizer::{
+    AuditRecord, AuthorizationBundle, Authorizer, AuthorizerBuilder, AuthorizerConfig,
+    AuthorizerLimits, AuthorizerMetrics, AuthorizerReport, CheckResult, CheckStatus, DenialReasons,
+    FactProvenance, FactResolver, NonceStore, PathNormalization, QueryDiagnostics, Replay,
+    TokenSummary, WorldFact, AUDIT_RECORD_VERSION, EVALUATION_SEMANTICS_VERSION,
+};
+#[cfg(feature = "serde_json")]
+pub use token::authorizer::{JsonFacts, JsonLimits, JsonWarning};
 pub use token::builder;
 pub use token::builder_ext;
+pub use token::predicate_schema::{Schema, TermKind};
 pub use token::unverified::UnverifiedBiscuit;
-pub use token::Biscuit;
+pub use token::{Biscuit, BlockRef, Rights, SealedBiscuit, MAX_TOKEN_SIZE};
 pub use token::{ThirdPartyBlock, ThirdPartyRequest};
 
 #[cfg(cargo_c)]
@@ -242,6 +250,18 @@ pub use capi::*;
 
 mod time;
 
+#[cfg(any(feature = "wasm", test))]
+#[cfg_attr(feature = "docsrs", doc(cfg(feature = "wasm")))]
+pub mod wasm;
+
+#[cfg(feature = "http")]
+#[cfg_attr(feature = "docsrs", doc(cfg(feature = "http")))]
+pub mod http;
+
+#[cfg(any(feature = "testing", test))]
+#[cfg_attr(feature = "docsrs", doc(cfg(feature = "testing")))]
+pub mod testing;
+
 /// Procedural macros to construct Datalog policies
 #[cfg(feature = "datalog-macro")]
 #[cfg_attr(feature = "docsrs", doc(cfg(feature = "datalog-macro")))]
@@ -25,6 +25,7 @@ mod tests {
     };
     use biscuit_parser::parser::*;
     use nom::error::ErrorKind;
+    use std::convert::TryInto;
 
     #[derive(Debug, PartialEq)]
     enum Expr {
@@ -78,7 +79,7 @@ mod tests {
             biscuit_parser::parser::rule(
                 "right($0, \"read\") <- resource( $0), operation(\"read\")"
             )
-            .map(|(i, o)| (i, o.into())),
+            .map(|(i, o)| (i, o.try_into().unwrap())),
             Ok((
                 "",
                 builder::rule(
@@ -99,7 +100,7 @@ mod tests {
         use std::time::{Duration, SystemTime};
 
         assert_eq!(
-            biscuit_parser::parser::rule("valid_date(\"file1\") <- time($0 ), resource(\"file1\"), $0 <= 2019-12-04T09:46:41+00:00").map(|(i, o)| (i, o.into())),
+            biscuit_parser::parser::rule("valid_date(\"file1\") <- time($0 ), resource(\"file1\"), $0 <= 2019-12-04T09:46:41+00:00").map(|(i, o)| (i, o.try_into().unwrap())),
             Ok((
                 "",
                 builder::constrained_rule(
@@ -129,7 +130,7 @@ mod tests {
         use std::time::{Duration, SystemTime};
 
         assert_eq!(
-            biscuit_parser::parser::rule("valid_date(\"file1\") <- time( $0 ), $0 <= 2019-12-04T09:46:41+00:00, resource(\"file1\")").map(|(i, o)| (i, o.into())),
+            biscuit_parser::parser::rule("valid_date(\"file1\") <- time( $0 ), $0 <= 2019-12-04T09:46:41+00:00, resource(\"file1\")").map(|(i, o)| (i, o.try_into().unwrap())),
             Ok((
                 "",
                 builder::constrained_rule(
@@ -172,7 +173,7 @@ mod tests {
             biscuit_parser::parser::check(
                 "check if resource( $0), operation(\"read\") or admin(\"authority\")"
             )
-            .map(|(i, o)| (i, o.into())),
+            .map(|(i, o)| (i, o.try_into().unwrap())),
             Ok((
                 "",
                 builder::Check {
@@ -192,6 +193,7 @@ mod tests {
                         ),
                     ],
                     kind: CheckKind::One,
+                    name: None,
                 }
             ))
         );
@@ -476,6 +478,7 @@ mod tests {
                     }],
                 )],
                 kind: CheckKind::One,
+                name: None,
             },
             Check {
                 queries: vec![
@@ -494,6 +497,7 @@ mod tests {
                     ),
                 ],
                 kind: CheckKind::One,
+                name: None,
             },
             Check {
                 queries: vec![constrained_rule(
@@ -513,6 +517,7 @@ mod tests {
                     }],
                 )],
                 kind: CheckKind::One,
+                name: None,
             },
         ];
 
@@ -551,7 +556,7 @@ mod tests {
             result
                 .rules
                 .drain(..)
-                .map(|(_, r)| r.into())
+                .map(|(_, r)| r.try_into().unwrap())
                 .collect::<Vec<builder::Rule>>(),
             expected_rules
         );
@@ -559,7 +564,7 @@ mod tests {
             result
                 .checks
                 .drain(..)
-                .map(|(_, r)| r.into())
+                .map(|(_, r)| r.try_into().unwrap())
                 .collect::<Vec<builder::Check>>(),
             expected_checks
         );
@@ -567,7 +572,7 @@ mod tests {
             result
                 .policies
                 .drain(..)
-                .map(|(_, r)| r.into())
+                .map(|(_, r)| r.try_into().unwrap())
                 .collect::<Vec<builder::Policy>>(),
             expected_policies
         );
@@ -634,6 +639,7 @@ mod tests {
                     }],
                 )],
                 kind: CheckKind::One,
+                name: None,
             },
             Check {
                 queries: vec![
@@ -652,6 +658,7 @@ mod tests {
                     ),
                 ],
                 kind: CheckKind::One,
+                name: None,
             },
             Check {
                 queries: vec![constrained_rule(
@@ -671,6 +678,7 @@ mod tests {
                     }],
                 )],
                 kind: CheckKind::One,
+                name: None,
             },
         ];
 
@@ -687,7 +695,7 @@ mod tests {
             result
                 .rules
                 .drain(..)
-                .map(|(_, r)| r.into())
+                .map(|(_, r)| r.try_into().unwrap())
                 .collect::<Vec<builder::Rule>>(),
             expected_rules
         );
@@ -695,7 +703,7 @@ mod tests {
             result
                 .checks
                 .drain(..)
-                .map(|(_, r)| r.into())
+                .map(|(_, r)| r.try_into().unwrap())
                 .collect::<Vec<builder::Check>>(),
             expected_checks
         );
@@ -0,0 +1,605 @@
+//! generators for property based testing, available with the `testing` feature
+//!
+//! [`Arbitrary`] follows the quickcheck model: a value is generated from a
+//! random number generator, and a value that makes a property fail can be
+//! shrunk into smaller candidates. Generated Datalog is always valid (rules
+//! only use variables bound in their body) and tokens are built with a
+//! deterministic RNG and a fixed root key, so a failing case can be replayed.
+//!
+//! ```ignore
+//! use biscuit_auth::testing::{check_property, ArbitraryBiscuit};
+//!
+//! check_property(20, |token: &ArbitraryBiscuit| {
+//!     token.build().block_count() == token.blocks.len() + 1
+//! });
+//! ```
+use std::collections::BTreeSet;
+use std::fmt;
+
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use crate::builder::{
+    fact, string, Binary, BlockBuilder, Check, CheckKind, Expression, Fact, Op, Policy, PolicyKind,
+    Predicate, Rule, Scope, Term, Unary,
+};
+use crate::{Biscuit, KeyPair};
+
+/// names used for generated predicates. The list is short so that facts and
+/// rules from different blocks can match each other
+const PREDICATE_NAMES: &[&str] = &["a", "b", "c", "resource", "operation"];
+const VARIABLES: &[&str] = &["x", "y", "z"];
+const STRINGS: &[&str] = &["", "file1", "read", "write", "hello world"];
+
+/// a type that can be randomly generated and shrunk
+pub trait Arbitrary: Clone + fmt::Debug + Sized {
+    /// generates a random value
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self;
+
+    /// returns smaller values to try when a property fails on `self`
+    fn shrink(&self) -> Vec<Self> {
+        Vec::new()
+    }
+}
+
+/// runs `property` on `cases` generated values
+///
+/// the values come from a fixed seed, so runs are reproducible. If the
+/// property fails, the value is shrunk as long as a smaller one still fails,
+/// then this function panics with the smallest failing value
+pub fn check_property<T: Arbitrary, F: Fn(&T) -> bool>(cases: usize, property: F) {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+
+    for case in 0..cases {
+        let value = T::arbitrary(&mut rng);
+        if !property(&value) {
+            let minimal = shrink_failure(value, &property);
+            panic!(
+                "property failed on case {}, minimal failing value: {:#?}",
+                case, minimal
+            );
+        }
+    }
+}
+
+fn shrink_failure<T: Arbitrary, F: Fn(&T) -> bool>(mut value: T, property: &F) -> T {
+    'outer: loop {
+        for candidate in value.shrink() {
+            if !property(&candidate) {
+                value = candidate;
+                continue 'outer;
+            }
+        }
+        return value;
+    }
+}
+
+/// shrinks a list by removing one element, or shrinking one element
+fn shrink_vec<T: Arbitrary>(v: &[T]) -> Vec<Vec<T>> {
+    let mut res = Vec::new();
+    for i in 0..v.len() {
+        let mut smaller = v.to_vec();
+        smaller.remove(i);
+        res.push(smaller);
+    }
+    for (i, element) in v.iter().enumerate() {
+        for shrunk in element.shrink() {
+            let mut smaller = v.to_vec();
+            smaller[i] = shrunk;
+            res.push(smaller);
+        }
+    }
+    res
+}
+
+fn choose<'a, R: Rng + ?Sized>(rng: &mut R, values: &[&'a str]) -> &'a str {
+    values[rng.gen_range(0, values.len())]
+}
+
+/// generates ground terms: variables and parameters are never generated
+impl Arbitrary for Term {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        match rng.gen_range(0, 6) {
+            0 => Term::Integer(rng.gen_range(-100, 100)),
+            1 => Term::Str(choose(rng, STRINGS).to_string()),
+            2 => Term::Date(rng.gen_range(0, 2_000_000_000)),
+            3 => {
+                let len = rng.gen_range(0, 4);
+                Term::Bytes((0..len).map(|_| rng.gen()).collect())
+            }
+            4 => Term::Bool(rng.gen()),
+            _ => {
+                let len = rng.gen_range(0, 3);
+                Term::Set(
+                    (0..len)
+                        .map(|_| Term::Integer(rng.gen_range(0, 10)))
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            Term::Integer(0) => Vec::new(),
+            Term::Integer(i) => vec![Term::Integer(0), Term::Integer(i / 2)],
+            Term::Str(s) if !s.is_empty() => vec![Term::Str(String::new())],
+            Term::Date(d) if *d != 0 => vec![Term::Date(0)],
+            Term::Bytes(b) if !b.is_empty() => vec![Term::Bytes(Vec::new())],
+            Term::Bool(true) => vec![Term::Bool(false)],
+            Term::Set(s) if !s.is_empty() => vec![Term::Set(BTreeSet::new())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// generates predicates with ground terms
+impl Arbitrary for Predicate {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let len = rng.gen_range(0, 3);
+        Predicate::new(
+            choose(rng, PREDICATE_NAMES).to_string(),
+            (0..len).map(|_| Term::arbitrary(rng)).collect::<Vec<_>>(),
+        )
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        shrink_vec(&self.terms)
+            .into_iter()
+            .map(|terms| Predicate::new(self.name.clone(), terms))
+            .collect()
+    }
+}
+
+impl Arbitrary for Fact {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let predicate = Predicate::arbitrary(rng);
+        Fact::new(predicate.name, predicate.terms)
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        self.predicate
+            .shrink()
+            .into_iter()
+            .map(|p| Fact::new(p.name, p.terms))
+            .collect()
+    }
+}
+
+/// generates rules whose head only uses variables bound by the body
+impl Arbitrary for Rule {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let body_len = rng.gen_range(1, 3);
+        let mut body = Vec::new();
+        let mut bound = Vec::new();
+        for _ in 0..body_len {
+            let len = rng.gen_range(1, 3);
+            let terms = (0..len)
+                .map(|_| {
+                    if rng.gen() {
+                        let name = choose(rng, VARIABLES);
+                        bound.push(name);
+                        Term::Variable(name.to_string())
+                    } else {
+                        Term::arbitrary(rng)
+                    }
+                })
+                .collect::<Vec<_>>();
+            body.push(Predicate::new(
+                choose(rng, PREDICATE_NAMES).to_string(),
+                terms,
+            ));
+        }
+
+        let head_len = rng.gen_range(0, 3);
+        let head_terms = (0..head_len)
+            .map(|_| {
+                if !bound.is_empty() && rng.gen() {
+                    Term::Variable(choose(rng, &bound).to_string())
+                } else {
+                    Term::arbitrary(rng)
+                }
+            })
+            .collect::<Vec<_>>();
+        let head = Predicate::new(choose(rng, PREDICATE_NAMES).to_string(), head_terms);
+
+        let expressions = if bound.is_empty() {
+            Vec::new()
+        } else {
+            (0..rng.gen_range(0, 3))
+                .map(|_| expression(rng, &bound))
+                .collect()
+        };
+        let scopes = match rng.gen_range(0, 4) {
+            0 => vec![Scope::Authority],
+            1 => vec![Scope::Previous],
+            _ => Vec::new(),
+        };
+
+        Rule::new(head, body, expressions, scopes)
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        // removing a body predicate could unbind a variable, so only the
+        // head, expressions and scopes are shrunk
+        let mut res = Vec::new();
+        for i in 0..self.head.terms.len() {
+            let mut rule = self.clone();
+            rule.head.terms.remove(i);
+            res.push(rule);
+        }
+        for i in 0..self.expressions.len() {
+            let mut rule = self.clone();
+            rule.expressions.remove(i);
+            res.push(rule);
+        }
+        if !self.scopes.is_empty() {
+            let mut rule = self.clone();
+            rule.scopes.clear();
+            res.push(rule);
+        }
+        res
+    }
+}
+
+const COMPARISONS: &[Binary] = &[
+    Binary::LessThan,
+    Binary::GreaterThan,
+    Binary::LessOrEqual,
+    Binary::GreaterOrEqual,
+    Binary::Equal,
+    Binary::NotEqual,
+];
+
+/// generates a boolean expression over variables bound by the rule body.
+/// Its evaluation can still fail if the variables have unexpected types
+fn expression<R: Rng + ?Sized>(rng: &mut R, bound: &[&str]) -> Expression {
+    let variable = Op::Value(Term::Variable(choose(rng, bound).to_string()));
+    let comparison = Op::Binary(COMPARISONS[rng.gen_range(0, COMPARISONS.len())].clone());
+
+    let mut ops = match rng.gen_range(0, 5) {
+        0 => vec![variable, Op::Value(Term::arbitrary(rng)), comparison],
+        1 => vec![
+            variable,
+            Op::Value(Term::Variable(choose(rng, bound).to_string())),
+            comparison,
+        ],
+        2 => vec![
+            Op::Value(Term::Set(
+                (0..rng.gen_range(0, 3))
+                    .map(|_| Term::Integer(rng.gen_range(0, 10)))
+                    .collect(),
+            )),
+            variable,
+            Op::Binary(Binary::Contains),
+        ],
+        3 => vec![
+            variable,
+            Op::Value(string(choose(rng, STRINGS))),
+            Op::Binary(if rng.gen() {
+                Binary::Prefix
+            } else {
+                Binary::Suffix
+            }),
+        ],
+        _ => vec![
+            variable,
+            Op::Unary(Unary::Length),
+            Op::Value(Term::Integer(rng.gen_range(0, 5))),
+            comparison,
+        ],
+    };
+    if rng.gen_range(0, 4) == 0 {
+        ops.push(Op::Unary(Unary::Parens));
+        ops.push(Op::Unary(Unary::Negate));
+    }
+
+    Expression { ops }
+}
+
+impl Arbitrary for Check {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let len = rng.gen_range(1, 3);
+        Check {
+            queries: (0..len).map(|_| query(rng)).collect(),
+            kind: if rng.gen() {
+                CheckKind::One
+            } else {
+                CheckKind::All
+            },
+            name: None,
+        }
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        shrink_vec(&self.queries)
+            .into_iter()
+            .filter(|queries| !queries.is_empty())
+            .map(|queries| Check {
+                queries,
+                kind: self.kind.clone(),
+                name: self.name.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Arbitrary for Policy {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let len = rng.gen_range(1, 3);
+        Policy {
+            queries: (0..len).map(|_| query(rng)).collect(),
+            kind: if rng.gen() {
+                PolicyKind::Allow
+            } else {
+                PolicyKind::Deny
+            },
+        }
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        shrink_vec(&self.queries)
+            .into_iter()
+            .filter(|queries| !queries.is_empty())
+            .map(|queries| Policy {
+                queries,
+                kind: self.kind.clone(),
+            })
+            .collect()
+    }
+}
+
+/// a rule with an empty `query` head, as used in checks and policies
+fn query<R: Rng + ?Sized>(rng: &mut R) -> Rule {
+    let rule = Rule::arbitrary(rng);
+    Rule::new(
+        Predicate::new("query".to_string(), Vec::<Term>::new()),
+        rule.body,
+        rule.expressions,
+        rule.scopes,
+    )
+}
+
+/// generates the facts, rules and checks of a block
+impl Arbitrary for BlockBuilder {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut block = BlockBuilder::new();
+        for _ in 0..rng.gen_range(0, 5) {
+            block.facts.push(Fact::arbitrary(rng));
+        }
+        for _ in 0..rng.gen_range(0, 3) {
+            block.rules.push(Rule::arbitrary(rng));
+        }
+        for _ in 0..rng.gen_range(0, 3) {
+            block.checks.push(Check::arbitrary(rng));
+        }
+        block
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        let mut res = Vec::new();
+        for facts in shrink_vec(&self.facts) {
+            res.push(BlockBuilder {
+                facts,
+                ..self.clone()
+            });
+        }
+        for rules in shrink_vec(&self.rules) {
+            res.push(BlockBuilder {
+                rules,
+                ..self.clone()
+            });
+        }
+        for checks in shrink_vec(&self.checks) {
+            res.push(BlockBuilder {
+                checks,
+                ..self.clone()
+            });
+        }
+        res
+    }
+}
+
+/// the contents of a token, built with [`ArbitraryBiscuit::build`]
+#[derive(Clone, Debug)]
+pub struct ArbitraryBiscuit {
+    pub authority: BlockBuilder,
+    pub blocks: Vec<BlockBuilder>,
+}
+
+impl ArbitraryBiscuit {
+    /// the root key pair used to sign every generated token
+    pub fn root() -> KeyPair {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        KeyPair::new_with_rng(&mut rng)
+    }
+
+    /// builds the token, signed by [`ArbitraryBiscuit::root`]
+    ///
+    /// block key pairs come from a fixed seed, so the same contents
+    /// always produce the same token
+    pub fn build(&self) -> Biscuit {
+        let root = Self::root();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+
+        let mut builder = Biscuit::builder();
+        builder
+            .merge(self.authority.clone())
+            .expect("generated authority blocks have no schema");
+        let mut token = builder
+            .build_with_rng(&root, crate::token::default_symbol_table(), &mut rng)
+            .expect("generated authority blocks are valid");
+
+        for block in &self.blocks {
+            let keypair = KeyPair::new_with_rng(&mut rng);
+            token = token
+                .append_with_keypair(&keypair, block.clone())
+                .expect("generated blocks are valid");
+        }
+        token
+    }
+}
+
+/// builds a token with `blocks` blocks (the authority block included), signed by
+/// [`ArbitraryBiscuit::root`], to be used as a shared fixture by benchmarks
+///
+/// the authority block holds `facts` facts `right("file{i}", "read")` and a
+/// check on the operation. Each attenuation block checks the resource, the
+/// operation and the time. Authorizing it with `resource("file0")`,
+/// `operation("read")` and the current time succeeds
+pub fn sample_token(blocks: usize, facts: usize) -> Biscuit {
+    assert!(blocks > 0, "a token contains at least an authority block");
+    let root = ArbitraryBiscuit::root();
+    let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+
+    let mut builder = Biscuit::builder();
+    for i in 0..facts {
+        builder
+            .add_fact(fact(
+                "right",
+                &[string(&format!("file{}", i)), string("read")],
+            ))
+            .unwrap();
+    }
+    builder
+        .add_check(r#"check if operation($op), ["read", "write"].contains($op)"#)
+        .unwrap();
+    let mut token = builder
+        .build_with_rng(&root, crate::token::default_symbol_table(), &mut rng)
+        .unwrap();
+
+    for _ in 1..blocks {
+        let mut block = BlockBuilder::new();
+        block
+            .add_check(r#"check if resource($r), operation("read"), right($r, "read")"#)
+            .unwrap();
+        block
+            .add_check("check if time($t), $t < 2100-01-01T00:00:00Z")
+            .unwrap();
+        let keypair = KeyPair::new_with_rng(&mut rng);
+        token = token.append_with_keypair(&keypair, block).unwrap();
+    }
+    token
+}
+
+impl Arbitrary for ArbitraryBiscuit {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        ArbitraryBiscuit {
+            authority: BlockBuilder::arbitrary(rng),
+            blocks: (0..rng.gen_range(0, 3))
+                .map(|_| BlockBuilder::arbitrary(rng))
+                .collect(),
+        }
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        let mut res = Vec::new();
+        for blocks in shrink_vec(&self.blocks) {
+            res.push(ArbitraryBiscuit {
+                authority: self.authority.clone(),
+                blocks,
+            });
+        }
+        for authority in self.authority.shrink() {
+            res.push(ArbitraryBiscuit {
+                authority,
+                blocks: self.blocks.clone(),
+            });
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder_ext::AuthorizerExt;
+    use crate::{error, Authorizer, AuthorizerLimits};
+    use std::time::Duration;
+
+    #[test]
+    fn serialization_fixed_point() {
+        check_property(50, |token: &ArbitraryBiscuit| {
+            let serialized = token.build().to_vec().unwrap();
+            let parsed = Biscuit::from(&serialized, ArbitraryBiscuit::root().public()).unwrap();
+
+            parsed.to_vec().unwrap() == serialized
+        });
+    }
+
+    #[test]
+    fn rule_conversion_round_trip() {
+        use crate::builder::Convert;
+        use crate::datalog::SymbolTable;
+
+        check_property(200, |rule: &Rule| {
+            let mut symbols = SymbolTable::new();
+            let converted = rule.convert(&mut symbols);
+            let back = match Rule::convert_from(&converted, &symbols) {
+                Ok(rule) => rule,
+                Err(_) => return false,
+            };
+
+            back.convert(&mut symbols) == converted
+        });
+    }
+
+    #[test]
+    fn stable_authorization() {
+        fn authorize(token: &Biscuit, policies: &[Policy]) -> Result<usize, error::Token> {
+            let mut authorizer = Authorizer::new();
+            authorizer.add_token(token)?;
+            for policy in policies {
+                authorizer.add_policy(policy.clone())?;
+            }
+            authorizer.add_allow_all();
+            // the default time limit can be hit when tests run in parallel,
+            // and make the two authorizations differ
+            authorizer.authorize_with_limits(AuthorizerLimits {
+                max_time: Duration::from_secs(10),
+                ..Default::default()
+            })
+        }
+
+        check_property(50, |token: &ArbitraryBiscuit| {
+            let mut rng: StdRng = SeedableRng::seed_from_u64(2);
+            let policies = (0..2)
+                .map(|_| Policy::arbitrary(&mut rng))
+                .collect::<Vec<_>>();
+
+            let token = token.build();
+            let serialized = token.to_vec().unwrap();
+            let parsed = Biscuit::from(&serialized, ArbitraryBiscuit::root().public()).unwrap();
+
+            authorize(&token, &policies) == authorize(&parsed, &policies)
+        });
+    }
+
+    #[test]
+    fn shrinks_to_minimal_value() {
+        let minimal = shrink_failure(
+            ArbitraryBiscuit {
+                authority: BlockBuilder::arbitrary(&mut StdRng::seed_from_u64(3)),
+                blocks: vec![BlockBuilder::new(), BlockBuilder::new()],
+            },
+            &|token: &ArbitraryBiscuit| token.blocks.is_empty(),
+        );
+
+        assert_eq!(minimal.blocks.len(), 1);
+        assert!(minimal.authority.facts.is_empty());
+        assert!(minimal.authority.rules.is_empty());
+        assert!(minimal.authority.checks.is_empty());
+    }
+
+    #[test]
+    fn sample_token_authorizes() {
+        let token = sample_token(3, 10);
+        assert_eq!(token.block_count(), 3);
+
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer.add_fact(r#"resource("file0")"#).unwrap();
+        authorizer.add_fact(r#"operation("read")"#).unwrap();
+        authorizer.set_time();
+        authorizer.allow().unwrap();
+        authorizer.authorize().unwrap();
+    }
+}
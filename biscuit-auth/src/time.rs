@@ -44,6 +44,34 @@ extern "C" {
     fn performance_now() -> f64;
 }
 
+#[cfg(target_arch = "wasm32")]
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(inline_js = r#"
+export function date_now() {
+  return Date.now();
+}"#)]
+extern "C" {
+    fn date_now() -> f64;
+}
+
+/// current time, from the system clock
+///
+/// `SystemTime::now()` panics on `wasm32-unknown-unknown`, so the time
+/// is obtained from the JS `Date` object instead
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+pub fn now() -> SystemTime {
+    SystemTime::now()
+}
+
+/// current time, from the system clock
+///
+/// `SystemTime::now()` panics on `wasm32-unknown-unknown`, so the time
+/// is obtained from the JS `Date` object instead
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(date_now() as u64)
+}
+
 #[cfg(target_arch = "wasm32")]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Instant(u64);
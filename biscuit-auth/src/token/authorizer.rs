@@ -1,31 +1,44 @@
 //! Authorizer structure and associated functions
 use super::builder::{
-    constrained_rule, date, fact, pred, rule, string, var, Binary, BlockBuilder, Check, Expression,
-    Fact, Op, Policy, PolicyKind, Rule, Scope, Term,
+    ascii_case_insensitive, client_ip_fact, constrained_rule, date, fact, int, integer_check,
+    operation_fact, pred, resource_check, resource_fact, rule, string, time_fact, var, Binary,
+    BlockBuilder, Check, Expression, Fact, Op, Policy, PolicyKind, Rule, Scope, Term,
 };
 use super::builder_ext::{AuthorizerExt, BuilderExt};
+use super::predicate_schema::{Schema, TermKind};
 use super::{Biscuit, Block};
 use crate::builder::{CheckKind, Convert};
 use crate::crypto::PublicKey;
-use crate::datalog::{self, Origin, RunLimits, SymbolTable, TrustedOrigins};
+use crate::datalog::{self, Origin, RunLimits, RunStats, SymbolTable, TrustedOrigins};
 use crate::error;
 use crate::time::Instant;
 use crate::token;
-use biscuit_parser::parser::parse_source;
+use biscuit_parser::parser::{parse_source, SourceResult};
 use prost::Message;
 use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
 use std::time::Duration;
 use std::{
     collections::HashMap,
     convert::{TryFrom, TryInto},
     default::Default,
     fmt::Write,
+    net::IpAddr,
     time::SystemTime,
 };
 
+mod bundle;
+pub use bundle::*;
+mod config;
+pub use config::*;
 mod snapshot;
 pub use snapshot::*;
 
+#[cfg(feature = "serde_json")]
+mod json;
+#[cfg(feature = "serde_json")]
+pub use json::*;
+
 /// used to check authorization policies on a token
 ///
 /// can be created from [Biscuit::authorizer] or [Authorizer::new]
@@ -40,6 +53,32 @@ pub struct Authorizer {
     public_key_to_block_id: HashMap<usize, Vec<usize>>,
     limits: AuthorizerLimits,
     execution_time: Duration,
+    check_results: Vec<CheckResult>,
+    matched_policy: Option<error::MatchedPolicy>,
+    derived_facts: usize,
+    signature_time: Duration,
+    fail_fast: bool,
+    order_by_cost: bool,
+    max_bindings: usize,
+    run_stats: RunStats,
+    schema: Option<Schema>,
+    nonce: Option<Vec<u8>>,
+    issued_at: Option<SystemTime>,
+    max_token_age: Option<Duration>,
+    require_issued_at: bool,
+    clock_skew: Duration,
+    secondary_tokens: Vec<Biscuit>,
+    imported_facts: HashMap<datalog::Fact, usize>,
+    denial_reasons: Option<DenialReasons>,
+    resolvers: HashMap<String, Arc<FactResolver>>,
+    token_summary: Option<TokenSummary>,
+    time: Option<SystemTime>,
+    // number of facts of the authorizer block already in the world, and
+    // converted rules of the authorizer block, shared by its clones, see
+    // `AuthorizerConfig`
+    compiled_facts: usize,
+    compiled_rules: Arc<Vec<datalog::Rule>>,
+    diagnostics: bool,
 }
 
 impl Authorizer {
@@ -74,6 +113,29 @@ impl Authorizer {
             public_key_to_block_id: HashMap::new(),
             limits: AuthorizerLimits::default(),
             execution_time: Duration::default(),
+            check_results: vec![],
+            matched_policy: None,
+            derived_facts: 0,
+            signature_time: Duration::default(),
+            fail_fast: false,
+            order_by_cost: false,
+            max_bindings: 0,
+            run_stats: RunStats::default(),
+            schema: None,
+            nonce: None,
+            issued_at: None,
+            max_token_age: None,
+            require_issued_at: true,
+            clock_skew: Duration::default(),
+            secondary_tokens: Vec::new(),
+            imported_facts: HashMap::new(),
+            denial_reasons: None,
+            resolvers: HashMap::new(),
+            token_summary: None,
+            time: None,
+            compiled_facts: 0,
+            compiled_rules: Arc::new(Vec::new()),
+            diagnostics: false,
         }
     }
 
@@ -83,10 +145,31 @@ impl Authorizer {
     }
 
     /// add a token to an empty authorizer
+    ///
+    /// a [`SealedBiscuit`](crate::SealedBiscuit) is authorized with
+    /// [`SealedBiscuit::authorize`](crate::SealedBiscuit::authorize) instead
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(blocks = token.block_count(), facts = tracing::field::Empty)
+        )
+    )]
     pub fn add_token(&mut self, token: &Biscuit) -> Result<(), error::Token> {
         if self.blocks.is_some() {
             return Err(error::Logic::AuthorizerNotEmpty.into());
         }
+        if let Some(schema) = &self.schema {
+            let violations = schema.check_token(token)?;
+            if !violations.is_empty() {
+                return Err(error::Token::SchemaViolations(violations));
+            }
+        }
+        self.signature_time = token.verification_time;
+        self.nonce = token.nonce();
+        self.issued_at = token.issued_at();
+        self.token_summary = Some(TokenSummary::new(token));
 
         for (key_id, block_ids) in &token.public_key_to_block_id {
             let key = token
@@ -111,6 +194,8 @@ impl Authorizer {
         }
 
         self.blocks = Some(blocks);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("facts", self.world.facts.len());
         self.token_origins = TrustedOrigins::from_scopes(
             &[token::Scope::Previous],
             &TrustedOrigins::default(),
@@ -121,6 +206,23 @@ impl Authorizer {
         Ok(())
     }
 
+    /// adds another token, like a service token presented with the user's token
+    ///
+    /// each token is verified against its own root key when it is deserialized.
+    /// During authorization, the checks of the secondary tokens are evaluated
+    /// with the authorizer's facts and rules, then the facts of their authority
+    /// blocks (including those generated by the authority block's rules from
+    /// the authorizer's facts) are added to the authorizer, so they can be used
+    /// by its rules, checks and policies. The blocks of the main token do not
+    /// trust them. Their attenuation blocks can only restrict them with checks.
+    ///
+    /// failed checks of these tokens are reported with
+    /// [`CheckResult::token_id`], starting at 1 in the order the tokens were
+    /// added. Secondary tokens are not saved in snapshots
+    pub fn add_secondary_token(&mut self, token: &Biscuit) {
+        self.secondary_tokens.push(token.clone());
+    }
+
     fn add_block(
         &mut self,
         block: &mut Block,
@@ -128,12 +230,14 @@ impl Authorizer {
         token_symbols: &SymbolTable,
     ) -> Result<(), error::Token> {
         // if it is a 3rd party block, it should not affect the main symbol table
+        let third_party_symbols;
         let block_symbols = if i == 0 || block.external_key.is_none() {
-            token_symbols.clone()
+            token_symbols
         } else {
             let mut symbols = block.symbols.clone();
             symbols.public_keys = token_symbols.public_keys.clone();
-            symbols
+            third_party_symbols = symbols;
+            &third_party_symbols
         };
 
         let mut block_origin = Origin::default();
@@ -147,17 +251,26 @@ impl Authorizer {
         );
 
         for fact in block.facts.iter() {
-            let fact = Fact::convert_from(fact, &block_symbols)?.convert(&mut self.symbols);
+            let fact = Fact::convert_from(fact, block_symbols)?.convert(&mut self.symbols);
             self.world.facts.insert(&block_origin, fact);
         }
 
         for rule in block.rules.iter() {
-            if let Err(_message) = rule.validate_variables(&block_symbols) {
+            if let Err(_message) = rule.validate_variables(block_symbols) {
                 return Err(
                     error::Logic::InvalidBlockRule(0, block_symbols.print_rule(rule)).into(),
                 );
             }
-            let rule = rule.translate(&block_symbols, &mut self.symbols)?;
+            if let Some((expression, variable)) = rule.unbound_expression_variable() {
+                return Err(error::Logic::UnboundExpressionVariable {
+                    block: i as u32,
+                    rule: block_symbols.print_rule(rule),
+                    expression,
+                    variable: block_symbols.print_symbol_default(variable as u64),
+                }
+                .into());
+            }
+            let rule = rule.translate(block_symbols, &mut self.symbols)?;
 
             let rule_trusted_origins = TrustedOrigins::from_scopes(
                 &rule.scopes,
@@ -170,7 +283,18 @@ impl Authorizer {
         }
 
         for check in block.checks.iter_mut() {
-            let c = Check::convert_from(check, &block_symbols)?;
+            for query in check.queries.iter() {
+                if let Some((expression, variable)) = query.unbound_expression_variable() {
+                    return Err(error::Logic::UnboundExpressionVariable {
+                        block: i as u32,
+                        rule: block_symbols.print_check(check),
+                        expression,
+                        variable: block_symbols.print_symbol_default(variable as u64),
+                    }
+                    .into());
+                }
+            }
+            let c = Check::convert_from(check, block_symbols)?;
             *check = c.convert(&mut self.symbols);
         }
 
@@ -221,7 +345,8 @@ impl Authorizer {
 
     /// Add the rules, facts, and checks of another `BlockBuilder`.
     pub fn merge_block(&mut self, other: BlockBuilder) {
-        self.authorizer_block_builder.merge(other)
+        // the authorizer's block has no schema, so merging cannot fail
+        self.authorizer_block_builder.merge(other).unwrap()
     }
 
     pub fn add_fact<F: TryInto<Fact>>(&mut self, fact: F) -> Result<(), error::Token>
@@ -245,6 +370,21 @@ impl Authorizer {
         self.authorizer_block_builder.add_check(check)
     }
 
+    /// adds a check with a name, reported in [`error::FailedAuthorizerCheck`]
+    /// and [`CheckResult`] if it fails, and used by [`DenialReasons`]
+    ///
+    /// see [`BlockBuilder::add_check_named`]
+    pub fn add_check_named<C: TryInto<Check>>(
+        &mut self,
+        name: &str,
+        check: C,
+    ) -> Result<(), error::Token>
+    where
+        error::Token: From<<C as TryInto<Check>>::Error>,
+    {
+        self.authorizer_block_builder.add_check_named(name, check)
+    }
+
     /// adds some datalog code to the authorizer
     ///
     /// ```rust
@@ -280,9 +420,73 @@ impl Authorizer {
             e2
         })?;
 
+        self.add_source_result(source_result, &params, &scope_params)
+    }
+
+    /// adds a policy document to the authorizer
+    ///
+    /// the document is a list of statements, each followed by `;`, with the
+    /// same syntax as [`Authorizer::add_code`]:
+    /// * facts: `resource("file1");`
+    /// * rules: `can_read($r) <- right($r, "read");`
+    /// * checks: `check if ...;` or `check all ...;`
+    /// * policies: `allow if ...;` or `deny if ...;`, tried in the order they appear
+    /// * comments: `// ...` until the end of the line, or `/* ... */`
+    ///
+    /// [`Authorizer::dump_code`] prints the authorizer in this format. Parse errors
+    /// are returned as [`error::Token::PolicySyntax`], with their position and
+    /// `name`, which identifies the document, like a file path
+    ///
+    /// ```rust
+    /// extern crate biscuit_auth as biscuit;
+    ///
+    /// use biscuit::Authorizer;
+    ///
+    /// let mut authorizer = Authorizer::new();
+    /// let err = authorizer
+    ///     .add_policy_source("policy.datalog", "allow if true;\ncheck if ;")
+    ///     .unwrap_err();
+    /// assert!(err.to_string().contains("policy.datalog:2:10"));
+    /// ```
+    pub fn add_policy_source(&mut self, name: &str, source: &str) -> Result<(), error::Token> {
+        let source_result = parse_source(source).map_err(|errors| {
+            error::Token::PolicySyntax(
+                errors
+                    .into_iter()
+                    .map(|e| {
+                        let (line, column) = source_position(source, e.input);
+                        error::PolicySyntaxError {
+                            source: name.to_string(),
+                            line,
+                            column,
+                            input: e.input.to_string(),
+                            message: e.message,
+                        }
+                    })
+                    .collect(),
+            )
+        })?;
+
+        self.add_source_result(source_result, &HashMap::new(), &HashMap::new())
+    }
+
+    /// creates an authorizer from a policy document, see
+    /// [`Authorizer::add_policy_source`]
+    pub fn from_policy_source(name: &str, source: &str) -> Result<Self, error::Token> {
+        let mut authorizer = Authorizer::new();
+        authorizer.add_policy_source(name, source)?;
+        Ok(authorizer)
+    }
+
+    fn add_source_result(
+        &mut self,
+        source_result: SourceResult,
+        params: &HashMap<String, Term>,
+        scope_params: &HashMap<String, PublicKey>,
+    ) -> Result<(), error::Token> {
         for (_, fact) in source_result.facts.into_iter() {
             let mut fact: Fact = fact.into();
-            for (name, value) in &params {
+            for (name, value) in params {
                 let res = match fact.set(name, value) {
                     Ok(_) => Ok(()),
                     Err(error::Token::Language(
@@ -299,8 +503,8 @@ impl Authorizer {
         }
 
         for (_, rule) in source_result.rules.into_iter() {
-            let mut rule: Rule = rule.into();
-            for (name, value) in &params {
+            let mut rule: Rule = rule.try_into()?;
+            for (name, value) in params {
                 let res = match rule.set(name, value) {
                     Ok(_) => Ok(()),
                     Err(error::Token::Language(
@@ -312,7 +516,7 @@ impl Authorizer {
                 };
                 res?;
             }
-            for (name, value) in &scope_params {
+            for (name, value) in scope_params {
                 let res = match rule.set_scope(name, *value) {
                     Ok(_) => Ok(()),
                     Err(error::Token::Language(
@@ -329,8 +533,8 @@ impl Authorizer {
         }
 
         for (_, check) in source_result.checks.into_iter() {
-            let mut check: Check = check.into();
-            for (name, value) in &params {
+            let mut check: Check = check.try_into()?;
+            for (name, value) in params {
                 let res = match check.set(name, value) {
                     Ok(_) => Ok(()),
                     Err(error::Token::Language(
@@ -342,7 +546,7 @@ impl Authorizer {
                 };
                 res?;
             }
-            for (name, value) in &scope_params {
+            for (name, value) in scope_params {
                 let res = match check.set_scope(name, *value) {
                     Ok(_) => Ok(()),
                     Err(error::Token::Language(
@@ -358,8 +562,8 @@ impl Authorizer {
             self.authorizer_block_builder.checks.push(check);
         }
         for (_, policy) in source_result.policies.into_iter() {
-            let mut policy: Policy = policy.into();
-            for (name, value) in &params {
+            let mut policy: Policy = policy.try_into()?;
+            for (name, value) in params {
                 let res = match policy.set(name, value) {
                     Ok(_) => Ok(()),
                     Err(error::Token::Language(
@@ -371,7 +575,7 @@ impl Authorizer {
                 };
                 res?;
             }
-            for (name, value) in &scope_params {
+            for (name, value) in scope_params {
                 let res = match policy.set_scope(name, *value) {
                     Ok(_) => Ok(()),
                     Err(error::Token::Language(
@@ -408,6 +612,179 @@ impl Authorizer {
         self.limits = limits;
     }
 
+    /// stops evaluating checks after the first failure
+    ///
+    /// by default, all checks are evaluated so that the error lists every
+    /// failed check, in this order: checks of the secondary tokens (see
+    /// [`Authorizer::add_secondary_token`]), authorizer checks, authority block
+    /// checks, then the checks of each following block. Policies are tried
+    /// before the checks of the following blocks. With this option, the error
+    /// only contains the first failed check, and the remaining blocks are not
+    /// evaluated. A matching deny policy, or no matching policy, also skips the
+    /// checks of the following blocks. The remaining checks appear as
+    /// [`CheckStatus::Skipped`] in [`Authorizer::report`]
+    ///
+    /// in each block, checks are evaluated in the order they were added,
+    /// unless [`Authorizer::order_checks_by_cost`] is enabled
+    pub fn set_fail_fast(&mut self, fail_fast: bool) {
+        self.fail_fast = fail_fast;
+    }
+
+    /// in fail fast mode, evaluates the checks of each block by increasing
+    /// estimated cost instead of insertion order
+    ///
+    /// the cost is estimated from the number of predicates and expression
+    /// operations of the check's queries, regular expressions being the most
+    /// expensive. Checks of the same cost keep their insertion order, and
+    /// blocks are still evaluated in the order described in
+    /// [`Authorizer::set_fail_fast`]. This has no effect if fail fast is
+    /// disabled, since all checks are evaluated
+    pub fn order_checks_by_cost(&mut self, enabled: bool) {
+        self.order_by_cost = enabled;
+    }
+
+    /// fills [`error::FailedCheck::reason`] in authorization errors with
+    /// application codes
+    pub fn set_denial_reasons(&mut self, reasons: Option<DenialReasons>) {
+        self.denial_reasons = reasons;
+    }
+
+    /// provides the `name` facts when authorization needs them
+    ///
+    /// once the rules reached their fixpoint, if a rule, check or policy uses
+    /// the `name` predicate and no `name` fact is available, `resolver` is
+    /// called with the terms of the first use of the predicate: constants are
+    /// given as hints, variables as `None`. It returns the terms of the facts,
+    /// which are added as authorizer facts, and the rules are run again.
+    /// A resolver is called at most once per authorization, and the number of
+    /// facts it can provide is limited by [`RunLimits::max_resolved_facts`]
+    ///
+    /// ```rust
+    /// extern crate biscuit_auth as biscuit;
+    ///
+    /// use biscuit::{builder::string, Authorizer};
+    ///
+    /// let mut authorizer = Authorizer::new();
+    /// authorizer.register_resolver(
+    ///     "role",
+    ///     Box::new(|hints| match hints.first() {
+    ///         Some(Some(user)) if *user == string("alice") => {
+    ///             vec![vec![string("alice"), string("admin")]]
+    ///         }
+    ///         _ => vec![],
+    ///     }),
+    /// );
+    /// authorizer
+    ///     .add_code(r#"allow if role("alice", "admin");"#)
+    ///     .unwrap();
+    /// assert_eq!(authorizer.authorize(), Ok(0));
+    /// ```
+    pub fn register_resolver(&mut self, name: &str, resolver: Box<FactResolver>) {
+        self.resolvers.insert(name.to_string(), Arc::from(resolver));
+    }
+
+    /// rejects tokens that do not match `schema`
+    ///
+    /// this must be set before calling [`Authorizer::add_token`], which then
+    /// returns [`error::Token::SchemaViolations`] for a mismatching token
+    pub fn set_schema(&mut self, schema: Option<Schema>) {
+        self.schema = schema;
+    }
+
+    /// records the variable bindings of satisfied checks in the [`CheckResult`]s
+    ///
+    /// at most `max` sets of bindings are kept per check, and
+    /// [`CheckResult::bindings_truncated`] is set if there were more. Collecting
+    /// them runs the matching query again, so this is disabled by default
+    /// (`max` = 0)
+    pub fn record_bindings(&mut self, max: usize) {
+        self.max_bindings = max;
+    }
+
+    /// records, for each query of a failed check, the number of facts matching
+    /// each predicate and the number of combinations rejected by each
+    /// expression, in [`CheckResult::diagnostics`]
+    ///
+    /// this runs the queries of failed checks again, so it is disabled by
+    /// default
+    pub fn record_diagnostics(&mut self, enabled: bool) {
+        self.diagnostics = enabled;
+    }
+
+    /// records how each generated fact was obtained, to fill
+    /// [`WorldFact::provenance`]
+    ///
+    /// this must be enabled before [`Authorizer::authorize`]. It does not
+    /// change the evaluation, but keeps a copy of each generated fact, so it is
+    /// disabled by default
+    pub fn record_provenance(&mut self, enabled: bool) {
+        self.world.record_derivations(enabled);
+    }
+
+    /// returns the facts of the authorizer's world, with their origin
+    ///
+    /// facts are sorted by origin, then by their textual representation
+    pub fn world_facts(&self) -> Result<Vec<WorldFact>, error::Token> {
+        let mut facts = self
+            .world
+            .facts
+            .iter_all()
+            .map(|(origin, fact)| {
+                Ok(WorldFact {
+                    fact: Fact::convert_from(fact, &self.symbols)?,
+                    origin: origin
+                        .inner
+                        .iter()
+                        .map(|id| {
+                            if *id == usize::MAX || *id == datalog::SECONDARY_TOKENS_ORIGIN {
+                                None
+                            } else {
+                                Some(*id)
+                            }
+                        })
+                        .collect(),
+                    provenance: self.provenance(origin, fact),
+                })
+            })
+            .collect::<Result<Vec<_>, error::Format>>()?;
+
+        // the authorizer comes last, as in the origins' `Display` output
+        facts.sort_by_cached_key(|f| {
+            let origin: Vec<usize> = f.origin.iter().map(|id| id.unwrap_or(usize::MAX)).collect();
+            (origin, f.fact.to_string())
+        });
+        Ok(facts)
+    }
+
+    fn provenance(&self, origin: &Origin, fact: &datalog::Fact) -> Option<FactProvenance> {
+        if !self.world.records_derivations() {
+            return None;
+        }
+
+        if let Some(block) = self.world.derived_by(origin, fact) {
+            return Some(FactProvenance::Derived {
+                rule_block: if block == usize::MAX {
+                    None
+                } else {
+                    Some(block)
+                },
+            });
+        }
+
+        let mut ids = origin.inner.iter();
+        match (ids.next(), ids.next()) {
+            (Some(0), None) => Some(FactProvenance::Authority),
+            (Some(&usize::MAX), None) => Some(FactProvenance::Authorizer),
+            (Some(&datalog::SECONDARY_TOKENS_ORIGIN), None) => self
+                .imported_facts
+                .get(fact)
+                .map(|token_id| FactProvenance::Token(*token_id)),
+            (Some(&block), None) => Some(FactProvenance::Block(block)),
+            // generated facts restored from a snapshot
+            _ => None,
+        }
+    }
+
     /// run a query over the authorizer's Datalog engine to gather data
     ///
     /// ```rust
@@ -596,8 +973,211 @@ impl Authorizer {
 
     /// adds a fact with the current time
     pub fn set_time(&mut self) {
-        let fact = fact("time", &[date(&SystemTime::now())]);
-        self.authorizer_block_builder.add_fact(fact).unwrap();
+        self.set_time_at(crate::time::now());
+    }
+
+    /// adds a fact with the provided time
+    ///
+    /// this can be used when the system clock is not available, or to
+    /// inject a clock in tests. If a clock skew is set with
+    /// [`Authorizer::set_clock_skew`], `time - skew` and `time + skew` are
+    /// added too when authorizing
+    pub fn set_time_at(&mut self, time: SystemTime) {
+        self.time = Some(time);
+        self.authorizer_block_builder
+            .add_fact(time_fact(time))
+            .unwrap();
+    }
+
+    /// `time` facts for both ends of the clock skew window around the time set
+    /// with [`Authorizer::set_time_at`]
+    fn skewed_time_facts(&self) -> Vec<Fact> {
+        match self.time {
+            Some(time) if self.clock_skew > Duration::default() => vec![
+                Some(
+                    time.checked_sub(self.clock_skew)
+                        .unwrap_or(SystemTime::UNIX_EPOCH),
+                ),
+                time.checked_add(self.clock_skew),
+            ]
+            .into_iter()
+            .flatten()
+            .map(time_fact)
+            .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// adds a `name(value)` fact, checked by
+    /// [`BuilderExt::check_integer_at_most`] and the related methods
+    pub fn add_integer(&mut self, name: &str, value: i64) -> Result<(), error::Token> {
+        self.add_fact(fact(name, &[int(value)]))
+    }
+
+    /// adds the `resource(path)` and `operation(method)` facts of an HTTP request
+    ///
+    /// the operation is the method in lowercase: `GET` gives `"get"`, `POST`
+    /// gives `"post"`, and non standard methods follow the same rule, so
+    /// `PROPFIND` gives `"propfind"`. The path is modified as described in
+    /// [`PathNormalization`], by default it is used as is
+    pub fn http_request(&mut self, method: &str, path: &str, normalization: PathNormalization) {
+        self.authorizer_block_builder
+            .add_fact(resource_fact(&normalization.apply(path)))
+            .unwrap();
+        self.authorizer_block_builder
+            .add_fact(operation_fact(&method.to_ascii_lowercase()))
+            .unwrap();
+    }
+
+    /// adds a `client_ip` fact, checked by [`client_ip_in`](crate::builder::client_ip_in)
+    pub fn set_client_ip(&mut self, address: IpAddr) {
+        self.authorizer_block_builder
+            .add_fact(client_ip_fact(address))
+            .unwrap();
+    }
+
+    /// rejects tokens carrying one of these revocation ids, added with
+    /// [`BlockBuilder::revocation_id`]
+    ///
+    /// the ids are stored as `revoked_id(id)` facts, and authorization fails
+    /// with [`error::Logic::Revoked`] if a `revocation_id` fact from the token
+    /// matches one of them
+    pub fn revoke_ids(&mut self, ids: &[i64]) {
+        for id in ids {
+            let fact = fact("revoked_id", &[int(*id)]);
+            self.authorizer_block_builder.add_fact(fact).unwrap();
+        }
+    }
+
+    /// rejects tokens carrying one of these revocation ids, added with
+    /// [`BlockBuilder::revocation_uuid`]
+    ///
+    /// integer and UUID ids can be revoked in the same authorizer, each
+    /// kind is only compared with the ids of the same kind
+    #[cfg(feature = "uuid")]
+    pub fn revoke_uuids(&mut self, ids: &[uuid::Uuid]) {
+        for id in ids {
+            let fact = fact("revoked_id", &[super::builder::bytes(id.as_bytes())]);
+            self.authorizer_block_builder.add_fact(fact).unwrap();
+        }
+    }
+
+    /// rejects single use tokens that were already presented
+    ///
+    /// this must be called after [`Authorizer::add_token`]. It fails with
+    /// [`error::Logic::MissingNonce`] if the token has no nonce (see
+    /// [`BiscuitBuilder::add_nonce`](crate::builder::BiscuitBuilder::add_nonce)),
+    /// and with [`error::Logic::ReplayedNonce`] if `seen` returns true for it.
+    /// Otherwise it returns the nonce, that the application should record
+    /// once the authorization succeeds
+    pub fn require_nonce(&self, seen: impl Fn(&[u8]) -> bool) -> Result<Vec<u8>, error::Token> {
+        let nonce = self.nonce.clone().ok_or(error::Logic::MissingNonce)?;
+        if seen(&nonce) {
+            return Err(error::Logic::ReplayedNonce(hex::encode(&nonce)).into());
+        }
+
+        Ok(nonce)
+    }
+
+    /// rejects single use tokens that were already presented, or that were
+    /// not issued within `window` of the time set with
+    /// [`Authorizer::set_time_at`], or of the current time
+    ///
+    /// the token must carry a nonce and an issue timestamp (see
+    /// [`BiscuitBuilder::add_nonce`](crate::builder::BiscuitBuilder::add_nonce)
+    /// and [`BiscuitBuilder::add_issued_at`](crate::builder::BiscuitBuilder::add_issued_at)),
+    /// otherwise this fails with [`error::Logic::MissingNonce`] or
+    /// [`error::Logic::MissingTimestamp`]. It fails with
+    /// [`error::Logic::TimestampOutsideWindow`] if the timestamp is too far
+    /// from the current time, and with [`error::Logic::ReplayedNonce`] if the
+    /// nonce is already in `store`.
+    ///
+    /// the nonce is recorded in `store` until the end of the window, after
+    /// which the timestamp check rejects the token. Since it is recorded
+    /// immediately, this should be called once [`Authorizer::authorize`]
+    /// succeeded
+    pub fn replay_protection<S: NonceStore>(
+        &self,
+        window: Duration,
+        store: &mut S,
+    ) -> Result<(), error::Token> {
+        let nonce = self.nonce.as_ref().ok_or(error::Logic::MissingNonce)?;
+        let issued_at = self.issued_at.ok_or(error::Logic::MissingTimestamp)?;
+
+        let now = self.time.unwrap_or_else(crate::time::now);
+        let elapsed = match now.duration_since(issued_at) {
+            Ok(elapsed) => elapsed,
+            Err(e) => e.duration(),
+        };
+        let window = window.saturating_add(self.clock_skew);
+        if elapsed > window {
+            return Err(error::Logic::TimestampOutsideWindow(date(&issued_at).to_string()).into());
+        }
+
+        let expires_at = issued_at
+            .checked_add(window)
+            .ok_or_else(|| error::Logic::TimestampOutsideWindow(date(&issued_at).to_string()))?;
+        if !store.check_and_insert(nonce, expires_at) {
+            return Err(error::Logic::ReplayedNonce(hex::encode(nonce)).into());
+        }
+
+        Ok(())
+    }
+
+    /// rejects tokens issued more than `max_age` ago, even if their checks
+    /// accept the current time
+    ///
+    /// the age is computed from the issue timestamp added with
+    /// [`BiscuitBuilder::add_issued_at`](crate::builder::BiscuitBuilder::add_issued_at).
+    /// [`Authorizer::authorize`] then fails with [`error::Logic::TokenTooOld`],
+    /// with [`error::Logic::TimestampOutsideWindow`] if the token was issued in
+    /// the future, and with [`error::Logic::MissingTimestamp`] if the token has no
+    /// timestamp, unless [`Authorizer::set_require_issued_at`] is disabled. Both
+    /// limits are extended by [`Authorizer::set_clock_skew`]. The age is computed
+    /// at the time set with [`Authorizer::set_time_at`], or at the current time
+    pub fn set_max_token_age(&mut self, max_age: Option<Duration>) {
+        self.max_token_age = max_age;
+    }
+
+    /// rejects tokens without an issue timestamp when a maximum age is set with
+    /// [`Authorizer::set_max_token_age`]. This is enabled by default
+    pub fn set_require_issued_at(&mut self, required: bool) {
+        self.require_issued_at = required;
+    }
+
+    /// tolerated difference between the clocks of the token issuer and of the
+    /// authorizer, applied to [`Authorizer::set_max_token_age`],
+    /// [`Authorizer::replay_protection`] and time checks. It is zero by default
+    ///
+    /// for time checks, the authorizer gets `time` facts for the time set with
+    /// [`Authorizer::set_time`] or [`Authorizer::set_time_at`] and for both ends
+    /// of the window, whatever the order of the calls. A `check if` on the time passes if
+    /// one of these times satisfies it: an expiration check like
+    /// `check if time($t), $t <= 2030-01-01T00:00:00Z` still passes `skew` after
+    /// the expiration date, and a check like `$t >= date` passes `skew` before
+    /// the date. A `check all` on the time must be satisfied by all of them, so
+    /// the skew makes it stricter
+    pub fn set_clock_skew(&mut self, skew: Duration) {
+        self.clock_skew = skew;
+    }
+
+    fn check_token_age(&self, max_age: Duration) -> Result<(), error::Token> {
+        let issued_at = match self.issued_at {
+            Some(issued_at) => issued_at,
+            None if self.require_issued_at => return Err(error::Logic::MissingTimestamp.into()),
+            None => return Ok(()),
+        };
+
+        let now = self.time.unwrap_or_else(crate::time::now);
+        match now.duration_since(issued_at) {
+            Ok(age) if age > max_age.saturating_add(self.clock_skew) => {
+                Err(error::Logic::TokenTooOld(date(&issued_at).to_string()).into())
+            }
+            Err(e) if e.duration() > self.clock_skew => {
+                Err(error::Logic::TimestampOutsideWindow(date(&issued_at).to_string()).into())
+            }
+            _ => Ok(()),
+        }
     }
 
     /// add a policy to the authorizer
@@ -606,6 +1186,7 @@ impl Authorizer {
         error::Token: From<<P as TryInto<Policy>>::Error>,
     {
         let policy = policy.try_into()?;
+        policy.queries.iter().try_for_each(Rule::validate_names)?;
         policy.validate_parameters()?;
         self.policies.push(policy);
         Ok(())
@@ -654,17 +1235,317 @@ impl Authorizer {
         result
     }
 
+    // revocation ids from the token that match a `revoked_id` fact
+    fn revoked_ids(&mut self) -> Result<Vec<String>, error::Token> {
+        let query = rule(
+            "revoked",
+            &[var("id")],
+            &[
+                pred("revocation_id", &[var("id")]),
+                pred("revoked_id", &[var("id")]),
+            ],
+        )
+        .convert(&mut self.symbols);
+
+        let revoked =
+            self.world
+                .query_rule(query, usize::MAX, &self.token_origins, &self.symbols)?;
+
+        Ok(revoked
+            .iter_all()
+            .map(|(_, fact)| self.symbols.print_term(&fact.predicate.terms[0]))
+            .collect())
+    }
+
+    // bindings of a query that satisfied a check, if they are recorded
+    fn check_bindings(
+        &self,
+        query: &datalog::Rule,
+        scope: &TrustedOrigins,
+    ) -> Result<(Vec<HashMap<String, Term>>, bool), error::Token> {
+        if self.max_bindings == 0 {
+            return Ok((Vec::new(), false));
+        }
+
+        let bindings = self
+            .world
+            .query_bindings(query, scope, &self.symbols, self.max_bindings)?;
+        let sets = bindings
+            .sets
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .map(|(variable, value)| {
+                        Ok((
+                            self.symbols.print_symbol_default(*variable as u64),
+                            Term::convert_from(value, &self.symbols)?,
+                        ))
+                    })
+                    .collect::<Result<HashMap<_, _>, error::Format>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((sets, bindings.truncated))
+    }
+
+    // evaluates the checks of the secondary tokens and adds the facts of their
+    // authority blocks to the world
+    fn authorize_secondary_tokens(
+        &mut self,
+        limits: &AuthorizerLimits,
+        time_limit: Instant,
+    ) -> Result<Vec<CheckResult>, error::Token> {
+        let mut check_results = Vec::new();
+        let mut secondary_origin = Origin::default();
+        secondary_origin.insert(datalog::SECONDARY_TOKENS_ORIGIN);
+
+        for (i, token) in self.secondary_tokens.iter().enumerate() {
+            let token_id = i + 1;
+            if self.fail_fast && check_results.iter().any(CheckResult::failed) {
+                check_results.extend(skipped_token_checks(token, token_id as u32)?);
+                continue;
+            }
+
+            let mut authorizer = Authorizer::from_token(token)?;
+            let builder = &mut authorizer.authorizer_block_builder;
+            builder.facts = self.authorizer_block_builder.facts.clone();
+            builder.rules = self.authorizer_block_builder.rules.clone();
+            authorizer.time = self.time;
+            authorizer.clock_skew = self.clock_skew;
+            authorizer.fail_fast = self.fail_fast;
+            authorizer.order_by_cost = self.order_by_cost;
+            authorizer.diagnostics = self.diagnostics;
+            authorizer.add_allow_all();
+
+            let mut limits = limits.clone();
+            limits.max_time = time_limit - Instant::now();
+            match authorizer.authorize_with_limits(limits) {
+                Ok(_) | Err(error::Token::FailedLogic(error::Logic::Unauthorized { .. })) => {}
+                Err(e) => return Err(e),
+            }
+            check_results.extend(authorizer.check_results.into_iter().map(|mut check| {
+                check.token_id = token_id as u32;
+                check
+            }));
+
+            for (origin, fact) in authorizer.world.facts.iter_all() {
+                // facts of the authority block, or generated by its rules
+                // from the authorizer's facts
+                if !origin.inner.contains(&0)
+                    || origin.inner.iter().any(|id| *id != 0 && *id != usize::MAX)
+                {
+                    continue;
+                }
+                let fact =
+                    Fact::convert_from(fact, &authorizer.symbols)?.convert(&mut self.symbols);
+                self.imported_facts.insert(fact.clone(), token_id);
+                self.world.facts.insert(&secondary_origin, fact);
+            }
+        }
+
+        Ok(check_results)
+    }
+
+    fn query_diagnostics(
+        &self,
+        query: &datalog::Rule,
+        scope: &TrustedOrigins,
+    ) -> Result<QueryDiagnostics, error::Token> {
+        let diagnostics = self.world.query_diagnostics(query, scope, &self.symbols)?;
+        Ok(QueryDiagnostics {
+            query: self.symbols.print_rule_body(query),
+            predicates: query
+                .body
+                .iter()
+                .map(|predicate| self.symbols.print_predicate(predicate))
+                .zip(diagnostics.predicate_matches)
+                .collect(),
+            candidates: diagnostics.candidates,
+            expressions: query
+                .expressions
+                .iter()
+                .map(|expression| self.symbols.print_expression(expression))
+                .zip(diagnostics.rejected)
+                .collect(),
+        })
+    }
+
+    // facts that would match a predicate of a failed check if the terms had the
+    // same type, like a check on `operation("1")` and an `operation(1)` fact
+    fn type_hints(&self, check: &datalog::Check) -> Vec<String> {
+        let mut hints = Vec::new();
+        let predicates = check.queries.iter().flat_map(|query| query.body.iter());
+        for predicate in predicates {
+            for (_, fact) in self.world.facts.iter_all() {
+                let fact = &fact.predicate;
+                if fact.name != predicate.name || fact.terms.len() != predicate.terms.len() {
+                    continue;
+                }
+
+                let mut mismatches = Vec::new();
+                let mut matches = true;
+                for (expected, found) in predicate.terms.iter().zip(fact.terms.iter()) {
+                    if matches!(expected, datalog::Term::Variable(_)) || expected == found {
+                        continue;
+                    }
+                    match self.type_mismatch(expected, found) {
+                        Some(mismatch) => mismatches.push(mismatch),
+                        None => {
+                            matches = false;
+                            break;
+                        }
+                    }
+                }
+
+                if matches && !mismatches.is_empty() {
+                    let hint = format!(
+                        "{} {} in {}",
+                        self.symbols.print_predicate(predicate),
+                        mismatches.join(", "),
+                        self.symbols.print_predicate(fact)
+                    );
+                    if !hints.contains(&hint) {
+                        hints.push(hint);
+                    }
+                }
+            }
+        }
+        hints
+    }
+
+    // describes the difference between terms of different types that print
+    // the same, ignoring quotes
+    fn type_mismatch(&self, expected: &datalog::Term, found: &datalog::Term) -> Option<String> {
+        let kind = |term| {
+            Term::convert_from(term, &self.symbols)
+                .ok()
+                .as_ref()
+                .and_then(TermKind::of)
+        };
+        let (expected_kind, found_kind) = (kind(expected)?, kind(found)?);
+        let expected = self.symbols.print_term(expected);
+        let found = self.symbols.print_term(found);
+        if expected_kind == found_kind || expected.trim_matches('"') != found.trim_matches('"') {
+            return None;
+        }
+
+        Some(format!(
+            "expects {} {}, found {} {}",
+            expected_kind, expected, found_kind, found
+        ))
+    }
+
+    /// returns a summary of the last call to [`Authorizer::authorize`]
+    ///
+    /// it contains the outcome of every check (in evaluation order), the policy
+    /// that matched, and statistics on the Datalog execution
+    pub fn report(&self) -> AuthorizerReport {
+        let facts = if self.world.records_derivations() {
+            self.world_facts().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        AuthorizerReport {
+            checks: self.check_results.clone(),
+            policy: self.matched_policy.clone(),
+            fact_count: self.world.facts.len(),
+            derived_facts: self.derived_facts,
+            iterations: self.world.iterations,
+            execution_time: self.execution_time,
+            run_stats: self.run_stats,
+            facts,
+            token: self.token_summary.clone(),
+            resources: self.ambient_values("resource"),
+            operations: self.ambient_values("operation"),
+            time: self.time,
+            signature_time: self.signature_time,
+        }
+    }
+
+    /// values of the authorizer's facts with this name and a single term
+    fn ambient_values(&self, name: &str) -> Vec<String> {
+        self.authorizer_block_builder
+            .facts
+            .iter()
+            .filter(|fact| fact.predicate.name == name && fact.predicate.terms.len() == 1)
+            .map(|fact| match &fact.predicate.terms[0] {
+                Term::Str(s) => s.clone(),
+                term => term.to_string(),
+            })
+            .collect()
+    }
+
+    /// returns the counters of the last call to [`Authorizer::authorize`]
+    ///
+    /// unlike [`Authorizer::report`], this does not allocate
+    pub fn metrics(&self) -> AuthorizerMetrics {
+        AuthorizerMetrics {
+            facts: self.world.facts.len(),
+            derived_facts: self.derived_facts,
+            iterations: self.world.iterations,
+            checks: self
+                .check_results
+                .iter()
+                .filter(|check| check.status != CheckStatus::Skipped)
+                .count(),
+            signature_time: self.signature_time,
+            execution_time: self.execution_time,
+            derivation_depth_reached: self.world.derivation_depth_reached,
+        }
+    }
+
+    /// returns the statistics of the Datalog evaluation during the last call
+    /// to [`Authorizer::authorize`]
+    pub fn run_stats(&self) -> RunStats {
+        self.run_stats
+    }
+
+    /// returns the time spent in Datalog evaluation, accumulated over
+    /// [`Authorizer::authorize`] and query calls
+    pub fn execution_time(&self) -> Duration {
+        self.execution_time
+    }
+
+    /// returns the number of iterations of the Datalog engine
+    pub fn iterations(&self) -> u64 {
+        self.world.iterations
+    }
+
+    /// returns the number of facts in the authorizer's world
+    pub fn fact_count(&self) -> usize {
+        self.world.facts.len()
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "authorize",
+            level = "debug",
+            skip_all,
+            fields(
+                blocks = self.blocks.as_ref().map(|b| b.len()).unwrap_or(0),
+                facts = tracing::field::Empty,
+                iterations = tracing::field::Empty,
+            )
+        )
+    )]
     fn authorize_inner(&mut self, mut limits: AuthorizerLimits) -> Result<usize, error::Token> {
         let start = Instant::now();
         let time_limit = start + limits.max_time;
         let mut current_iterations = self.world.iterations;
 
-        let mut errors = vec![];
+        let mut check_results = vec![];
+        let mut failed = false;
         let mut policy_result: Option<Result<usize, usize>> = None;
 
         let mut authorizer_origin = Origin::default();
         authorizer_origin.insert(usize::MAX);
 
+        if let (Some(max_age), Some(_)) = (self.max_token_age, &self.blocks) {
+            self.check_token_age(max_age)?;
+        }
+
         let authorizer_scopes: Vec<token::Scope> = self
             .authorizer_block_builder
             .scopes
@@ -680,15 +1561,30 @@ impl Authorizer {
             &self.public_key_to_block_id,
         );
 
-        for fact in &self.authorizer_block_builder.facts {
+        for fact in &self.authorizer_block_builder.facts[self.compiled_facts..] {
+            self.world
+                .facts
+                .insert(&authorizer_origin, fact.convert(&mut self.symbols));
+        }
+        for fact in self.skewed_time_facts() {
             self.world
                 .facts
                 .insert(&authorizer_origin, fact.convert(&mut self.symbols));
         }
 
-        for rule in &self.authorizer_block_builder.rules {
-            let rule = rule.convert(&mut self.symbols);
+        let symbols = &mut self.symbols;
+        let authorizer_rules: Vec<datalog::Rule> = self
+            .compiled_rules
+            .iter()
+            .cloned()
+            .chain(
+                self.authorizer_block_builder.rules[self.compiled_rules.len()..]
+                    .iter()
+                    .map(|rule| rule.convert(symbols)),
+            )
+            .collect();
 
+        for rule in authorizer_rules {
             let rule_trusted_origins = TrustedOrigins::from_scopes(
                 &rule.scopes,
                 &authorizer_trusted_origins,
@@ -701,8 +1597,27 @@ impl Authorizer {
                 .insert(usize::MAX, &rule_trusted_origins, rule);
         }
 
+        let secondary_results = self.authorize_secondary_tokens(&limits, time_limit)?;
+        failed |= secondary_results.iter().any(CheckResult::failed);
+        check_results.extend(secondary_results);
+
         limits.max_time = time_limit - Instant::now();
-        self.world.run_with_limits(&self.symbols, limits.clone())?;
+        let mut run_stats = if self.resolvers.is_empty() {
+            self.world.run_with_limits(&self.symbols, limits.clone())?
+        } else {
+            let queries = self.resolver_queries();
+            self.world.run_with_resolver(
+                &mut self.symbols,
+                limits.clone(),
+                &queries,
+                &mut Resolvers(&self.resolvers),
+            )?
+        };
+
+        let revoked = self.revoked_ids()?;
+        if !revoked.is_empty() {
+            return Err(error::Token::FailedLogic(error::Logic::Revoked(revoked)));
+        }
 
         let authorizer_scopes: Vec<token::Scope> = self
             .authorizer_block_builder
@@ -719,9 +1634,33 @@ impl Authorizer {
             &self.public_key_to_block_id,
         );
 
-        for (i, check) in self.authorizer_block_builder.checks.iter().enumerate() {
-            let c = check.convert(&mut self.symbols);
+        let symbols = &mut self.symbols;
+        let authorizer_checks: Vec<datalog::Check> = self
+            .authorizer_block_builder
+            .checks
+            .iter()
+            .map(|check| check.convert(symbols))
+            .collect();
+        let by_cost = self.fail_fast && self.order_by_cost;
+
+        for i in check_order(&authorizer_checks, by_cost) {
+            let check = &self.authorizer_block_builder.checks[i];
+            let c = &authorizer_checks[i];
+            if self.fail_fast && failed {
+                check_results.push(CheckResult::skipped(
+                    None,
+                    0,
+                    i as u32,
+                    self.symbols.print_check(c),
+                    check.name.clone(),
+                ));
+                continue;
+            }
+
+            let start = Instant::now();
             let mut successful = false;
+            let mut bindings = (Vec::new(), false);
+            let mut diagnostics = Vec::new();
 
             for query in check.queries.iter() {
                 let query = query.convert(&mut self.symbols);
@@ -732,16 +1671,17 @@ impl Authorizer {
                     &self.public_key_to_block_id,
                 );
                 let res = match check.kind {
-                    CheckKind::One => self.world.query_match(
-                        query,
+                    CheckKind::One => self.world.query_match_ref(
+                        &query,
                         usize::MAX,
                         &rule_trusted_origins,
                         &self.symbols,
                     )?,
-                    CheckKind::All => {
-                        self.world
-                            .query_match_all(query, &rule_trusted_origins, &self.symbols)?
-                    }
+                    CheckKind::All => self.world.query_match_all_ref(
+                        &query,
+                        &rule_trusted_origins,
+                        &self.symbols,
+                    )?,
                 };
 
                 let now = Instant::now();
@@ -749,25 +1689,55 @@ impl Authorizer {
                     return Err(error::Token::RunLimit(error::RunLimit::Timeout));
                 }
 
+                if !res && self.diagnostics {
+                    diagnostics.push(self.query_diagnostics(&query, &rule_trusted_origins)?);
+                }
                 if res {
                     successful = true;
+                    bindings = self.check_bindings(&query, &rule_trusted_origins)?;
                     break;
                 }
             }
 
-            if !successful {
-                errors.push(error::FailedCheck::Authorizer(
-                    error::FailedAuthorizerCheck {
-                        check_id: i as u32,
-                        rule: self.symbols.print_check(&c),
-                    },
-                ));
-            }
+            failed |= !successful;
+            check_results.push(CheckResult {
+                block_id: None,
+                token_id: 0,
+                check_id: i as u32,
+                rule: self.symbols.print_check(c),
+                name: check.name.clone(),
+                success: successful,
+                status: CheckStatus::from_success(successful),
+                duration: start.elapsed(),
+                bindings: bindings.0,
+                bindings_truncated: bindings.1,
+                hints: if successful {
+                    Vec::new()
+                } else {
+                    self.type_hints(c)
+                },
+                diagnostics: if successful { Vec::new() } else { diagnostics },
+            });
         }
 
         if let Some(blocks) = self.blocks.as_ref() {
-            for (j, check) in blocks[0].checks.iter().enumerate() {
+            for j in check_order(&blocks[0].checks, by_cost) {
+                let check = &blocks[0].checks[j];
+                if self.fail_fast && failed {
+                    check_results.push(CheckResult::skipped(
+                        Some(0),
+                        0,
+                        j as u32,
+                        self.symbols.print_check(check),
+                        check.name.clone(),
+                    ));
+                    continue;
+                }
+
+                let start = Instant::now();
                 let mut successful = false;
+                let mut bindings = (Vec::new(), false);
+                let mut diagnostics = Vec::new();
 
                 let authority_trusted_origins = TrustedOrigins::from_scopes(
                     &blocks[0].scopes,
@@ -784,14 +1754,14 @@ impl Authorizer {
                         &self.public_key_to_block_id,
                     );
                     let res = match check.kind {
-                        CheckKind::One => self.world.query_match(
-                            query.clone(),
+                        CheckKind::One => self.world.query_match_ref(
+                            query,
                             0,
                             &rule_trusted_origins,
                             &self.symbols,
                         )?,
-                        CheckKind::All => self.world.query_match_all(
-                            query.clone(),
+                        CheckKind::All => self.world.query_match_all_ref(
+                            query,
                             &rule_trusted_origins,
                             &self.symbols,
                         )?,
@@ -802,19 +1772,35 @@ impl Authorizer {
                         return Err(error::Token::RunLimit(error::RunLimit::Timeout));
                     }
 
+                    if !res && self.diagnostics {
+                        diagnostics.push(self.query_diagnostics(query, &rule_trusted_origins)?);
+                    }
                     if res {
                         successful = true;
+                        bindings = self.check_bindings(query, &rule_trusted_origins)?;
                         break;
                     }
                 }
 
-                if !successful {
-                    errors.push(error::FailedCheck::Block(error::FailedBlockCheck {
-                        block_id: 0u32,
-                        check_id: j as u32,
-                        rule: self.symbols.print_check(&check),
-                    }));
-                }
+                failed |= !successful;
+                check_results.push(CheckResult {
+                    block_id: Some(0),
+                    token_id: 0,
+                    check_id: j as u32,
+                    rule: self.symbols.print_check(check),
+                    name: check.name.clone(),
+                    success: successful,
+                    status: CheckStatus::from_success(successful),
+                    duration: start.elapsed(),
+                    bindings: bindings.0,
+                    bindings_truncated: bindings.1,
+                    hints: if successful {
+                        Vec::new()
+                    } else {
+                        self.type_hints(check)
+                    },
+                    diagnostics: if successful { Vec::new() } else { diagnostics },
+                });
             }
         }
 
@@ -828,8 +1814,8 @@ impl Authorizer {
                     &self.public_key_to_block_id,
                 );
 
-                let res = self.world.query_match(
-                    query,
+                let res = self.world.query_match_ref(
+                    &query,
                     usize::MAX,
                     &rule_trusted_origins,
                     &self.symbols,
@@ -849,9 +1835,23 @@ impl Authorizer {
                 }
             }
         }
+        failed |= !matches!(policy_result, Some(Ok(_)));
 
         if let Some(blocks) = self.blocks.as_ref() {
             for (i, block) in (&blocks[1..]).iter().enumerate() {
+                if self.fail_fast && failed {
+                    for (j, check) in block.checks.iter().enumerate() {
+                        check_results.push(CheckResult::skipped(
+                            Some((i + 1) as u32),
+                            0,
+                            j as u32,
+                            self.symbols.print_check(check),
+                            check.name.clone(),
+                        ));
+                    }
+                    continue;
+                }
+
                 let block_trusted_origins = TrustedOrigins::from_scopes(
                     &block.scopes,
                     &TrustedOrigins::default(),
@@ -863,10 +1863,26 @@ impl Authorizer {
                 limits.max_iterations -= self.world.iterations - current_iterations;
                 current_iterations = self.world.iterations;
 
-                self.world.run_with_limits(&self.symbols, limits.clone())?;
+                let stats = self.world.run_with_limits(&self.symbols, limits.clone())?;
+                run_stats.accumulate(&stats);
+
+                for j in check_order(&block.checks, by_cost) {
+                    let check = &block.checks[j];
+                    if self.fail_fast && failed {
+                        check_results.push(CheckResult::skipped(
+                            Some((i + 1) as u32),
+                            0,
+                            j as u32,
+                            self.symbols.print_check(check),
+                            check.name.clone(),
+                        ));
+                        continue;
+                    }
 
-                for (j, check) in block.checks.iter().enumerate() {
+                    let start = Instant::now();
                     let mut successful = false;
+                    let mut bindings = (Vec::new(), false);
+                    let mut diagnostics = Vec::new();
 
                     for query in check.queries.iter() {
                         let rule_trusted_origins = TrustedOrigins::from_scopes(
@@ -877,14 +1893,14 @@ impl Authorizer {
                         );
 
                         let res = match check.kind {
-                            CheckKind::One => self.world.query_match(
-                                query.clone(),
+                            CheckKind::One => self.world.query_match_ref(
+                                query,
                                 i + 1,
                                 &rule_trusted_origins,
                                 &self.symbols,
                             )?,
-                            CheckKind::All => self.world.query_match_all(
-                                query.clone(),
+                            CheckKind::All => self.world.query_match_all_ref(
+                                query,
                                 &rule_trusted_origins,
                                 &self.symbols,
                             )?,
@@ -895,23 +1911,71 @@ impl Authorizer {
                             return Err(error::Token::RunLimit(error::RunLimit::Timeout));
                         }
 
+                        if !res && self.diagnostics {
+                            diagnostics.push(self.query_diagnostics(query, &rule_trusted_origins)?);
+                        }
                         if res {
                             successful = true;
+                            bindings = self.check_bindings(query, &rule_trusted_origins)?;
                             break;
                         }
                     }
 
-                    if !successful {
-                        errors.push(error::FailedCheck::Block(error::FailedBlockCheck {
-                            block_id: (i + 1) as u32,
-                            check_id: j as u32,
-                            rule: self.symbols.print_check(&check),
-                        }));
-                    }
+                    failed |= !successful;
+                    check_results.push(CheckResult {
+                        block_id: Some((i + 1) as u32),
+                        token_id: 0,
+                        check_id: j as u32,
+                        rule: self.symbols.print_check(check),
+                        name: check.name.clone(),
+                        success: successful,
+                        status: CheckStatus::from_success(successful),
+                        duration: start.elapsed(),
+                        bindings: bindings.0,
+                        bindings_truncated: bindings.1,
+                        hints: if successful {
+                            Vec::new()
+                        } else {
+                            self.type_hints(check)
+                        },
+                        diagnostics: if successful { Vec::new() } else { diagnostics },
+                    });
                 }
             }
         }
 
+        self.derived_facts = run_stats.facts_after - run_stats.facts_before;
+        self.run_stats = run_stats;
+        self.matched_policy = policy_result.map(|res| match res {
+            Ok(i) => error::MatchedPolicy::Allow(i),
+            Err(i) => error::MatchedPolicy::Deny(i),
+        });
+        let mut errors: Vec<error::FailedCheck> = check_results
+            .iter()
+            .filter_map(CheckResult::failure)
+            .collect();
+        if let Some(reasons) = &self.denial_reasons {
+            for error in errors.iter_mut() {
+                reasons.resolve(error);
+            }
+        }
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            span.record("facts", self.world.facts.len());
+            span.record("iterations", self.world.iterations);
+            // the check's source is not recorded: it can contain token data
+            for check in &check_results {
+                tracing::debug!(
+                    block_id = check.block_id,
+                    check_id = check.check_id,
+                    success = check.success,
+                    "check evaluated"
+                );
+            }
+        }
+        self.check_results = check_results;
+
         match (policy_result, errors.is_empty()) {
             (Some(Ok(i)), true) => Ok(i),
             (None, _) => Err(error::Token::FailedLogic(error::Logic::NoMatchingPolicy {
@@ -928,6 +1992,29 @@ impl Authorizer {
         }
     }
 
+    // queries of the checks and policies, whose predicates can be resolved
+    fn resolver_queries(&mut self) -> Vec<datalog::Rule> {
+        let symbols = &mut self.symbols;
+        let mut queries: Vec<datalog::Rule> = self
+            .authorizer_block_builder
+            .checks
+            .iter()
+            .flat_map(|check| check.queries.iter())
+            .chain(
+                self.policies
+                    .iter()
+                    .flat_map(|policy| policy.queries.iter()),
+            )
+            .map(|query| query.convert(symbols))
+            .collect();
+        for block in self.blocks.iter().flatten() {
+            for check in &block.checks {
+                queries.extend(check.queries.iter().cloned());
+            }
+        }
+        queries
+    }
+
     /// prints the content of the authorizer
     pub fn print_world(&self) -> String {
         self.to_string()
@@ -1006,10 +2093,10 @@ impl std::fmt::Display for Authorizer {
         }
 
         let mut all_facts = BTreeMap::new();
-        for (origin, factset) in &self.world.facts.inner {
+        for (origin, factset) in self.world.facts.by_origin() {
             let mut facts = Vec::new();
             for fact in factset {
-                facts.push(self.symbols.print_fact(&fact));
+                facts.push(self.symbols.print_fact(fact));
             }
             facts.sort();
 
@@ -1102,6 +2189,287 @@ impl std::fmt::Display for Authorizer {
     }
 }
 
+// indices of the checks in evaluation order, see `Authorizer::order_checks_by_cost`
+fn check_order(checks: &[datalog::Check], by_cost: bool) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..checks.len()).collect();
+    if by_cost {
+        // the sort is stable, so checks of the same cost keep their order
+        order.sort_by_key(|i| check_cost(&checks[*i]));
+    }
+    order
+}
+
+// rough estimate of the cost of a check, in arbitrary units
+fn check_cost(check: &datalog::Check) -> usize {
+    check
+        .queries
+        .iter()
+        .map(|query| {
+            let ops: usize = query
+                .expressions
+                .iter()
+                .flat_map(|e| e.ops.iter())
+                .map(|op| match op {
+                    datalog::Op::Value(_) => 0,
+                    datalog::Op::Binary(datalog::Binary::Regex) => 20,
+                    datalog::Op::Unary(_) | datalog::Op::Binary(_) => 1,
+                })
+                .sum();
+            2 * query.body.len() + ops
+        })
+        .sum()
+}
+
+/// provides facts during authorization, see [`Authorizer::register_resolver`]
+pub type FactResolver = dyn Fn(&[Option<Term>]) -> Vec<Vec<Term>> + Send + Sync;
+
+// calls the resolvers registered with `Authorizer::register_resolver`
+struct Resolvers<'a>(&'a HashMap<String, Arc<FactResolver>>);
+
+impl datalog::Resolver for Resolvers<'_> {
+    fn resolve(
+        &mut self,
+        name: datalog::SymbolIndex,
+        hints: &[Option<datalog::Term>],
+        symbols: &mut SymbolTable,
+    ) -> Option<Vec<datalog::Fact>> {
+        let name = symbols.get_symbol(name)?.to_string();
+        let resolver = self.0.get(&name)?;
+        let hints: Vec<Option<Term>> = hints
+            .iter()
+            .map(|hint| {
+                hint.as_ref()
+                    .and_then(|term| Term::convert_from(term, symbols).ok())
+            })
+            .collect();
+
+        Some(
+            resolver(&hints)
+                .into_iter()
+                .filter(|terms| {
+                    terms
+                        .iter()
+                        .all(|term| !matches!(term, Term::Variable(_) | Term::Parameter(_)))
+                })
+                .map(|terms| fact(&name, &terms).convert(symbols))
+                .collect(),
+        )
+    }
+}
+
+/// transformations of the path in [`Authorizer::http_request`]
+///
+/// they are applied in the order of the fields. Without them, the token's
+/// checks must match the path exactly as it was received, and a check like
+/// `$path.starts_with("/public/")` accepts `/public/../admin`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathNormalization {
+    /// decodes `%XX` sequences. Invalid sequences are kept as is, and invalid
+    /// UTF-8 is replaced with U+FFFD. Since this happens first, `%2F` becomes a
+    /// separator and `%2E%2E` a parent segment for the next steps
+    pub percent_decode: bool,
+    /// removes the `.` segments, and the `..` segments with the preceding one,
+    /// as in RFC 3986: `/a/./b/../c` becomes `/a/c`, and `..` cannot go above
+    /// the root, so `/../a` becomes `/a`. Empty segments are kept: `/a//b`
+    /// is unchanged
+    pub remove_dot_segments: bool,
+    /// removes a trailing `/`, except for the root path `/`
+    pub strip_trailing_slash: bool,
+}
+
+impl PathNormalization {
+    /// applies all the transformations
+    pub fn all() -> Self {
+        PathNormalization {
+            percent_decode: true,
+            remove_dot_segments: true,
+            strip_trailing_slash: true,
+        }
+    }
+
+    /// returns the transformed path
+    pub fn apply(&self, path: &str) -> String {
+        let mut path = if self.percent_decode {
+            percent_decode(path)
+        } else {
+            path.to_string()
+        };
+
+        if self.remove_dot_segments {
+            path = remove_dot_segments(&path);
+        }
+
+        if self.strip_trailing_slash && path.len() > 1 && path.ends_with('/') {
+            path.pop();
+        }
+
+        path
+    }
+}
+
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        // from_str_radix would also accept a sign, as in `%+F`
+        let escaped = match bytes.get(i + 1..i + 3) {
+            Some(hex) if bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit) => {
+                std::str::from_utf8(hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            }
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let mut segments: Vec<&str> = Vec::new();
+    let mut parts = path.split('/').peekable();
+    if absolute {
+        parts.next();
+    }
+
+    // a path ending with a dot segment designates a directory
+    let mut trailing_slash = false;
+    while let Some(segment) = parts.next() {
+        let last = parts.peek().is_none();
+        match segment {
+            "." => trailing_slash = last,
+            ".." => {
+                segments.pop();
+                trailing_slash = last;
+            }
+            segment => {
+                segments.push(segment);
+                trailing_slash = false;
+            }
+        }
+    }
+
+    let mut result = segments.join("/");
+    if absolute {
+        result.insert(0, '/');
+    }
+    if trailing_slash && !result.ends_with('/') {
+        result.push('/');
+    }
+    result
+}
+
+// line and column, starting at 1, of `input` in `source`
+fn source_position(source: &str, input: &str) -> (usize, usize) {
+    let offset = (input.as_ptr() as usize)
+        .checked_sub(source.as_ptr() as usize)
+        .filter(|offset| *offset <= source.len())
+        .unwrap_or(0);
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// records the nonces of single use tokens, see [`Authorizer::replay_protection`]
+pub trait NonceStore {
+    /// records `nonce` until `expires_at`, and returns false if it was
+    /// already recorded
+    fn check_and_insert(&mut self, nonce: &[u8], expires_at: SystemTime) -> bool;
+}
+
+/// in memory store, removing the expired nonces on insertion
+impl NonceStore for HashMap<Vec<u8>, SystemTime> {
+    fn check_and_insert(&mut self, nonce: &[u8], expires_at: SystemTime) -> bool {
+        let now = crate::time::now();
+        self.retain(|_, expiration| *expiration >= now);
+        if self.contains_key(nonce) {
+            return false;
+        }
+
+        self.insert(nonce.to_vec(), expires_at);
+        true
+    }
+}
+
+/// application reason codes for failed checks, set with
+/// [`Authorizer::set_denial_reasons`]
+///
+/// a failed check gets the code registered for its name, then for its
+/// position, then the default code. Only the authorizer's checks have a
+/// name, and positions only refer to the checks of the authorizer and of
+/// the token added with [`Authorizer::add_token`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenialReasons {
+    names: HashMap<String, String>,
+    positions: HashMap<(Option<u32>, u32), String>,
+    default: String,
+}
+
+impl DenialReasons {
+    /// creates a mapping where every failed check gets the `default` code
+    pub fn new(default: &str) -> Self {
+        DenialReasons {
+            names: HashMap::new(),
+            positions: HashMap::new(),
+            default: default.to_string(),
+        }
+    }
+
+    /// maps the authorizer checks named `name` to `reason`, see
+    /// [`Authorizer::add_check_named`]
+    pub fn add_named(&mut self, name: &str, reason: &str) {
+        self.names.insert(name.to_string(), reason.to_string());
+    }
+
+    /// maps the check at index `check_id` of block `block_id` to `reason`.
+    /// `None` refers to the authorizer's checks
+    pub fn add_position(&mut self, block_id: Option<u32>, check_id: u32, reason: &str) {
+        self.positions
+            .insert((block_id, check_id), reason.to_string());
+    }
+
+    /// returns the code of a failed check
+    pub fn reason(&self, check: &error::FailedCheck) -> &str {
+        let (name, position) = match check {
+            error::FailedCheck::Block(check) => (
+                None,
+                Some((Some(check.block_id), check.check_id)).filter(|_| check.token_id == 0),
+            ),
+            error::FailedCheck::Authorizer(check) => {
+                (check.name.as_ref(), Some((None, check.check_id)))
+            }
+        };
+
+        name.and_then(|name| self.names.get(name))
+            .or_else(|| position.and_then(|position| self.positions.get(&position)))
+            .unwrap_or(&self.default)
+    }
+
+    fn resolve(&self, check: &mut error::FailedCheck) {
+        let reason = Some(self.reason(check).to_string());
+        match check {
+            error::FailedCheck::Block(check) => check.reason = reason,
+            error::FailedCheck::Authorizer(check) => check.reason = reason,
+        }
+    }
+}
+
 impl TryFrom<AuthorizerPolicies> for Authorizer {
     type Error = error::Token;
 
@@ -1174,10 +2542,398 @@ impl AuthorizerPolicies {
 
 pub type AuthorizerLimits = RunLimits;
 
+/// outcome of a check evaluated during authorization
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+pub struct CheckResult {
+    /// index of the block containing the check, `None` for the authorizer's checks
+    pub block_id: Option<u32>,
+    /// index of the token containing the check, see [`error::FailedBlockCheck::token_id`]
+    pub token_id: u32,
+    /// index of the check in its block
+    pub check_id: u32,
+    /// pretty print of the check
+    pub rule: String,
+    /// name of the check, see [`Authorizer::add_check_named`]. Names are not
+    /// serialized, so the checks of the token do not have one
+    pub name: Option<String>,
+    /// true if the check passed
+    pub success: bool,
+    pub status: CheckStatus,
+    /// time spent evaluating the check, zero if it was skipped
+    pub duration: Duration,
+    /// variables of the query that satisfied the check, indexed by name,
+    /// if enabled with [`Authorizer::record_bindings`]. They are not serialized
+    #[cfg_attr(feature = "serde-error", serde(skip))]
+    pub bindings: Vec<HashMap<String, Term>>,
+    /// true if more bindings matched than the configured maximum
+    pub bindings_truncated: bool,
+    /// for a failed check, facts that match a predicate of the check except
+    /// for the type of a term, like the integer `1` instead of the string `"1"`
+    pub hints: Vec<String>,
+    /// for a failed check, match counts of each query, if enabled with
+    /// [`Authorizer::record_diagnostics`]
+    pub diagnostics: Vec<QueryDiagnostics>,
+}
+
+/// match counts of a query of a failed check, see
+/// [`Authorizer::record_diagnostics`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryDiagnostics {
+    /// pretty print of the query
+    pub query: String,
+    /// each predicate of the query, with the number of facts matching it on
+    /// its own
+    pub predicates: Vec<(String, usize)>,
+    /// number of combinations of facts matching all the predicates
+    pub candidates: usize,
+    /// each expression of the query, with the number of combinations it
+    /// rejected. Expressions are evaluated in order, so a combination is only
+    /// counted for the first expression rejecting it
+    pub expressions: Vec<(String, usize)>,
+}
+
+impl CheckResult {
+    fn skipped(
+        block_id: Option<u32>,
+        token_id: u32,
+        check_id: u32,
+        rule: String,
+        name: Option<String>,
+    ) -> Self {
+        CheckResult {
+            block_id,
+            token_id,
+            check_id,
+            rule,
+            name,
+            success: false,
+            status: CheckStatus::Skipped,
+            duration: Duration::default(),
+            bindings: Vec::new(),
+            bindings_truncated: false,
+            hints: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.status == CheckStatus::Failed
+    }
+
+    /// converts a failed check to the error type
+    pub fn failure(&self) -> Option<error::FailedCheck> {
+        if !self.failed() {
+            return None;
+        }
+
+        Some(match self.block_id {
+            None => error::FailedCheck::Authorizer(error::FailedAuthorizerCheck {
+                check_id: self.check_id,
+                rule: self.rule.clone(),
+                name: self.name.clone(),
+                reason: None,
+            }),
+            Some(block_id) => error::FailedCheck::Block(error::FailedBlockCheck {
+                block_id,
+                check_id: self.check_id,
+                rule: self.rule.clone(),
+                token_id: self.token_id,
+                reason: None,
+            }),
+        })
+    }
+}
+
+/// outcome of a check, see [`CheckResult`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-error", serde(rename_all = "snake_case"))]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// not evaluated because a previous check failed, see
+    /// [`Authorizer::set_fail_fast`]
+    Skipped,
+}
+
+impl CheckStatus {
+    fn from_success(success: bool) -> Self {
+        if success {
+            CheckStatus::Passed
+        } else {
+            CheckStatus::Failed
+        }
+    }
+}
+
+impl std::fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CheckStatus::Passed => "passed",
+            CheckStatus::Failed => "failed",
+            CheckStatus::Skipped => "skipped",
+        })
+    }
+}
+
+// checks of all the blocks of a token, when fail fast mode skips them
+fn skipped_token_checks(token: &Biscuit, token_id: u32) -> Result<Vec<CheckResult>, error::Token> {
+    let mut results = Vec::new();
+    for i in 0..token.block_count() {
+        let block = token.block(i)?;
+        let symbols = if block.external_key.is_some() {
+            &block.symbols
+        } else {
+            &token.symbols
+        };
+        for (j, check) in block.checks.iter().enumerate() {
+            results.push(CheckResult::skipped(
+                Some(i as u32),
+                token_id,
+                j as u32,
+                symbols.print_check(check),
+                check.name.clone(),
+            ));
+        }
+    }
+    Ok(results)
+}
+
+/// counters of an authorization, obtained with [`Authorizer::metrics`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthorizerMetrics {
+    /// number of facts in the world after evaluation
+    pub facts: usize,
+    /// number of facts generated by rules during the last authorization
+    pub derived_facts: usize,
+    /// number of iterations of the Datalog engine
+    pub iterations: u64,
+    /// number of checks evaluated
+    pub checks: usize,
+    /// time spent decoding the token and verifying its signatures, zero if
+    /// the token was not deserialized
+    pub signature_time: Duration,
+    /// total time spent in Datalog evaluation
+    pub execution_time: Duration,
+    /// true if [`AuthorizerLimits::max_derivation_depth`] prevented the
+    /// generation of some facts
+    pub derivation_depth_reached: bool,
+}
+
+/// detailed result of an authorization, obtained with [`Authorizer::report`]
+///
+/// it is displayed as a table of the checks, followed by the matched policy
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthorizerReport {
+    /// outcome of each check, in the evaluation order described in
+    /// [`Authorizer::set_fail_fast`]
+    pub checks: Vec<CheckResult>,
+    /// the policy that matched, if any
+    pub policy: Option<error::MatchedPolicy>,
+    /// number of facts in the world after evaluation
+    pub fact_count: usize,
+    /// number of facts generated by rules during the last authorization
+    pub derived_facts: usize,
+    /// number of iterations of the Datalog engine
+    pub iterations: u64,
+    /// total time spent in Datalog evaluation
+    pub execution_time: Duration,
+    /// statistics of the Datalog evaluation, see [`Authorizer::run_stats`]
+    pub run_stats: RunStats,
+    /// facts of the world, only filled if enabled with
+    /// [`Authorizer::record_provenance`]. They are not serialized
+    #[cfg_attr(feature = "serde-error", serde(skip))]
+    pub facts: Vec<WorldFact>,
+    /// the token, if one was added with [`Authorizer::add_token`]
+    pub token: Option<TokenSummary>,
+    /// values of the authorizer's `resource` facts
+    pub resources: Vec<String>,
+    /// values of the authorizer's `operation` facts
+    pub operations: Vec<String>,
+    /// time set with [`Authorizer::set_time`] or [`Authorizer::set_time_at`]
+    pub time: Option<SystemTime>,
+    /// time spent decoding the token and verifying its signatures
+    pub signature_time: Duration,
+}
+
+impl AuthorizerReport {
+    /// creates a record of the authorization decision, to be stored in an
+    /// audit log
+    ///
+    /// it contains enough information to identify the token and explain the
+    /// decision, but no key material, token bytes or facts
+    pub fn to_audit_record(&self) -> AuditRecord {
+        let failed_checks: Vec<CheckResult> = self
+            .checks
+            .iter()
+            .filter(|check| check.failed())
+            .cloned()
+            .collect();
+
+        AuditRecord {
+            version: AUDIT_RECORD_VERSION,
+            authorized: failed_checks.is_empty()
+                && matches!(self.policy, Some(error::MatchedPolicy::Allow(_))),
+            policy: self.policy.clone(),
+            failed_checks,
+            token: self.token.clone(),
+            resources: self.resources.clone(),
+            operations: self.operations.clone(),
+            time: self.time.map(unix_seconds),
+            signature_time: self.signature_time,
+            execution_time: self.execution_time,
+        }
+    }
+}
+
+/// version of the [`AuditRecord`] structure
+///
+/// it changes when fields are removed or change meaning
+pub const AUDIT_RECORD_VERSION: u32 = 1;
+
+/// record of an authorization decision, created by
+/// [`AuthorizerReport::to_audit_record`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuditRecord {
+    /// see [`AUDIT_RECORD_VERSION`]
+    pub version: u32,
+    /// true if a policy allowed the request and all checks passed
+    pub authorized: bool,
+    pub policy: Option<error::MatchedPolicy>,
+    pub failed_checks: Vec<CheckResult>,
+    pub token: Option<TokenSummary>,
+    pub resources: Vec<String>,
+    pub operations: Vec<String>,
+    /// time of the authorization, in seconds since the Unix epoch
+    pub time: Option<u64>,
+    pub signature_time: Duration,
+    pub execution_time: Duration,
+}
+
+/// identifies a token without containing it, see [`AuthorizerReport::token`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenSummary {
+    /// hex encoded [`Biscuit::digest`]
+    pub digest: String,
+    pub root_key_id: Option<u32>,
+    /// hex encoded SHA-256 hash of the root public key, if it is known
+    pub root_key_fingerprint: Option<String>,
+    pub block_count: usize,
+    /// hex encoded [`Biscuit::revocation_identifiers`]
+    pub revocation_ids: Vec<String>,
+    /// issue timestamp of the authority block, in seconds since the Unix epoch
+    pub issued_at: Option<u64>,
+}
+
+impl TokenSummary {
+    fn new(token: &Biscuit) -> Self {
+        use sha2::{Digest, Sha256};
+
+        TokenSummary {
+            digest: hex::encode(token.digest()),
+            root_key_id: token.root_key_id,
+            root_key_fingerprint: token
+                .root_public_key
+                .map(|key| hex::encode(Sha256::digest(&key.to_bytes()))),
+            block_count: token.block_count(),
+            revocation_ids: token
+                .revocation_identifiers()
+                .iter()
+                .map(hex::encode)
+                .collect(),
+            issued_at: token.issued_at().map(unix_seconds),
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl std::fmt::Display for AuthorizerReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows: Vec<(String, String)> = self
+            .checks
+            .iter()
+            .map(|check| {
+                let mut location = match (check.token_id, check.block_id) {
+                    (_, None) => "authorizer".to_string(),
+                    (0, Some(block)) => format!("block {}", block),
+                    (token, Some(block)) => format!("token {} block {}", token, block),
+                };
+                location.push_str(&format!(" #{}", check.check_id));
+                if let Some(name) = &check.name {
+                    location.push_str(&format!(" ({})", name));
+                }
+                let duration = match check.status {
+                    CheckStatus::Skipped => String::new(),
+                    _ => format!("{:?}", check.duration),
+                };
+                (location, duration)
+            })
+            .collect();
+        let location_width = rows.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+        let duration_width = rows.iter().map(|(_, d)| d.len()).max().unwrap_or(0);
+
+        for (check, (location, duration)) in self.checks.iter().zip(rows.iter()) {
+            writeln!(
+                f,
+                "{:<7}  {:<lw$}  {:>dw$}  {}",
+                check.status.to_string(),
+                location,
+                duration,
+                check.rule,
+                lw = location_width,
+                dw = duration_width,
+            )?;
+        }
+        match &self.policy {
+            Some(policy) => write!(f, "policy: {}", policy),
+            None => write!(f, "policy: no policy matched"),
+        }
+    }
+}
+
+/// a fact of the authorizer's world, obtained with [`Authorizer::world_facts`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorldFact {
+    pub fact: Fact,
+    /// blocks trusted to provide this fact (the block that defined it, or the
+    /// blocks of the facts and rule that generated it), `None` being the authorizer
+    /// or the secondary tokens
+    pub origin: Vec<Option<usize>>,
+    /// how the fact was added, if enabled with [`Authorizer::record_provenance`]
+    pub provenance: Option<FactProvenance>,
+}
+
+/// how a fact was added to the authorizer's world
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FactProvenance {
+    /// defined in the authority block of the token
+    Authority,
+    /// defined in the attenuation block with this index
+    Block(usize),
+    /// added to the authorizer
+    Authorizer,
+    /// defined in the authority block of the secondary token with this index,
+    /// see [`Authorizer::add_secondary_token`]
+    Token(usize),
+    /// generated by a rule of the block with this index, `None` for the
+    /// authorizer's rules
+    Derived { rule_block: Option<usize> },
+}
+
 impl BuilderExt for Authorizer {
     fn add_resource(&mut self, name: &str) {
-        let f = fact("resource", &[string(name)]);
-        self.add_fact(f).unwrap();
+        self.add_fact(resource_fact(name)).unwrap();
     }
     fn check_resource(&mut self, name: &str) {
         self.add_check(Check {
@@ -1187,12 +2943,12 @@ impl BuilderExt for Authorizer {
                 &[pred("resource", &[string(name)])],
             )],
             kind: CheckKind::One,
+            name: Some("resource_check".to_string()),
         })
         .unwrap();
     }
     fn add_operation(&mut self, name: &str) {
-        let f = fact("operation", &[string(name)]);
-        self.add_fact(f).unwrap();
+        self.add_fact(operation_fact(name)).unwrap();
     }
     fn check_operation(&mut self, name: &str) {
         self.add_check(Check {
@@ -1202,6 +2958,7 @@ impl BuilderExt for Authorizer {
                 &[pred("operation", &[string(name)])],
             )],
             kind: CheckKind::One,
+            name: Some("operation_check".to_string()),
         })
         .unwrap();
     }
@@ -1222,6 +2979,7 @@ impl BuilderExt for Authorizer {
         self.add_check(Check {
             queries: vec![check],
             kind: CheckKind::One,
+            name: Some("prefix".to_string()),
         })
         .unwrap();
     }
@@ -1243,10 +3001,38 @@ impl BuilderExt for Authorizer {
         self.add_check(Check {
             queries: vec![check],
             kind: CheckKind::One,
+            name: Some("suffix".to_string()),
         })
         .unwrap();
     }
 
+    fn check_resource_ci(&mut self, name: &str) {
+        self.add_check(resource_check(
+            "resource_check",
+            &ascii_case_insensitive("^", name, "$"),
+            Binary::Regex,
+        ))
+        .unwrap();
+    }
+
+    fn check_resource_prefix_ci(&mut self, prefix: &str) {
+        self.add_check(resource_check(
+            "prefix",
+            &ascii_case_insensitive("^", prefix, ""),
+            Binary::Regex,
+        ))
+        .unwrap();
+    }
+
+    fn check_resource_suffix_ci(&mut self, suffix: &str) {
+        self.add_check(resource_check(
+            "suffix",
+            &ascii_case_insensitive("", suffix, "$"),
+            Binary::Regex,
+        ))
+        .unwrap();
+    }
+
     fn check_expiration_date(&mut self, exp: SystemTime) {
         let check = constrained_rule(
             "expiration",
@@ -1264,9 +3050,30 @@ impl BuilderExt for Authorizer {
         self.add_check(Check {
             queries: vec![check],
             kind: CheckKind::One,
+            name: Some("expiration".to_string()),
         })
         .unwrap();
     }
+
+    fn check_integer_at_most(&mut self, name: &str, max: i64) {
+        self.add_check(integer_check(name, max, Binary::LessOrEqual))
+            .unwrap();
+    }
+
+    fn check_integer_below(&mut self, name: &str, max: i64) {
+        self.add_check(integer_check(name, max, Binary::LessThan))
+            .unwrap();
+    }
+
+    fn check_integer_at_least(&mut self, name: &str, min: i64) {
+        self.add_check(integer_check(name, min, Binary::GreaterOrEqual))
+            .unwrap();
+    }
+
+    fn check_integer_above(&mut self, name: &str, min: i64) {
+        self.add_check(integer_check(name, min, Binary::GreaterThan))
+            .unwrap();
+    }
 }
 
 impl AuthorizerExt for Authorizer {
@@ -1447,37 +3254,158 @@ mod tests {
     }
 
     #[test]
-    fn authorizer_with_scopes() {
+    fn ambient_facts_from_attenuation() {
+        use crate::Biscuit;
+        use crate::KeyPair;
         let root = KeyPair::new();
-        let external = KeyPair::new();
 
         let mut builder = Biscuit::builder();
-        let mut scope_params = HashMap::new();
-        scope_params.insert("external_pub".to_string(), external.public());
         builder
-            .add_code_with_params(
-                r#"right("read");
-               check if group("admin") trusting {external_pub};
-            "#,
-                HashMap::new(),
-                scope_params,
-            )
+            .add_code(r#"check if resource($path), $path.starts_with("/public/")"#)
             .unwrap();
+        let token = builder.build(&root).unwrap();
 
-        let biscuit1 = builder.build(&root).unwrap();
-
-        let req = biscuit1.third_party_request().unwrap();
+        // an attenuation block claims the ambient data it wants
+        let mut block = BlockBuilder::new();
+        block
+            .add_code(r#"resource("/public/admin"); operation("write");"#)
+            .unwrap();
+        let serialized = token.append(block).unwrap().to_vec().unwrap();
+        let token = Biscuit::from(&serialized, root.public()).unwrap();
 
-        let mut builder = BlockBuilder::new();
-        builder
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer
             .add_code(
-                r#"group("admin");
-             check if right("read");
-            "#,
+                r#"resource("/admin");
+                allow if operation("write");"#,
             )
             .unwrap();
-        let res = req.create_block(&external.private(), builder).unwrap();
-        let biscuit2 = biscuit1.append_third_party(external.public(), res).unwrap();
+        let res = authorizer.authorize();
+        assert!(matches!(
+            res,
+            Err(error::Token::FailedLogic(error::Logic::NoMatchingPolicy { ref checks }))
+                if checks.len() == 1
+        ));
+
+        // the facts still exist, but only for the block that defined them
+        let operations: Vec<(String,)> =
+            authorizer.query_all("data($op) <- operation($op)").unwrap();
+        assert_eq!(operations, vec![("write".to_string(),)]);
+    }
+
+    #[test]
+    fn fact_provenance() {
+        use crate::Biscuit;
+        use crate::KeyPair;
+        let root = KeyPair::new();
+
+        let mut builder = Biscuit::builder();
+        builder
+            .add_code(r#"user("alice"); role($u, "reader") <- user($u);"#)
+            .unwrap();
+        let token = builder
+            .build(&root)
+            .unwrap()
+            .attenuate(|block| block.add_code(r#"note("x"); check if operation("read");"#))
+            .unwrap();
+
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer
+            .add_code(
+                r#"operation("read");
+                allowed($u) <- role($u, "reader");
+                allow if allowed("alice");"#,
+            )
+            .unwrap();
+        let mut recording = authorizer.clone();
+        recording.record_provenance(true);
+
+        assert_eq!(authorizer.authorize(), Ok(0));
+        assert_eq!(recording.authorize(), Ok(0));
+
+        let facts = authorizer.world_facts().unwrap();
+        let recorded = recording.world_facts().unwrap();
+        let without_provenance = |facts: &[WorldFact]| {
+            facts
+                .iter()
+                .map(|f| (f.fact.clone(), f.origin.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(without_provenance(&facts), without_provenance(&recorded));
+        assert!(facts.iter().all(|f| f.provenance.is_none()));
+        assert!(authorizer.report().facts.is_empty());
+
+        let provenance: Vec<(String, Vec<Option<usize>>, Option<FactProvenance>)> = recorded
+            .into_iter()
+            .map(|f| (f.fact.to_string(), f.origin, f.provenance))
+            .collect();
+        assert_eq!(
+            provenance,
+            vec![
+                (
+                    r#"role("alice", "reader")"#.to_string(),
+                    vec![Some(0)],
+                    Some(FactProvenance::Derived {
+                        rule_block: Some(0)
+                    })
+                ),
+                (
+                    r#"user("alice")"#.to_string(),
+                    vec![Some(0)],
+                    Some(FactProvenance::Authority)
+                ),
+                (
+                    r#"allowed("alice")"#.to_string(),
+                    vec![Some(0), None],
+                    Some(FactProvenance::Derived { rule_block: None })
+                ),
+                (
+                    r#"note("x")"#.to_string(),
+                    vec![Some(1)],
+                    Some(FactProvenance::Block(1))
+                ),
+                (
+                    r#"operation("read")"#.to_string(),
+                    vec![None],
+                    Some(FactProvenance::Authorizer)
+                ),
+            ]
+        );
+        assert_eq!(recording.report().facts.len(), 5);
+    }
+
+    #[test]
+    fn authorizer_with_scopes() {
+        let root = KeyPair::new();
+        let external = KeyPair::new();
+
+        let mut builder = Biscuit::builder();
+        let mut scope_params = HashMap::new();
+        scope_params.insert("external_pub".to_string(), external.public());
+        builder
+            .add_code_with_params(
+                r#"right("read");
+               check if group("admin") trusting {external_pub};
+            "#,
+                HashMap::new(),
+                scope_params,
+            )
+            .unwrap();
+
+        let biscuit1 = builder.build(&root).unwrap();
+
+        let req = biscuit1.third_party_request().unwrap();
+
+        let mut builder = BlockBuilder::new();
+        builder
+            .add_code(
+                r#"group("admin");
+             check if right("read");
+            "#,
+            )
+            .unwrap();
+        let res = req.create_block(&external.private(), builder).unwrap();
+        let biscuit2 = biscuit1.append_third_party(external.public(), res).unwrap();
 
         let mut authorizer = Authorizer::new();
         let external2 = KeyPair::new();
@@ -1579,4 +3507,1567 @@ mod tests {
             .unwrap();
         assert_eq!(block_facts_query_all_explicit.len(), 0);
     }
+
+    #[test]
+    fn authorization_report() {
+        use crate::Biscuit;
+        use crate::KeyPair;
+        let keypair = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        builder
+            .add_code("can_read($f) <- right($f, \"read\"); check if operation(\"read\")")
+            .unwrap();
+
+        let biscuit = builder
+            .build(&keypair)
+            .unwrap()
+            .attenuate(|block| block.add_check("check if resource(\"file2\")"))
+            .unwrap();
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer
+            .add_code(
+                r#"resource("file1");
+                operation("read");
+                check if can_read("file1");
+                allow if true;
+                "#,
+            )
+            .unwrap();
+
+        let res = authorizer.authorize();
+        assert!(res.is_err());
+
+        let report = authorizer.report();
+        // durations vary between runs
+        let mut checks = report.checks.clone();
+        for check in checks.iter_mut() {
+            check.duration = Duration::default();
+        }
+        assert_eq!(
+            checks,
+            vec![
+                CheckResult {
+                    block_id: None,
+                    check_id: 0,
+                    rule: "check if can_read(\"file1\")".to_string(),
+                    success: true,
+                    status: CheckStatus::Passed,
+                    duration: Duration::default(),
+                    bindings: vec![],
+                    bindings_truncated: false,
+                    name: None,
+                    hints: vec![],
+                    diagnostics: vec![],
+                    token_id: 0,
+                },
+                CheckResult {
+                    block_id: Some(0),
+                    check_id: 0,
+                    rule: "check if operation(\"read\")".to_string(),
+                    success: true,
+                    status: CheckStatus::Passed,
+                    duration: Duration::default(),
+                    bindings: vec![],
+                    bindings_truncated: false,
+                    name: None,
+                    hints: vec![],
+                    diagnostics: vec![],
+                    token_id: 0,
+                },
+                CheckResult {
+                    block_id: Some(1),
+                    check_id: 0,
+                    rule: "check if resource(\"file2\")".to_string(),
+                    success: false,
+                    status: CheckStatus::Failed,
+                    duration: Duration::default(),
+                    bindings: vec![],
+                    bindings_truncated: false,
+                    name: None,
+                    hints: vec![],
+                    diagnostics: vec![],
+                    token_id: 0,
+                },
+            ]
+        );
+        assert_eq!(report.policy, Some(error::MatchedPolicy::Allow(0)));
+        assert_eq!(report.fact_count, 4);
+        assert_eq!(report.derived_facts, 1);
+        assert!(report.iterations > 0);
+        assert_eq!(report.execution_time, authorizer.execution_time());
+
+        let failed: Vec<_> = report.checks.iter().filter_map(|c| c.failure()).collect();
+        assert_eq!(
+            res,
+            Err(error::Token::FailedLogic(error::Logic::Unauthorized {
+                policy: error::MatchedPolicy::Allow(0),
+                checks: failed,
+            }))
+        );
+    }
+
+    #[test]
+    fn audit_record() {
+        use crate::Biscuit;
+        use crate::KeyPair;
+        use sha2::{Digest, Sha256};
+
+        let root = KeyPair::new();
+        let issued_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"/files/1\", \"read\")").unwrap();
+        builder.add_issued_at(issued_at);
+        let biscuit = builder
+            .build(&root)
+            .unwrap()
+            .attenuate(|block| block.add_check("check if operation(\"read\")"))
+            .unwrap();
+        let serialized = biscuit.to_vec().unwrap();
+        let biscuit = Biscuit::from(&serialized, root.public()).unwrap();
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer.set_time_at(issued_at + Duration::from_secs(60));
+        authorizer.http_request("POST", "/files/1", PathNormalization::default());
+        authorizer
+            .add_code("allow if right($path, $op), resource($path), operation($op)")
+            .unwrap();
+        assert!(authorizer.authorize().is_err());
+
+        let record = authorizer.report().to_audit_record();
+        assert_eq!(record.version, AUDIT_RECORD_VERSION);
+        assert!(!record.authorized);
+        assert_eq!(record.policy, None);
+        assert_eq!(record.failed_checks.len(), 1);
+        assert_eq!(record.failed_checks[0].block_id, Some(1));
+        assert_eq!(record.resources, vec!["/files/1".to_string()]);
+        assert_eq!(record.operations, vec!["post".to_string()]);
+        assert_eq!(record.time, Some(1_700_000_060));
+        assert_eq!(record.signature_time, biscuit.verification_time);
+
+        let token = record.token.as_ref().unwrap();
+        assert_eq!(token.digest, hex::encode(Sha256::digest(&serialized)));
+        assert_eq!(
+            token.root_key_fingerprint,
+            Some(hex::encode(Sha256::digest(&root.public().to_bytes())))
+        );
+        assert_eq!(token.block_count, 2);
+        assert_eq!(
+            token.revocation_ids,
+            biscuit
+                .revocation_identifiers()
+                .iter()
+                .map(hex::encode)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(token.issued_at, Some(1_700_000_000));
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer.add_operation("read");
+        authorizer.add_allow_all();
+        authorizer.authorize().unwrap();
+        let record = authorizer.report().to_audit_record();
+        assert!(record.authorized);
+        assert_eq!(record.policy, Some(error::MatchedPolicy::Allow(0)));
+        assert!(record.failed_checks.is_empty());
+
+        #[cfg(feature = "serde-error")]
+        {
+            let json = serde_json::to_string(&record).unwrap();
+            assert!(!json.contains(&biscuit.to_base64().unwrap()));
+            assert!(!json.contains(&root.public().to_bytes_hex()));
+            let parsed: AuditRecord = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, record);
+        }
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut authorizer = Authorizer::new();
+        authorizer
+            .add_code(
+                r#"right("file1", "read");
+                right("file2", "read");
+                right("file3", "write");
+                resource("file1");
+                resource("file3");
+                check if right($f, "read"), resource($f), $f.starts_with("file"), $f != "file1"
+                    or user($u);
+                check if resource("file1");
+                allow if true;"#,
+            )
+            .unwrap();
+        authorizer.authorize().unwrap_err();
+        assert!(authorizer
+            .report()
+            .checks
+            .iter()
+            .all(|c| c.diagnostics.is_empty()));
+
+        authorizer.record_diagnostics(true);
+        authorizer.authorize().unwrap_err();
+        let checks = authorizer.report().checks;
+        assert_eq!(
+            checks[0].diagnostics,
+            vec![
+                QueryDiagnostics {
+                    query:
+                        r#"right($f, "read"), resource($f), $f.starts_with("file"), $f != "file1""#
+                            .to_string(),
+                    predicates: vec![
+                        (r#"right($f, "read")"#.to_string(), 2),
+                        ("resource($f)".to_string(), 2),
+                    ],
+                    candidates: 1,
+                    expressions: vec![
+                        (r#"$f.starts_with("file")"#.to_string(), 0),
+                        (r#"$f != "file1""#.to_string(), 1),
+                    ],
+                },
+                QueryDiagnostics {
+                    query: "user($u)".to_string(),
+                    predicates: vec![("user($u)".to_string(), 0)],
+                    candidates: 0,
+                    expressions: vec![],
+                },
+            ]
+        );
+        assert!(checks[1].success);
+        assert!(checks[1].diagnostics.is_empty());
+    }
+
+    #[test]
+    fn check_statuses() {
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        builder.add_check("check if operation(\"write\")").unwrap();
+        let biscuit = builder
+            .build(&root)
+            .unwrap()
+            .attenuate(|block| block.add_check("check if resource(\"file1\")"))
+            .unwrap();
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer
+            .add_code(
+                r#"resource("file1");
+                operation("read");
+                check if right("file1", "read");
+                allow if true;
+                "#,
+            )
+            .unwrap();
+        authorizer.set_fail_fast(true);
+        authorizer.authorize().unwrap_err();
+
+        let report = authorizer.report();
+        let statuses: Vec<_> = report
+            .checks
+            .iter()
+            .map(|c| (c.block_id, c.check_id, c.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (None, 0, CheckStatus::Passed),
+                (Some(0), 0, CheckStatus::Failed),
+                (Some(1), 0, CheckStatus::Skipped),
+            ]
+        );
+        assert_eq!(report.checks[2].duration, Duration::default());
+        assert_eq!(report.checks[2].rule, "check if resource(\"file1\")");
+        assert_eq!(report.checks.iter().filter_map(|c| c.failure()).count(), 1);
+        assert_eq!(authorizer.metrics().checks, 2);
+
+        let table = report.to_string();
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("passed   authorizer #0"));
+        assert!(lines[0].ends_with("check if right(\"file1\", \"read\")"));
+        assert!(lines[1].starts_with("failed   block 0 #0"));
+        assert!(lines[2].starts_with("skipped  block 1 #0"));
+        assert!(lines[2].ends_with("check if resource(\"file1\")"));
+        assert_eq!(
+            lines[3],
+            "policy: an allow policy matched (policy index: 0)"
+        );
+
+        // without fail fast, every check is evaluated
+        authorizer.set_fail_fast(false);
+        authorizer.authorize().unwrap_err();
+        assert!(authorizer
+            .report()
+            .checks
+            .iter()
+            .all(|c| c.status != CheckStatus::Skipped));
+
+        #[cfg(feature = "serde-error")]
+        {
+            let json = serde_json::to_value(&report).unwrap();
+            assert_eq!(json["checks"][1]["status"], "failed");
+            assert_eq!(json["checks"][2]["status"], "skipped");
+            let parsed: AuthorizerReport = serde_json::from_value(json).unwrap();
+            assert_eq!(parsed.checks.len(), 3);
+            assert_eq!(parsed.policy, report.policy);
+        }
+    }
+
+    #[test]
+    fn denial_reasons() {
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_check("check if resource(\"file2\")").unwrap();
+        let biscuit = builder
+            .build(&root)
+            .unwrap()
+            .attenuate(|block| block.add_check("check if operation(\"write\")"))
+            .unwrap();
+
+        let mut reasons = DenialReasons::new("forbidden");
+        reasons.add_named("expiration", "link_expired");
+        reasons.add_position(None, 0, "ignored");
+        reasons.add_position(None, 1, "missing_operation");
+        reasons.add_position(Some(0), 0, "no_access");
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer
+            .add_check_named("expiration", "check if time($t), $t < 2020-01-01T00:00:00Z")
+            .unwrap();
+        authorizer
+            .add_code(
+                r#"time(2021-01-01T00:00:00Z);
+                resource("file1");
+                check if operation($op);
+                allow if true;
+                "#,
+            )
+            .unwrap();
+
+        let reasons_of = |res: Result<usize, error::Token>| match res {
+            Err(error::Token::FailedLogic(error::Logic::Unauthorized { checks, .. })) => checks
+                .iter()
+                .map(|c| c.reason().map(str::to_string))
+                .collect::<Vec<_>>(),
+            res => panic!("unexpected result: {:?}", res),
+        };
+
+        let mut unmapped = authorizer.clone();
+        assert_eq!(
+            reasons_of(unmapped.authorize()),
+            vec![None, None, None, None]
+        );
+
+        authorizer.set_denial_reasons(Some(reasons.clone()));
+        assert_eq!(
+            reasons_of(authorizer.authorize()),
+            vec![
+                Some("link_expired".to_string()),
+                Some("missing_operation".to_string()),
+                Some("no_access".to_string()),
+                Some("forbidden".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_source() {
+        let document = r#"
+            // facts and rules
+            resource("file1");
+            can_read($r) <- right($r, "read");
+
+            /* checks and policies */
+            check if resource($r), can_read($r);
+            check all operation($op), ["read", "list"].contains($op);
+            deny if resource("file2");
+            allow if operation("read");
+        "#;
+
+        let authorizer = Authorizer::from_policy_source("policy.datalog", document).unwrap();
+        let printed = authorizer.dump_code();
+        assert_eq!(
+            printed,
+            r#"resource("file1");
+
+can_read($r) <- right($r, "read");
+
+check if resource($r), can_read($r);
+check all operation($op), ["read", "list"].contains($op);
+
+deny if resource("file2");
+allow if operation("read");
+"#
+        );
+        let parsed = Authorizer::from_policy_source("printed", &printed).unwrap();
+        assert_eq!(parsed.dump_code(), printed);
+
+        let res = Authorizer::from_policy_source(
+            "policy.datalog",
+            "resource(\"file1\");\n  allow if resource($r;\n\tdeny if true;\ncheck if ;",
+        );
+        let errors = match res {
+            Err(error::Token::PolicySyntax(errors)) => errors,
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        };
+        let positions: Vec<_> = errors
+            .iter()
+            .map(|e| (e.source.as_str(), e.line, e.column))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![("policy.datalog", 2, 12), ("policy.datalog", 4, 10)]
+        );
+    }
+
+    #[test]
+    fn http_request() {
+        let decode = PathNormalization {
+            percent_decode: true,
+            ..Default::default()
+        };
+        let dots = PathNormalization {
+            remove_dot_segments: true,
+            ..Default::default()
+        };
+        let slash = PathNormalization {
+            strip_trailing_slash: true,
+            ..Default::default()
+        };
+        let all = PathNormalization::all();
+
+        let cases = [
+            (PathNormalization::default(), "/a/../b%20c/", "/a/../b%20c/"),
+            (decode, "/b%20c/%C3%A9", "/b c/é"),
+            (decode, "/a%2Fb", "/a/b"),
+            (decode, "/100%/%zz/%4", "/100%/%zz/%4"),
+            (decode, "/%+F/%-1", "/%+F/%-1"),
+            (decode, "/%FF", "/\u{FFFD}"),
+            (dots, "/a/../b", "/b"),
+            (dots, "/a/./b/../c", "/a/c"),
+            (dots, "/../../a", "/a"),
+            (dots, "/a/b/..", "/a/"),
+            (dots, "/a//b/.", "/a//b/"),
+            (dots, "/a/..b/c.", "/a/..b/c."),
+            (dots, "/public/%2E%2E/admin", "/public/%2E%2E/admin"),
+            (slash, "/a/b/", "/a/b"),
+            (slash, "/", "/"),
+            (all, "/public/%2E%2E/admin/", "/admin"),
+            (all, "/public/..%2Fadmin", "/admin"),
+            (all, "/a/b/..", "/a"),
+        ];
+        for (normalization, path, expected) in cases.iter() {
+            assert_eq!(
+                normalization.apply(path),
+                *expected,
+                "{:?} {}",
+                normalization,
+                path
+            );
+        }
+
+        let mut authorizer = Authorizer::new();
+        authorizer.http_request("PROPFIND", "/files/../admin/", all);
+        authorizer
+            .add_code(
+                r#"allow if operation("propfind"), resource("/admin");
+                deny if true;"#,
+            )
+            .unwrap();
+        assert_eq!(authorizer.authorize(), Ok(0));
+
+        // without normalization, the prefix check accepts a path outside of /public/
+        let prefix_policy = |normalization| {
+            let mut authorizer = Authorizer::new();
+            authorizer.http_request("GET", "/public/../admin", normalization);
+            authorizer
+                .add_code(
+                    r#"allow if operation("get"), resource($path), $path.starts_with("/public/");
+                    deny if true;"#,
+                )
+                .unwrap();
+            authorizer.authorize()
+        };
+        assert_eq!(prefix_policy(PathNormalization::default()), Ok(0));
+        assert!(prefix_policy(dots).is_err());
+    }
+
+    #[test]
+    fn resolvers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder
+            .add_code(r#"can_read($u) <- role($u, "reader"); check if user($u), can_read($u);"#)
+            .unwrap();
+        let biscuit = builder.build(&root).unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let register = |authorizer: &mut Authorizer| {
+            let calls = calls.clone();
+            authorizer.register_resolver(
+                "role",
+                Box::new(move |hints| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    assert_eq!(hints, &[None, Some(string("reader"))][..]);
+                    vec![
+                        vec![string("alice"), string("reader")],
+                        vec![string("bob"), string("writer")],
+                        vec![string("carol"), var("role")],
+                    ]
+                }),
+            );
+        };
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        register(&mut authorizer);
+        authorizer
+            .add_code(r#"user("alice"); allow if true;"#)
+            .unwrap();
+        assert_eq!(authorizer.authorize(), Ok(0));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let roles: Vec<(String, String)> =
+            authorizer.query("data($u, $r) <- role($u, $r)").unwrap();
+        assert_eq!(roles.len(), 2);
+
+        // the resolver is not called if the facts are available
+        let mut authorizer = biscuit.authorizer().unwrap();
+        register(&mut authorizer);
+        authorizer
+            .add_code(r#"user("bob"); role("bob", "reader"); allow if true;"#)
+            .unwrap();
+        assert_eq!(authorizer.authorize(), Ok(0));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // resolvers for unused predicates are not called
+        let mut authorizer = Authorizer::new();
+        register(&mut authorizer);
+        authorizer.add_allow_all();
+        assert_eq!(authorizer.authorize(), Ok(0));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let mut authorizer = Authorizer::new();
+        authorizer.register_resolver(
+            "item",
+            Box::new(|_| (0..200).map(|i| vec![int(i)]).collect()),
+        );
+        authorizer.add_code("allow if item(1);").unwrap();
+        assert_eq!(
+            authorizer.authorize(),
+            Err(error::Token::RunLimit(
+                error::RunLimit::TooManyResolvedFacts
+            ))
+        );
+    }
+
+    #[test]
+    fn check_bindings() {
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        builder.add_fact("right(\"file2\", \"read\")").unwrap();
+        builder.add_fact("right(\"file3\", \"read\")").unwrap();
+        builder.add_fact("right(\"file3\", \"write\")").unwrap();
+        builder
+            .add_check("check if right($file, \"read\"), $file != \"file1\"")
+            .unwrap();
+        builder.add_check("check if right(\"file1\", $op)").unwrap();
+        builder
+            .add_check("check if right($file, \"delete\")")
+            .unwrap();
+        let token = builder.build(&root).unwrap();
+
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer.add_allow_all();
+        authorizer.authorize().unwrap_err();
+        assert!(authorizer
+            .report()
+            .checks
+            .iter()
+            .all(|c| c.bindings.is_empty()));
+
+        authorizer.record_bindings(1);
+        authorizer.authorize().unwrap_err();
+        let checks = authorizer.report().checks;
+        assert_eq!(checks[0].bindings.len(), 1);
+        assert!(
+            checks[0].bindings[0] == HashMap::from([("file".to_string(), string("file2"))])
+                || checks[0].bindings[0] == HashMap::from([("file".to_string(), string("file3"))])
+        );
+        assert!(checks[0].bindings_truncated);
+        assert_eq!(
+            checks[1].bindings,
+            vec![HashMap::from([("op".to_string(), string("read"))])]
+        );
+        assert!(!checks[1].bindings_truncated);
+        // failed checks have no bindings
+        assert!(!checks[2].success);
+        assert!(checks[2].bindings.is_empty());
+
+        authorizer.record_bindings(10);
+        authorizer.authorize().unwrap_err();
+        let checks = authorizer.report().checks;
+        assert_eq!(checks[0].bindings.len(), 2);
+        assert!(!checks[0].bindings_truncated);
+    }
+
+    #[test]
+    fn metrics() {
+        use crate::Biscuit;
+        use crate::KeyPair;
+        let root = KeyPair::new();
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        builder
+            .add_rule("can_read($f) <- right($f, \"read\")")
+            .unwrap();
+        builder.add_check("check if operation(\"read\")").unwrap();
+        let serialized = builder.build(&root).unwrap().to_vec().unwrap();
+        let biscuit = Biscuit::from(&serialized, root.public()).unwrap();
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer
+            .add_code("operation(\"read\"); allow if can_read(\"file1\");")
+            .unwrap();
+        authorizer.authorize().unwrap();
+
+        let metrics = authorizer.metrics();
+        let report = authorizer.report();
+        assert_eq!(metrics.facts, report.fact_count);
+        assert_eq!(metrics.derived_facts, 1);
+        assert_eq!(metrics.iterations, report.iterations);
+        assert_eq!(metrics.checks, 1);
+        assert_eq!(metrics.execution_time, report.execution_time);
+        assert_eq!(metrics.signature_time, biscuit.verification_time);
+        assert!(!metrics.derivation_depth_reached);
+
+        // the token's rule generates can_read("file1"), then the next
+        // iteration finds nothing new
+        let stats = authorizer.run_stats();
+        assert_eq!(report.run_stats, stats);
+        assert_eq!(stats.iterations, 2);
+        assert_eq!(stats.facts_after, metrics.facts);
+        assert_eq!(
+            stats.facts_after - stats.facts_before,
+            metrics.derived_facts
+        );
+
+        #[cfg(feature = "serde-error")]
+        {
+            let json = serde_json::to_value(metrics).unwrap();
+            assert_eq!(json["derived_facts"], 1);
+        }
+    }
+
+    #[test]
+    fn derivation_depth() {
+        let mut authorizer = Authorizer::new();
+        authorizer
+            .add_code(
+                r#"edge(0, 1); edge(1, 2); edge(2, 3);
+                path($x, $y) <- edge($x, $y);
+                path($x, $z) <- path($x, $y), edge($y, $z);
+                allow if path(0, 3);"#,
+            )
+            .unwrap();
+
+        // path(0, 3) has a depth of 3
+        let limits = |depth| AuthorizerLimits {
+            max_derivation_depth: Some(depth),
+            ..Default::default()
+        };
+        let mut exact = authorizer.clone();
+        exact.set_limits(limits(3));
+        assert_eq!(exact.authorize(), Ok(0));
+        assert!(!exact.metrics().derivation_depth_reached);
+
+        let mut limited = authorizer.clone();
+        limited.set_limits(limits(2));
+        assert!(matches!(
+            limited.authorize(),
+            Err(error::Token::FailedLogic(
+                error::Logic::NoMatchingPolicy { .. }
+            ))
+        ));
+        assert!(limited.metrics().derivation_depth_reached);
+        let paths: Vec<(i64, i64)> = limited.query("data($x, $y) <- path($x, $y)").unwrap();
+        assert_eq!(paths.len(), 5);
+    }
+
+    #[test]
+    fn zero_arity_facts() {
+        use crate::Biscuit;
+        use crate::KeyPair;
+        let keypair = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_fact(fact("emergency", &[] as &[Term])).unwrap();
+        builder.add_rule("alert() <- emergency()").unwrap();
+        let biscuit = builder.build(&keypair).unwrap();
+        assert_eq!(
+            biscuit.print_block_source(0).unwrap(),
+            "emergency();\nalert() <- emergency();\n"
+        );
+
+        let serialized = biscuit.to_vec().unwrap();
+        let biscuit = Biscuit::from(&serialized, keypair.public()).unwrap();
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer.add_code("allow if alert()").unwrap();
+        assert_eq!(authorizer.authorize(), Ok(0));
+
+        // a zero arity predicate does not match facts with terms
+        let mut authorizer = Authorizer::new();
+        authorizer
+            .add_code("emergency(true); allow if emergency()")
+            .unwrap();
+        assert!(authorizer.authorize().is_err());
+    }
+
+    #[test]
+    fn fail_fast() {
+        use crate::Biscuit;
+        use crate::KeyPair;
+        let keypair = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_check("check if operation(\"write\")").unwrap();
+
+        let biscuit = builder
+            .build(&keypair)
+            .unwrap()
+            .attenuate(|block| block.add_check("check if resource(\"file2\")"))
+            .unwrap();
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer
+            .add_code(
+                r#"resource("file1");
+                operation("read");
+                check if resource("file3");
+                allow if true;
+                "#,
+            )
+            .unwrap();
+
+        let failed_ids = |res: Result<usize, error::Token>| match res {
+            Err(error::Token::FailedLogic(error::Logic::Unauthorized { checks, .. })) => checks
+                .into_iter()
+                .map(|c| match c {
+                    error::FailedCheck::Block(c) => Some(c.block_id),
+                    error::FailedCheck::Authorizer(_) => None,
+                })
+                .collect::<Vec<_>>(),
+            res => panic!("unexpected result: {:?}", res),
+        };
+
+        let mut all = authorizer.clone();
+        assert_eq!(failed_ids(all.authorize()), vec![None, Some(0), Some(1)]);
+
+        authorizer.set_fail_fast(true);
+        assert_eq!(failed_ids(authorizer.authorize()), vec![None]);
+        let statuses: Vec<_> = authorizer
+            .report()
+            .checks
+            .iter()
+            .map(|c| c.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                CheckStatus::Failed,
+                CheckStatus::Skipped,
+                CheckStatus::Skipped
+            ]
+        );
+
+        // a matching deny policy skips the checks of the following blocks
+        let mut denied = biscuit.authorizer().unwrap();
+        denied
+            .add_code("operation(\"write\"); deny if true;")
+            .unwrap();
+        denied.set_fail_fast(true);
+        assert_eq!(
+            denied.authorize(),
+            Err(error::Token::FailedLogic(error::Logic::Unauthorized {
+                policy: error::MatchedPolicy::Deny(0),
+                checks: vec![],
+            }))
+        );
+        let statuses: Vec<_> = denied
+            .report()
+            .checks
+            .iter()
+            .map(|c| (c.block_id, c.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (Some(0), CheckStatus::Passed),
+                (Some(1), CheckStatus::Skipped)
+            ]
+        );
+    }
+
+    #[test]
+    fn check_order() {
+        use crate::Biscuit;
+        use crate::KeyPair;
+        let keypair = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder
+            .add_check(r#"check if resource($r), $r.matches("^file[0-9]+$")"#)
+            .unwrap();
+        builder
+            .add_check("check if time($t), $t < 2020-01-01T00:00:00Z")
+            .unwrap();
+
+        let biscuit = builder
+            .build(&keypair)
+            .unwrap()
+            .attenuate(|block| block.add_check("check if operation(\"read\")"))
+            .unwrap();
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer
+            .add_code(
+                r#"resource("file1");
+                operation("read");
+                time(2021-01-01T00:00:00Z);
+                check if resource("file1");
+                allow if true;
+                "#,
+            )
+            .unwrap();
+        // a timeout would leave the report empty
+        authorizer.set_limits(AuthorizerLimits {
+            max_time: Duration::from_secs(1),
+            ..Default::default()
+        });
+
+        let order = |authorizer: &Authorizer| {
+            authorizer
+                .report()
+                .checks
+                .iter()
+                .filter(|c| c.status != CheckStatus::Skipped)
+                .map(|c| (c.block_id, c.check_id))
+                .collect::<Vec<_>>()
+        };
+
+        // authorizer checks, then each block in order, checks in insertion order
+        let mut all = authorizer.clone();
+        all.order_checks_by_cost(true);
+        assert!(all.authorize().is_err());
+        assert_eq!(
+            order(&all),
+            vec![(None, 0), (Some(0), 0), (Some(0), 1), (Some(1), 0)]
+        );
+
+        authorizer.set_fail_fast(true);
+        let mut by_insertion = authorizer.clone();
+        assert!(by_insertion.authorize().is_err());
+        assert_eq!(
+            order(&by_insertion),
+            vec![(None, 0), (Some(0), 0), (Some(0), 1)]
+        );
+
+        // the regular expression is skipped since the cheaper check fails first
+        authorizer.order_checks_by_cost(true);
+        assert!(authorizer.authorize().is_err());
+        assert_eq!(order(&authorizer), vec![(None, 0), (Some(0), 1)]);
+    }
+
+    #[test]
+    fn type_mismatch_hints() {
+        let hints = |code: &str| {
+            let mut authorizer = Authorizer::new();
+            authorizer.add_code(code).unwrap();
+            let _ = authorizer.authorize();
+            authorizer
+                .report()
+                .checks
+                .into_iter()
+                .map(|c| c.hints)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            hints(r#"operation("1"); check if operation(1); allow if true;"#),
+            vec![vec![
+                "operation(1) expects integer 1, found string \"1\" in operation(\"1\")"
+                    .to_string()
+            ]]
+        );
+        assert_eq!(
+            hints(
+                r#"right("file1", true); check if right($file, "true"); allow if true;"#
+            ),
+            vec![vec![
+                "right($file, \"true\") expects string \"true\", found bool true in right(\"file1\", true)"
+                    .to_string()
+            ]]
+        );
+        // other terms must match
+        assert_eq!(
+            hints(r#"right("file1", true); check if right("file2", "true"); allow if true;"#),
+            vec![Vec::<String>::new()]
+        );
+        // no hints for successful checks
+        assert_eq!(
+            hints(r#"operation(1); operation("1"); check if operation(1); allow if true;"#),
+            vec![Vec::<String>::new()]
+        );
+    }
+
+    #[test]
+    fn revoked_integer_ids() {
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.revocation_id(1);
+        let mut block = BlockBuilder::new();
+        block.revocation_id(2);
+        let biscuit = builder.build(&root).unwrap().append(block).unwrap();
+        assert_eq!(biscuit.revocation_integer_ids(), Ok(vec![1, 2]));
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer.allow().unwrap();
+        authorizer.revoke_ids(&[3]);
+        assert_eq!(authorizer.clone().authorize(), Ok(0));
+
+        authorizer.revoke_ids(&[2]);
+        assert_eq!(
+            authorizer.authorize(),
+            Err(error::Token::FailedLogic(error::Logic::Revoked(vec![
+                "2".to_string()
+            ])))
+        );
+    }
+
+    #[test]
+    fn single_use_tokens() {
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_nonce(&mut rand::rngs::OsRng);
+        let biscuit = builder.build(&root).unwrap();
+        // a nonce added by an attenuation block is ignored
+        let mut block = BlockBuilder::new();
+        block.add_fact("nonce(hex:00)").unwrap();
+        let biscuit = biscuit.append(block).unwrap();
+        let nonce = biscuit.nonce().unwrap();
+        assert_eq!(nonce.len(), 16);
+
+        let used = std::cell::RefCell::new(HashSet::new());
+        let authorize = |token: &Biscuit| {
+            let mut authorizer = token.authorizer()?;
+            let nonce = authorizer.require_nonce(|nonce| used.borrow().contains(nonce))?;
+            authorizer.add_allow_all();
+            authorizer.authorize()?;
+            used.borrow_mut().insert(nonce);
+            Ok::<_, error::Token>(())
+        };
+        assert_eq!(authorize(&biscuit), Ok(()));
+        assert_eq!(
+            authorize(&biscuit),
+            Err(error::Token::FailedLogic(error::Logic::ReplayedNonce(
+                hex::encode(&nonce)
+            )))
+        );
+
+        // the nonce is kept in snapshots
+        let authorizer = biscuit.authorizer().unwrap();
+        let authorizer =
+            Authorizer::from_base64_snapshot(&authorizer.to_base64_snapshot().unwrap()).unwrap();
+        assert_eq!(authorizer.require_nonce(|_| false), Ok(nonce));
+
+        let without_nonce = Biscuit::builder().build(&root).unwrap();
+        assert_eq!(without_nonce.nonce(), None);
+        assert_eq!(
+            authorize(&without_nonce),
+            Err(error::Token::FailedLogic(error::Logic::MissingNonce))
+        );
+        assert_eq!(
+            Authorizer::new().require_nonce(|_| false),
+            Err(error::Token::FailedLogic(error::Logic::MissingNonce))
+        );
+    }
+
+    #[test]
+    fn max_token_age() {
+        let root = KeyPair::new();
+        let token = |issued_at: Option<SystemTime>| {
+            let mut builder = Biscuit::builder();
+            if let Some(issued_at) = issued_at {
+                builder.add_issued_at(issued_at);
+            }
+            builder.build(&root).unwrap()
+        };
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 3600);
+        let minute = Duration::from_secs(60);
+
+        let authorize = |biscuit: &Biscuit, skew: Duration, required: bool| {
+            let mut authorizer = biscuit.authorizer().unwrap();
+            authorizer.set_max_token_age(Some(day));
+            authorizer.set_clock_skew(skew);
+            authorizer.set_require_issued_at(required);
+            authorizer.add_allow_all();
+            authorizer.authorize().map(|_| ())
+        };
+
+        assert_eq!(
+            authorize(&token(Some(now - minute)), Duration::default(), true),
+            Ok(())
+        );
+
+        let old = now - day - 2 * minute;
+        assert_eq!(
+            authorize(&token(Some(old)), Duration::default(), true),
+            Err(error::Token::FailedLogic(error::Logic::TokenTooOld(
+                date(&old).to_string()
+            )))
+        );
+        assert_eq!(authorize(&token(Some(old)), 5 * minute, true), Ok(()));
+        assert_eq!(authorize(&token(Some(old)), Duration::MAX, true), Ok(()));
+
+        let future = now + 2 * minute;
+        assert_eq!(
+            authorize(&token(Some(future)), Duration::default(), true),
+            Err(error::Token::FailedLogic(
+                error::Logic::TimestampOutsideWindow(date(&future).to_string())
+            ))
+        );
+        assert_eq!(authorize(&token(Some(future)), 5 * minute, true), Ok(()));
+
+        assert_eq!(
+            authorize(&token(None), Duration::default(), true),
+            Err(error::Token::FailedLogic(error::Logic::MissingTimestamp))
+        );
+        assert_eq!(authorize(&token(None), Duration::default(), false), Ok(()));
+
+        // the age is not checked without a maximum
+        let mut authorizer = token(Some(old)).authorizer().unwrap();
+        authorizer.add_allow_all();
+        assert!(authorizer.authorize().is_ok());
+    }
+
+    #[test]
+    fn clock_skew() {
+        let root = KeyPair::new();
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(1_900_000_000);
+        let mut builder = Biscuit::builder();
+        builder.check_expiration_date(date);
+        builder
+            .add_check("check if time($t), $t >= 2030-03-17T17:46:40Z")
+            .unwrap();
+        let biscuit = builder.build(&root).unwrap();
+        let second = Duration::from_secs(1);
+
+        let authorize = |time: SystemTime, skew: Duration| {
+            let mut authorizer = biscuit.authorizer().unwrap();
+            authorizer.set_clock_skew(skew);
+            authorizer.set_time_at(time);
+            authorizer.add_allow_all();
+            authorizer
+                .authorize()
+                .map_err(|e| match e {
+                    error::Token::FailedLogic(error::Logic::Unauthorized { checks, .. }) => checks
+                        .iter()
+                        .map(|c| match c {
+                            error::FailedCheck::Block(c) => c.check_id,
+                            error::FailedCheck::Authorizer(c) => c.check_id,
+                        })
+                        .collect::<Vec<_>>(),
+                    e => panic!("unexpected error: {:?}", e),
+                })
+                .map(|_| ())
+        };
+
+        // the checks only accept the date itself
+        assert_eq!(authorize(date, Duration::default()), Ok(()));
+        // the expiration date is included, up to the end of its second
+        assert_eq!(
+            authorize(date + Duration::from_millis(999), Duration::default()),
+            Ok(())
+        );
+        assert_eq!(
+            authorize(date + 2 * second, Duration::default()),
+            Err(vec![0])
+        );
+        assert_eq!(
+            authorize(date - 2 * second, Duration::default()),
+            Err(vec![1])
+        );
+
+        assert_eq!(authorize(date + 2 * second, 2 * second), Ok(()));
+        assert_eq!(authorize(date - 2 * second, 2 * second), Ok(()));
+        assert_eq!(authorize(date + 3 * second, 2 * second), Err(vec![0]));
+        assert_eq!(authorize(date - 3 * second, 2 * second), Err(vec![1]));
+        // the window is clamped instead of overflowing
+        assert_eq!(authorize(date + 3 * second, Duration::MAX), Ok(()));
+
+        // the skew applies whatever the order of the calls
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer.set_time_at(date + 2 * second);
+        authorizer.set_clock_skew(2 * second);
+        authorizer.add_allow_all();
+        assert!(authorizer.authorize().is_ok());
+    }
+
+    #[test]
+    fn replay_protection() {
+        let root = KeyPair::new();
+        let window = Duration::from_secs(60);
+        let token = |issued_at: Option<SystemTime>| {
+            let mut builder = Biscuit::builder();
+            builder.add_nonce(&mut rand::rngs::OsRng);
+            if let Some(issued_at) = issued_at {
+                builder.add_issued_at(issued_at);
+            }
+            builder.build(&root).unwrap()
+        };
+        let now = SystemTime::now();
+
+        let mut store = HashMap::new();
+        let biscuit = token(Some(now));
+        assert!(biscuit.issued_at().is_some());
+        let authorizer = biscuit.authorizer().unwrap();
+        assert_eq!(authorizer.replay_protection(window, &mut store), Ok(()));
+        assert_eq!(
+            authorizer.replay_protection(window, &mut store),
+            Err(error::Token::FailedLogic(error::Logic::ReplayedNonce(
+                hex::encode(biscuit.nonce().unwrap())
+            )))
+        );
+        // the issue timestamp is kept in snapshots
+        let snapshot = authorizer.to_base64_snapshot().unwrap();
+        let authorizer = Authorizer::from_base64_snapshot(&snapshot).unwrap();
+        assert_eq!(authorizer.issued_at, Some(biscuit.issued_at().unwrap()));
+
+        for issued_at in [now - 2 * window, now + 2 * window] {
+            let biscuit = token(Some(issued_at));
+            let res = biscuit
+                .authorizer()
+                .unwrap()
+                .replay_protection(window, &mut store);
+            assert_eq!(
+                res,
+                Err(error::Token::FailedLogic(
+                    error::Logic::TimestampOutsideWindow(date(&issued_at).to_string())
+                ))
+            );
+        }
+        // the window is relative to the authorizer's time
+        let issued_at = now - 2 * window;
+        let mut authorizer = token(Some(issued_at)).authorizer().unwrap();
+        authorizer.set_time_at(issued_at);
+        assert_eq!(authorizer.replay_protection(window, &mut store), Ok(()));
+        assert_eq!(store.len(), 2);
+        store.remove(authorizer.nonce.as_ref().unwrap());
+
+        // a window too large for the nonce expiration is rejected
+        let biscuit = token(Some(now));
+        assert_eq!(
+            biscuit
+                .authorizer()
+                .unwrap()
+                .replay_protection(Duration::MAX, &mut store),
+            Err(error::Token::FailedLogic(
+                error::Logic::TimestampOutsideWindow(date(&now).to_string())
+            ))
+        );
+        // rejected tokens do not record their nonce
+        assert_eq!(store.len(), 1);
+
+        assert_eq!(
+            token(None)
+                .authorizer()
+                .unwrap()
+                .replay_protection(window, &mut store),
+            Err(error::Token::FailedLogic(error::Logic::MissingTimestamp))
+        );
+        // a timestamp that does not fit in a SystemTime is ignored
+        let mut builder = Biscuit::builder();
+        builder.add_nonce(&mut rand::rngs::OsRng);
+        builder
+            .add_fact(crate::builder::fact("issued_at", &[Term::Date(u64::MAX)]))
+            .unwrap();
+        let biscuit = builder.build(&root).unwrap();
+        assert_eq!(biscuit.issued_at(), None);
+        assert_eq!(
+            biscuit
+                .authorizer()
+                .unwrap()
+                .replay_protection(window, &mut store),
+            Err(error::Token::FailedLogic(error::Logic::MissingTimestamp))
+        );
+
+        // expired nonces are removed
+        let mut store = HashMap::new();
+        assert!(store.check_and_insert(&[1], now - window));
+        assert!(store.check_and_insert(&[2], now + window));
+        assert_eq!(store.len(), 1);
+        assert!(!store.check_and_insert(&[2], now + window));
+    }
+
+    #[test]
+    fn secondary_tokens() {
+        let user_root = KeyPair::new();
+        let service_root = KeyPair::new();
+
+        let mut builder = Biscuit::builder();
+        builder.add_code(r#"user("alice");"#).unwrap();
+        let user_token = builder.build(&user_root).unwrap();
+
+        let mut builder = Biscuit::builder();
+        builder
+            .add_code(
+                r#"service("backup");
+                can_access($r) <- resource($r), $r.starts_with("/backup/");"#,
+            )
+            .unwrap();
+        let service_token = builder
+            .build(&service_root)
+            .unwrap()
+            .attenuate(|block| block.add_code(r#"service("admin"); check if operation("read");"#))
+            .unwrap();
+        // each token is verified against its own root key
+        let service_token =
+            Biscuit::from(service_token.to_vec().unwrap(), service_root.public()).unwrap();
+
+        let authorizer = |operation: &str| {
+            let mut authorizer = user_token.authorizer().unwrap();
+            authorizer.add_secondary_token(&service_token);
+            authorizer.record_provenance(true);
+            authorizer.set_limits(AuthorizerLimits {
+                max_time: Duration::from_secs(1),
+                ..Default::default()
+            });
+            authorizer
+                .add_code(format!(
+                    r#"resource("/backup/db"); operation("{}");
+                    deny if service("admin");
+                    allow if user($user), service("backup"), can_access("/backup/db");"#,
+                    operation
+                ))
+                .unwrap();
+            authorizer
+        };
+
+        let mut read = authorizer("read");
+        assert_eq!(read.authorize(), Ok(1));
+        let provenance = read
+            .world_facts()
+            .unwrap()
+            .into_iter()
+            .filter(|f| f.fact.predicate.name == "service")
+            .map(|f| (f.fact.to_string(), f.provenance))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            provenance,
+            vec![(
+                "service(\"backup\")".to_string(),
+                Some(FactProvenance::Token(1))
+            )]
+        );
+
+        let mut write = authorizer("write");
+        let res = write.authorize();
+        let failed = error::FailedCheck::Block(error::FailedBlockCheck {
+            block_id: 1,
+            check_id: 0,
+            rule: "check if operation(\"read\")".to_string(),
+            token_id: 1,
+            reason: None,
+        });
+        assert_eq!(
+            res,
+            Err(error::Token::FailedLogic(error::Logic::Unauthorized {
+                policy: error::MatchedPolicy::Allow(1),
+                checks: vec![failed.clone()],
+            }))
+        );
+        assert_eq!(
+            failed.to_string(),
+            "check 0 of block 1 of token 1 failed: check if operation(\"read\")"
+        );
+        let report = write.report();
+        assert_eq!(report.checks.len(), 1);
+        assert_eq!(
+            (report.checks[0].token_id, report.checks[0].block_id),
+            (1, Some(1))
+        );
+    }
+
+    #[test]
+    fn secondary_token_facts_scope() {
+        let user_root = KeyPair::new();
+        let service_root = KeyPair::new();
+
+        let user_token = Biscuit::builder()
+            .build(&user_root)
+            .unwrap()
+            .attenuate(|block| block.add_check(r#"check if resource("/x")"#))
+            .unwrap();
+        let mut builder = Biscuit::builder();
+        builder.add_fact(r#"resource("/x")"#).unwrap();
+        let service_token = builder.build(&service_root).unwrap();
+
+        let mut authorizer = user_token.authorizer().unwrap();
+        authorizer.add_secondary_token(&service_token);
+        authorizer.set_limits(AuthorizerLimits {
+            max_time: Duration::from_secs(1),
+            ..Default::default()
+        });
+        authorizer
+            .add_code(
+                r#"from_service($r) <- resource($r);
+                check if from_service("/x");
+                allow if resource("/x");"#,
+            )
+            .unwrap();
+
+        // the authorizer trusts the service token's facts, the user token's
+        // attenuation block does not
+        let res = authorizer.authorize();
+        assert_eq!(
+            res,
+            Err(error::Token::FailedLogic(error::Logic::Unauthorized {
+                policy: error::MatchedPolicy::Allow(0),
+                checks: vec![error::FailedCheck::Block(error::FailedBlockCheck {
+                    block_id: 1,
+                    check_id: 0,
+                    rule: "check if resource(\"/x\")".to_string(),
+                    token_id: 0,
+                    reason: None,
+                })],
+            }))
+        );
+        assert!(authorizer.save().is_ok());
+    }
+
+    #[test]
+    fn unbound_expression_variable() {
+        let root = KeyPair::new();
+        let token = Biscuit::builder().build(&root).unwrap();
+
+        // the bodies never match, so without the upfront validation the
+        // expressions would not be evaluated and the token would be accepted
+        let mut block = BlockBuilder::new();
+        block.add_rule("valid($r) <- missing($r), $x > 0").unwrap();
+        let attenuated = token.append(block).unwrap();
+        assert_eq!(
+            attenuated.authorizer().err().unwrap(),
+            error::Token::FailedLogic(error::Logic::UnboundExpressionVariable {
+                block: 1,
+                rule: "valid($r) <- missing($r), $x > 0".to_string(),
+                expression: 0,
+                variable: "x".to_string(),
+            })
+        );
+
+        let mut block = BlockBuilder::new();
+        block
+            .add_check("check if missing($r), $r == 1, $x.length() > 2")
+            .unwrap();
+        let attenuated = token.append(block).unwrap();
+        assert_eq!(
+            attenuated.authorizer().err().unwrap(),
+            error::Token::FailedLogic(error::Logic::UnboundExpressionVariable {
+                block: 1,
+                rule: "check if missing($r), $r == 1, $x.length() > 2".to_string(),
+                expression: 1,
+                variable: "x".to_string(),
+            })
+        );
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn revoked_uuids() {
+        let id1 = uuid::Uuid::from_u128(0x8c7f_1fe6_2f4c_4e9b_9bd9_0e4b_12c4_a1d2);
+        let id2 = uuid::Uuid::from_u128(0x1b4e_28ba_2fa1_41d2_883f_0016_d3cc_a427);
+
+        // the authority block uses an integer id, the attenuation block a UUID
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.revocation_id(1);
+        let mut block = BlockBuilder::new();
+        block.revocation_uuid(&id1);
+        let biscuit = builder.build(&root).unwrap().append(block).unwrap();
+        assert_eq!(biscuit.revocation_integer_ids(), Ok(vec![1]));
+        assert_eq!(biscuit.revocation_uuids(), Ok(vec![id1]));
+
+        let mut authorizer = biscuit.authorizer().unwrap();
+        authorizer.allow().unwrap();
+        authorizer.revoke_ids(&[2]);
+        authorizer.revoke_uuids(&[id2]);
+        assert_eq!(authorizer.clone().authorize(), Ok(0));
+
+        let mut revoked = authorizer.clone();
+        revoked.revoke_uuids(&[id1]);
+        assert_eq!(
+            revoked.authorize(),
+            Err(error::Token::FailedLogic(error::Logic::Revoked(vec![
+                format!("hex:{}", hex::encode(id1.as_bytes()))
+            ])))
+        );
+
+        authorizer.revoke_ids(&[1]);
+        assert!(authorizer.authorize().is_err());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans() {
+        use std::fmt;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::{span, Event, Metadata, Subscriber};
+
+        type Fields = HashMap<String, String>;
+
+        #[derive(Default)]
+        struct Recorded {
+            spans: Vec<(&'static Metadata<'static>, Fields)>,
+            events: Vec<Fields>,
+            stack: Vec<span::Id>,
+        }
+
+        struct Visitor<'a>(&'a mut Fields);
+
+        impl Visit for Visitor<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0
+                    .insert(field.name().to_string(), format!("{:?}", value));
+            }
+        }
+
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Recorded>>);
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                let mut fields = Fields::new();
+                span.record(&mut Visitor(&mut fields));
+                let mut recorded = self.0.lock().unwrap();
+                recorded.spans.push((span.metadata(), fields));
+                span::Id::from_u64(recorded.spans.len() as u64)
+            }
+
+            fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+                let mut recorded = self.0.lock().unwrap();
+                let (_, fields) = &mut recorded.spans[id.into_u64() as usize - 1];
+                values.record(&mut Visitor(fields));
+            }
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::new();
+                event.record(&mut Visitor(&mut fields));
+                self.0.lock().unwrap().events.push(fields);
+            }
+
+            fn enter(&self, id: &span::Id) {
+                self.0.lock().unwrap().stack.push(id.clone());
+            }
+
+            fn exit(&self, _: &span::Id) {
+                self.0.lock().unwrap().stack.pop();
+            }
+
+            fn current_span(&self) -> tracing_core::span::Current {
+                let recorded = self.0.lock().unwrap();
+                match recorded.stack.last() {
+                    Some(id) => {
+                        let (metadata, _) = recorded.spans[id.into_u64() as usize - 1];
+                        tracing_core::span::Current::new(id.clone(), metadata)
+                    }
+                    None => tracing_core::span::Current::none(),
+                }
+            }
+        }
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder
+            .add_code(r#"right("file1", "read"); check if operation("read");"#)
+            .unwrap();
+        let mut block = BlockBuilder::new();
+        block.add_code(r#"check if resource("file1");"#).unwrap();
+        let serialized = builder
+            .build(&root)
+            .unwrap()
+            .append(block)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let biscuit = Biscuit::from(&serialized, root.public()).unwrap();
+            let mut authorizer = Authorizer::new();
+            authorizer.add_token(&biscuit).unwrap();
+            authorizer
+                .add_code(r#"resource("file1"); operation("write"); allow if true;"#)
+                .unwrap();
+            assert!(authorizer.authorize().is_err());
+        });
+
+        let recorded = recorder.0.lock().unwrap();
+        let span = |name: &str| {
+            recorded
+                .spans
+                .iter()
+                .find(|(metadata, _)| metadata.name() == name)
+                .map(|(_, fields)| fields)
+                .unwrap_or_else(|| panic!("missing span {}", name))
+        };
+
+        assert_eq!(span("verify")["blocks"], "2");
+        assert_eq!(span("add_token")["blocks"], "2");
+        assert_eq!(span("add_token")["facts"], "1");
+        assert!(span("world_run").contains_key("iterations"));
+        assert_eq!(span("authorize")["blocks"], "2");
+        assert!(span("authorize").contains_key("facts"));
+        assert!(span("authorize").contains_key("iterations"));
+
+        let checks = recorded
+            .events
+            .iter()
+            .map(|e| (e["block_id"].as_str(), e["success"].as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(checks, vec![("0", "false"), ("1", "true")]);
+
+        // only counters and identifiers are recorded
+        let allowed = [
+            "blocks",
+            "facts",
+            "iterations",
+            "block_id",
+            "check_id",
+            "success",
+            "message",
+        ];
+        for fields in recorded
+            .spans
+            .iter()
+            .map(|(_, fields)| fields)
+            .chain(recorded.events.iter())
+        {
+            for name in fields.keys() {
+                assert!(
+                    allowed.contains(&name.as_str()),
+                    "unexpected field {}",
+                    name
+                );
+            }
+        }
+    }
 }
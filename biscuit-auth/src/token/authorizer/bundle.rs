@@ -0,0 +1,190 @@
+use std::time::{Duration, SystemTime};
+
+use super::{Authorizer, AuthorizerLimits, AuthorizerReport};
+use crate::{builder::Scope, error, Biscuit, PublicKey};
+
+/// version of the evaluation semantics of the authorizer
+///
+/// it changes when a release can take a different decision for the same
+/// token, configuration and facts. [`AuthorizationBundle::replay`] refuses the
+/// bundles recorded with another version
+pub const EVALUATION_SEMANTICS_VERSION: u32 = 1;
+
+/// token, configuration and facts of an authorization, to run it again later
+///
+/// the facts provided by resolvers (see [`Authorizer::register_resolver`]) and
+/// the secondary tokens are not recorded. Replaying with a
+/// [`AuthorizerLimits::max_time`] close to the original execution time can
+/// time out while the original run did not
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthorizationBundle {
+    /// see [`EVALUATION_SEMANTICS_VERSION`]
+    pub semantics_version: u32,
+    /// the token, as it was received or serialized
+    pub token: Vec<u8>,
+    /// facts, rules, checks and policies of the authorizer, serialized with
+    /// [`AuthorizerPolicies::serialize`](super::AuthorizerPolicies::serialize)
+    pub policies: Vec<u8>,
+    /// scopes added with [`Authorizer::add_scope`]
+    pub scopes: Vec<String>,
+    pub limits: AuthorizerLimits,
+    pub fail_fast: bool,
+    pub order_checks_by_cost: bool,
+    pub clock_skew: Duration,
+    pub max_token_age: Option<Duration>,
+    pub require_issued_at: bool,
+    /// time of the authorization, used by [`Authorizer::set_max_token_age`]
+    pub time: SystemTime,
+}
+
+/// outcome of [`AuthorizationBundle::replay`]
+#[derive(Clone, Debug)]
+pub struct Replay {
+    /// result of [`Authorizer::authorize`]
+    pub result: Result<usize, error::Token>,
+    pub report: AuthorizerReport,
+}
+
+impl AuthorizationBundle {
+    /// records the token, and the configuration and facts of the authorizer
+    ///
+    /// this should be called after the request's facts were added. The time is
+    /// the one set with [`Authorizer::set_time`] or [`Authorizer::set_time_at`],
+    /// or the current time
+    pub fn new(token: &Biscuit, authorizer: &Authorizer) -> Result<Self, error::Token> {
+        Ok(AuthorizationBundle {
+            semantics_version: EVALUATION_SEMANTICS_VERSION,
+            token: token.container_bytes().to_vec(),
+            policies: authorizer.save()?.serialize()?,
+            scopes: authorizer
+                .authorizer_block_builder
+                .scopes
+                .iter()
+                .map(|scope| scope.to_string())
+                .collect(),
+            limits: authorizer.limits.clone(),
+            fail_fast: authorizer.fail_fast,
+            order_checks_by_cost: authorizer.order_by_cost,
+            clock_skew: authorizer.clock_skew,
+            max_token_age: authorizer.max_token_age,
+            require_issued_at: authorizer.require_issued_at,
+            time: authorizer.time.unwrap_or_else(crate::time::now),
+        })
+    }
+
+    /// runs the authorization again, with the recorded time
+    ///
+    /// it fails with [`error::Token::EvaluationSemantics`] if the bundle was
+    /// recorded with another [`EVALUATION_SEMANTICS_VERSION`], and if the token
+    /// or the configuration cannot be loaded. The result of the authorization
+    /// itself is in [`Replay::result`]
+    pub fn replay(&self, root: PublicKey) -> Result<Replay, error::Token> {
+        if self.semantics_version != EVALUATION_SEMANTICS_VERSION {
+            return Err(error::Token::EvaluationSemantics {
+                expected: EVALUATION_SEMANTICS_VERSION,
+                actual: self.semantics_version,
+            });
+        }
+
+        let token = Biscuit::from(&self.token, root)?;
+        let mut authorizer = Authorizer::from(&self.policies)?;
+        for scope in &self.scopes {
+            authorizer.add_scope(parse_scope(scope)?);
+        }
+        authorizer.set_limits(self.limits.clone());
+        authorizer.set_fail_fast(self.fail_fast);
+        authorizer.order_checks_by_cost(self.order_checks_by_cost);
+        authorizer.set_clock_skew(self.clock_skew);
+        authorizer.set_max_token_age(self.max_token_age);
+        authorizer.set_require_issued_at(self.require_issued_at);
+        // the time facts are part of the policies
+        authorizer.time = Some(self.time);
+        authorizer.add_token(&token)?;
+
+        let result = authorizer.authorize();
+        Ok(Replay {
+            result,
+            report: authorizer.report(),
+        })
+    }
+}
+
+fn parse_scope(scope: &str) -> Result<Scope, error::Token> {
+    match scope {
+        "authority" => Ok(Scope::Authority),
+        "previous" => Ok(Scope::Previous),
+        key => Ok(Scope::PublicKey(key.parse()?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder_ext::BuilderExt, KeyPair};
+
+    #[test]
+    fn replay() {
+        let root = KeyPair::new();
+        let issued_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let mut builder = Biscuit::builder();
+        builder.add_fact(r#"right("file1", "read")"#).unwrap();
+        builder.add_issued_at(issued_at);
+        builder.check_expiration_date(issued_at + Duration::from_secs(3600));
+        let token = builder.build(&root).unwrap();
+
+        let authorize = |resource: &str| {
+            let mut authorizer = token.authorizer().unwrap();
+            authorizer.set_time_at(issued_at + Duration::from_secs(600));
+            authorizer.set_max_token_age(Some(Duration::from_secs(3600)));
+            authorizer.add_scope(Scope::Authority);
+            authorizer.add_scope(Scope::PublicKey(KeyPair::new().public()));
+            authorizer
+                .add_fact(format!("resource({:?})", resource).as_str())
+                .unwrap();
+            authorizer
+                .add_code(
+                    r#"operation("read"); allow if right($r, $op), resource($r), operation($op);"#,
+                )
+                .unwrap();
+            let result = authorizer.authorize();
+            (result, authorizer)
+        };
+
+        let (result, authorizer) = authorize("file1");
+        assert_eq!(result, Ok(0));
+        let bundle = AuthorizationBundle::new(&token, &authorizer).unwrap();
+        assert_eq!(bundle.scopes.len(), 2);
+
+        // the token is much older than the maximum age now, but not at the
+        // recorded time
+        let replay = bundle.replay(root.public()).unwrap();
+        assert_eq!(replay.result, Ok(0));
+        assert_eq!(replay.report.policy, authorizer.report().policy);
+        assert_eq!(replay.report.checks.len(), authorizer.report().checks.len());
+
+        #[cfg(feature = "serde-error")]
+        {
+            let json = serde_json::to_string(&bundle).unwrap();
+            let parsed: AuthorizationBundle = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, bundle);
+            assert_eq!(parsed.replay(root.public()).unwrap().result, Ok(0));
+        }
+
+        let (result, authorizer) = authorize("file2");
+        let bundle = AuthorizationBundle::new(&token, &authorizer).unwrap();
+        assert_eq!(bundle.replay(root.public()).unwrap().result, result);
+
+        assert!(bundle.replay(KeyPair::new().public()).is_err());
+
+        let mut bundle = bundle;
+        bundle.semantics_version += 1;
+        assert_eq!(
+            bundle.replay(root.public()).unwrap_err(),
+            error::Token::EvaluationSemantics {
+                expected: EVALUATION_SEMANTICS_VERSION,
+                actual: EVALUATION_SEMANTICS_VERSION + 1,
+            }
+        );
+    }
+}
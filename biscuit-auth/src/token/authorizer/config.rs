@@ -0,0 +1,340 @@
+use std::{sync::Arc, time::Duration};
+
+use super::{Authorizer, AuthorizerLimits};
+use crate::{
+    builder::{Convert, Scope},
+    datalog::Origin,
+    error, Biscuit, PublicKey,
+};
+
+impl Authorizer {
+    /// creates an [`AuthorizerBuilder`]
+    pub fn builder() -> AuthorizerBuilder {
+        AuthorizerBuilder::new()
+    }
+
+    /// adds the facts of the authorizer block to the world, converts its rules
+    /// and interns the symbols of its checks and policies
+    ///
+    /// the facts are moved to a base shared by the clones of this authorizer,
+    /// which then only convert the code added afterwards
+    fn compile(&mut self) {
+        let mut origin = Origin::default();
+        origin.insert(usize::MAX);
+
+        let symbols = &mut self.symbols;
+        for fact in &self.authorizer_block_builder.facts[self.compiled_facts..] {
+            self.world.facts.insert(&origin, fact.convert(symbols));
+        }
+        self.compiled_facts = self.authorizer_block_builder.facts.len();
+        let compiled_rules = Arc::make_mut(&mut self.compiled_rules);
+        compiled_rules.extend(
+            self.authorizer_block_builder.rules[compiled_rules.len()..]
+                .iter()
+                .map(|rule| rule.convert(symbols)),
+        );
+        for check in &self.authorizer_block_builder.checks {
+            check.convert(symbols);
+        }
+        for policy in &self.policies {
+            for query in &policy.queries {
+                query.convert(symbols);
+            }
+        }
+
+        self.world.freeze();
+    }
+}
+
+/// collects and validates the configuration of an authorizer
+///
+/// it produces an [`AuthorizerConfig`] that can be stored and used to
+/// authorize each token:
+///
+/// ```rust
+/// use biscuit_auth::{Authorizer, Biscuit, KeyPair};
+/// use std::time::Duration;
+///
+/// let root = KeyPair::new();
+/// let mut builder = Biscuit::builder();
+/// builder.add_code(r#"right("file1", "read");"#).unwrap();
+/// let token = builder.build(&root).unwrap();
+///
+/// let mut builder = Authorizer::builder();
+/// builder.set_clock_skew(Duration::from_secs(5));
+/// builder
+///     .add_code(r#"allow if right($resource, $operation), resource($resource), operation($operation);"#)
+///     .unwrap();
+/// let config = builder.build().unwrap();
+///
+/// assert!(config
+///     .authorize(&token, |authorizer| {
+///         authorizer.add_fact(r#"resource("file1")"#).unwrap();
+///         authorizer.add_fact(r#"operation("read")"#).unwrap();
+///     })
+///     .is_ok());
+/// ```
+#[derive(Clone, Default)]
+pub struct AuthorizerBuilder {
+    limits: AuthorizerLimits,
+    fail_fast: bool,
+    clock_skew: Duration,
+    max_token_age: Option<Duration>,
+    require_issued_at: Option<bool>,
+    code: Vec<String>,
+    trusted_keys: Vec<PublicKey>,
+}
+
+impl AuthorizerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// see [`Authorizer::set_limits`]. Zero limits are rejected
+    pub fn set_limits(&mut self, limits: AuthorizerLimits) {
+        self.limits = limits;
+    }
+
+    /// see [`Authorizer::set_fail_fast`]
+    pub fn set_fail_fast(&mut self, fail_fast: bool) {
+        self.fail_fast = fail_fast;
+    }
+
+    /// see [`Authorizer::set_clock_skew`]. It must be smaller than the maximum
+    /// token age
+    pub fn set_clock_skew(&mut self, skew: Duration) {
+        self.clock_skew = skew;
+    }
+
+    /// see [`Authorizer::set_max_token_age`]
+    pub fn set_max_token_age(&mut self, max_age: Option<Duration>) {
+        self.max_token_age = max_age;
+    }
+
+    /// see [`Authorizer::set_require_issued_at`]. It can only be set with a
+    /// maximum token age
+    pub fn set_require_issued_at(&mut self, required: bool) {
+        self.require_issued_at = Some(required);
+    }
+
+    /// adds facts, rules, checks and policies, see [`Authorizer::add_code`]
+    ///
+    /// the code is parsed immediately. At least one policy must be defined
+    pub fn add_code<T: AsRef<str>>(&mut self, source: T) -> Result<(), error::Token> {
+        Authorizer::new().add_code(source.as_ref())?;
+        self.code.push(source.as_ref().to_string());
+        Ok(())
+    }
+
+    /// trusts the blocks signed by this key in the authorizer's rules, checks
+    /// and policies, in addition to the authority block
+    pub fn trust_key(&mut self, key: PublicKey) {
+        self.trusted_keys.push(key);
+    }
+
+    /// validates the configuration
+    ///
+    /// all the issues are reported in a single
+    /// [`error::Token::InvalidConfiguration`]
+    pub fn build(self) -> Result<AuthorizerConfig, error::Token> {
+        let mut authorizer = Authorizer::new();
+        if !self.trusted_keys.is_empty() {
+            authorizer.add_scope(Scope::Authority);
+            for key in &self.trusted_keys {
+                authorizer.add_scope(Scope::PublicKey(*key));
+            }
+        }
+        for source in &self.code {
+            authorizer.add_code(source)?;
+        }
+
+        let mut issues = Vec::new();
+        if self.limits.max_facts == 0 {
+            issues.push("max_facts is zero".to_string());
+        }
+        if self.limits.max_iterations == 0 {
+            issues.push("max_iterations is zero".to_string());
+        }
+        if self.limits.max_time == Duration::default() {
+            issues.push("max_time is zero".to_string());
+        }
+        if self.limits.max_derivation_depth == Some(0) {
+            issues.push("max_derivation_depth is zero".to_string());
+        }
+        match self.max_token_age {
+            Some(max_age) if max_age == Duration::default() => {
+                issues.push("max_token_age is zero".to_string());
+            }
+            Some(max_age) if self.clock_skew >= max_age => {
+                issues.push("clock_skew is not smaller than max_token_age".to_string());
+            }
+            None if self.require_issued_at.is_some() => {
+                issues.push("require_issued_at is set without max_token_age".to_string());
+            }
+            _ => {}
+        }
+        if authorizer.policies.is_empty() {
+            issues.push("no policy is defined".to_string());
+        }
+        if !issues.is_empty() {
+            return Err(error::Token::InvalidConfiguration(issues));
+        }
+
+        authorizer.set_limits(self.limits.clone());
+        authorizer.set_fail_fast(self.fail_fast);
+        authorizer.set_clock_skew(self.clock_skew);
+        authorizer.set_max_token_age(self.max_token_age);
+        if let Some(required) = self.require_issued_at {
+            authorizer.set_require_issued_at(required);
+        }
+        authorizer.compile();
+
+        Ok(AuthorizerConfig {
+            authorizer: Arc::new(authorizer),
+            trusted_keys: self.trusted_keys,
+        })
+    }
+}
+
+/// validated authorizer configuration, created by [`AuthorizerBuilder::build`]
+///
+/// it is immutable and cheap to clone. The authorizer's facts and rules are
+/// converted once, when the configuration is built, and the facts are shared
+/// by the authorizers created for each token. Its `Debug` output omits the
+/// Datalog code, which can contain secret values, and only shows the number of
+/// policies
+#[derive(Clone)]
+pub struct AuthorizerConfig {
+    authorizer: Arc<Authorizer>,
+    trusted_keys: Vec<PublicKey>,
+}
+
+impl AuthorizerConfig {
+    /// creates an authorizer with this configuration and the token
+    pub fn authorizer(&self, token: &Biscuit) -> Result<Authorizer, error::Token> {
+        let mut authorizer = (*self.authorizer).clone();
+        authorizer.add_token(token)?;
+        Ok(authorizer)
+    }
+
+    /// authorizes the token, after adding the request's data with `setup`
+    pub fn authorize<F>(&self, token: &Biscuit, setup: F) -> Result<usize, error::Token>
+    where
+        F: FnOnce(&mut Authorizer),
+    {
+        let mut authorizer = self.authorizer(token)?;
+        setup(&mut authorizer);
+        authorizer.authorize()
+    }
+}
+
+impl std::fmt::Debug for AuthorizerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthorizerConfig")
+            .field("limits", &self.authorizer.limits)
+            .field("fail_fast", &self.authorizer.fail_fast)
+            .field("clock_skew", &self.authorizer.clock_skew)
+            .field("max_token_age", &self.authorizer.max_token_age)
+            .field("require_issued_at", &self.authorizer.require_issued_at)
+            .field("policies", &self.authorizer.policies.len())
+            .field("trusted_keys", &self.trusted_keys)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::BlockBuilder, KeyPair};
+
+    #[test]
+    fn validation() {
+        let mut builder = Authorizer::builder();
+        builder.set_limits(AuthorizerLimits {
+            max_facts: 0,
+            ..Default::default()
+        });
+        builder.set_require_issued_at(false);
+        assert_eq!(
+            builder.build().unwrap_err(),
+            error::Token::InvalidConfiguration(vec![
+                "max_facts is zero".to_string(),
+                "require_issued_at is set without max_token_age".to_string(),
+                "no policy is defined".to_string(),
+            ])
+        );
+
+        let mut builder = Authorizer::builder();
+        builder.add_code("allow if true").unwrap();
+        builder.set_max_token_age(Some(Duration::from_secs(60)));
+        builder.set_clock_skew(Duration::from_secs(60));
+        assert_eq!(
+            builder.build().unwrap_err(),
+            error::Token::InvalidConfiguration(vec![
+                "clock_skew is not smaller than max_token_age".to_string()
+            ])
+        );
+
+        let mut builder = Authorizer::builder();
+        assert!(matches!(
+            builder.add_code("allow if"),
+            Err(error::Token::Language(_))
+        ));
+    }
+
+    #[test]
+    fn authorize() {
+        let root = KeyPair::new();
+        let external = KeyPair::new();
+
+        let mut builder = Biscuit::builder();
+        builder.add_code(r#"right("file1", "read");"#).unwrap();
+        let biscuit1 = builder.build(&root).unwrap();
+
+        let req = biscuit1.third_party_request().unwrap();
+        let mut block = BlockBuilder::new();
+        block.add_code(r#"group("admin");"#).unwrap();
+        let res = req.create_block(&external.private(), block).unwrap();
+        let biscuit2 = biscuit1.append_third_party(external.public(), res).unwrap();
+        let biscuit2 = Biscuit::from(biscuit2.to_vec().unwrap(), root.public()).unwrap();
+
+        let mut builder = Authorizer::builder();
+        builder.set_fail_fast(true);
+        builder
+            .add_code(
+                r#"secret("hunter2");
+                readable($resource) <- right($resource, "read");
+                check if group("admin");
+                allow if readable($resource), resource($resource);"#,
+            )
+            .unwrap();
+        let untrusting = builder.clone().build().unwrap();
+        builder.trust_key(external.public());
+        let config = builder.build().unwrap();
+
+        let setup = |authorizer: &mut Authorizer| {
+            authorizer.add_fact(r#"resource("file1")"#).unwrap();
+        };
+        config.authorize(&biscuit2, setup).unwrap();
+        assert!(config.authorize(&biscuit1, setup).is_err());
+        assert!(untrusting.authorize(&biscuit2, setup).is_err());
+        assert!(config
+            .authorize(&biscuit2, |authorizer| {
+                authorizer.add_fact(r#"resource("file2")"#).unwrap();
+            })
+            .is_err());
+
+        // the configuration's facts and rules are not duplicated
+        let mut authorizer = config.authorizer(&biscuit2).unwrap();
+        setup(&mut authorizer);
+        authorizer.authorize().unwrap();
+        let secrets: Vec<(String,)> = authorizer.query_all("data($s) <- secret($s)").unwrap();
+        assert_eq!(secrets, vec![("hunter2".to_string(),)]);
+        assert_eq!(authorizer.world.rules.inner.values().flatten().count(), 1);
+
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("fail_fast: true"));
+        assert!(debug.contains("policies: 1"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
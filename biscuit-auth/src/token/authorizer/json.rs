@@ -0,0 +1,245 @@
+//! ambient facts from JSON values, available with the `serde_json` feature
+use serde_json::Value;
+
+use crate::{
+    builder::{boolean, fact, int, string, Fact},
+    error,
+};
+
+/// limits applied by [`Authorizer::add_json_facts_with`](super::Authorizer::add_json_facts_with)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonLimits {
+    /// maximum nesting of objects and arrays
+    pub max_depth: usize,
+    /// maximum number of facts generated from one value
+    pub max_facts: usize,
+}
+
+impl Default for JsonLimits {
+    fn default() -> Self {
+        JsonLimits {
+            max_depth: 8,
+            max_facts: 1000,
+        }
+    }
+}
+
+/// result of [`Authorizer::add_json_facts`](super::Authorizer::add_json_facts)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonFacts {
+    /// number of facts added
+    pub facts: usize,
+    /// values that were skipped
+    pub warnings: Vec<JsonWarning>,
+}
+
+/// a JSON value that cannot be represented as a term
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonWarning {
+    /// `null` values are skipped
+    Null { path: String },
+    /// numbers that do not fit in an `i64` (floats, large unsigned integers) are skipped
+    NumberOutOfRange { path: String, number: String },
+}
+
+impl super::Authorizer {
+    /// flattens a JSON value into `prefix("path.to.key", value)` facts,
+    /// with the default [`JsonLimits`]
+    ///
+    /// object keys are joined with `.` and array elements are indexed by their
+    /// position, so `{"user": {"roles": ["admin"]}}` produces
+    /// `prefix("user.roles.0", "admin")`. A scalar root value has the empty path.
+    /// Empty objects and arrays produce no fact. `null` and numbers that do not
+    /// fit in an `i64` are skipped and listed in the returned warnings
+    ///
+    /// if a limit is exceeded, a [`error::Token::ConversionError`] is returned
+    /// and no fact is added
+    pub fn add_json_facts(
+        &mut self,
+        prefix: &str,
+        value: &Value,
+    ) -> Result<JsonFacts, error::Token> {
+        self.add_json_facts_with(prefix, value, &JsonLimits::default())
+    }
+
+    /// same as [`Authorizer::add_json_facts`](super::Authorizer::add_json_facts),
+    /// with custom limits
+    pub fn add_json_facts_with(
+        &mut self,
+        prefix: &str,
+        value: &Value,
+        limits: &JsonLimits,
+    ) -> Result<JsonFacts, error::Token> {
+        let mut flattener = Flattener {
+            prefix,
+            limits,
+            facts: Vec::new(),
+            warnings: Vec::new(),
+        };
+        flattener.flatten(&mut String::new(), value, 0)?;
+
+        let summary = JsonFacts {
+            facts: flattener.facts.len(),
+            warnings: flattener.warnings,
+        };
+        for fact in flattener.facts {
+            self.authorizer_block_builder.add_fact(fact)?;
+        }
+        Ok(summary)
+    }
+}
+
+struct Flattener<'a> {
+    prefix: &'a str,
+    limits: &'a JsonLimits,
+    facts: Vec<Fact>,
+    warnings: Vec<JsonWarning>,
+}
+
+impl<'a> Flattener<'a> {
+    fn flatten(
+        &mut self,
+        path: &mut String,
+        value: &Value,
+        depth: usize,
+    ) -> Result<(), error::Token> {
+        let term = match value {
+            Value::Object(_) | Value::Array(_) => {
+                if depth >= self.limits.max_depth {
+                    return Err(error::Token::ConversionError(format!(
+                        "JSON value nested deeper than {} levels at \"{}\"",
+                        self.limits.max_depth, path
+                    )));
+                }
+
+                let children: Box<dyn Iterator<Item = (String, &Value)>> = match value {
+                    Value::Object(map) => Box::new(map.iter().map(|(k, v)| (k.clone(), v))),
+                    Value::Array(array) => {
+                        Box::new(array.iter().enumerate().map(|(i, v)| (i.to_string(), v)))
+                    }
+                    _ => unreachable!(),
+                };
+
+                for (key, child) in children {
+                    let len = path.len();
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(&key);
+                    self.flatten(path, child, depth + 1)?;
+                    path.truncate(len);
+                }
+                return Ok(());
+            }
+            Value::Null => {
+                self.warnings.push(JsonWarning::Null { path: path.clone() });
+                return Ok(());
+            }
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => int(i),
+                None => {
+                    self.warnings.push(JsonWarning::NumberOutOfRange {
+                        path: path.clone(),
+                        number: n.to_string(),
+                    });
+                    return Ok(());
+                }
+            },
+            Value::String(s) => string(s),
+            Value::Bool(b) => boolean(*b),
+        };
+
+        if self.facts.len() >= self.limits.max_facts {
+            return Err(error::Token::ConversionError(format!(
+                "JSON value generates more than {} facts",
+                self.limits.max_facts
+            )));
+        }
+        self.facts.push(fact(self.prefix, &[string(path), term]));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Authorizer;
+    use serde_json::json;
+
+    #[test]
+    fn flatten() {
+        let mut authorizer = Authorizer::new();
+        let summary = authorizer
+            .add_json_facts(
+                "request",
+                &json!({
+                    "method": "GET",
+                    "user": { "id": 1234, "admin": false, "roles": ["reader", "writer"] },
+                    "size": 1.5,
+                    "big": u64::MAX,
+                    "missing": null,
+                    "empty": {},
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(summary.facts, 5);
+        assert_eq!(
+            summary.warnings,
+            vec![
+                JsonWarning::NumberOutOfRange {
+                    path: "big".to_string(),
+                    number: u64::MAX.to_string()
+                },
+                JsonWarning::Null {
+                    path: "missing".to_string()
+                },
+                JsonWarning::NumberOutOfRange {
+                    path: "size".to_string(),
+                    number: "1.5".to_string()
+                },
+            ]
+        );
+
+        authorizer
+            .add_code(
+                r#"check if request("method", "GET"), request("user.id", 1234),
+                    request("user.admin", false), request("user.roles.1", "writer");
+                allow if true;"#,
+            )
+            .unwrap();
+        authorizer.authorize().unwrap();
+
+        let scalar = Authorizer::new()
+            .add_json_facts("value", &json!(12))
+            .unwrap();
+        assert_eq!(scalar.facts, 1);
+    }
+
+    #[test]
+    fn limits() {
+        let limits = JsonLimits {
+            max_depth: 2,
+            max_facts: 3,
+        };
+
+        let mut authorizer = Authorizer::new();
+        assert!(authorizer
+            .add_json_facts_with("a", &json!({ "b": { "c": 1 } }), &limits)
+            .is_ok());
+        assert!(matches!(
+            authorizer.add_json_facts_with("a", &json!({ "b": { "c": [1] } }), &limits),
+            Err(error::Token::ConversionError(_))
+        ));
+        assert!(matches!(
+            authorizer.add_json_facts_with("a", &json!([1, 2, 3, 4]), &limits),
+            Err(error::Token::ConversionError(_))
+        ));
+
+        // nothing is added when a limit is exceeded
+        authorizer.add_policy("allow if true").unwrap();
+        authorizer.authorize().unwrap();
+        let facts: Vec<(String, i64)> = authorizer.query("data($k, $v) <- a($k, $v)").unwrap();
+        assert_eq!(facts, vec![("b.c".to_string(), 1)]);
+    }
+}
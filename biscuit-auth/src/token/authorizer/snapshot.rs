@@ -3,7 +3,7 @@ use std::{collections::HashMap, time::Duration};
 
 use crate::{
     builder::{BlockBuilder, Convert, Policy},
-    datalog::{Origin, RunLimits, TrustedOrigins},
+    datalog::{Origin, RunLimits, TrustedOrigins, SECONDARY_TOKENS_ORIGIN},
     error,
     format::{
         convert::{
@@ -31,6 +31,8 @@ impl super::Authorizer {
             max_facts: limits.max_facts,
             max_iterations: limits.max_iterations,
             max_time: Duration::from_nanos(limits.max_time),
+            max_derivation_depth: limits.max_derivation_depth,
+            ..Default::default()
         };
 
         let execution_time = Duration::from_nanos(execution_time);
@@ -104,6 +106,8 @@ impl super::Authorizer {
                 blocks.len(),
                 &authorizer.public_key_to_block_id,
             );
+            authorizer.nonce = crate::token::block_nonce(&blocks[0], &authorizer.symbols);
+            authorizer.issued_at = crate::token::block_issued_at(&blocks[0], &authorizer.symbols);
             authorizer.blocks = Some(blocks);
         }
 
@@ -164,8 +168,10 @@ impl super::Authorizer {
         let generated_facts = self
             .world
             .facts
-            .inner
-            .iter()
+            .by_origin()
+            .into_iter()
+            // secondary tokens are not saved
+            .filter(|(origin, _)| !origin.inner.contains(&SECONDARY_TOKENS_ORIGIN))
             .map(|(origin, facts)| {
                 Ok(GeneratedFacts {
                     origins: authorizer_origin_to_proto_origin(origin),
@@ -205,6 +211,7 @@ impl super::Authorizer {
                 max_facts: self.limits.max_facts,
                 max_iterations: self.limits.max_iterations,
                 max_time: self.limits.max_time.as_nanos() as u64,
+                max_derivation_depth: self.limits.max_derivation_depth,
             },
         })
     }
@@ -1,3 +1,5 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use crate::{
     builder::{self, Convert},
     crypto::PublicKey,
@@ -29,6 +31,9 @@ pub struct Block {
     pub public_keys: PublicKeys,
     /// list of scopes defining which blocks are trusted by this block
     pub scopes: Vec<Scope>,
+    /// creation time, in seconds since the Unix epoch. Blocks created by older
+    /// versions do not have it
+    pub created_at: Option<u64>,
 }
 
 impl Block {
@@ -108,6 +113,97 @@ impl Block {
                     builder::Scope::convert_from(s, from_symbols).map(|s| s.convert(to_symbols))
                 })
                 .collect::<Result<Vec<Scope>, error::Format>>()?,
+            created_at: self.created_at,
         })
     }
 }
+
+/// borrowed view of a block from a [`Biscuit`](crate::Biscuit), obtained with
+/// [`Biscuit::blocks`](crate::Biscuit::blocks)
+///
+/// facts, rules and checks are converted on demand
+#[derive(Clone, Debug)]
+pub struct BlockRef<'a> {
+    index: usize,
+    block: Block,
+    token_symbols: &'a SymbolTable,
+}
+
+impl<'a> BlockRef<'a> {
+    pub(crate) fn new(index: usize, block: Block, token_symbols: &'a SymbolTable) -> Self {
+        BlockRef {
+            index,
+            block,
+            token_symbols,
+        }
+    }
+
+    /// index of the block in the token (the authority block has index 0)
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// list of symbols introduced by this block
+    pub fn symbols(&self) -> Vec<String> {
+        self.block.symbols.strings()
+    }
+
+    /// facts provided by this block
+    pub fn facts(&self) -> impl Iterator<Item = Result<builder::Fact, error::Format>> + '_ {
+        let symbols = self.symbol_table();
+        self.block
+            .facts
+            .iter()
+            .map(move |f| builder::Fact::convert_from(f, symbols))
+    }
+
+    /// rules provided by this block
+    pub fn rules(&self) -> impl Iterator<Item = Result<builder::Rule, error::Format>> + '_ {
+        let symbols = self.symbol_table();
+        self.block
+            .rules
+            .iter()
+            .map(move |r| builder::Rule::convert_from(r, symbols))
+    }
+
+    /// checks provided by this block
+    pub fn checks(&self) -> impl Iterator<Item = Result<builder::Check, error::Format>> + '_ {
+        let symbols = self.symbol_table();
+        self.block
+            .checks
+            .iter()
+            .map(move |c| builder::Check::convert_from(c, symbols))
+    }
+
+    /// contextual information attached to this block
+    pub fn context(&self) -> Option<&str> {
+        self.block.context.as_deref()
+    }
+
+    /// key used in the optional external signature
+    pub fn external_key(&self) -> Option<PublicKey> {
+        self.block.external_key
+    }
+
+    /// creation time of the block, see
+    /// [`Biscuit::block_created_at`](crate::Biscuit::block_created_at)
+    pub fn created_at(&self) -> Option<SystemTime> {
+        self.block
+            .created_at
+            .and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+    }
+
+    /// prints the content of the block as Datalog source code
+    pub fn print_source(&self) -> String {
+        self.block.print_source(self.symbol_table())
+    }
+
+    // third party blocks have their own symbol table
+    fn symbol_table(&self) -> &SymbolTable {
+        if self.block.external_key.is_some() {
+            &self.block.symbols
+        } else {
+            self.token_symbols
+        }
+    }
+}
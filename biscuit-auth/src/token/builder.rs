@@ -3,20 +3,38 @@ use super::{default_symbol_table, Biscuit, Block};
 use crate::crypto::{KeyPair, PublicKey};
 use crate::datalog::{self, get_schema_version, SymbolTable};
 use crate::error;
-use crate::token::builder_ext::BuilderExt;
+use crate::token::builder_ext::{AuthorizerExt, BuilderExt};
+use crate::token::predicate_schema::Schema;
 use biscuit_parser::parser::parse_block_source;
 use nom::Finish;
 use rand_core::{CryptoRng, RngCore};
 use std::str::FromStr;
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
     convert::{TryFrom, TryInto},
     fmt::{self, Write},
+    net::IpAddr,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 // reexport those because the builder uses the same definitions
 pub use crate::datalog::{Binary, Expression as DatalogExpression, Op as DatalogOp, Unary};
+pub use biscuit_parser::builder::{is_name_char, validate_name, MAX_NAME_LENGTH};
+
+/// predicates that are usually provided by the authorizer, and can be
+/// used in checks without being defined in the token
+///
+/// nothing prevents a block from defining facts with these names, but facts
+/// from an attenuation block are only trusted by the rules and checks of that
+/// block (or those explicitly trusting its external key), so they cannot
+/// satisfy authorizer policies or the checks of other blocks
+pub const AMBIENT_PREDICATES: &[&str] = &[
+    "resource",
+    "operation",
+    "time",
+    "revocation_id",
+    "client_ip",
+];
 
 /// creates a Block content to append to an existing token
 #[derive(Clone, Debug, Default)]
@@ -26,6 +44,14 @@ pub struct BlockBuilder {
     pub checks: Vec<Check>,
     pub scopes: Vec<Scope>,
     pub context: Option<String>,
+    /// see [`BlockBuilder::set_strict`]
+    pub strict: bool,
+    /// see [`BlockBuilder::with_schema`], setting it directly does not check
+    /// the elements already in the block
+    pub schema: Option<Schema>,
+    /// `None` to use the time of the block's creation, `Some(None)` to omit
+    /// it, see [`BlockBuilder::set_created_at`]
+    pub created_at: Option<Option<SystemTime>>,
 }
 
 impl BlockBuilder {
@@ -33,14 +59,86 @@ impl BlockBuilder {
         BlockBuilder::default()
     }
 
-    pub fn merge(&mut self, mut other: BlockBuilder) {
-        self.facts.append(&mut other.facts);
-        self.rules.append(&mut other.rules);
-        self.checks.append(&mut other.checks);
+    /// creates a block builder that is not meant to be added to a token, to
+    /// unit test policies (see [`BlockBuilder::test_check`]) or inspect them
+    ///
+    /// it is the same as [`BlockBuilder::new`]: a builder only refers to names,
+    /// so it does not depend on a token's symbol table or block index until
+    /// it is appended
+    pub fn new_standalone() -> BlockBuilder {
+        BlockBuilder::default()
+    }
+
+    /// checks the current and future facts, rules and checks against `schema`
+    ///
+    /// the elements added afterwards with [`BlockBuilder::add_fact`],
+    /// [`BlockBuilder::add_rule`], [`BlockBuilder::add_check`] or
+    /// [`BlockBuilder::add_code`] are rejected with
+    /// [`error::Token::SchemaViolations`] if they do not match
+    pub fn with_schema(mut self, schema: &Schema) -> Result<Self, error::Token> {
+        let violations = schema.check_block(&self);
+        if !violations.is_empty() {
+            return Err(error::Token::SchemaViolations(violations));
+        }
+
+        self.schema = Some(schema.clone());
+        Ok(self)
+    }
+
+    // applies `check` with the schema, if there is one
+    fn check_schema(
+        &self,
+        check: impl FnOnce(&Schema) -> Vec<error::SchemaViolation>,
+    ) -> Result<(), error::Token> {
+        match self.schema.as_ref().map(check) {
+            Some(violations) if !violations.is_empty() => {
+                Err(error::Token::SchemaViolations(violations))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// appends the facts, rules, checks and scopes of `other` that are not
+    /// already in this block
+    ///
+    /// builders refer to names and not to symbol indices, so the symbols of
+    /// both builders are interned in the same table when the block is built.
+    /// This fails with [`error::Token::SchemaViolations`] if the contents of
+    /// `other` do not match the schema of this block, without modifying it
+    pub fn merge(&mut self, other: BlockBuilder) -> Result<(), error::Token> {
+        if let Some(schema) = &self.schema {
+            let violations = schema.check_block(&other);
+            if !violations.is_empty() {
+                return Err(error::Token::SchemaViolations(violations));
+            }
+        }
+
+        for fact in other.facts {
+            if !self.facts.contains(&fact) {
+                self.facts.push(fact);
+            }
+        }
+        for rule in other.rules {
+            if !self.rules.contains(&rule) {
+                self.rules.push(rule);
+            }
+        }
+        for check in other.checks {
+            if !self.checks.contains(&check) {
+                self.checks.push(check);
+            }
+        }
+        for scope in other.scopes {
+            if !self.scopes.contains(&scope) {
+                self.scopes.push(scope);
+            }
+        }
 
         if let Some(c) = other.context {
             self.set_context(c);
         }
+        self.strict |= other.strict;
+        Ok(())
     }
 
     pub fn add_fact<F: TryInto<Fact>>(&mut self, fact: F) -> Result<(), error::Token>
@@ -48,7 +146,9 @@ impl BlockBuilder {
         error::Token: From<<F as TryInto<Fact>>::Error>,
     {
         let fact = fact.try_into()?;
+        fact.predicate.validate_name()?;
         fact.validate()?;
+        self.check_schema(|schema| schema.check_fact(&fact))?;
 
         self.facts.push(fact);
         Ok(())
@@ -59,7 +159,10 @@ impl BlockBuilder {
         error::Token: From<<R as TryInto<Rule>>::Error>,
     {
         let rule = rule.try_into()?;
+        rule.validate_names()?;
+        rule.validate_body()?;
         rule.validate_parameters()?;
+        self.check_schema(|schema| schema.check_rule(&rule))?;
         self.rules.push(rule);
         Ok(())
     }
@@ -69,11 +172,35 @@ impl BlockBuilder {
         error::Token: From<<C as TryInto<Check>>::Error>,
     {
         let check = check.try_into()?;
+        check.queries.iter().try_for_each(Rule::validate_names)?;
+        check.queries.iter().try_for_each(Rule::validate_body)?;
         check.validate_parameters()?;
+        self.check_schema(|schema| schema.check_check(&check))?;
         self.checks.push(check);
         Ok(())
     }
 
+    /// adds a check with a name, reported in [`error::FailedCheck`] and
+    /// [`CheckResult`](crate::CheckResult) if it fails
+    ///
+    /// the name is not part of the Datalog code and does not change the
+    /// evaluation. It is not serialized either, so it is only kept for the
+    /// checks of an authorizer (see [`Authorizer::merge_block`](crate::Authorizer::merge_block)),
+    /// and dropped from token blocks and snapshots. Names do not have to be
+    /// unique
+    pub fn add_check_named<C: TryInto<Check>>(
+        &mut self,
+        name: &str,
+        check: C,
+    ) -> Result<(), error::Token>
+    where
+        error::Token: From<<C as TryInto<Check>>::Error>,
+    {
+        let mut check = check.try_into()?;
+        check.name = Some(name.to_string());
+        self.add_check::<Check>(check)
+    }
+
     pub fn add_code<T: AsRef<str>>(&mut self, source: T) -> Result<(), error::Token> {
         self.add_code_with_params(source, HashMap::new(), HashMap::new())
     }
@@ -108,11 +235,12 @@ impl BlockBuilder {
                 res?;
             }
             fact.validate()?;
+            self.check_schema(|schema| schema.check_fact(&fact))?;
             self.facts.push(fact);
         }
 
         for (_, rule) in source_result.rules.into_iter() {
-            let mut rule: Rule = rule.into();
+            let mut rule: Rule = rule.try_into()?;
             for (name, value) in &params {
                 let res = match rule.set(name, value) {
                     Ok(_) => Ok(()),
@@ -137,12 +265,14 @@ impl BlockBuilder {
                 };
                 res?;
             }
+            rule.validate_body()?;
             rule.validate_parameters()?;
+            self.check_schema(|schema| schema.check_rule(&rule))?;
             self.rules.push(rule);
         }
 
         for (_, check) in source_result.checks.into_iter() {
-            let mut check: Check = check.into();
+            let mut check: Check = check.try_into()?;
             for (name, value) in &params {
                 let res = match check.set(name, value) {
                     Ok(_) => Ok(()),
@@ -167,7 +297,9 @@ impl BlockBuilder {
                 };
                 res?;
             }
+            check.queries.iter().try_for_each(Rule::validate_body)?;
             check.validate_parameters()?;
+            self.check_schema(|schema| schema.check_check(&check))?;
             self.checks.push(check);
         }
 
@@ -178,10 +310,267 @@ impl BlockBuilder {
         self.scopes.push(scope);
     }
 
+    /// adds rules deriving the lower operations from the higher ones
+    ///
+    /// each `(higher, lower)` pair generates the rule
+    /// `right($resource, lower) <- right($resource, higher)`, so with
+    /// `[("admin", "write"), ("write", "read")]` a token granting `admin`
+    /// on a resource also grants `write` and `read`. This fails with
+    /// [`error::Token::OperationCycle`] if the pairs contain a cycle,
+    /// without modifying the block
+    pub fn add_operation_hierarchy(&mut self, pairs: &[(&str, &str)]) -> Result<(), error::Token> {
+        if let Some(cycle) = operation_cycle(pairs) {
+            return Err(error::Token::OperationCycle(cycle));
+        }
+
+        let rules = pairs
+            .iter()
+            .map(|(higher, lower)| {
+                rule(
+                    "right",
+                    &[var("resource"), string(lower)],
+                    &[pred("right", &[var("resource"), string(higher)])],
+                )
+            })
+            .collect::<Vec<_>>();
+        for rule in rules.iter() {
+            self.check_schema(|schema| schema.check_rule(rule))?;
+        }
+
+        for rule in rules {
+            if !self.rules.contains(&rule) {
+                self.rules.push(rule);
+            }
+        }
+        Ok(())
+    }
+
     pub fn set_context(&mut self, context: String) {
         self.context = Some(context);
     }
 
+    /// sets the creation time written in the block, or omits it with `None`
+    ///
+    /// by default, it is the time at which the block is added to a token.
+    /// Setting it makes the serialized token deterministic, for tests
+    pub fn set_created_at(&mut self, created_at: Option<SystemTime>) {
+        self.created_at = Some(created_at);
+    }
+
+    /// maps the scalar claims of an identity (like OIDC claims) to
+    /// `claim(subject, "key", value)` facts
+    ///
+    /// `claims` must be an object. Strings, booleans and integers map to the
+    /// corresponding terms, other values (arrays, objects, `null`, numbers
+    /// that do not fit in an `i64`) are skipped
+    #[cfg(feature = "serde_json")]
+    pub fn add_identity_facts(
+        &mut self,
+        subject: &str,
+        claims: &serde_json::Value,
+    ) -> Result<(), error::Token> {
+        self.add_identity_facts_with(subject, claims, NestedClaims::Skip)
+    }
+
+    /// same as [`BlockBuilder::add_identity_facts`], with a choice of
+    /// how to handle claims that cannot be mapped to a term
+    ///
+    /// on error, no fact is added
+    #[cfg(feature = "serde_json")]
+    pub fn add_identity_facts_with(
+        &mut self,
+        subject: &str,
+        claims: &serde_json::Value,
+        nested: NestedClaims,
+    ) -> Result<(), error::Token> {
+        use serde_json::Value;
+
+        let claims = claims.as_object().ok_or_else(|| {
+            error::Token::ConversionError("identity claims must be a JSON object".to_string())
+        })?;
+
+        let mut facts = Vec::new();
+        for (key, value) in claims {
+            let term = match value {
+                Value::String(s) => Some(string(s)),
+                Value::Bool(b) => Some(boolean(*b)),
+                Value::Number(n) => n.as_i64().map(int),
+                Value::Null | Value::Array(_) | Value::Object(_) => None,
+            };
+
+            match (term, &nested) {
+                (Some(term), _) => facts.push(fact("claim", &[string(subject), string(key), term])),
+                (None, NestedClaims::Skip) => {}
+                (None, NestedClaims::Error) => {
+                    return Err(error::Token::ConversionError(format!(
+                        "claim \"{}\" cannot be converted to a term: {}",
+                        key, value
+                    )))
+                }
+            }
+        }
+
+        self.facts.extend(facts);
+        Ok(())
+    }
+
+    /// adds an application defined revocation id, as a `revocation_id(id)` fact
+    ///
+    /// tokens carrying it are rejected by [`Authorizer::revoke_ids`](crate::Authorizer::revoke_ids)
+    pub fn revocation_id(&mut self, id: i64) {
+        self.facts.push(fact("revocation_id", &[int(id)]));
+    }
+
+    /// adds an application defined revocation id, as a `revocation_id(hex:...)`
+    /// fact containing the UUID's bytes
+    ///
+    /// tokens carrying it are rejected by [`Authorizer::revoke_uuids`](crate::Authorizer::revoke_uuids)
+    #[cfg(feature = "uuid")]
+    pub fn revocation_uuid(&mut self, id: &uuid::Uuid) {
+        self.facts
+            .push(fact("revocation_id", &[bytes(id.as_bytes())]));
+    }
+
+    /// in strict mode, building the token or appending this block fails with
+    /// [`error::Token::UnknownPredicates`] if checks reference predicates that
+    /// are not defined (see [`BlockBuilder::unknown_predicates`])
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// the facts as they will be printed from the built block, with the
+    /// parameters replaced
+    pub fn facts(&self) -> impl ExactSizeIterator<Item = String> {
+        let mut symbols = default_symbol_table();
+        self.facts
+            .iter()
+            .map(|fact| {
+                let fact = fact.convert(&mut symbols);
+                symbols.print_fact(&fact)
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// the rules as they will be printed from the built block, see
+    /// [`BlockBuilder::facts`]
+    pub fn rules(&self) -> impl ExactSizeIterator<Item = String> {
+        let mut symbols = default_symbol_table();
+        self.rules
+            .iter()
+            .map(|rule| {
+                let rule = rule.convert(&mut symbols);
+                symbols.print_rule(&rule)
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// the checks as they will be printed from the built block, see
+    /// [`BlockBuilder::facts`]
+    pub fn checks(&self) -> impl ExactSizeIterator<Item = String> {
+        let mut symbols = default_symbol_table();
+        self.checks
+            .iter()
+            .map(|check| {
+                let check = check.convert(&mut symbols);
+                symbols.print_check(&check)
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// lists the predicates used in checks that are not defined by this block,
+    /// by the `known` list (usually the predicates defined in previous blocks)
+    /// or part of [`AMBIENT_PREDICATES`]
+    pub fn unknown_predicates(&self, known: &HashSet<String>) -> Vec<error::UnknownPredicate> {
+        let defined: HashSet<&str> = self.defined_predicates().collect();
+        let mut unknown = Vec::new();
+
+        for check in &self.checks {
+            let mut names: Vec<&str> = Vec::new();
+            for predicate in check.queries.iter().flat_map(|query| query.body.iter()) {
+                let name = predicate.name.as_str();
+                if !defined.contains(name)
+                    && !known.contains(name)
+                    && !AMBIENT_PREDICATES.contains(&name)
+                    && !names.contains(&name)
+                {
+                    names.push(name);
+                }
+            }
+
+            for name in names {
+                unknown.push(error::UnknownPredicate {
+                    check: check.to_string(),
+                    predicate: name.to_string(),
+                });
+            }
+        }
+
+        unknown
+    }
+
+    /// evaluates `check` before shipping it, as if it was added to this block
+    ///
+    /// a throwaway token is built with an authority block containing
+    /// `authority_facts`, then an attenuation block with this block's facts,
+    /// rules and `check` (but not its other checks), and is authorized with
+    /// `ambient_facts` added by the authorizer. This goes through the same
+    /// code as a real authorization, so the results are the same.
+    ///
+    /// returns `Ok(false)` if the check fails, and errors from building the
+    /// token or from the evaluation (like reaching the default limits, except
+    /// for the time limit, raised to one second)
+    pub fn test_check(
+        &self,
+        check: &Check,
+        authority_facts: &[Fact],
+        ambient_facts: &[Fact],
+    ) -> Result<bool, error::Token> {
+        let mut authority = Biscuit::builder();
+        for fact in authority_facts {
+            authority.add_fact(fact.clone())?;
+        }
+        let token = authority.build(&KeyPair::new())?;
+
+        let block = BlockBuilder {
+            facts: self.facts.clone(),
+            rules: self.rules.clone(),
+            checks: vec![check.clone()],
+            scopes: self.scopes.clone(),
+            ..BlockBuilder::default()
+        };
+        let token = token.append(block)?;
+
+        let mut authorizer = token.authorizer()?;
+        for fact in ambient_facts {
+            authorizer.add_fact(fact.clone())?;
+        }
+        authorizer.add_allow_all();
+
+        // the result should not depend on the load of the machine
+        let limits = crate::AuthorizerLimits {
+            max_time: Duration::from_secs(1),
+            ..Default::default()
+        };
+        match authorizer.authorize_with_limits(limits) {
+            Ok(_) => Ok(true),
+            Err(error::Token::FailedLogic(error::Logic::Unauthorized { .. })) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// names of the predicates generated by this block's facts and rules
+    pub(crate) fn defined_predicates(&self) -> impl Iterator<Item = &str> {
+        self.facts
+            .iter()
+            .map(|f| f.predicate.name.as_str())
+            .chain(self.rules.iter().map(|r| r.head.name.as_str()))
+    }
+
+    // symbols are only interned here, while converting the block's contents,
+    // so the new symbol table only contains referenced symbols
     pub(crate) fn build(self, mut symbols: SymbolTable) -> Block {
         let symbols_start = symbols.current_offset();
         let public_keys_start = symbols.public_keys.current_offset();
@@ -220,6 +609,10 @@ impl BlockBuilder {
             external_key: None,
             public_keys,
             scopes,
+            created_at: self
+                .created_at
+                .unwrap_or_else(|| Some(crate::time::now()))
+                .map(|t| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()),
         }
     }
 
@@ -249,6 +642,9 @@ impl BlockBuilder {
                 .map(|s| Scope::convert_from(s, &symbols))
                 .collect::<Result<Vec<Scope>, error::Format>>()?,
             context: block.context.clone(),
+            strict: false,
+            schema: None,
+            created_at: None,
         })
     }
 
@@ -269,6 +665,41 @@ impl BlockBuilder {
     }
 }
 
+/// returns the operations forming a cycle in the `(higher, lower)` pairs,
+/// starting and ending with the same operation
+fn operation_cycle(pairs: &[(&str, &str)]) -> Option<Vec<String>> {
+    fn visit<'a>(
+        operation: &'a str,
+        pairs: &[(&'a str, &'a str)],
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|op| *op == operation) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|op| op.to_string()).collect();
+            cycle.push(operation.to_string());
+            return Some(cycle);
+        }
+        if done.contains(operation) {
+            return None;
+        }
+
+        path.push(operation);
+        for (_, lower) in pairs.iter().filter(|(higher, _)| *higher == operation) {
+            if let Some(cycle) = visit(lower, pairs, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(operation);
+        None
+    }
+
+    let mut done = HashSet::new();
+    pairs
+        .iter()
+        .find_map(|(higher, _)| visit(higher, pairs, &mut Vec::new(), &mut done))
+}
+
 impl fmt::Display for BlockBuilder {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for mut fact in self.facts.clone().into_iter() {
@@ -288,7 +719,7 @@ impl fmt::Display for BlockBuilder {
 }
 
 /// creates a Biscuit
-#[derive(Clone, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct BiscuitBuilder {
     inner: BlockBuilder,
     root_key_id: Option<u32>,
@@ -302,7 +733,8 @@ impl BiscuitBuilder {
         }
     }
 
-    pub fn merge(&mut self, other: BlockBuilder) {
+    /// see [`BlockBuilder::merge`]
+    pub fn merge(&mut self, other: BlockBuilder) -> Result<(), error::Token> {
         self.inner.merge(other)
     }
 
@@ -346,20 +778,110 @@ impl BiscuitBuilder {
         self.inner.add_scope(scope);
     }
 
+    /// see [`BlockBuilder::add_operation_hierarchy`]
+    pub fn add_operation_hierarchy(&mut self, pairs: &[(&str, &str)]) -> Result<(), error::Token> {
+        self.inner.add_operation_hierarchy(pairs)
+    }
+
     #[cfg(test)]
     pub(crate) fn add_right(&mut self, resource: &str, right: &str) {
-        let _ = self.add_fact(fact("right", &[string(resource), string(right)]));
+        let _ = self.add_fact(right_fact(resource, right));
     }
 
     pub fn set_context(&mut self, context: String) {
         self.inner.set_context(context);
     }
 
+    /// see [`BlockBuilder::set_created_at`]
+    pub fn set_created_at(&mut self, created_at: Option<SystemTime>) {
+        self.inner.set_created_at(created_at);
+    }
+
+    /// see [`BlockBuilder::revocation_id`]
+    pub fn revocation_id(&mut self, id: i64) {
+        self.inner.revocation_id(id);
+    }
+
+    /// see [`BlockBuilder::add_identity_facts`]
+    #[cfg(feature = "serde_json")]
+    pub fn add_identity_facts(
+        &mut self,
+        subject: &str,
+        claims: &serde_json::Value,
+    ) -> Result<(), error::Token> {
+        self.inner.add_identity_facts(subject, claims)
+    }
+
+    /// see [`BlockBuilder::add_identity_facts_with`]
+    #[cfg(feature = "serde_json")]
+    pub fn add_identity_facts_with(
+        &mut self,
+        subject: &str,
+        claims: &serde_json::Value,
+        nested: NestedClaims,
+    ) -> Result<(), error::Token> {
+        self.inner.add_identity_facts_with(subject, claims, nested)
+    }
+
+    /// see [`BlockBuilder::revocation_uuid`]
+    #[cfg(feature = "uuid")]
+    pub fn revocation_uuid(&mut self, id: &uuid::Uuid) {
+        self.inner.revocation_uuid(id);
+    }
+
+    /// adds a random 16 bytes `nonce(hex:...)` fact, to make a single use token
+    ///
+    /// the nonce is read with [`Biscuit::nonce`], and checked against the
+    /// already used ones with [`Authorizer::require_nonce`](crate::Authorizer::require_nonce)
+    pub fn add_nonce<R: RngCore + CryptoRng>(&mut self, rng: &mut R) {
+        let mut nonce = [0u8; 16];
+        rng.fill_bytes(&mut nonce);
+        self.inner.facts.push(nonce_fact(&nonce));
+    }
+
+    /// adds an `issued_at(date)` fact, checked with the nonce by
+    /// [`Authorizer::replay_protection`](crate::Authorizer::replay_protection)
+    pub fn add_issued_at(&mut self, t: SystemTime) {
+        self.inner.facts.push(issued_at_fact(t));
+    }
+
     pub fn set_root_key_id(&mut self, root_key_id: u32) {
         self.root_key_id = Some(root_key_id);
     }
 
+    /// see [`BlockBuilder::set_strict`]
+    pub fn set_strict(&mut self, strict: bool) {
+        self.inner.set_strict(strict);
+    }
+
+    /// see [`BlockBuilder::with_schema`]
+    pub fn with_schema(mut self, schema: &Schema) -> Result<Self, error::Token> {
+        self.inner = self.inner.with_schema(schema)?;
+        Ok(self)
+    }
+
+    /// lists the predicates used in the authority block's checks that
+    /// are not defined in it
+    pub fn unknown_predicates(&self) -> Vec<error::UnknownPredicate> {
+        self.inner.unknown_predicates(&HashSet::new())
+    }
+
     /// returns all of the datalog loaded in the biscuit builder
+    /// see [`BlockBuilder::facts`]
+    pub fn facts(&self) -> impl ExactSizeIterator<Item = String> {
+        self.inner.facts()
+    }
+
+    /// see [`BlockBuilder::rules`]
+    pub fn rules(&self) -> impl ExactSizeIterator<Item = String> {
+        self.inner.rules()
+    }
+
+    /// see [`BlockBuilder::checks`]
+    pub fn checks(&self) -> impl ExactSizeIterator<Item = String> {
+        self.inner.checks()
+    }
+
     pub fn dump(&self) -> (Vec<Fact>, Vec<Rule>, Vec<Check>) {
         (
             self.inner.facts.clone(),
@@ -401,11 +923,144 @@ impl BiscuitBuilder {
         symbols: SymbolTable,
         rng: &mut R,
     ) -> Result<Biscuit, error::Token> {
+        if self.inner.strict {
+            let unknown = self.unknown_predicates();
+            if !unknown.is_empty() {
+                return Err(error::Token::UnknownPredicates(unknown));
+            }
+        }
+
         let authority_block = self.inner.build(symbols.clone());
         Biscuit::new_with_rng(rng, self.root_key_id, root, symbols, authority_block)
     }
 }
 
+/// Datalog code with parameters, used to create tokens that only differ by
+/// a few values
+///
+/// ```rust
+/// use biscuit_auth::{builder::{string, BiscuitTemplate}, KeyPair};
+///
+/// let template = BiscuitTemplate::new(
+///     r#"user({user_id});
+///     check if operation("read");"#,
+/// )
+/// .unwrap();
+///
+/// let root = KeyPair::new();
+/// let token = template
+///     .instantiate(&[("user_id", string("alice"))], &mut rand::rngs::OsRng, &root)
+///     .unwrap();
+/// ```
+///
+/// with the `serde-error` feature, a template is serialized as its source code
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde-error",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct BiscuitTemplate {
+    source: String,
+    parameters: BTreeSet<String>,
+}
+
+impl BiscuitTemplate {
+    /// parses the source of the authority block. Parameters are written
+    /// `{name}`, like in [`BlockBuilder::add_code_with_params`]
+    pub fn new<T: Into<String>>(source: T) -> Result<Self, error::Token> {
+        let source = source.into();
+        let parsed = parse_block_source(&source).map_err(|e| {
+            let e: biscuit_parser::error::LanguageError = e.into();
+            e
+        })?;
+
+        let mut parameters = BTreeSet::new();
+        let rules = parsed.rules.iter().map(|(_, rule)| rule).chain(
+            parsed
+                .checks
+                .iter()
+                .flat_map(|(_, check)| check.queries.iter()),
+        );
+        for fact_parameters in parsed.facts.iter().map(|(_, fact)| &fact.parameters) {
+            parameters.extend(fact_parameters.iter().flat_map(|p| p.keys().cloned()));
+        }
+        for rule in rules {
+            parameters.extend(rule.parameters.iter().flat_map(|p| p.keys().cloned()));
+            parameters.extend(rule.scope_parameters.iter().flat_map(|p| p.keys().cloned()));
+        }
+
+        Ok(BiscuitTemplate { source, parameters })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// names of the parameters that must be bound by [`BiscuitTemplate::instantiate`]
+    pub fn parameters(&self) -> impl Iterator<Item = &str> {
+        self.parameters.iter().map(|name| name.as_str())
+    }
+
+    /// creates a token from the template, replacing each parameter with its term
+    ///
+    /// fails with [`LanguageError::Parameters`](biscuit_parser::error::LanguageError::Parameters)
+    /// if a parameter is not bound, or if a binding does not match a parameter.
+    /// Scope parameters cannot be bound here, so templates using them always fail
+    pub fn instantiate<R: RngCore + CryptoRng>(
+        &self,
+        bindings: &[(&str, Term)],
+        rng: &mut R,
+        root: &KeyPair,
+    ) -> Result<Biscuit, error::Token> {
+        let params: HashMap<String, Term> = bindings
+            .iter()
+            .map(|(name, term)| (name.to_string(), term.clone()))
+            .collect();
+
+        let missing_parameters: Vec<String> = self
+            .parameters
+            .iter()
+            .filter(|name| !params.contains_key(*name))
+            .cloned()
+            .collect();
+        let mut unused_parameters: Vec<String> = params
+            .keys()
+            .filter(|name| !self.parameters.contains(*name))
+            .cloned()
+            .collect();
+        unused_parameters.sort();
+        if !missing_parameters.is_empty() || !unused_parameters.is_empty() {
+            return Err(error::Token::Language(
+                biscuit_parser::error::LanguageError::Parameters {
+                    missing_parameters,
+                    unused_parameters,
+                },
+            ));
+        }
+
+        let mut builder = BiscuitBuilder::new();
+        builder
+            .inner
+            .add_code_with_params(&self.source, params, HashMap::new())?;
+        builder.build_with_rng(root, default_symbol_table(), rng)
+    }
+}
+
+impl TryFrom<String> for BiscuitTemplate {
+    type Error = error::Token;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        BiscuitTemplate::new(source)
+    }
+}
+
+impl From<BiscuitTemplate> for String {
+    fn from(template: BiscuitTemplate) -> Self {
+        template.source
+    }
+}
+
 pub trait Convert<T>: Sized {
     fn convert(&self, symbols: &mut SymbolTable) -> T;
     fn convert_from(f: &T, symbols: &SymbolTable) -> Result<Self, error::Format>;
@@ -418,6 +1073,17 @@ pub trait Convert<T>: Sized {
     }
 }
 
+/// how [`BlockBuilder::add_identity_facts_with`] handles claims that are not
+/// strings, booleans or integers
+#[cfg(feature = "serde_json")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NestedClaims {
+    /// the claim is ignored
+    Skip,
+    /// a [`error::Token::ConversionError`] is returned
+    Error,
+}
+
 /// Builder for a Datalog value
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Term {
@@ -592,16 +1258,18 @@ impl fmt::Display for Scope {
     }
 }
 
-impl From<biscuit_parser::builder::Scope> for Scope {
-    fn from(scope: biscuit_parser::builder::Scope) -> Self {
-        match scope {
+impl TryFrom<biscuit_parser::builder::Scope> for Scope {
+    type Error = error::Token;
+
+    fn try_from(scope: biscuit_parser::builder::Scope) -> Result<Self, Self::Error> {
+        Ok(match scope {
             biscuit_parser::builder::Scope::Authority => Scope::Authority,
             biscuit_parser::builder::Scope::Previous => Scope::Previous,
             biscuit_parser::builder::Scope::PublicKey(pk) => {
-                Scope::PublicKey(PublicKey::from_bytes(&pk).expect("invalid public key"))
+                Scope::PublicKey(PublicKey::from_bytes(&pk)?)
             }
             biscuit_parser::builder::Scope::Parameter(s) => Scope::Parameter(s),
-        }
+        })
     }
 }
 
@@ -619,6 +1287,13 @@ impl Predicate {
             terms: terms.into(),
         }
     }
+
+    /// checks the predicate name with [`validate_name`] and [`MAX_NAME_LENGTH`]
+    pub fn validate_name(&self) -> Result<(), error::Token> {
+        validate_name(&self.name, MAX_NAME_LENGTH).map_err(|e| {
+            error::Token::Language(biscuit_parser::error::LanguageError::InvalidName(e))
+        })
+    }
 }
 
 impl Convert<datalog::Predicate> for Predicate {
@@ -885,8 +1560,11 @@ impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut syms = super::default_symbol_table();
         let expr = self.convert(&mut syms);
-        let s = expr.print(&syms).unwrap();
-        write!(f, "{}", s)
+        // expressions coming from a token are not validated until they are evaluated
+        match expr.print(&syms) {
+            Some(s) => write!(f, "{}", s),
+            None => write!(f, "<invalid expression: {:?}>", expr.ops),
+        }
     }
 }
 
@@ -1030,6 +1708,24 @@ impl Rule {
         }
     }
 
+    /// checks the names of the head and body predicates with
+    /// [`Predicate::validate_name`]
+    pub fn validate_names(&self) -> Result<(), error::Token> {
+        std::iter::once(&self.head)
+            .chain(self.body.iter())
+            .try_for_each(Predicate::validate_name)
+    }
+
+    /// rejects rules and check queries without predicates or expressions,
+    /// which would always match
+    pub fn validate_body(&self) -> Result<(), error::Token> {
+        if self.body.is_empty() && self.expressions.is_empty() {
+            return Err(error::Format::EmptyRuleBody(self.to_string()).into());
+        }
+
+        Ok(())
+    }
+
     pub fn validate_parameters(&self) -> Result<(), error::Token> {
         let mut invalid_parameters = match &self.parameters {
             None => vec![],
@@ -1395,9 +2091,11 @@ impl fmt::Display for Rule {
     }
 }
 
-impl From<biscuit_parser::builder::Rule> for Rule {
-    fn from(r: biscuit_parser::builder::Rule) -> Self {
-        Rule {
+impl TryFrom<biscuit_parser::builder::Rule> for Rule {
+    type Error = error::Token;
+
+    fn try_from(r: biscuit_parser::builder::Rule) -> Result<Self, Self::Error> {
+        Ok(Rule {
             head: r.head.into(),
             body: r.body.into_iter().map(|p| p.into()).collect(),
             expressions: r.expressions.into_iter().map(|e| e.into()).collect(),
@@ -1406,20 +2104,20 @@ impl From<biscuit_parser::builder::Rule> for Rule {
                     .map(|(k, v)| (k, v.map(|term| term.into())))
                     .collect()
             }),
-            scopes: r.scopes.into_iter().map(|s| s.into()).collect(),
-            scope_parameters: r.scope_parameters.map(|h| {
-                h.into_iter()
-                    .map(|(k, v)| {
-                        (
-                            k,
-                            v.map(|bytes| {
-                                PublicKey::from_bytes(&bytes).expect("invalid public key")
-                            }),
-                        )
-                    })
-                    .collect()
-            }),
-        }
+            scopes: r
+                .scopes
+                .into_iter()
+                .map(Scope::try_from)
+                .collect::<Result<_, _>>()?,
+            scope_parameters: r
+                .scope_parameters
+                .map(|h| {
+                    h.into_iter()
+                        .map(|(k, v)| Ok((k, v.map(|b| PublicKey::from_bytes(&b)).transpose()?)))
+                        .collect::<Result<_, error::Format>>()
+                })
+                .transpose()?,
+        })
     }
 }
 
@@ -1428,6 +2126,9 @@ impl From<biscuit_parser::builder::Rule> for Rule {
 pub struct Check {
     pub queries: Vec<Rule>,
     pub kind: CheckKind,
+    /// label reported when the check fails, it is not used in evaluation,
+    /// not part of the Datalog syntax and not serialized
+    pub name: Option<String>,
 }
 
 /// Builder for a Biscuit check
@@ -1529,6 +2230,57 @@ impl Check {
             rule.apply_parameters();
         }
     }
+
+    /// returns the expiration date enforced by this check, if it is an
+    /// expiration check like the ones generated by [`BuilderExt::check_expiration_date`]
+    /// (`check if time($time), $time <= 2022-01-01T00:00:00Z`)
+    ///
+    /// if the check has multiple queries, they must all be expiration checks,
+    /// and the latest date is returned. Dates that cannot be represented by
+    /// [`SystemTime`] are ignored
+    pub fn expiration_date(&self) -> Option<SystemTime> {
+        self.queries
+            .iter()
+            .map(|query| query.expiration_date())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|dates| dates.into_iter().max())
+            .and_then(|date| UNIX_EPOCH.checked_add(Duration::from_secs(date)))
+    }
+
+    /// same as [`Check::expiration_date`], returning a `chrono` date
+    #[cfg(feature = "chrono")]
+    pub fn expiration_date_chrono(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.expiration_date()
+            .and_then(|expiration| date(&expiration).try_into().ok())
+    }
+}
+
+impl Rule {
+    // earliest date compared to the time fact in this rule's expressions
+    fn expiration_date(&self) -> Option<u64> {
+        let time_variables: Vec<&String> = self
+            .body
+            .iter()
+            .filter(|p| p.name == "time" && p.terms.len() == 1)
+            .filter_map(|p| match &p.terms[0] {
+                Term::Variable(v) => Some(v),
+                _ => None,
+            })
+            .collect();
+
+        self.expressions
+            .iter()
+            .filter_map(|e| match e.ops.as_slice() {
+                [Op::Value(Term::Variable(v)), Op::Value(Term::Date(d)), Op::Binary(Binary::LessOrEqual | Binary::LessThan)]
+                | [Op::Value(Term::Date(d)), Op::Value(Term::Variable(v)), Op::Binary(Binary::GreaterOrEqual | Binary::GreaterThan)]
+                    if time_variables.contains(&v) =>
+                {
+                    Some(*d)
+                }
+                _ => None,
+            })
+            .min()
+    }
 }
 
 impl Convert<datalog::Check> for Check {
@@ -1541,6 +2293,7 @@ impl Convert<datalog::Check> for Check {
         datalog::Check {
             queries,
             kind: self.kind.clone(),
+            name: self.name.clone(),
         }
     }
 
@@ -1553,6 +2306,7 @@ impl Convert<datalog::Check> for Check {
         Ok(Check {
             queries,
             kind: r.kind.clone(),
+            name: r.name.clone(),
         })
     }
 }
@@ -1564,6 +2318,7 @@ impl TryFrom<Rule> for Check {
         Ok(Check {
             queries: vec![value],
             kind: CheckKind::One,
+            name: None,
         })
     }
 }
@@ -1575,6 +2330,7 @@ impl TryFrom<&[Rule]> for Check {
         Ok(Check {
             queries: values.to_vec(),
             kind: CheckKind::One,
+            name: None,
         })
     }
 }
@@ -1605,15 +2361,22 @@ impl fmt::Display for Check {
     }
 }
 
-impl From<biscuit_parser::builder::Check> for Check {
-    fn from(c: biscuit_parser::builder::Check) -> Self {
-        Check {
-            queries: c.queries.into_iter().map(|q| q.into()).collect(),
+impl TryFrom<biscuit_parser::builder::Check> for Check {
+    type Error = error::Token;
+
+    fn try_from(c: biscuit_parser::builder::Check) -> Result<Self, Self::Error> {
+        Ok(Check {
+            queries: c
+                .queries
+                .into_iter()
+                .map(Rule::try_from)
+                .collect::<Result<_, _>>()?,
             kind: match c.kind {
                 biscuit_parser::builder::CheckKind::One => CheckKind::One,
                 biscuit_parser::builder::CheckKind::All => CheckKind::All,
             },
-        }
+            name: None,
+        })
     }
 }
 
@@ -1745,15 +2508,21 @@ impl fmt::Display for Policy {
     }
 }
 
-impl From<biscuit_parser::builder::Policy> for Policy {
-    fn from(p: biscuit_parser::builder::Policy) -> Self {
-        Policy {
-            queries: p.queries.into_iter().map(|q| q.into()).collect(),
+impl TryFrom<biscuit_parser::builder::Policy> for Policy {
+    type Error = error::Token;
+
+    fn try_from(p: biscuit_parser::builder::Policy) -> Result<Self, Self::Error> {
+        Ok(Policy {
+            queries: p
+                .queries
+                .into_iter()
+                .map(Rule::try_from)
+                .collect::<Result<_, _>>()?,
             kind: match p.kind {
                 biscuit_parser::builder::PolicyKind::Allow => PolicyKind::Allow,
                 biscuit_parser::builder::PolicyKind::Deny => PolicyKind::Deny,
             },
-        }
+        })
     }
 }
 
@@ -1811,6 +2580,7 @@ pub fn check<P: AsRef<Predicate>>(predicates: &[P], kind: CheckKind) -> Check {
             vec![],
         )],
         kind,
+        name: None,
     }
 }
 
@@ -1826,22 +2596,133 @@ pub fn string(s: &str) -> Term {
 
 /// creates a date
 ///
-/// internally the date will be stored as seconds since UNIX_EPOCH
+/// internally the date will be stored as seconds since UNIX_EPOCH.
+/// Dates before UNIX_EPOCH cannot be represented and are clamped to it
 pub fn date(t: &SystemTime) -> Term {
-    let dur = t.duration_since(UNIX_EPOCH).unwrap();
+    let dur = t.duration_since(UNIX_EPOCH).unwrap_or_default();
     Term::Date(dur.as_secs())
 }
 
-/// creates a variable for a rule
-pub fn var(s: &str) -> Term {
-    Term::Variable(s.to_string())
-}
+/// creates an expiration check (`check if time($time), $time <= date`)
+/// from a `time::OffsetDateTime`, or a `chrono::DateTime<Utc>` with the
+/// `chrono` feature
+///
+/// dates before UNIX_EPOCH cannot be represented and return an error
+pub fn expiration_check<T: TryInto<Term, Error = error::Token>>(
+    date: T,
+) -> Result<Check, error::Token> {
+    Ok(Check {
+        queries: vec![constrained_rule(
+            "expiration",
+            &[var("time")],
+            &[pred("time", &[var("time")])],
+            &[Expression {
+                ops: vec![
+                    Op::Value(var("time")),
+                    Op::Value(date.try_into()?),
+                    Op::Binary(Binary::LessOrEqual),
+                ],
+            }],
+        )],
+        kind: CheckKind::One,
+        name: Some("expiration".to_string()),
+    })
+}
+
+/// creates a variable for a rule
+pub fn var(s: &str) -> Term {
+    Term::Variable(s.to_string())
+}
 
 /// creates a variable for a rule
 pub fn variable(s: &str) -> Term {
     Term::Variable(s.to_string())
 }
 
+/// hands out variables with unique names, to build rules from independent
+/// fragments without merging their variables by accident
+///
+/// ```rust
+/// use biscuit_auth::builder::vars;
+///
+/// let mut v = vars();
+/// let first = v.next("resource");
+/// let second = v.next("resource");
+/// assert_eq!(first.to_string(), "$resource");
+/// assert_eq!(second.to_string(), "$resource_1");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct VariableAllocator {
+    names: Vec<String>,
+}
+
+impl VariableAllocator {
+    pub fn new() -> Self {
+        VariableAllocator::default()
+    }
+
+    /// returns `$name` the first time, then `$name_1`, `$name_2`, ... skipping
+    /// the names already given
+    pub fn next(&mut self, name: &str) -> Term {
+        let mut candidate = name.to_string();
+        let mut i = 0;
+        while self.names.contains(&candidate) {
+            i += 1;
+            candidate = format!("{}_{}", name, i);
+        }
+
+        self.names.push(candidate.clone());
+        Term::Variable(candidate)
+    }
+
+    /// names of the variables given so far, in order
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+/// creates a [`VariableAllocator`]
+pub fn vars() -> VariableAllocator {
+    VariableAllocator::new()
+}
+
+/// creates a rule whose head terms, body and expressions are built by `f`
+/// with a new [`VariableAllocator`]
+///
+/// ```rust
+/// use biscuit_auth::builder::{pred, rule_with};
+///
+/// let rule = rule_with("allowed", |v| {
+///     let user = v.next("user");
+///     let other = v.next("user");
+///     (
+///         vec![user.clone()],
+///         vec![
+///             pred("user", &[&user]),
+///             pred("delegates", &[&other, &user]),
+///             pred("admin", &[&other]),
+///         ],
+///         vec![],
+///     )
+/// });
+/// assert_eq!(
+///     rule.to_string(),
+///     "allowed($user) <- user($user), delegates($user_1, $user), admin($user_1)"
+/// );
+/// ```
+pub fn rule_with<F>(head_name: &str, f: F) -> Rule
+where
+    F: FnOnce(&mut VariableAllocator) -> (Vec<Term>, Vec<Predicate>, Vec<Expression>),
+{
+    let (head_terms, predicates, expressions) = f(&mut vars());
+    Rule::new(
+        Predicate::new(head_name.to_string(), head_terms),
+        predicates,
+        expressions,
+        vec![],
+    )
+}
+
 /// creates a byte array
 pub fn bytes(s: &[u8]) -> Term {
     Term::Bytes(s.to_vec())
@@ -1862,6 +2743,368 @@ pub fn parameter(p: &str) -> Term {
     Term::Parameter(p.to_string())
 }
 
+/// creates the `resource(path)` fact provided by the authorizer
+pub fn resource_fact(path: &str) -> Fact {
+    fact("resource", &[string(path)])
+}
+
+/// creates the `operation(op)` fact provided by the authorizer
+pub fn operation_fact(op: &str) -> Fact {
+    fact("operation", &[string(op)])
+}
+
+/// creates the `time(t)` fact provided by the authorizer, truncated to the
+/// second like [`date`]
+pub fn time_fact(t: SystemTime) -> Fact {
+    fact("time", &[date(&t)])
+}
+
+/// creates the `nonce(bytes)` fact, see [`BiscuitBuilder::add_nonce`]
+pub fn nonce_fact(nonce: &[u8]) -> Fact {
+    fact("nonce", &[bytes(nonce)])
+}
+
+/// creates the `issued_at(date)` fact, see [`BiscuitBuilder::add_issued_at`]
+pub fn issued_at_fact(t: SystemTime) -> Fact {
+    fact("issued_at", &[date(&t)])
+}
+
+/// creates the `right(resource, operation)` fact, usually found in the
+/// authority block
+pub fn right_fact(resource: &str, operation: &str) -> Fact {
+    fact("right", &[string(resource), string(operation)])
+}
+
+/// creates an IP address, stored as a string of the hexadecimal octets:
+/// 8 digits for IPv4 and 32 digits for IPv6
+pub fn ip(address: IpAddr) -> Term {
+    string(&ip_hex(address))
+}
+
+fn ip_hex(address: IpAddr) -> String {
+    let octets = match address {
+        IpAddr::V4(address) => address.octets().to_vec(),
+        IpAddr::V6(address) => address.octets().to_vec(),
+    };
+
+    octets.iter().map(|o| format!("{:02x}", o)).collect()
+}
+
+/// creates the `client_ip(address)` fact provided by the authorizer
+pub fn client_ip_fact(address: IpAddr) -> Fact {
+    fact("client_ip", &[ip(address)])
+}
+
+/// creates a check that the `client_ip` fact is in a CIDR block, like `10.0.0.0/8`
+pub fn client_ip_in(cidr: &str) -> Result<Check, error::Token> {
+    let cidr: Cidr = cidr.parse()?;
+
+    Ok(Check {
+        queries: vec![constrained_rule(
+            "client_ip_check",
+            &[var("ip")],
+            &[pred("client_ip", &[var("ip")])],
+            &[Expression {
+                ops: vec![
+                    Op::Value(var("ip")),
+                    Op::Value(cidr.into()),
+                    Op::Binary(Binary::Regex),
+                ],
+            }],
+        )],
+        kind: CheckKind::One,
+        name: Some("client_ip".to_string()),
+    })
+}
+
+/// a block of IP addresses, like `10.0.0.0/8` or `2001:db8::/32`
+///
+/// it is converted to a regular expression matching the addresses created
+/// with [`ip`] that are in the block, to be used with the `matches` operator
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// fails if the prefix length is above 32 for IPv4 or 128 for IPv6
+    pub fn new(address: IpAddr, prefix_len: u8) -> Result<Self, error::Token> {
+        let maximum = if address.is_ipv4() { 32 } else { 128 };
+        if prefix_len > maximum {
+            return Err(error::Token::InvalidCidr(format!(
+                "prefix length {} is above {}",
+                prefix_len, maximum
+            )));
+        }
+
+        Ok(Cidr {
+            address,
+            prefix_len,
+        })
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// regular expression matching the addresses of the block
+    ///
+    /// the digits fully covered by the prefix are matched as is, the one
+    /// holding the end of the prefix with the set of values it can take, and
+    /// the following ones with any digit. The length of the address keeps
+    /// IPv4 and IPv6 apart
+    pub fn pattern(&self) -> String {
+        let address = ip_hex(self.address);
+        let prefix_len = self.prefix_len as usize;
+        let (full_digits, remaining_bits) = (prefix_len / 4, prefix_len % 4);
+
+        let mut pattern = format!("^{}", &address[..full_digits]);
+        let mut any_digits = address.len() - full_digits;
+        if remaining_bits != 0 {
+            let digit = u8::from_str_radix(&address[full_digits..=full_digits], 16).unwrap();
+            let free_bits = 4 - remaining_bits;
+            let low = digit >> free_bits << free_bits;
+            pattern.push('[');
+            for value in low..low + (1 << free_bits) {
+                write!(pattern, "{:x}", value).unwrap();
+            }
+            pattern.push(']');
+            any_digits -= 1;
+        }
+        if any_digits != 0 {
+            write!(pattern, "[0-9a-f]{{{}}}", any_digits).unwrap();
+        }
+        pattern.push('$');
+
+        pattern
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = error::Token;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = s
+            .split_once('/')
+            .ok_or_else(|| error::Token::InvalidCidr(format!("missing prefix length in {}", s)))?;
+        let address = address
+            .parse()
+            .map_err(|_| error::Token::InvalidCidr(format!("invalid address in {}", s)))?;
+        let prefix_len = prefix_len
+            .parse()
+            .map_err(|_| error::Token::InvalidCidr(format!("invalid prefix length in {}", s)))?;
+
+        Cidr::new(address, prefix_len)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+impl From<Cidr> for Term {
+    fn from(cidr: Cidr) -> Self {
+        string(&cidr.pattern())
+    }
+}
+
+/// creates the expression `$variable != value`
+///
+/// comparing a non integer value fails the expression, so the binding is rejected
+pub fn int_neq(variable: &str, value: i64) -> Expression {
+    Expression {
+        ops: vec![
+            Op::Value(var(variable)),
+            Op::Value(int(value)),
+            Op::Binary(Binary::NotEqual),
+        ],
+    }
+}
+
+/// creates an expression checking that the integer `$variable` is not one of `values`
+///
+/// it is written as `$variable != a && $variable != b ...`, so that, as with
+/// [`int_neq`], a non integer value is rejected. With an empty list, it only
+/// checks that the value is an integer
+pub fn int_not_in(variable: &str, values: &[i64]) -> Expression {
+    let mut values = values.to_vec();
+    values.sort_unstable();
+    values.dedup();
+
+    let mut ops = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        ops.extend(int_neq(variable, *value).ops);
+        if i > 0 {
+            ops.push(Op::Binary(Binary::And));
+        }
+    }
+    if ops.is_empty() {
+        ops = vec![
+            Op::Value(var(variable)),
+            Op::Value(int(i64::MIN)),
+            Op::Binary(Binary::GreaterOrEqual),
+        ];
+    }
+
+    Expression { ops }
+}
+
+// `$variable <op> date`, the date being truncated to the second like [`date`]
+fn date_comparison(variable: &str, t: &SystemTime, op: Binary) -> Expression {
+    Expression {
+        ops: vec![Op::Value(var(variable)), Op::Value(date(t)), Op::Binary(op)],
+    }
+}
+
+/// creates the expression `$variable < date`: the date's second is excluded
+///
+/// dates are compared with a one second precision, like [`date`] creates them,
+/// so `$variable` matches up to `date - 1s`
+pub fn before(variable: &str, date: &SystemTime) -> Expression {
+    date_comparison(variable, date, Binary::LessThan)
+}
+
+/// creates the expression `$variable <= date`: the date's second is included
+///
+/// this is the comparison used by expiration checks: a token expiring at
+/// `12:00:00` is still valid at `12:00:00.999`
+pub fn before_inclusive(variable: &str, date: &SystemTime) -> Expression {
+    date_comparison(variable, date, Binary::LessOrEqual)
+}
+
+/// creates the expression `$variable > date`: the date's second is excluded
+pub fn after(variable: &str, date: &SystemTime) -> Expression {
+    date_comparison(variable, date, Binary::GreaterThan)
+}
+
+/// creates the expression `$variable >= date`: the date's second is included
+pub fn after_inclusive(variable: &str, date: &SystemTime) -> Expression {
+    date_comparison(variable, date, Binary::GreaterOrEqual)
+}
+
+/// creates the expression `$variable == date`, matching only the date's second
+pub fn date_equal(variable: &str, date: &SystemTime) -> Expression {
+    date_comparison(variable, date, Binary::Equal)
+}
+
+/// creates an expression matching the UTC calendar day containing `date`:
+/// `$variable >= <day>T00:00:00Z && $variable < <next day>T00:00:00Z`
+pub fn on_date(variable: &str, date: &SystemTime) -> Expression {
+    const DAY: u64 = 24 * 60 * 60;
+    let seconds = date
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let start = UNIX_EPOCH + Duration::from_secs(seconds - seconds % DAY);
+    let end = start + Duration::from_secs(DAY);
+
+    let mut ops = after_inclusive(variable, &start).ops;
+    ops.extend(before(variable, &end).ops);
+    ops.push(Op::Binary(Binary::And));
+    Expression { ops }
+}
+
+// `$left <op> $right`, both variables being bound to dates
+fn dates_comparison(left: &str, right: &str, op: Binary) -> Expression {
+    Expression {
+        ops: vec![Op::Value(var(left)), Op::Value(var(right)), Op::Binary(op)],
+    }
+}
+
+/// creates the expression `$left < $right`, comparing two dates bound in the
+/// same rule body: equal dates do not match
+///
+/// dates are stored with a one second precision, so two dates in the same
+/// second are equal. If one of the variables is not bound to a date, the
+/// expression fails with an error, and so does the authorization
+pub fn date_before(left: &str, right: &str) -> Expression {
+    dates_comparison(left, right, Binary::LessThan)
+}
+
+/// creates the expression `$left <= $right`: equal dates match
+pub fn date_before_inclusive(left: &str, right: &str) -> Expression {
+    dates_comparison(left, right, Binary::LessOrEqual)
+}
+
+/// creates the expression `$left > $right`: equal dates do not match
+pub fn date_after(left: &str, right: &str) -> Expression {
+    dates_comparison(left, right, Binary::GreaterThan)
+}
+
+/// creates the expression `$left >= $right`: equal dates match
+pub fn date_after_inclusive(left: &str, right: &str) -> Expression {
+    dates_comparison(left, right, Binary::GreaterOrEqual)
+}
+
+// `$variable.length() <op> length`, lengths above i64::MAX are clamped
+fn length_comparison(variable: &str, length: usize, op: Binary) -> Expression {
+    Expression {
+        ops: vec![
+            Op::Value(var(variable)),
+            Op::Unary(Unary::Length),
+            Op::Value(int(i64::try_from(length).unwrap_or(i64::MAX))),
+            Op::Binary(op),
+        ],
+    }
+}
+
+/// creates the expression `$variable.length() < length`
+///
+/// the length of a string is its size in bytes of UTF-8, not its number of
+/// characters: `"é"` has a length of 2. Byte arrays and sets are measured in
+/// bytes and elements, other values fail the expression
+pub fn str_length_less_than(variable: &str, length: usize) -> Expression {
+    length_comparison(variable, length, Binary::LessThan)
+}
+
+/// creates the expression `$variable.length() > length`, measured in bytes
+/// like [`str_length_less_than`]
+pub fn str_length_greater_than(variable: &str, length: usize) -> Expression {
+    length_comparison(variable, length, Binary::GreaterThan)
+}
+
+// joins expressions with `op`, wrapped in parentheses
+fn combine(expressions: &[Expression], op: Binary, name: &str) -> Result<Expression, error::Token> {
+    let mut ops = Vec::new();
+    for (i, expression) in expressions.iter().enumerate() {
+        ops.extend(expression.ops.iter().cloned());
+        if i > 0 {
+            ops.push(Op::Binary(op.clone()));
+        }
+    }
+    if ops.is_empty() {
+        return Err(error::Token::ConversionError(format!(
+            "{} needs at least one expression",
+            name
+        )));
+    }
+    ops.push(Op::Unary(Unary::Parens));
+
+    Ok(Expression { ops })
+}
+
+/// creates an expression that succeeds if any of `expressions` is true,
+/// printed as `(e1 || e2 ...)`
+///
+/// this avoids duplicating a rule to express alternatives on a variable,
+/// like `any_of(&[prefix, suffix])`. An empty list is rejected
+pub fn any_of(expressions: &[Expression]) -> Result<Expression, error::Token> {
+    combine(expressions, Binary::Or, "any_of")
+}
+
+/// creates an expression that succeeds if all of `expressions` are true,
+/// printed as `(e1 && e2 ...)`. An empty list is rejected
+pub fn all_of(expressions: &[Expression]) -> Result<Expression, error::Token> {
+    combine(expressions, Binary::And, "all_of")
+}
+
 #[cfg(feature = "datalog-macro")]
 pub enum AnyParam {
     Term(Term),
@@ -1892,7 +3135,7 @@ impl TryFrom<Term> for i64 {
         match value {
             Term::Integer(i) => Ok(i),
             _ => Err(error::Token::ConversionError(format!(
-                "expected integer, got {:?}",
+                "expected integer, got {}",
                 value
             ))),
         }
@@ -1918,7 +3161,7 @@ impl TryFrom<Term> for bool {
         match value {
             Term::Bool(b) => Ok(b),
             _ => Err(error::Token::ConversionError(format!(
-                "expected boolean, got {:?}",
+                "expected boolean, got {}",
                 value
             ))),
         }
@@ -1957,7 +3200,7 @@ impl TryFrom<Term> for String {
         match value {
             Term::Str(s) => Ok(s),
             _ => Err(error::Token::ConversionError(format!(
-                "expected string or symbol, got {:?}",
+                "expected string or symbol, got {}",
                 value
             ))),
         }
@@ -1983,7 +3226,7 @@ impl TryFrom<Term> for Vec<u8> {
         match value {
             Term::Bytes(b) => Ok(b),
             _ => Err(error::Token::ConversionError(format!(
-                "expected byte array, got {:?}",
+                "expected byte array, got {}",
                 value
             ))),
         }
@@ -2012,7 +3255,7 @@ impl ToAnyParam for uuid::Uuid {
 
 impl From<SystemTime> for Term {
     fn from(t: SystemTime) -> Self {
-        let dur = t.duration_since(UNIX_EPOCH).unwrap();
+        let dur = t.duration_since(UNIX_EPOCH).unwrap_or_default();
         Term::Date(dur.as_secs())
     }
 }
@@ -2030,7 +3273,69 @@ impl TryFrom<Term> for SystemTime {
         match value {
             Term::Date(d) => Ok(UNIX_EPOCH + Duration::from_secs(d)),
             _ => Err(error::Token::ConversionError(format!(
-                "expected date, got {:?}",
+                "expected date, got {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// converts a date with its sub-second part truncated
+///
+/// dates before UNIX_EPOCH cannot be represented and return an error
+impl TryFrom<time::OffsetDateTime> for Term {
+    type Error = error::Token;
+    fn try_from(value: time::OffsetDateTime) -> Result<Self, Self::Error> {
+        u64::try_from(value.unix_timestamp())
+            .map(Term::Date)
+            .map_err(|_| error::Token::ConversionError(format!("date out of range: {}", value)))
+    }
+}
+
+impl TryFrom<Term> for time::OffsetDateTime {
+    type Error = error::Token;
+    fn try_from(value: Term) -> Result<Self, Self::Error> {
+        match value {
+            Term::Date(d) => i64::try_from(d)
+                .ok()
+                .and_then(|d| time::OffsetDateTime::from_unix_timestamp(d).ok())
+                .ok_or_else(|| error::Token::ConversionError(format!("date out of range: {}", d))),
+            _ => Err(error::Token::ConversionError(format!(
+                "expected date, got {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// converts a date with its sub-second part truncated
+///
+/// dates before UNIX_EPOCH cannot be represented and return an error
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::DateTime<chrono::Utc>> for Term {
+    type Error = error::Token;
+    fn try_from(value: chrono::DateTime<chrono::Utc>) -> Result<Self, Self::Error> {
+        u64::try_from(value.timestamp())
+            .map(Term::Date)
+            .map_err(|_| {
+                error::Token::ConversionError(format!("date out of range: {}", value.to_rfc3339()))
+            })
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Term> for chrono::DateTime<chrono::Utc> {
+    type Error = error::Token;
+    fn try_from(value: Term) -> Result<Self, Self::Error> {
+        use chrono::TimeZone;
+
+        match value {
+            Term::Date(d) => i64::try_from(d)
+                .ok()
+                .and_then(|d| chrono::Utc.timestamp_opt(d, 0).single())
+                .ok_or_else(|| error::Token::ConversionError(format!("date out of range: {}", d))),
+            _ => Err(error::Token::ConversionError(format!(
+                "expected date, got {}",
                 value
             ))),
         }
@@ -2056,7 +3361,7 @@ impl<T: Ord + TryFrom<Term, Error = error::Token>> TryFrom<Term> for BTreeSet<T>
         match value {
             Term::Set(d) => d.iter().cloned().map(TryFrom::try_from).collect(),
             _ => Err(error::Token::ConversionError(format!(
-                "expected set, got {:?}",
+                "expected set, got {}",
                 value
             ))),
         }
@@ -2133,10 +3438,11 @@ impl TryFrom<&str> for Rule {
     type Error = error::Token;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(biscuit_parser::parser::rule(value)
+        biscuit_parser::parser::rule(value)
             .finish()
-            .map(|(_, o)| o.into())
-            .map_err(biscuit_parser::error::LanguageError::from)?)
+            .map_err(biscuit_parser::error::LanguageError::from)?
+            .1
+            .try_into()
     }
 }
 
@@ -2155,10 +3461,11 @@ impl FromStr for Rule {
     type Err = error::Token;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(biscuit_parser::parser::rule(s)
+        biscuit_parser::parser::rule(s)
             .finish()
-            .map(|(_, o)| o.into())
-            .map_err(biscuit_parser::error::LanguageError::from)?)
+            .map_err(biscuit_parser::error::LanguageError::from)?
+            .1
+            .try_into()
     }
 }
 
@@ -2166,10 +3473,11 @@ impl TryFrom<&str> for Check {
     type Error = error::Token;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(biscuit_parser::parser::check(value)
+        biscuit_parser::parser::check(value)
             .finish()
-            .map(|(_, o)| o.into())
-            .map_err(biscuit_parser::error::LanguageError::from)?)
+            .map_err(biscuit_parser::error::LanguageError::from)?
+            .1
+            .try_into()
     }
 }
 
@@ -2177,10 +3485,11 @@ impl FromStr for Check {
     type Err = error::Token;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(biscuit_parser::parser::check(s)
+        biscuit_parser::parser::check(s)
             .finish()
-            .map(|(_, o)| o.into())
-            .map_err(biscuit_parser::error::LanguageError::from)?)
+            .map_err(biscuit_parser::error::LanguageError::from)?
+            .1
+            .try_into()
     }
 }
 
@@ -2188,10 +3497,11 @@ impl TryFrom<&str> for Policy {
     type Error = error::Token;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(biscuit_parser::parser::policy(value)
+        biscuit_parser::parser::policy(value)
             .finish()
-            .map(|(_, o)| o.into())
-            .map_err(biscuit_parser::error::LanguageError::from)?)
+            .map_err(biscuit_parser::error::LanguageError::from)?
+            .1
+            .try_into()
     }
 }
 
@@ -2199,16 +3509,64 @@ impl FromStr for Policy {
     type Err = error::Token;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(biscuit_parser::parser::policy(s)
+        biscuit_parser::parser::policy(s)
             .finish()
-            .map(|(_, o)| o.into())
-            .map_err(biscuit_parser::error::LanguageError::from)?)
+            .map_err(biscuit_parser::error::LanguageError::from)?
+            .1
+            .try_into()
+    }
+}
+
+// `check if resource($resource), $resource.<op>(value)`
+pub(crate) fn resource_check(name: &str, value: &str, op: Binary) -> Check {
+    Check {
+        queries: vec![constrained_rule(
+            name,
+            &[var("resource")],
+            &[pred("resource", &[var("resource")])],
+            &[Expression {
+                ops: vec![
+                    Op::Value(var("resource")),
+                    Op::Value(string(value)),
+                    Op::Binary(op),
+                ],
+            }],
+        )],
+        kind: CheckKind::One,
+        name: Some(name.to_string()),
+    }
+}
+
+// regular expression matching `value` between the `start` and `end` anchors,
+// ignoring ASCII case. `(?i-u)` does not fold non ASCII letters, so the result
+// does not depend on Unicode case tables
+pub(crate) fn ascii_case_insensitive(start: &str, value: &str, end: &str) -> String {
+    format!("(?i-u){}{}{}", start, regex::escape(value), end)
+}
+
+// `check if name($value), $value <op> value`
+pub(crate) fn integer_check(name: &str, value: i64, op: Binary) -> Check {
+    Check {
+        queries: vec![constrained_rule(
+            "integer_check",
+            &[var("value")],
+            &[pred(name, &[var("value")])],
+            &[Expression {
+                ops: vec![
+                    Op::Value(var("value")),
+                    Op::Value(int(value)),
+                    Op::Binary(op),
+                ],
+            }],
+        )],
+        kind: CheckKind::One,
+        name: Some(format!("{}_limit", name)),
     }
 }
 
 impl BuilderExt for BlockBuilder {
     fn add_resource(&mut self, name: &str) {
-        self.facts.push(fact("resource", &[string(name)]));
+        self.facts.push(resource_fact(name));
     }
     fn check_resource(&mut self, name: &str) {
         self.checks.push(Check {
@@ -2218,10 +3576,11 @@ impl BuilderExt for BlockBuilder {
                 &[pred("resource", &[string(name)])],
             )],
             kind: CheckKind::One,
+            name: Some("resource_check".to_string()),
         });
     }
     fn add_operation(&mut self, name: &str) {
-        self.facts.push(fact("operation", &[string(name)]));
+        self.facts.push(operation_fact(name));
     }
     fn check_operation(&mut self, name: &str) {
         self.checks.push(Check {
@@ -2231,6 +3590,7 @@ impl BuilderExt for BlockBuilder {
                 &[pred("operation", &[string(name)])],
             )],
             kind: CheckKind::One,
+            name: Some("operation_check".to_string()),
         });
     }
     fn check_resource_prefix(&mut self, prefix: &str) {
@@ -2250,6 +3610,7 @@ impl BuilderExt for BlockBuilder {
         self.checks.push(Check {
             queries: vec![check],
             kind: CheckKind::One,
+            name: Some("prefix".to_string()),
         });
     }
 
@@ -2270,9 +3631,34 @@ impl BuilderExt for BlockBuilder {
         self.checks.push(Check {
             queries: vec![check],
             kind: CheckKind::One,
+            name: Some("suffix".to_string()),
         });
     }
 
+    fn check_resource_ci(&mut self, name: &str) {
+        self.checks.push(resource_check(
+            "resource_check",
+            &ascii_case_insensitive("^", name, "$"),
+            Binary::Regex,
+        ));
+    }
+
+    fn check_resource_prefix_ci(&mut self, prefix: &str) {
+        self.checks.push(resource_check(
+            "prefix",
+            &ascii_case_insensitive("^", prefix, ""),
+            Binary::Regex,
+        ));
+    }
+
+    fn check_resource_suffix_ci(&mut self, suffix: &str) {
+        self.checks.push(resource_check(
+            "suffix",
+            &ascii_case_insensitive("", suffix, "$"),
+            Binary::Regex,
+        ));
+    }
+
     fn check_expiration_date(&mut self, exp: SystemTime) {
         let check = constrained_rule(
             "expiration",
@@ -2290,8 +3676,24 @@ impl BuilderExt for BlockBuilder {
         self.checks.push(Check {
             queries: vec![check],
             kind: CheckKind::One,
+            name: Some("expiration".to_string()),
         });
     }
+    fn check_integer_at_most(&mut self, name: &str, max: i64) {
+        self.checks
+            .push(integer_check(name, max, Binary::LessOrEqual));
+    }
+    fn check_integer_below(&mut self, name: &str, max: i64) {
+        self.checks.push(integer_check(name, max, Binary::LessThan));
+    }
+    fn check_integer_at_least(&mut self, name: &str, min: i64) {
+        self.checks
+            .push(integer_check(name, min, Binary::GreaterOrEqual));
+    }
+    fn check_integer_above(&mut self, name: &str, min: i64) {
+        self.checks
+            .push(integer_check(name, min, Binary::GreaterThan));
+    }
 }
 
 impl fmt::Display for BiscuitBuilder {
@@ -2317,6 +3719,15 @@ impl BuilderExt for BiscuitBuilder {
     fn check_resource_suffix(&mut self, suffix: &str) {
         self.inner.check_resource_suffix(suffix);
     }
+    fn check_resource_ci(&mut self, name: &str) {
+        self.inner.check_resource_ci(name);
+    }
+    fn check_resource_prefix_ci(&mut self, prefix: &str) {
+        self.inner.check_resource_prefix_ci(prefix);
+    }
+    fn check_resource_suffix_ci(&mut self, suffix: &str) {
+        self.inner.check_resource_suffix_ci(suffix);
+    }
     fn add_operation(&mut self, name: &str) {
         self.inner.add_operation(name);
     }
@@ -2326,6 +3737,18 @@ impl BuilderExt for BiscuitBuilder {
     fn check_expiration_date(&mut self, date: SystemTime) {
         self.inner.check_expiration_date(date);
     }
+    fn check_integer_at_most(&mut self, name: &str, max: i64) {
+        self.inner.check_integer_at_most(name, max);
+    }
+    fn check_integer_below(&mut self, name: &str, max: i64) {
+        self.inner.check_integer_below(name, max);
+    }
+    fn check_integer_at_least(&mut self, name: &str, min: i64) {
+        self.inner.check_integer_at_least(name, min);
+    }
+    fn check_integer_above(&mut self, name: &str, min: i64) {
+        self.inner.check_integer_above(name, min);
+    }
 }
 
 #[cfg(test)]
@@ -2333,18 +3756,57 @@ mod tests {
     use super::*;
 
     #[test]
-    fn set_rule_parameters() {
-        let mut rule = Rule::try_from(
-            "fact($var1, {p2}, {p5}) <- f1($var1, $var3), f2({p2}, $var3, {p4}), $var3.starts_with({p2})",
-        )
-        .unwrap();
-        rule.set("p2", "hello").unwrap();
-        rule.set("p4", 0i64).unwrap();
-        rule.set("p4", 1i64).unwrap();
+    fn predicate_names() {
+        use biscuit_parser::error::{LanguageError, NameError};
 
-        let mut term_set = BTreeSet::new();
-        term_set.insert(int(0i64));
-        rule.set("p5", term_set).unwrap();
+        let mut builder = BlockBuilder::new();
+        assert_eq!(
+            builder.add_fact(fact("", &[int(0)])),
+            Err(error::Token::Language(LanguageError::InvalidName(
+                NameError::Empty
+            )))
+        );
+        assert_eq!(
+            builder.add_rule(rule(
+                "allowed",
+                &[var("x")],
+                &[pred("right\u{7}", &[var("x")])]
+            )),
+            Err(error::Token::Language(LanguageError::InvalidName(
+                NameError::InvalidCharacter {
+                    character: '\u{7}',
+                    position: 5
+                }
+            )))
+        );
+        assert!(builder
+            .add_check(check(
+                &[pred(&"a".repeat(MAX_NAME_LENGTH + 1), &[int(0)])],
+                CheckKind::One
+            ))
+            .is_err());
+        assert!(builder.facts.is_empty() && builder.rules.is_empty() && builder.checks.is_empty());
+
+        // the parser accepts exactly the names that are valid
+        let long = "a".repeat(MAX_NAME_LENGTH);
+        builder.add_code(format!("{}(0)", long)).unwrap();
+        builder.add_fact(fact(&long, &[int(0)])).unwrap();
+        assert!(builder.add_code(format!("{}a(0)", long)).is_err());
+    }
+
+    #[test]
+    fn set_rule_parameters() {
+        let mut rule = Rule::try_from(
+            "fact($var1, {p2}, {p5}) <- f1($var1, $var3), f2({p2}, $var3, {p4}), $var3.starts_with({p2})",
+        )
+        .unwrap();
+        rule.set("p2", "hello").unwrap();
+        rule.set("p4", 0i64).unwrap();
+        rule.set("p4", 1i64).unwrap();
+
+        let mut term_set = BTreeSet::new();
+        term_set.insert(int(0i64));
+        rule.set("p5", term_set).unwrap();
 
         let s = rule.to_string();
         assert_eq!(s, "fact($var1, \"hello\", [0]) <- f1($var1, $var3), f2(\"hello\", $var3, 1), $var3.starts_with(\"hello\")");
@@ -2475,4 +3937,1316 @@ check if true trusting ed25519/6e9e6d5a75cf0c0e87ec1256b4dfed0ca3ba452912d213fcc
             ))
         )
     }
+
+    #[test]
+    fn invalid_public_key_in_scope() {
+        let source = format!("check if true trusting ed25519/{}", "02".repeat(32));
+        assert!(matches!(
+            Check::try_from(source.as_str()),
+            Err(error::Token::Format(error::Format::InvalidKey(_)))
+        ));
+
+        let mut builder = BlockBuilder::new();
+        assert!(matches!(
+            builder.add_code(&source),
+            Err(error::Token::Format(error::Format::InvalidKey(_)))
+        ));
+    }
+
+    #[test]
+    fn date_before_epoch() {
+        let t = UNIX_EPOCH - Duration::from_secs(10);
+        assert_eq!(date(&t), Term::Date(0));
+        assert_eq!(Term::from(t), Term::Date(0));
+    }
+
+    #[test]
+    fn offset_date_time_conversion() {
+        let t = time::OffsetDateTime::from_unix_timestamp(1_608_542_592).unwrap();
+        let term = Term::try_from(t).unwrap();
+        assert_eq!(term, Term::Date(1_608_542_592));
+        assert_eq!(term.to_string(), "2020-12-21T09:23:12Z");
+        assert_eq!(time::OffsetDateTime::try_from(term).unwrap(), t);
+
+        let before_epoch = time::OffsetDateTime::from_unix_timestamp(-1).unwrap();
+        assert!(Term::try_from(before_epoch).is_err());
+        assert!(time::OffsetDateTime::try_from(Term::Date(u64::MAX)).is_err());
+        assert_eq!(
+            time::OffsetDateTime::try_from(Term::Integer(1)),
+            Err(error::Token::ConversionError(
+                "expected date, got 1".to_string()
+            ))
+        );
+
+        let check = expiration_check(t).unwrap();
+        assert_eq!(
+            check.to_string(),
+            "check if time($time), $time <= 2020-12-21T09:23:12Z"
+        );
+        assert_eq!(
+            check.expiration_date(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_608_542_592))
+        );
+
+        // a date that does not fit in a SystemTime is not an expiration date
+        let mut check = check;
+        check.queries[0].expressions[0].ops[1] = Op::Value(Term::Date(u64::MAX));
+        assert_eq!(check.expiration_date(), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_conversion() {
+        use chrono::{DateTime, TimeZone, Utc};
+
+        let t = Utc.timestamp_opt(1_608_542_592, 500).unwrap();
+        let term = Term::try_from(t).unwrap();
+        assert_eq!(term, Term::Date(1_608_542_592));
+        assert_eq!(
+            DateTime::<Utc>::try_from(term).unwrap(),
+            Utc.timestamp_opt(1_608_542_592, 0).unwrap()
+        );
+
+        assert!(Term::try_from(Utc.timestamp_opt(-1, 0).unwrap()).is_err());
+        assert!(DateTime::<Utc>::try_from(Term::Date(u64::MAX)).is_err());
+
+        let check = expiration_check(t).unwrap();
+        assert_eq!(
+            check.expiration_date_chrono(),
+            Some(Utc.timestamp_opt(1_608_542_592, 0).unwrap())
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn identity_facts() {
+        use crate::builder_ext::AuthorizerExt;
+        use crate::{Authorizer, KeyPair};
+
+        let claims = serde_json::json!({
+            "email": "alice@example.com",
+            "email_verified": true,
+            "age": 42,
+            "groups": ["admin"],
+            "address": { "country": "FR" },
+            "nickname": null,
+            "score": 0.5,
+        });
+
+        let mut builder = Biscuit::builder();
+        builder.add_identity_facts("alice", &claims).unwrap();
+        assert_eq!(
+            builder.dump_code(),
+            r#"claim("alice", "age", 42);
+claim("alice", "email", "alice@example.com");
+claim("alice", "email_verified", true);
+"#
+        );
+
+        let mut strict = BlockBuilder::new();
+        assert_eq!(
+            strict.add_identity_facts_with("alice", &claims, NestedClaims::Error),
+            Err(error::Token::ConversionError(
+                r#"claim "address" cannot be converted to a term: {"country":"FR"}"#.to_string()
+            ))
+        );
+        assert!(strict.facts.is_empty());
+        assert!(strict
+            .add_identity_facts("alice", &serde_json::json!(["alice"]))
+            .is_err());
+
+        let token = builder.build(&KeyPair::new()).unwrap();
+        let mut authorizer = Authorizer::new();
+        authorizer.add_token(&token).unwrap();
+        authorizer
+            .add_code(r#"check if claim($sub, "email_verified", true), claim($sub, "age", $age), $age >= 18;"#)
+            .unwrap();
+        authorizer.add_allow_all();
+        assert_eq!(authorizer.authorize(), Ok(0));
+    }
+
+    #[test]
+    fn int_not_in_constraint() {
+        use crate::builder_ext::AuthorizerExt;
+        use crate::{Authorizer, Biscuit, KeyPair};
+
+        assert_eq!(int_neq("shard", 3).to_string(), "$shard != 3");
+        assert_eq!(
+            int_not_in("shard", &[3, 1, 3]).to_string(),
+            "$shard != 1 && $shard != 3"
+        );
+        assert_eq!(
+            int_not_in("shard", &[]).to_string(),
+            "$shard >= -9223372036854775808"
+        );
+
+        let check = Check {
+            queries: vec![constrained_rule(
+                "query",
+                &[var("shard")],
+                &[pred("shard", &[var("shard")])],
+                &[int_not_in("shard", &[1, 3])],
+            )],
+            kind: CheckKind::One,
+            name: None,
+        };
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_check(check).unwrap();
+        let token = builder.build(&root).unwrap();
+        let token = Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(
+            token.print_block_source(0).unwrap(),
+            "check if shard($shard), $shard != 1 && $shard != 3;\n"
+        );
+
+        let authorize = |shard: Term| {
+            let mut authorizer = Authorizer::new();
+            authorizer.add_token(&token).unwrap();
+            authorizer.add_fact(fact("shard", &[shard])).unwrap();
+            authorizer.add_allow_all();
+            authorizer.authorize()
+        };
+        assert!(authorize(int(2)).is_ok());
+        assert!(authorize(int(3)).is_err());
+        // a non integer value fails the binding instead of being "not in" the list
+        assert!(authorize(string("2")).is_err());
+    }
+
+    #[test]
+    fn date_boundaries() {
+        use crate::builder_ext::AuthorizerExt;
+        use crate::Authorizer;
+
+        let matches = |expression: Expression, time: SystemTime| {
+            let mut authorizer = Authorizer::new();
+            authorizer.set_time_at(time);
+            authorizer
+                .add_check(Check {
+                    queries: vec![constrained_rule(
+                        "query",
+                        &[var("time")],
+                        &[pred("time", &[var("time")])],
+                        &[expression],
+                    )],
+                    kind: CheckKind::One,
+                    name: None,
+                })
+                .unwrap();
+            authorizer.add_allow_all();
+            authorizer.authorize().is_ok()
+        };
+
+        // 2022-03-15T12:00:00Z
+        let boundary = UNIX_EPOCH + Duration::from_secs(1_647_345_600);
+        let second = Duration::from_secs(1);
+        let just_before = boundary - second;
+        let within = boundary + Duration::from_millis(999);
+        let just_after = boundary + second;
+
+        assert!(matches(before("time", &boundary), just_before));
+        assert!(!matches(before("time", &boundary), boundary));
+
+        assert!(matches(before_inclusive("time", &boundary), boundary));
+        assert!(matches(before_inclusive("time", &boundary), within));
+        assert!(!matches(before_inclusive("time", &boundary), just_after));
+
+        assert!(!matches(after("time", &boundary), boundary));
+        assert!(matches(after("time", &boundary), just_after));
+
+        assert!(!matches(after_inclusive("time", &boundary), just_before));
+        assert!(matches(after_inclusive("time", &boundary), boundary));
+
+        assert!(!matches(date_equal("time", &boundary), just_before));
+        assert!(matches(date_equal("time", &boundary), boundary));
+        assert!(matches(date_equal("time", &boundary), within));
+        assert!(!matches(date_equal("time", &boundary), just_after));
+
+        let day = on_date("time", &boundary);
+        assert_eq!(
+            day.to_string(),
+            "$time >= 2022-03-15T00:00:00Z && $time < 2022-03-16T00:00:00Z"
+        );
+        let midnight = UNIX_EPOCH + Duration::from_secs(1_647_302_400);
+        let next_midnight = midnight + Duration::from_secs(24 * 60 * 60);
+        assert!(!matches(day.clone(), midnight - second));
+        assert!(matches(day.clone(), midnight));
+        assert!(matches(day.clone(), next_midnight - second));
+        assert!(!matches(day, next_midnight));
+
+        // expiration checks include the expiration second
+        let mut builder = BlockBuilder::new();
+        builder.check_expiration_date(boundary);
+        let check = builder.checks.remove(0);
+        for (time, valid) in [(boundary, true), (within, true), (just_after, false)] {
+            let mut authorizer = Authorizer::new();
+            authorizer.set_time_at(time);
+            authorizer.add_check(check.clone()).unwrap();
+            authorizer.add_allow_all();
+            assert_eq!(authorizer.authorize().is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let check: Check = r#"check if resource($r), operation($op), allowed($r, $op)"#
+            .try_into()
+            .unwrap();
+        let authority = [fact("right", &[string("/a/file1.txt"), string("read")])];
+        let ambient = |operation: &str| {
+            [
+                fact("resource", &[string("/a/file1.txt")]),
+                fact("operation", &[string(operation)]),
+            ]
+        };
+
+        let mut block = BlockBuilder::new();
+        block
+            .add_rule("allowed($r, $op) <- right($r, $op)")
+            .unwrap();
+        // the block's other checks are not evaluated
+        block.add_check("check if false").unwrap();
+
+        assert!(block
+            .test_check(&check, &authority, &ambient("read"))
+            .unwrap());
+        assert!(!block
+            .test_check(&check, &authority, &ambient("write"))
+            .unwrap());
+        // without the block's rule
+        assert!(!BlockBuilder::new()
+            .test_check(&check, &authority, &ambient("read"))
+            .unwrap());
+
+        let check: Check = "check if operation($op), $op > 1".try_into().unwrap();
+        assert_eq!(
+            block.test_check(&check, &authority, &ambient("read")),
+            Err(error::Token::Execution(error::Expression::InvalidType))
+        );
+    }
+
+    #[test]
+    fn resource_and_operation_types() {
+        use crate::builder_ext::{AuthorizerExt, BuilderExt};
+        use crate::error::SchemaMismatch;
+        use crate::{Biscuit, KeyPair, Schema};
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.check_resource("file1");
+        // "read" is in the default symbol table
+        builder.check_operation("read");
+        let token = builder.build(&root).unwrap();
+        let token = Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap();
+
+        let authorize = |code: &str| {
+            let mut authorizer = token.authorizer().unwrap();
+            authorizer.add_code(code).unwrap();
+            authorizer.add_allow_all();
+            authorizer.authorize()
+        };
+        assert!(authorize("resource(\"file1\"); operation(\"read\");").is_ok());
+        // there is no string and symbol mismatch, but other types do not match
+        assert!(authorize("resource(1); operation(\"read\");").is_err());
+
+        let mut ambient = BlockBuilder::new();
+        ambient.add_fact("resource(1)").unwrap();
+        ambient.add_fact("operation(\"read\")").unwrap();
+        let violations = Schema::well_known().check_block(&ambient);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(
+            violations[0].mismatch,
+            SchemaMismatch::TermKind {
+                position: 0,
+                expected: "string".to_string(),
+                actual: "integer".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn inspection() {
+        use crate::{Biscuit, KeyPair};
+
+        let mut builder = Biscuit::builder();
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), int(1234));
+        params.insert("tenant".to_string(), string("acme"));
+        builder
+            .add_code_with_params(
+                r#"user({id});
+                tenant({tenant});
+                member($user) <- user($user), tenant({tenant});
+                check if time($time), $time < 2030-01-01T00:00:00Z;"#,
+                params,
+                HashMap::new(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            builder.facts().collect::<Vec<_>>(),
+            vec!["user(1234)", "tenant(\"acme\")"]
+        );
+        assert_eq!(builder.rules().len(), 1);
+        assert_eq!(builder.checks().len(), 1);
+
+        let printed = builder
+            .facts()
+            .chain(builder.rules())
+            .chain(builder.checks())
+            .map(|line| format!("{};\n", line))
+            .collect::<String>();
+        let token = builder.build(&KeyPair::new()).unwrap();
+        assert_eq!(printed, token.print_block_source(0).unwrap());
+    }
+
+    #[test]
+    fn variable_allocator() {
+        let mut v = vars();
+        assert_eq!(v.next("user_1"), var("user_1"));
+        assert_eq!(v.next("user"), var("user"));
+        // "user_1" is already used
+        assert_eq!(v.next("user"), var("user_2"));
+        assert_eq!(v.names(), &["user_1", "user", "user_2"]);
+
+        let rule = rule_with("allowed", |v| {
+            let resource = v.next("resource");
+            let other = v.next("resource");
+            (
+                vec![resource.clone()],
+                vec![pred("resource", &[&resource]), pred("owner", &[&other])],
+                vec![Expression {
+                    ops: vec![
+                        Op::Value(resource),
+                        Op::Value(other),
+                        Op::Binary(Binary::NotEqual),
+                    ],
+                }],
+            )
+        });
+        assert_eq!(
+            rule.to_string(),
+            "allowed($resource) <- resource($resource), owner($resource_1), $resource != $resource_1"
+        );
+        let mut block = BlockBuilder::new();
+        block.add_rule(rule).unwrap();
+    }
+
+    #[test]
+    fn named_checks() {
+        use crate::builder_ext::BuilderExt;
+        use crate::error::{FailedAuthorizerCheck, FailedCheck, Logic};
+        use crate::{Biscuit, KeyPair};
+        use std::time::{Duration, SystemTime};
+
+        let root = KeyPair::new();
+        let token = Biscuit::builder().build(&root).unwrap();
+        let mut block = BlockBuilder::new();
+        block
+            .add_check_named("files", "check if resource(\"/storage/file1\")")
+            .unwrap();
+        // names do not have to be unique
+        block
+            .add_check_named("files", "check if operation(\"read\")")
+            .unwrap();
+        block.add_check("check if true").unwrap();
+        block.check_expiration_date(SystemTime::now() - Duration::from_secs(60));
+        // the names are not serialized in the token
+        let token = token.append(block.clone()).unwrap();
+        assert!(!token.print_block_source(1).unwrap().contains("files"));
+
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer.merge_block(block);
+        authorizer.add_fact("resource(\"/storage/file1\")").unwrap();
+        authorizer.add_fact("operation(\"write\")").unwrap();
+        authorizer.set_time();
+        authorizer.add_allow_all();
+        let checks = match authorizer.authorize() {
+            Err(error::Token::FailedLogic(Logic::Unauthorized { checks, .. })) => checks,
+            res => panic!("unexpected result: {:?}", res),
+        };
+        assert_eq!(
+            checks[0],
+            FailedCheck::Authorizer(FailedAuthorizerCheck {
+                check_id: 1,
+                rule: "check if operation(\"read\")".to_string(),
+                name: Some("files".to_string()),
+                reason: None,
+            })
+        );
+        assert_eq!(
+            checks[0].to_string(),
+            "authorizer check 1 (files) failed: check if operation(\"read\")"
+        );
+        match &checks[1] {
+            FailedCheck::Authorizer(check) => {
+                assert_eq!(check.name.as_deref(), Some("expiration"))
+            }
+            check => panic!("unexpected check: {:?}", check),
+        }
+
+        let names = authorizer
+            .report()
+            .checks
+            .into_iter()
+            .map(|check| (check.block_id, check.name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                (None, Some("files".to_string())),
+                (None, Some("files".to_string())),
+                (None, None),
+                (None, Some("expiration".to_string())),
+                (Some(1), None),
+                (Some(1), None),
+                (Some(1), None),
+                (Some(1), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_rule_body() {
+        let no_predicates: &[Predicate] = &[];
+        let mut block = BlockBuilder::new();
+
+        assert_eq!(
+            block.add_check(check(no_predicates, CheckKind::One)),
+            Err(error::Token::Format(error::Format::EmptyRuleBody(
+                "query() <- ".to_string()
+            )))
+        );
+        assert_eq!(
+            block.add_rule(rule("admin", &[string("alice")], no_predicates)),
+            Err(error::Token::Format(error::Format::EmptyRuleBody(
+                "admin(\"alice\") <- ".to_string()
+            )))
+        );
+        assert!(block.checks.is_empty());
+        assert!(block.rules.is_empty());
+
+        // an expression is enough
+        block.add_check("check if true").unwrap();
+        block.add_rule("admin(\"alice\") <- 1 < 2").unwrap();
+    }
+
+    #[test]
+    fn standalone() {
+        let mut block = BlockBuilder::new_standalone();
+        block.add_fact("user(\"alice\")").unwrap();
+        block.add_rule("allowed($u) <- user($u)").unwrap();
+        block.add_check("check if allowed(\"alice\")").unwrap();
+
+        let built = block.clone().build(SymbolTable::default());
+        assert_eq!(built.facts.len(), 1);
+        assert_eq!(built.rules.len(), 1);
+        assert_eq!(built.checks.len(), 1);
+        let mut symbols = SymbolTable::default();
+        symbols.extend(&built.symbols).unwrap();
+        assert_eq!(
+            BlockBuilder::convert_from(&built, &symbols)
+                .unwrap()
+                .to_string(),
+            block.to_string()
+        );
+
+        let check: Check = "check if allowed(\"alice\")".try_into().unwrap();
+        assert!(block.test_check(&check, &[], &[]).unwrap());
+    }
+
+    #[test]
+    fn template() {
+        use biscuit_parser::error::LanguageError;
+
+        let template = BiscuitTemplate::new(
+            r#"user({user_id});
+            tenant({tenant});
+            check if operation($op), $op == {extra};"#,
+        )
+        .unwrap();
+        assert_eq!(
+            template.parameters().collect::<Vec<_>>(),
+            vec!["extra", "tenant", "user_id"]
+        );
+
+        let root = KeyPair::new();
+        let mut rng: rand::rngs::StdRng = rand::SeedableRng::seed_from_u64(0);
+        let token = template
+            .instantiate(
+                &[
+                    ("user_id", string("alice")),
+                    ("tenant", int(1)),
+                    ("extra", string("list")),
+                ],
+                &mut rng,
+                &root,
+            )
+            .unwrap();
+        assert_eq!(
+            token.print_block_source(0).unwrap(),
+            "user(\"alice\");\ntenant(1);\ncheck if operation($op), $op == \"list\";\n"
+        );
+
+        assert_eq!(
+            template
+                .instantiate(
+                    &[("user_id", string("alice")), ("group", string("admin"))],
+                    &mut rng,
+                    &root,
+                )
+                .unwrap_err(),
+            error::Token::Language(LanguageError::Parameters {
+                missing_parameters: vec!["extra".to_string(), "tenant".to_string()],
+                unused_parameters: vec!["group".to_string()],
+            })
+        );
+    }
+
+    #[cfg(feature = "serde-error")]
+    #[test]
+    fn template_serde() {
+        let template = BiscuitTemplate::new("user({user_id});").unwrap();
+        let serialized = serde_json::to_string(&template).unwrap();
+        assert_eq!(serialized, r#""user({user_id});""#);
+        let deserialized: BiscuitTemplate = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, template);
+
+        assert!(serde_json::from_str::<BiscuitTemplate>(r#""user(""#).is_err());
+    }
+
+    #[test]
+    fn ambient_facts() {
+        assert_eq!(
+            resource_fact("/a/file1.txt"),
+            fact("resource", &[string("/a/file1.txt")])
+        );
+        assert_eq!(operation_fact("read"), fact("operation", &[string("read")]));
+        assert_eq!(
+            right_fact("/a/file1.txt", "read"),
+            fact("right", &[string("/a/file1.txt"), string("read")])
+        );
+        // truncated to the second
+        let time = UNIX_EPOCH + Duration::from_millis(1_647_345_600_500);
+        assert_eq!(
+            time_fact(time),
+            fact(
+                "time",
+                &[date(&(UNIX_EPOCH + Duration::from_secs(1_647_345_600)))]
+            )
+        );
+        assert_eq!(time_fact(time).to_string(), "time(2022-03-15T12:00:00Z)");
+
+        // the facts match the predicates of `check_right`
+        let mut block = BlockBuilder::new();
+        block.check_right("read");
+        let query = &block.checks[0].queries[0];
+        for fact in [
+            resource_fact("/a/file1.txt"),
+            operation_fact("read"),
+            right_fact("/a/file1.txt", "read"),
+        ] {
+            let predicate = query
+                .body
+                .iter()
+                .find(|p| p.name == fact.predicate.name)
+                .unwrap();
+            assert_eq!(predicate.terms.len(), fact.predicate.terms.len());
+        }
+
+        let mut block = BlockBuilder::new();
+        block.add_resource("/a/file1.txt");
+        block.add_operation("read");
+        assert_eq!(
+            block.facts,
+            vec![resource_fact("/a/file1.txt"), operation_fact("read")]
+        );
+    }
+
+    #[test]
+    fn client_ip() {
+        use crate::{Authorizer, Biscuit, KeyPair};
+
+        assert_eq!(ip("10.1.2.3".parse().unwrap()), string("0a010203"));
+        let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert_eq!(cidr.to_string(), "10.0.0.0/8");
+        assert_eq!(Term::from(cidr), string("^0a[0-9a-f]{6}$"));
+        assert_eq!(
+            "172.16.0.0/12".parse::<Cidr>().unwrap().pattern(),
+            "^ac1[0-9a-f]{5}$"
+        );
+        assert_eq!(
+            "172.16.0.0/13".parse::<Cidr>().unwrap().pattern(),
+            "^ac1[01234567][0-9a-f]{4}$"
+        );
+        assert_eq!(
+            "10.1.2.3/32".parse::<Cidr>().unwrap().pattern(),
+            "^0a010203$"
+        );
+        let cidr: Cidr = "2001:db8::/32".parse().unwrap();
+        assert_eq!(cidr.prefix_len(), 32);
+        assert!(cidr.address().is_ipv6());
+        // the address length tells IPv4 and IPv6 blocks apart
+        assert_eq!(
+            "::/8".parse::<Cidr>().unwrap().pattern(),
+            "^00[0-9a-f]{30}$"
+        );
+        assert_eq!("::/0".parse::<Cidr>().unwrap().pattern(), "^[0-9a-f]{32}$");
+
+        for invalid in [
+            "10.0.0.0/33",
+            "::/129",
+            "10.0.0.0",
+            "10.0.0/8",
+            "10.0.0.0/-1",
+        ] {
+            assert!(
+                matches!(invalid.parse::<Cidr>(), Err(error::Token::InvalidCidr(_))),
+                "{}",
+                invalid
+            );
+        }
+        assert!(Cidr::new("10.0.0.0".parse().unwrap(), 32).is_ok());
+        assert!(Cidr::new("::".parse().unwrap(), 128).is_ok());
+        assert!(client_ip_in("10.0.0.0/40").is_err());
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder
+            .add_check(client_ip_in("10.0.0.0/8").unwrap())
+            .unwrap();
+        builder.add_check(client_ip_in("::/0").unwrap()).unwrap();
+        let token = builder.build(&root).unwrap();
+        let token = Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(
+            token.print_block_source(0).unwrap(),
+            "check if client_ip($ip), $ip.matches(\"^0a[0-9a-f]{6}$\");\n\
+             check if client_ip($ip), $ip.matches(\"^[0-9a-f]{32}$\");\n"
+        );
+
+        // regular expressions are compiled at each evaluation, the result
+        // should not depend on the load of the machine
+        let limits = crate::AuthorizerLimits {
+            max_time: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let authorize = |address: &str, check: &str| {
+            let mut builder = BlockBuilder::new();
+            builder.add_check(client_ip_in(check).unwrap()).unwrap();
+            let mut authorizer = Authorizer::new();
+            authorizer.set_limits(limits.clone());
+            authorizer.set_client_ip(address.parse().unwrap());
+            authorizer.add_policy("allow if true").unwrap();
+            let check = builder.checks.remove(0);
+            authorizer.add_check(check).unwrap();
+            authorizer.authorize().is_ok()
+        };
+        for (address, cidr, expected) in [
+            ("10.1.2.3", "10.0.0.0/8", true),
+            ("11.1.2.3", "10.0.0.0/8", false),
+            // prefixes that are not a multiple of 8
+            ("172.31.0.1", "172.16.0.0/12", true),
+            ("172.32.0.1", "172.16.0.0/12", false),
+            ("172.23.0.1", "172.16.0.0/13", true),
+            ("172.24.0.1", "172.16.0.0/13", false),
+            ("1.2.3.4", "0.0.0.0/0", true),
+            ("1.2.3.4", "1.2.3.4/32", true),
+            ("1.2.3.5", "1.2.3.4/32", false),
+            ("2001:db8::1", "2001:db8::/32", true),
+            ("2001:db9::1", "2001:db8::/32", false),
+            ("2000::", "2000::/3", true),
+            ("4000::", "2000::/3", false),
+            // IPv4 addresses are not in IPv6 blocks, even IPv4 mapped ones
+            ("10.1.2.3", "::ffff:10.0.0.0/104", false),
+            ("::ffff:10.1.2.3", "10.0.0.0/8", false),
+            ("10.1.2.3", "::/0", false),
+        ] {
+            assert_eq!(
+                authorize(address, cidr),
+                expected,
+                "{} in {}",
+                address,
+                cidr
+            );
+        }
+    }
+
+    #[test]
+    fn integer_limits() {
+        use crate::builder_ext::{AuthorizerExt, BuilderExt};
+        use crate::{Authorizer, Biscuit, KeyPair};
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.check_integer_at_most("size", 10);
+        builder.check_integer_at_least("size", 2);
+        let token = builder.build(&root).unwrap();
+        let mut block = BlockBuilder::new();
+        block.check_integer_below("count", 5);
+        block.check_integer_above("count", 0);
+        let token = token.append(block).unwrap();
+        let token = Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(
+            token.print_block_source(0).unwrap(),
+            "check if size($value), $value <= 10;\ncheck if size($value), $value >= 2;\n"
+        );
+        assert_eq!(
+            token.print_block_source(1).unwrap(),
+            "check if count($value), $value < 5;\ncheck if count($value), $value > 0;\n"
+        );
+
+        let authorize = |size: Option<i64>, count: Option<i64>| {
+            let mut authorizer = token.authorizer().unwrap();
+            if let Some(size) = size {
+                authorizer.add_integer("size", size).unwrap();
+            }
+            if let Some(count) = count {
+                authorizer.add_integer("count", count).unwrap();
+            }
+            authorizer.add_allow_all();
+            authorizer.authorize().is_ok()
+        };
+        assert!(authorize(Some(5), Some(3)));
+        // the limits of the inclusive variants are accepted
+        assert!(authorize(Some(10), Some(3)));
+        assert!(authorize(Some(2), Some(3)));
+        assert!(!authorize(Some(11), Some(3)));
+        assert!(!authorize(Some(1), Some(3)));
+        // and those of the exclusive ones are rejected
+        assert!(!authorize(Some(5), Some(5)));
+        assert!(!authorize(Some(5), Some(0)));
+        assert!(authorize(Some(5), Some(4)));
+        assert!(authorize(Some(5), Some(1)));
+        // a missing fact fails the check
+        assert!(!authorize(None, Some(3)));
+        assert!(!authorize(Some(5), None));
+
+        let mut authorizer = Authorizer::new();
+        authorizer.check_integer_below("size", 10);
+        authorizer.add_integer("size", 10).unwrap();
+        authorizer.add_allow_all();
+        assert!(authorizer.authorize().is_err());
+        assert!(Authorizer::new().add_integer("in valid", 1).is_err());
+    }
+
+    #[test]
+    fn merge() {
+        use crate::{Biscuit, KeyPair, Schema, TermKind};
+
+        let mut storage = BlockBuilder::new();
+        storage.add_fact("tenant(\"acme\")").unwrap();
+        storage
+            .add_check("check if resource($file), $file.starts_with(\"/storage/\")")
+            .unwrap();
+        let mut time = BlockBuilder::new();
+        time.add_fact("tenant(\"acme\")").unwrap();
+        // "eu-west" is only in this builder
+        time.add_fact("region(\"eu-west\")").unwrap();
+        time.add_check("check if time($time), $time < 2030-01-01T00:00:00Z")
+            .unwrap();
+        time.add_check("check if resource($file), $file.starts_with(\"/storage/\")")
+            .unwrap();
+
+        storage.merge(time).unwrap();
+        assert_eq!(storage.facts.len(), 2);
+        assert_eq!(storage.checks.len(), 2);
+
+        let root = KeyPair::new();
+        let token = Biscuit::builder().build(&root).unwrap();
+        let token = token.append(storage).unwrap();
+        let token = Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(
+            token.print_block_source(1).unwrap(),
+            "tenant(\"acme\");\nregion(\"eu-west\");\n\
+             check if resource($file), $file.starts_with(\"/storage/\");\n\
+             check if time($time), $time < 2030-01-01T00:00:00Z;\n"
+        );
+
+        let mut schema = Schema::new();
+        schema.insert("region", &[TermKind::String]);
+        let mut block = BlockBuilder::new().with_schema(&schema).unwrap();
+        let mut other = BlockBuilder::new();
+        other.add_fact("tenant(\"acme\")").unwrap();
+        other.add_fact("region(1)").unwrap();
+        assert!(matches!(
+            block.merge(other),
+            Err(error::Token::SchemaViolations(_))
+        ));
+        assert!(block.facts.is_empty());
+    }
+
+    #[test]
+    fn schema() {
+        use crate::error::{SchemaMismatch, SchemaViolation};
+        use crate::{Authorizer, Biscuit, KeyPair, Schema, TermKind};
+
+        let mut schema = Schema::well_known();
+        schema.insert("user", &[TermKind::Integer]);
+
+        let mut block = BlockBuilder::new().with_schema(&schema).unwrap();
+        block.add_fact("user(1234)").unwrap();
+        block.add_fact("other(\"a\", 1)").unwrap();
+        // variables match any kind of term
+        block.add_rule("u($id) <- user($id)").unwrap();
+        block
+            .add_check("check if right($resource, \"read\")")
+            .unwrap();
+
+        assert_eq!(
+            block.add_fact("user(\"alice\")").unwrap_err(),
+            error::Token::SchemaViolations(vec![SchemaViolation {
+                block_id: None,
+                element: "user(\"alice\")".to_string(),
+                predicate: "user".to_string(),
+                mismatch: SchemaMismatch::TermKind {
+                    position: 0,
+                    expected: "integer".to_string(),
+                    actual: "string".to_string(),
+                },
+            }])
+        );
+        assert_eq!(
+            block.add_check("check if resource($r, $op)").unwrap_err(),
+            error::Token::SchemaViolations(vec![SchemaViolation {
+                block_id: None,
+                element: "check if resource($r, $op)".to_string(),
+                predicate: "resource".to_string(),
+                mismatch: SchemaMismatch::Arity {
+                    expected: 1,
+                    actual: 2,
+                },
+            }])
+        );
+        assert!(block.add_code("user(true);").is_err());
+        assert_eq!(block.facts.len(), 2);
+
+        let mut invalid = BlockBuilder::new();
+        invalid.add_fact("user(\"alice\")").unwrap();
+        assert!(invalid.clone().with_schema(&schema).is_err());
+        assert!(invalid.clone().with_schema(&Schema::well_known()).is_ok());
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_fact("user(1234)").unwrap();
+        let token = builder.build(&root).unwrap().append(invalid).unwrap();
+
+        let violations = schema.check_token(&token).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].block_id, Some(1));
+        assert_eq!(
+            violations[0].to_string(),
+            "block 1: user has a string instead of a integer at position 0 in `user(\"alice\")`"
+        );
+
+        let mut authorizer = Authorizer::new();
+        authorizer.set_schema(Some(schema));
+        assert!(matches!(
+            authorizer.add_token(&token),
+            Err(error::Token::SchemaViolations(v)) if v == violations
+        ));
+    }
+
+    #[test]
+    fn date_variables() {
+        use crate::builder_ext::AuthorizerExt;
+        use crate::{Authorizer, Biscuit, KeyPair};
+
+        assert_eq!(date_before("issued", "time").to_string(), "$issued < $time");
+        assert_eq!(
+            date_after_inclusive("issued", "time").to_string(),
+            "$issued >= $time"
+        );
+
+        // 2022-03-15T12:00:00Z
+        let issued = UNIX_EPOCH + Duration::from_secs(1_647_345_600);
+        let second = Duration::from_secs(1);
+
+        let token = |issued: Term, expression: Expression| {
+            let root = KeyPair::new();
+            let mut builder = Biscuit::builder();
+            builder.add_fact(fact("issued_at", &[issued])).unwrap();
+            builder
+                .add_check(Check {
+                    queries: vec![constrained_rule(
+                        "query",
+                        &[var("issued")],
+                        &[
+                            pred("issued_at", &[var("issued")]),
+                            pred("time", &[var("time")]),
+                        ],
+                        &[expression],
+                    )],
+                    kind: CheckKind::One,
+                    name: None,
+                })
+                .unwrap();
+            let token = builder.build(&root).unwrap();
+            Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap()
+        };
+        let authorize = |token: &Biscuit, time: SystemTime| {
+            let mut authorizer = Authorizer::new();
+            authorizer.add_token(token).unwrap();
+            authorizer.set_time_at(time);
+            authorizer.add_allow_all();
+            authorizer.authorize()
+        };
+
+        let strict = token(date(&issued), date_before("issued", "time"));
+        assert_eq!(
+            strict.print_block_source(0).unwrap(),
+            "issued_at(2022-03-15T12:00:00Z);
+check if issued_at($issued), time($time), $issued < $time;
+"
+        );
+        assert!(authorize(&strict, issued - second).is_err());
+        assert!(authorize(&strict, issued).is_err());
+        // same second
+        assert!(authorize(&strict, issued + Duration::from_millis(999)).is_err());
+        assert!(authorize(&strict, issued + second).is_ok());
+
+        let inclusive = token(date(&issued), date_before_inclusive("issued", "time"));
+        assert!(authorize(&inclusive, issued - second).is_err());
+        assert!(authorize(&inclusive, issued).is_ok());
+
+        let after = token(date(&issued), date_after("issued", "time"));
+        assert!(authorize(&after, issued).is_err());
+        assert!(authorize(&after, issued - second).is_ok());
+
+        let after = token(date(&issued), date_after_inclusive("issued", "time"));
+        assert!(authorize(&after, issued).is_ok());
+        assert!(authorize(&after, issued + second).is_err());
+
+        // a value that is not a date is an error, not a failed comparison
+        let mismatched = token(int(0), date_before("issued", "time"));
+        assert!(matches!(
+            authorize(&mismatched, issued),
+            Err(error::Token::Execution(error::Expression::InvalidType))
+        ));
+    }
+
+    #[test]
+    fn string_length() {
+        use crate::builder_ext::AuthorizerExt;
+        use crate::{Authorizer, Biscuit, KeyPair};
+
+        let check = Check {
+            queries: vec![constrained_rule(
+                "query",
+                &[var("key")],
+                &[pred("key", &[var("key")])],
+                &[
+                    str_length_less_than("key", 5),
+                    str_length_greater_than("key", 2),
+                ],
+            )],
+            kind: CheckKind::One,
+            name: None,
+        };
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_check(check).unwrap();
+        let token = builder.build(&root).unwrap();
+        let token = Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(
+            token.print_block_source(0).unwrap(),
+            "check if key($key), $key.length() < 5, $key.length() > 2;\n"
+        );
+
+        let authorize = |key: Term| {
+            let mut authorizer = Authorizer::new();
+            authorizer.add_token(&token).unwrap();
+            authorizer.add_fact(fact("key", &[key])).unwrap();
+            authorizer.add_allow_all();
+            authorizer.authorize().is_ok()
+        };
+        assert!(!authorize(string("ab")));
+        assert!(authorize(string("abc")));
+        assert!(authorize(string("abcd")));
+        assert!(!authorize(string("abcde")));
+        // lengths are in bytes: 2 characters, 4 bytes
+        assert!(authorize(string("éé")));
+        // 3 characters, 6 bytes
+        assert!(!authorize(string("ééé")));
+        // 1 character, 4 bytes
+        assert!(authorize(string("🍪")));
+        assert!(!authorize(int(1234)));
+    }
+
+    #[test]
+    fn combinators() {
+        use crate::builder_ext::AuthorizerExt;
+        use crate::{Authorizer, Biscuit, KeyPair};
+
+        assert!(any_of(&[]).is_err());
+        assert!(all_of(&[]).is_err());
+
+        let starts_with = |prefix: &str| Expression {
+            ops: vec![
+                Op::Value(var("path")),
+                Op::Value(string(prefix)),
+                Op::Binary(Binary::Prefix),
+            ],
+        };
+        let ends_with = |suffix: &str| Expression {
+            ops: vec![
+                Op::Value(var("path")),
+                Op::Value(string(suffix)),
+                Op::Binary(Binary::Suffix),
+            ],
+        };
+        let expression = any_of(&[
+            starts_with("/pub/"),
+            all_of(&[ends_with(".json"), str_length_less_than("path", 20)]).unwrap(),
+        ])
+        .unwrap();
+
+        let check = Check {
+            queries: vec![constrained_rule(
+                "query",
+                &[var("path")],
+                &[pred("resource", &[var("path")])],
+                &[expression],
+            )],
+            kind: CheckKind::One,
+            name: None,
+        };
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_check(check).unwrap();
+        let token = builder.build(&root).unwrap();
+        let token = Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(
+            token.print_block_source(0).unwrap(),
+            "check if resource($path), ($path.starts_with(\"/pub/\") || ($path.ends_with(\".json\") && $path.length() < 20));\n"
+        );
+
+        let authorize = |path: &str| {
+            let mut authorizer = Authorizer::new();
+            authorizer.add_token(&token).unwrap();
+            authorizer
+                .add_fact(fact("resource", &[string(path)]))
+                .unwrap();
+            authorizer.add_allow_all();
+            authorizer.authorize().is_ok()
+        };
+        assert!(authorize("/pub/file.txt"));
+        assert!(authorize("/a/b.json"));
+        assert!(!authorize("/a/b.txt"));
+        assert!(!authorize("/a/very/long/path.json"));
+    }
+
+    #[test]
+    fn block_symbols_are_referenced() {
+        use crate::datalog;
+        use crate::token::default_symbol_table;
+        use crate::{Biscuit, KeyPair};
+        use std::collections::HashSet;
+
+        fn term_symbols(term: &datalog::Term, out: &mut Vec<u64>) {
+            match term {
+                datalog::Term::Str(i) => out.push(*i),
+                datalog::Term::Variable(i) => out.push(*i as u64),
+                datalog::Term::Set(set) => set.iter().for_each(|t| term_symbols(t, out)),
+                _ => {}
+            }
+        }
+        fn predicate_symbols(predicate: &datalog::Predicate, out: &mut Vec<u64>) {
+            out.push(predicate.name);
+            predicate.terms.iter().for_each(|t| term_symbols(t, out));
+        }
+        fn rule_symbols(rule: &datalog::Rule, out: &mut Vec<u64>) {
+            predicate_symbols(&rule.head, out);
+            rule.body.iter().for_each(|p| predicate_symbols(p, out));
+            for expression in &rule.expressions {
+                for op in &expression.ops {
+                    if let datalog::Op::Value(t) = op {
+                        term_symbols(t, out);
+                    }
+                }
+            }
+        }
+
+        let mut builder = BlockBuilder::new();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        // duplicated facts do not add symbols
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        builder
+            .add_fact("groups(\"alice\", [\"admin\", \"dev\"])")
+            .unwrap();
+        builder
+            .add_rule("can_read($file) <- right($file, \"read\"), $file.starts_with(\"file\")")
+            .unwrap();
+        builder
+            .add_check("check if resource($r), operation(\"read\"), $r != \"secret\"")
+            .unwrap();
+        // terms that are built but never added are not interned
+        let _unused = string("unused");
+        let source = builder.to_string();
+
+        let block = builder.clone().build(default_symbol_table());
+        let mut symbols = default_symbol_table();
+        symbols.extend(&block.symbols).unwrap();
+
+        let mut indexes = Vec::new();
+        block
+            .facts
+            .iter()
+            .for_each(|f| predicate_symbols(&f.predicate, &mut indexes));
+        block
+            .rules
+            .iter()
+            .for_each(|r| rule_symbols(r, &mut indexes));
+        block
+            .checks
+            .iter()
+            .flat_map(|c| c.queries.iter())
+            .for_each(|q| rule_symbols(q, &mut indexes));
+        let referenced = indexes
+            .into_iter()
+            .map(|i| symbols.print_symbol(i).unwrap())
+            .collect::<HashSet<_>>();
+
+        let new_symbols = block.symbols.strings();
+        assert_eq!(
+            new_symbols.len(),
+            new_symbols.iter().collect::<HashSet<_>>().len()
+        );
+        for symbol in &new_symbols {
+            assert!(
+                referenced.contains(symbol),
+                "unreferenced symbol {}",
+                symbol
+            );
+        }
+        assert!(!new_symbols.contains(&"unused".to_string()));
+        assert!(!new_symbols.contains(&"read".to_string()));
+
+        // the facts resolve to the same strings after a round trip
+        let root = KeyPair::new();
+        let token = Biscuit::builder().build(&root).unwrap();
+        let token = token.append(builder).unwrap();
+        let token = Biscuit::from(token.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(token.print_block_source(1).unwrap(), source);
+    }
+
+    #[test]
+    fn case_insensitive_resource_checks() {
+        use crate::builder_ext::AuthorizerExt;
+        use crate::format::schema;
+        use crate::{Authorizer, Biscuit, KeyPair};
+        use prost::Message;
+
+        // regular expressions are compiled at each evaluation, the result
+        // should not depend on the load of the machine
+        let limits = crate::AuthorizerLimits {
+            max_time: Duration::from_secs(1),
+            ..Default::default()
+        };
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.check_resource_prefix_ci("/Bucket/");
+        builder.check_resource_suffix_ci(".TXT");
+        let token = builder.build(&root).unwrap();
+        let serialized = token.to_vec().unwrap();
+        let token = Biscuit::from(&serialized, root.public()).unwrap();
+        assert_eq!(
+            token.print_block_source(0).unwrap(),
+            "check if resource($resource), $resource.matches(\"(?i-u)^/Bucket/\");\n\
+             check if resource($resource), $resource.matches(\"(?i-u)\\.TXT$\");\n"
+        );
+
+        let authorize = |resource: &str| {
+            let mut authorizer = Authorizer::new();
+            authorizer.set_limits(limits.clone());
+            authorizer.add_token(&token).unwrap();
+            authorizer
+                .add_fact(fact("resource", &[string(resource)]))
+                .unwrap();
+            authorizer.add_allow_all();
+            authorizer.authorize().is_ok()
+        };
+        assert!(authorize("/bucket/file.txt"));
+        assert!(authorize("/BUCKET/File.Txt"));
+        assert!(!authorize("/other/file.txt"));
+        assert!(!authorize("/bucket/file.pdf"));
+
+        // only ASCII letters are folded, and the value is not a pattern
+        let authorize_ci = |name: &str, resource: &str| {
+            let mut authorizer = Authorizer::new();
+            authorizer.set_limits(limits.clone());
+            authorizer.check_resource_ci(name);
+            authorizer
+                .add_fact(fact("resource", &[string(resource)]))
+                .unwrap();
+            authorizer.add_allow_all();
+            authorizer.authorize().is_ok()
+        };
+        assert!(authorize_ci("/Bucket/File.txt", "/bucket/FILE.TXT"));
+        assert!(!authorize_ci("/Bucket/File.txt", "/bucket/FILE.TXT.bak"));
+        assert!(!authorize_ci("CAFÉ.*", "café.*"));
+        assert!(authorize_ci("CAFé.*", "café.*"));
+        assert!(!authorize_ci("CAFé.*", "café.txt"));
+
+        // the checks only use operators from the v3 format
+        let block = schema::Block::decode(token.container().authority.data.as_slice()).unwrap();
+        assert_eq!(block.version, Some(3));
+    }
+
+    #[test]
+    fn operation_hierarchy() {
+        use crate::{Authorizer, Biscuit, KeyPair};
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_right("file1", "admin");
+        builder
+            .add_operation_hierarchy(&[("admin", "write"), ("write", "read")])
+            .unwrap();
+        assert_eq!(
+            builder.rules().collect::<Vec<_>>(),
+            vec![
+                "right($resource, \"write\") <- right($resource, \"admin\")",
+                "right($resource, \"read\") <- right($resource, \"write\")",
+            ]
+        );
+        // adding the same pairs again does not duplicate the rules
+        builder
+            .add_operation_hierarchy(&[("admin", "write")])
+            .unwrap();
+        assert_eq!(builder.rules().len(), 2);
+
+        let token = builder.build(&root).unwrap();
+        let mut block = BlockBuilder::new();
+        block.check_right("read");
+        let token = token.append(block).unwrap();
+
+        let authorize = |resource: &str| {
+            let mut authorizer = Authorizer::new();
+            authorizer.add_token(&token).unwrap();
+            authorizer.add_fact(resource_fact(resource)).unwrap();
+            authorizer.add_fact(operation_fact("read")).unwrap();
+            authorizer.add_policy("allow if true").unwrap();
+            authorizer.authorize().is_ok()
+        };
+        assert!(authorize("file1"));
+        assert!(!authorize("file2"));
+
+        for cycle in [
+            vec![("admin", "admin")],
+            vec![("admin", "write"), ("write", "read"), ("read", "admin")],
+        ] {
+            let mut builder = Biscuit::builder();
+            let err = builder.add_operation_hierarchy(&cycle).unwrap_err();
+            assert!(matches!(err, error::Token::OperationCycle(_)), "{:?}", err);
+            assert_eq!(builder.rules().len(), 0);
+        }
+        assert_eq!(
+            Biscuit::builder()
+                .add_operation_hierarchy(&[("read", "list"), ("write", "read"), ("read", "write")])
+                .unwrap_err()
+                .to_string(),
+            "the operation hierarchy contains a cycle: read -> write -> read"
+        );
+    }
 }
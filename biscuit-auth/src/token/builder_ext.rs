@@ -1,13 +1,47 @@
 use std::time::SystemTime;
 
 pub trait BuilderExt {
+    /// adds the `resource(name)` fact, with a string
     fn add_resource(&mut self, name: &str);
+    /// adds `check if resource(name)`, with a string
+    ///
+    /// Datalog strings are also the symbols, so this matches the resources
+    /// added by any service as strings. It does not match resources given as
+    /// another type, like an integer id: [`Schema::well_known`](crate::Schema::well_known)
+    /// reports those facts and checks
     fn check_resource(&mut self, name: &str);
     fn check_resource_prefix(&mut self, prefix: &str);
     fn check_resource_suffix(&mut self, suffix: &str);
+    /// checks that the resource is equal to `name`, ignoring ASCII case
+    ///
+    /// only ASCII letters are folded: `"É"` and `"é"` are different. The check
+    /// uses the `matches` operator with an escaped `(?i-u)` regular expression
+    fn check_resource_ci(&mut self, name: &str);
+    /// case insensitive [`BuilderExt::check_resource_prefix`], ASCII letters only
+    fn check_resource_prefix_ci(&mut self, prefix: &str);
+    /// case insensitive [`BuilderExt::check_resource_suffix`], ASCII letters only
+    fn check_resource_suffix_ci(&mut self, suffix: &str);
+    /// adds the `operation(name)` fact, with a string
     fn add_operation(&mut self, name: &str);
+    /// adds `check if operation(name)`, with a string, see
+    /// [`BuilderExt::check_resource`] for the matching rules
     fn check_operation(&mut self, name: &str);
+    /// adds `check if time($time), $time <= date`
+    ///
+    /// the expiration date is included: dates are truncated to the second, so
+    /// the token is valid until the end of that second
     fn check_expiration_date(&mut self, date: SystemTime);
+    /// adds `check if name($value), $value <= max`: `max` is allowed
+    ///
+    /// the check fails if the authorizer does not provide the `name` fact,
+    /// which must be a valid predicate name
+    fn check_integer_at_most(&mut self, name: &str, max: i64);
+    /// adds `check if name($value), $value < max`: `max` is rejected
+    fn check_integer_below(&mut self, name: &str, max: i64);
+    /// adds `check if name($value), $value >= min`: `min` is allowed
+    fn check_integer_at_least(&mut self, name: &str, min: i64);
+    /// adds `check if name($value), $value > min`: `min` is rejected
+    fn check_integer_above(&mut self, name: &str, min: i64);
 }
 
 pub trait AuthorizerExt {
@@ -1,43 +1,75 @@
 //! main structures to interact with Biscuit tokens
-use std::collections::HashMap;
-use std::convert::TryInto;
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
 use std::fmt::Display;
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
 
 use self::public_keys::PublicKeys;
 
 use super::crypto::{KeyPair, PublicKey};
-use super::datalog::SymbolTable;
+use super::datalog::{SymbolTable, Term};
 use super::error;
 use super::format::SerializedBiscuit;
-use builder::{BiscuitBuilder, BlockBuilder};
+use builder::{BiscuitBuilder, BlockBuilder, Convert};
 use prost::Message;
 use rand_core::{CryptoRng, RngCore};
 
 use crate::crypto::{self};
 use crate::format::convert::proto_block_to_token_block;
 use crate::format::schema::{self, ThirdPartyBlockContents};
+use crate::time::Instant;
 use authorizer::Authorizer;
 
 pub mod authorizer;
 pub(crate) mod block;
 pub mod builder;
 pub mod builder_ext;
+pub(crate) mod predicate_schema;
 pub(crate) mod public_keys;
+pub(crate) mod sealed;
 pub(crate) mod third_party;
 pub mod unverified;
 
-pub use block::Block;
+pub use block::{Block, BlockRef};
+pub use sealed::SealedBiscuit;
 pub use third_party::*;
 
 /// minimum supported version of the serialization format
 pub const MIN_SCHEMA_VERSION: u32 = 3;
 /// maximum supported version of the serialization format
 pub const MAX_SCHEMA_VERSION: u32 = 4;
+/// maximum size of a serialized token read by [`Biscuit::from_reader`]
+pub const MAX_TOKEN_SIZE: usize = 1024 * 1024;
 
 /// some symbols are predefined and available in every implementation, to avoid
 /// transmitting them with every token
 pub fn default_symbol_table() -> SymbolTable {
-    SymbolTable::new()
+    SymbolTable::default_table()
+}
+
+// first `nonce(bytes)` fact of a block
+pub(crate) fn block_nonce(block: &Block, symbols: &SymbolTable) -> Option<Vec<u8>> {
+    block.facts.iter().find_map(|fact| {
+        let fact = builder::Fact::convert_from(fact, symbols).ok()?;
+        match fact.predicate.terms.as_slice() {
+            [builder::Term::Bytes(nonce)] if fact.predicate.name == "nonce" => Some(nonce.clone()),
+            _ => None,
+        }
+    })
+}
+
+// first `issued_at(date)` fact of a block
+pub(crate) fn block_issued_at(block: &Block, symbols: &SymbolTable) -> Option<SystemTime> {
+    block.facts.iter().find_map(|fact| {
+        let fact = builder::Fact::convert_from(fact, symbols).ok()?;
+        match fact.predicate.terms.as_slice() {
+            [builder::Term::Date(t)] if fact.predicate.name == "issued_at" => {
+                std::time::UNIX_EPOCH.checked_add(Duration::from_secs(*t))
+            }
+            _ => None,
+        }
+    })
 }
 
 /// This structure represents a valid Biscuit token
@@ -71,7 +103,7 @@ pub fn default_symbol_table() -> SymbolTable {
 ///   let token2 = token1.append(builder2).unwrap();
 /// }
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Biscuit {
     pub(crate) root_key_id: Option<u32>,
     pub(crate) authority: schema::Block,
@@ -79,6 +111,27 @@ pub struct Biscuit {
     pub(crate) symbols: SymbolTable,
     pub(crate) container: SerializedBiscuit,
     pub(crate) public_key_to_block_id: HashMap<usize, Vec<usize>>,
+    /// time spent decoding the token and verifying its signatures
+    pub(crate) verification_time: Duration,
+    /// key the token was built or verified with
+    pub(crate) root_public_key: Option<PublicKey>,
+}
+
+/// the container is shown without its proof and serialized form, which
+/// contain the next private key
+impl std::fmt::Debug for Biscuit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Biscuit")
+            .field("root_key_id", &self.root_key_id)
+            .field("authority", &self.authority)
+            .field("blocks", &self.blocks)
+            .field("symbols", &self.symbols)
+            .field("container", &self.container)
+            .field("public_key_to_block_id", &self.public_key_to_block_id)
+            .field("verification_time", &self.verification_time)
+            .field("root_public_key", &self.root_public_key)
+            .finish()
+    }
 }
 
 impl Biscuit {
@@ -98,6 +151,39 @@ impl Biscuit {
         Biscuit::from_with_symbols(slice.as_ref(), key_provider, default_symbol_table())
     }
 
+    /// deserializes a token WITHOUT validating its signatures
+    ///
+    /// this must only be used with bytes whose signatures were already
+    /// verified, as an example when their [`Biscuit::cache_key_for`] is found in a
+    /// cache of verified tokens. Authorizing a token that was not verified
+    /// accepts forged tokens. See [`Biscuit::check_signatures_only`]
+    pub fn assume_verified<T>(slice: T) -> Result<Self, error::Token>
+    where
+        T: AsRef<[u8]>,
+    {
+        let container =
+            SerializedBiscuit::deserialize(slice.as_ref()).map_err(error::Token::Format)?;
+        Biscuit::from_serialized_container(container, default_symbol_table())
+    }
+
+    /// deserializes a token that will be attenuated, and validates the
+    /// signature using the root public key
+    ///
+    /// it fails with [`error::Token::AppendOnSealed`] if the token was sealed,
+    /// see [`Biscuit::is_attenuable`]
+    pub fn from_slice_attenuable<T, KP>(slice: T, key_provider: KP) -> Result<Self, error::Token>
+    where
+        T: AsRef<[u8]>,
+        KP: RootKeyProvider,
+    {
+        let token = Biscuit::from(slice, key_provider)?;
+        if token.is_attenuable() {
+            Ok(token)
+        } else {
+            Err(error::Token::AppendOnSealed)
+        }
+    }
+
     /// deserializes a token and validates the signature using the root public key
     pub fn from_base64<T, KP>(slice: T, key_provider: KP) -> Result<Self, error::Token>
     where
@@ -107,7 +193,69 @@ impl Biscuit {
         Biscuit::from_base64_with_symbols(slice, key_provider, default_symbol_table())
     }
 
+    /// deserializes a token written by [`Biscuit::to_writer`] and validates
+    /// the signature using the root public key
+    ///
+    /// the token is preceded by its length as a 4 bytes big endian integer:
+    /// only that many bytes are read, so the reader can carry other data after
+    /// the token. Tokens larger than [`MAX_TOKEN_SIZE`] are rejected before
+    /// they are read
+    pub fn from_reader<R, KP>(mut reader: R, key_provider: KP) -> Result<Self, error::Token>
+    where
+        R: Read,
+        KP: RootKeyProvider,
+    {
+        let read_error = |e: std::io::Error| {
+            error::Format::DeserializationError(format!("deserialization error: {}", e))
+        };
+
+        let mut length = [0u8; 4];
+        reader.read_exact(&mut length).map_err(read_error)?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length > MAX_TOKEN_SIZE {
+            return Err(error::Format::DeserializationError(format!(
+                "deserialization error: the token is larger than {} bytes",
+                MAX_TOKEN_SIZE
+            ))
+            .into());
+        }
+
+        let mut data = vec![0; length];
+        reader.read_exact(&mut data).map_err(read_error)?;
+
+        Biscuit::from(&data, key_provider)
+    }
+
+    /// writes the serialized token, see [`Biscuit::container_bytes`], preceded
+    /// by its length as a 4 bytes big endian integer
+    ///
+    /// returns the number of bytes written, length included
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<usize, error::Token> {
+        let bytes = self.container_bytes();
+        if bytes.len() > MAX_TOKEN_SIZE {
+            return Err(error::Format::SerializationError(format!(
+                "serialization error: the token is larger than {} bytes",
+                MAX_TOKEN_SIZE
+            ))
+            .into());
+        }
+
+        let length = (bytes.len() as u32).to_be_bytes();
+        writer
+            .write_all(&length)
+            .and_then(|()| writer.write_all(bytes))
+            .map_err(|e| {
+                error::Format::SerializationError(format!("serialization error: {}", e))
+            })?;
+        Ok(length.len() + bytes.len())
+    }
+
     /// serializes the token
+    ///
+    /// unless the token was sealed, the serialized form can be attenuated by
+    /// another service, after parsing it with [`Biscuit::from_slice_attenuable`].
+    /// A token serialized right after [`BiscuitBuilder::build`] can be used as
+    /// a template for the authority block
     pub fn to_vec(&self) -> Result<Vec<u8>, error::Token> {
         self.container.to_vec().map_err(error::Token::Format)
     }
@@ -128,13 +276,25 @@ impl Biscuit {
     /// creates a sealed version of the token
     ///
     /// sealed tokens cannot be attenuated
-    pub fn seal(&self) -> Result<Biscuit, error::Token> {
-        let container = self.container.seal()?;
+    pub fn seal(mut self) -> Result<SealedBiscuit, error::Token> {
+        self.container = self.container.seal()?;
 
-        let mut token = self.clone();
-        token.container = container;
+        SealedBiscuit::try_from(self)
+    }
 
-        Ok(token)
+    /// returns true if the token was sealed
+    ///
+    /// sealed tokens cannot be attenuated
+    pub fn is_sealed(&self) -> bool {
+        self.container.proof.is_sealed()
+    }
+
+    /// returns true if blocks can be appended to the token
+    ///
+    /// this is the case unless the token was sealed. Sealed tokens can only be
+    /// authorized, see [`SealedBiscuit`]
+    pub fn is_attenuable(&self) -> bool {
+        !self.is_sealed()
     }
 
     /// creates a authorizer from this token
@@ -158,6 +318,42 @@ impl Biscuit {
         self.append_with_keypair(&keypair, block_builder)
     }
 
+    /// creates a new token with an additional block, filled by the provided closure
+    ///
+    /// the current token is left unchanged. Errors returned by the closure
+    /// (as an example from [`BlockBuilder::add_check`]) abort the attenuation
+    ///
+    /// ```rust
+    /// # use biscuit_auth::{KeyPair, Biscuit};
+    /// # let root = KeyPair::new();
+    /// # let token = Biscuit::builder().build(&root).unwrap();
+    /// let attenuated = token
+    ///     .attenuate(|block| block.add_check("check if operation(\"read\")"))
+    ///     .unwrap();
+    /// assert_eq!(attenuated.block_count(), 2);
+    /// ```
+    pub fn attenuate<F>(&self, f: F) -> Result<Self, error::Token>
+    where
+        F: FnOnce(&mut BlockBuilder) -> Result<(), error::Token>,
+    {
+        self.attenuate_with_rng(&mut rand::rngs::OsRng, f)
+    }
+
+    /// creates a new token with an additional block, using the provided CSPRNG
+    ///
+    /// see [`Biscuit::attenuate`]
+    pub fn attenuate_with_rng<R, F>(&self, rng: &mut R, f: F) -> Result<Self, error::Token>
+    where
+        R: RngCore + CryptoRng,
+        F: FnOnce(&mut BlockBuilder) -> Result<(), error::Token>,
+    {
+        let mut block_builder = BlockBuilder::new();
+        f(&mut block_builder)?;
+
+        let keypair = KeyPair::new_with_rng(rng);
+        self.append_with_keypair(&keypair, block_builder)
+    }
+
     /// returns the list of context elements of each block
     ///
     /// the context is a free form text field in which application specific data
@@ -186,6 +382,70 @@ impl Biscuit {
         res
     }
 
+    /// returns the nonce of the authority block, added with
+    /// [`BiscuitBuilder::add_nonce`]
+    pub fn nonce(&self) -> Option<Vec<u8>> {
+        block_nonce(&self.block(0).ok()?, &self.symbols)
+    }
+
+    /// returns the issue timestamp of the authority block, added with
+    /// [`BiscuitBuilder::add_issued_at`]
+    pub fn issued_at(&self) -> Option<SystemTime> {
+        block_issued_at(&self.block(0).ok()?, &self.symbols)
+    }
+
+    /// returns the integer revocation ids declared with
+    /// [`BlockBuilder::revocation_id`], in block order
+    pub fn revocation_integer_ids(&self) -> Result<Vec<i64>, error::Token> {
+        Ok(self
+            .revocation_terms()?
+            .into_iter()
+            .filter_map(|term| match term {
+                builder::Term::Integer(id) => Some(id),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// returns the UUID revocation ids declared with
+    /// [`BlockBuilder::revocation_uuid`], in block order
+    #[cfg(feature = "uuid")]
+    pub fn revocation_uuids(&self) -> Result<Vec<uuid::Uuid>, error::Token> {
+        Ok(self
+            .revocation_terms()?
+            .into_iter()
+            .filter_map(|term| match term {
+                builder::Term::Bytes(id) => uuid::Uuid::from_slice(&id).ok(),
+                _ => None,
+            })
+            .collect())
+    }
+
+    // terms of the `revocation_id` facts of every block
+    fn revocation_terms(&self) -> Result<Vec<builder::Term>, error::Token> {
+        let mut res = Vec::new();
+
+        for i in 0..self.block_count() {
+            let block = self.block(i)?;
+            let symbols = if block.external_key.is_some() {
+                &block.symbols
+            } else {
+                &self.symbols
+            };
+
+            for fact in block.facts.iter() {
+                let fact = builder::Fact::convert_from(fact, symbols)?;
+                if let [term] = fact.predicate.terms.as_slice() {
+                    if fact.predicate.name == "revocation_id" {
+                        res.push(term.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
     /// returns a list of external key for each block, in order
     ///
     /// Blocks carrying an external public key are _third-party blocks_
@@ -206,6 +466,89 @@ impl Biscuit {
         format!("{}", &self)
     }
 
+    /// pretty printer for one block
+    ///
+    /// the header line is `authority block:` for the first block, and
+    /// `block <index>:` for the others, followed by `(third party)` if the
+    /// block was signed with an external key, which is then printed. The
+    /// symbols are the ones the block introduced, with their index in the
+    /// token's symbol table, or in the block's own table for third party
+    /// blocks. Returns `None` if there is no block at this index
+    pub fn print_block(&self, index: usize) -> Option<String> {
+        if index >= self.block_count() {
+            return None;
+        }
+        let block = self.block(index).ok()?;
+        let symbols = if block.external_key.is_some() {
+            &block.symbols
+        } else {
+            &self.symbols
+        };
+
+        let mut res = if index == 0 {
+            "authority block".to_string()
+        } else {
+            format!("block {}", index)
+        };
+        if block.external_key.is_some() {
+            res.push_str(" (third party)");
+        }
+        res.push_str(":\n");
+
+        let block_symbols: Vec<_> = block
+            .symbols
+            .strings()
+            .into_iter()
+            .map(|s| match symbols.get(&s) {
+                Some(i) => format!("{}: {:?}", i, s),
+                None => format!("?: {:?}", s),
+            })
+            .collect();
+        res.push_str(&format!("    symbols: [{}]\n", block_symbols.join(", ")));
+        res.push_str(&format!("    version: {}\n", block.version));
+        if let Some(created_at) = block.created_at {
+            res.push_str(&format!(
+                "    created at: {}\n",
+                symbols.print_term(&Term::Date(created_at))
+            ));
+        }
+        res.push_str(&format!(
+            "    context: {:?}\n",
+            block.context.as_deref().unwrap_or("")
+        ));
+        if let Some(key) = &block.external_key {
+            res.push_str(&format!("    external key: {}\n", key.print()));
+        }
+        res.push_str(&format!(
+            "    public keys: {:?}\n",
+            block
+                .public_keys
+                .keys
+                .iter()
+                .map(|k| k.print())
+                .collect::<Vec<_>>()
+        ));
+        res.push_str(&format!("    scopes: {:?}\n", block.scopes));
+        res.push_str(&print_list(
+            "facts",
+            block.facts.iter().map(|f| symbols.print_fact(f)).collect(),
+        ));
+        res.push_str(&print_list(
+            "rules",
+            block.rules.iter().map(|r| symbols.print_rule(r)).collect(),
+        ));
+        res.push_str(&print_list(
+            "checks",
+            block
+                .checks
+                .iter()
+                .map(|c| symbols.print_check(c))
+                .collect(),
+        ));
+
+        Some(res)
+    }
+
     /// prints the content of a block as Datalog source code
     pub fn print_block_source(&self, index: usize) -> Result<String, error::Token> {
         self.block(index).map(|block| {
@@ -232,12 +575,19 @@ impl Biscuit {
             return Err(error::Token::Format(error::Format::SymbolTableOverlap));
         }
 
+        let symbol_table_digest = symbols.digest();
         symbols.extend(&authority.symbols)?;
 
         let blocks = vec![];
 
         let next_keypair = KeyPair::new_with_rng(rng);
-        let container = SerializedBiscuit::new(root_key_id, root, &next_keypair, &authority)?;
+        let container = SerializedBiscuit::new(
+            root_key_id,
+            symbol_table_digest,
+            root,
+            &next_keypair,
+            &authority,
+        )?;
 
         symbols.public_keys.extend(&authority.public_keys)?;
 
@@ -255,11 +605,16 @@ impl Biscuit {
             symbols,
             container,
             public_key_to_block_id: HashMap::new(),
+            verification_time: Duration::default(),
+            root_public_key: Some(root.public()),
         })
     }
 
     /// deserializes a token and validates the signature using the root public key, with a custom symbol table
-    fn from_with_symbols<KP>(
+    ///
+    /// the symbol table must contain the same custom symbols as the one used to build
+    /// the token, otherwise this fails with [`error::Format::SymbolTableMismatch`]
+    pub fn from_with_symbols<KP>(
         slice: &[u8],
         key_provider: KP,
         symbols: SymbolTable,
@@ -267,16 +622,23 @@ impl Biscuit {
     where
         KP: RootKeyProvider,
     {
-        let container =
-            SerializedBiscuit::from_slice(slice, key_provider).map_err(error::Token::Format)?;
-
-        Biscuit::from_serialized_container(container, symbols)
+        let start = Instant::now();
+        let container = SerializedBiscuit::deserialize(slice).map_err(error::Token::Format)?;
+        let root = key_provider.choose(container.root_key_id)?;
+        container.verify(&root)?;
+        let verification_time = start.elapsed();
+
+        let mut token = Biscuit::from_serialized_container(container, symbols)?;
+        token.verification_time = verification_time;
+        token.root_public_key = Some(root);
+        Ok(token)
     }
 
     fn from_serialized_container(
         container: SerializedBiscuit,
         mut symbols: SymbolTable,
     ) -> Result<Self, error::Token> {
+        container.check_symbol_table(&symbols)?;
         let (authority, blocks, public_key_to_block_id) = container.extract_blocks(&mut symbols)?;
 
         let root_key_id = container.root_key_id;
@@ -288,11 +650,13 @@ impl Biscuit {
             symbols,
             container,
             public_key_to_block_id,
+            verification_time: Duration::default(),
+            root_public_key: None,
         })
     }
 
     /// deserializes a token and validates the signature using the root public key, with a custom symbol table
-    fn from_base64_with_symbols<T, KP>(
+    pub fn from_base64_with_symbols<T, KP>(
         slice: T,
         key_provider: KP,
         symbols: SymbolTable,
@@ -319,6 +683,13 @@ impl Biscuit {
         keypair: &KeyPair,
         block_builder: BlockBuilder,
     ) -> Result<Self, error::Token> {
+        if block_builder.strict {
+            let unknown = self.unknown_predicates(&block_builder)?;
+            if !unknown.is_empty() {
+                return Err(error::Token::UnknownPredicates(unknown));
+            }
+        }
+
         let block = block_builder.build(self.symbols.clone());
 
         if !self.symbols.is_disjoint(&block.symbols) {
@@ -367,6 +738,8 @@ impl Biscuit {
             symbols,
             container,
             public_key_to_block_id,
+            verification_time: self.verification_time,
+            root_public_key: self.root_public_key,
         })
     }
 
@@ -383,6 +756,26 @@ impl Biscuit {
 
         self.append_third_party_with_keypair(external_key, response, next_keypair)
     }
+
+    /// adds a third-party block signed with `external`, for when the third
+    /// party's key is available locally
+    ///
+    /// this is the same as creating a [`ThirdPartyRequest`] with
+    /// [`Biscuit::third_party_request`], signing it with
+    /// [`ThirdPartyRequest::create_block`] and calling
+    /// [`Biscuit::append_third_party`]. The facts of the block are only
+    /// visible to rules and checks trusting `external.public()`
+    pub fn append_third_party_signed(
+        &self,
+        external: &KeyPair,
+        block_builder: BlockBuilder,
+    ) -> Result<Self, error::Token> {
+        let response = self
+            .third_party_request()?
+            .create_block(&external.private(), block_builder)?;
+        self.append_third_party(external.public(), response)
+    }
+
     pub fn append_third_party_with_keypair(
         &self,
         external_key: PublicKey,
@@ -451,7 +844,7 @@ impl Biscuit {
             self.container
                 .append_serialized(&next_keypair, payload, Some(external_signature))?;
 
-        let token_block = proto_block_to_token_block(&block, Some(external_key)).unwrap();
+        let token_block = proto_block_to_token_block(&block, Some(external_key))?;
         for key in &token_block.public_keys.keys {
             symbols.public_keys.insert_fallible(key)?;
         }
@@ -476,6 +869,8 @@ impl Biscuit {
             symbols,
             container,
             public_key_to_block_id,
+            verification_time: self.verification_time,
+            root_public_key: self.root_public_key,
         })
     }
 
@@ -493,6 +888,26 @@ impl Biscuit {
         Ok(block.symbols.clone())
     }
 
+    /// gets the creation time of a block
+    ///
+    /// it is `None` for blocks created by older versions, or without a time
+    /// (see [`BlockBuilder::set_created_at`]), and for times that cannot be
+    /// represented by [`SystemTime`]. It is covered by the block's signature
+    pub fn block_created_at(&self, index: usize) -> Result<Option<SystemTime>, error::Token> {
+        let block = if index == 0 {
+            &self.authority
+        } else {
+            match self.blocks.get(index - 1) {
+                None => return Err(error::Token::Format(error::Format::InvalidBlockId(index))),
+                Some(block) => block,
+            }
+        };
+
+        Ok(block
+            .created_at
+            .and_then(|secs| SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))))
+    }
+
     /// gets the list of public keys from a block
     pub fn block_public_keys(&self, index: usize) -> Result<PublicKeys, error::Token> {
         let block = if index == 0 {
@@ -529,11 +944,211 @@ impl Biscuit {
             .map(|signature| signature.public_key))
     }
 
+    /// lists the predicates used in the checks of a block that are not defined
+    /// in this token, in that block, and are not ambient predicates
+    ///
+    /// this can be used before [`Biscuit::append`] to detect typos in checks
+    pub fn unknown_predicates(
+        &self,
+        block_builder: &BlockBuilder,
+    ) -> Result<Vec<error::UnknownPredicate>, error::Token> {
+        Ok(block_builder.unknown_predicates(&self.defined_predicates()?))
+    }
+
+    /// names of the predicates generated by facts and rules in all the blocks
+    fn defined_predicates(&self) -> Result<HashSet<String>, error::Token> {
+        let mut defined = HashSet::new();
+
+        for i in 0..self.block_count() {
+            let block = self.block(i)?;
+            let symbols = if block.external_key.is_some() {
+                &block.symbols
+            } else {
+                &self.symbols
+            };
+
+            for fact in &block.facts {
+                defined.insert(symbols.print_symbol(fact.predicate.name)?);
+            }
+            for rule in &block.rules {
+                defined.insert(symbols.print_symbol(rule.head.name)?);
+            }
+        }
+
+        Ok(defined)
+    }
+
+    /// iterates over the blocks of the token, starting with the authority block
+    ///
+    /// each block is deserialized when the iterator reaches it
+    pub fn blocks(&self) -> impl Iterator<Item = Result<BlockRef<'_>, error::Token>> + '_ {
+        (0..self.block_count()).map(move |i| {
+            self.block(i)
+                .map(|block| BlockRef::new(i, block, &self.symbols))
+        })
+    }
+
+    /// returns the earliest expiration date set by the checks of this token
+    ///
+    /// only checks with the form generated by
+    /// [`BuilderExt::check_expiration_date`](builder_ext::BuilderExt::check_expiration_date)
+    /// are recognized (see [`builder::Check::expiration_date`])
+    pub fn expiration(&self) -> Option<SystemTime> {
+        self.blocks()
+            .filter_map(Result::ok)
+            .flat_map(|block| {
+                block
+                    .checks()
+                    .filter_map(|check| check.ok()?.expiration_date())
+                    .collect::<Vec<_>>()
+            })
+            .min()
+    }
+
+    /// returns the facts of the authority block with the given name
+    pub fn authority_facts_named(&self, name: &str) -> Result<Vec<builder::Fact>, error::Token> {
+        let block = self.block(0)?;
+        let mut facts = Vec::new();
+
+        for fact in &block.facts {
+            if self.symbols.get_symbol(fact.predicate.name) == Some(name) {
+                facts.push(builder::Fact::convert_from(fact, &self.symbols)?);
+            }
+        }
+
+        Ok(facts)
+    }
+
+    /// returns the rights defined in the authority block, as `right($resource, $operation)` facts
+    ///
+    /// `right` facts that do not have exactly two string terms are skipped, and
+    /// reported in [`Rights::warnings`]
+    pub fn rights(&self) -> Result<Rights, error::Token> {
+        let mut rights = Rights::default();
+
+        for fact in self.authority_facts_named("right")? {
+            let res = if fact.predicate.terms.len() == 2 {
+                <(String, String)>::try_from(fact.clone())
+            } else {
+                Err(error::Token::ConversionError(format!(
+                    "expected 2 terms, got {}",
+                    fact.predicate.terms.len()
+                )))
+            };
+
+            match res {
+                Ok(right) => rights.rights.push(right),
+                Err(e) => rights
+                    .warnings
+                    .push(format!("skipped fact {}: {}", fact, e)),
+            }
+        }
+
+        Ok(rights)
+    }
+
     /// returns the number of blocks (at least 1)
     pub fn block_count(&self) -> usize {
         1 + self.blocks.len()
     }
 
+    /// returns the serialized (and signed) content of a block
+    ///
+    /// the authority block has index 0. Those bytes are the ones covered
+    /// by the block's signature
+    pub fn block_bytes(&self, index: usize) -> Option<&[u8]> {
+        if index == 0 {
+            Some(&self.container.authority.data)
+        } else {
+            self.container
+                .blocks
+                .get(index - 1)
+                .map(|block| &block.data[..])
+        }
+    }
+
+    /// returns the serialized form of the whole token
+    ///
+    /// for a token that was deserialized, these are the bytes it was parsed from
+    pub fn container_bytes(&self) -> &[u8] {
+        self.container.bytes()
+    }
+
+    /// SHA-256 hash of the serialized token
+    ///
+    /// it can be used as a cache key for authorization results. It changes
+    /// when a block is appended or the token is sealed
+    pub fn digest(&self) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+
+        Sha256::digest(self.container_bytes()).into()
+    }
+
+    /// key under which a verified token can be cached
+    ///
+    /// it is the [`Biscuit::digest`] of the exact serialized bytes, signatures
+    /// included, so two tokens with the same key were parsed from the same
+    /// bytes. It is equal to [`Biscuit::cache_key_for`] of these bytes
+    ///
+    /// only tokens that passed [`Biscuit::from`] or
+    /// [`Biscuit::check_signatures_only`] must be inserted in the cache
+    pub fn cache_key(&self) -> [u8; 32] {
+        self.digest()
+    }
+
+    /// key under which the token serialized in `slice` can be cached, see
+    /// [`Biscuit::cache_key`]
+    ///
+    /// it is computed without parsing the token, so a gateway can look up the
+    /// cache first, verify the signatures of the tokens it does not know yet,
+    /// then only run the Datalog evaluation for each request with a new
+    /// authorizer:
+    ///
+    /// ```rust
+    /// use biscuit_auth::{Biscuit, KeyPair};
+    /// use std::collections::HashMap;
+    ///
+    /// let root = KeyPair::new();
+    /// let mut builder = Biscuit::builder();
+    /// builder.add_code(r#"right("file1", "read");"#).unwrap();
+    /// let data = builder.build(&root).unwrap().to_vec().unwrap();
+    ///
+    /// let mut cache: HashMap<[u8; 32], Biscuit> = HashMap::new();
+    /// for _ in 0..2 {
+    ///     // the token is only parsed and verified if it is not in the cache
+    ///     let key = Biscuit::cache_key_for(&data);
+    ///     if !cache.contains_key(&key) {
+    ///         cache.insert(key, Biscuit::from(&data, root.public()).unwrap());
+    ///     }
+    ///     let token = &cache[&key];
+    ///
+    ///     let mut authorizer = token.authorizer().unwrap();
+    ///     authorizer
+    ///         .add_code(r#"resource("file1"); allow if right($r, "read"), resource($r);"#)
+    ///         .unwrap();
+    ///     assert!(authorizer.authorize().is_ok());
+    /// }
+    /// ```
+    pub fn cache_key_for<T: AsRef<[u8]>>(slice: T) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+
+        Sha256::digest(slice.as_ref()).into()
+    }
+
+    /// verifies the signatures of the token with the root public key
+    ///
+    /// it does not run the Datalog evaluation. A token created by
+    /// [`Biscuit::assume_verified`] that passes this check is equivalent to one
+    /// returned by [`Biscuit::from`]
+    pub fn check_signatures_only<KP>(&self, key_provider: KP) -> Result<(), error::Token>
+    where
+        KP: RootKeyProvider,
+    {
+        let root = key_provider.choose(self.root_key_id)?;
+        self.container.verify(&root)?;
+        Ok(())
+    }
+
     pub(crate) fn block(&self, index: usize) -> Result<Block, error::Token> {
         let mut block = if index == 0 {
             proto_block_to_token_block(
@@ -563,82 +1178,68 @@ impl Biscuit {
         };
 
         // we have to add the entire list of public keys here because
-        // they are used to validate 3rd party tokens
-        block.symbols.public_keys = self.symbols.public_keys.clone();
+        // they are used to validate 3rd party tokens. First party blocks
+        // use the token's symbol table, so they do not need a copy
+        if block.external_key.is_some() {
+            block.symbols.public_keys = self.symbols.public_keys.clone();
+        }
         Ok(block)
     }
 }
 
+/// tokens are equal if their serialized forms are the same
+impl PartialEq for Biscuit {
+    fn eq(&self, other: &Self) -> bool {
+        self.container_bytes() == other.container_bytes()
+    }
+}
+
+impl Eq for Biscuit {}
+
 impl Display for Biscuit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let authority = self
-            .block(0)
-            .as_ref()
-            .map(|block| print_block(&self.symbols, block))
-            .unwrap_or_else(|_| String::new());
-        let blocks: Vec<_> = (1..self.block_count())
-            .map(|i| {
-                self.block(i)
-                    .as_ref()
-                    .map(|block| print_block(&self.symbols, block))
-                    .unwrap_or_else(|_| String::new())
-            })
-            .collect();
-
-        write!(f, "Biscuit {{\n    symbols: {:?}\n    public keys: {:?}\n    authority: {}\n    blocks: [\n        {}\n    ]\n}}",
-        self.symbols.strings(),
-        self.symbols.public_keys.keys.iter().map(|pk| hex::encode(pk.to_bytes())).collect::<Vec<_>>(),
-        authority,
-        blocks.join(",\n\t")
-    )
+        writeln!(f, "Biscuit {{")?;
+        writeln!(f, "    symbols: {:?}", self.symbols.strings())?;
+        writeln!(
+            f,
+            "    public keys: {:?}",
+            self.symbols
+                .public_keys
+                .keys
+                .iter()
+                .map(|pk| pk.print())
+                .collect::<Vec<_>>()
+        )?;
+        for i in 0..self.block_count() {
+            if let Some(block) = self.print_block(i) {
+                for line in block.lines() {
+                    writeln!(f, "    {}", line)?;
+                }
+            }
+        }
+        write!(f, "}}")
     }
 }
-fn print_block(symbols: &SymbolTable, block: &Block) -> String {
-    let facts: Vec<_> = block.facts.iter().map(|f| symbols.print_fact(f)).collect();
-    let rules: Vec<_> = block.rules.iter().map(|r| symbols.print_rule(r)).collect();
-    let checks: Vec<_> = block
-        .checks
-        .iter()
-        .map(|r| symbols.print_check(r))
-        .collect();
-
-    let facts = if facts.is_empty() {
-        String::new()
-    } else {
-        format!(
-            "\n                {}\n            ",
-            facts.join(",\n                ")
-        )
-    };
-    let rules = if rules.is_empty() {
-        String::new()
-    } else {
-        format!(
-            "\n                {}\n            ",
-            rules.join(",\n                ")
-        )
-    };
-    let checks = if checks.is_empty() {
-        String::new()
+
+fn print_list(name: &str, elements: Vec<String>) -> String {
+    if elements.is_empty() {
+        format!("    {}: []\n", name)
     } else {
         format!(
-            "\n                {}\n            ",
-            checks.join(",\n                ")
+            "    {}: [\n        {}\n    ]\n",
+            name,
+            elements.join(",\n        ")
         )
-    };
-
-    format!(
-        "Block {{\n            symbols: {:?}\n            version: {}\n            context: \"{}\"\n            external key: {}\n            public keys: {:?}\n            scopes: {:?}\n            facts: [{}]\n            rules: [{}]\n            checks: [{}]\n        }}",
-        block.symbols.strings(),
-        block.version,
-        block.context.as_deref().unwrap_or(""),
-        block.external_key.as_ref().map(|k| hex::encode(k.to_bytes())).unwrap_or_else(String::new),
-        block.public_keys.keys.iter().map(|k | hex::encode(k.to_bytes())).collect::<Vec<_>>(),
-        block.scopes,
-        facts,
-        rules,
-        checks,
-    )
+    }
+}
+
+/// rights extracted from the authority block with [`Biscuit::rights`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rights {
+    /// list of `(resource, operation)` pairs
+    pub rights: Vec<(String, String)>,
+    /// one message for each `right` fact that could not be interpreted
+    pub warnings: Vec<String>,
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -841,8 +1442,8 @@ mod tests {
               Err(Token::FailedLogic(Logic::Unauthorized {
                   policy: MatchedPolicy::Allow(0),
                   checks: vec![
-                FailedCheck::Block(FailedBlockCheck { block_id: 1, check_id: 0, rule: String::from("check if resource($resource), operation(\"read\"), right($resource, \"read\")") }),
-                FailedCheck::Block(FailedBlockCheck { block_id: 2, check_id: 0, rule: String::from("check if resource(\"file1\")") })
+                FailedCheck::Block(FailedBlockCheck { block_id: 1, check_id: 0, rule: String::from("check if resource($resource), operation(\"read\"), right($resource, \"read\")"), token_id: 0, reason: None }),
+                FailedCheck::Block(FailedBlockCheck { block_id: 2, check_id: 0, rule: String::from("check if resource(\"file1\")"), token_id: 0, reason: None })
               ]
               })));
         }
@@ -904,7 +1505,9 @@ mod tests {
                         check_id: 0,
                         rule: String::from(
                             "check if resource($resource), $resource.starts_with(\"/folder1/\")"
-                        )
+                        ),
+                        token_id: 0,
+                        reason: None,
                     }),]
                 }))
             );
@@ -920,8 +1523,8 @@ mod tests {
             assert_eq!(res,
               Err(Token::FailedLogic(Logic::NoMatchingPolicy {
                   checks: vec![
-                FailedCheck::Block(FailedBlockCheck { block_id: 1, check_id: 0, rule: String::from("check if resource($resource), $resource.starts_with(\"/folder1/\")") }),
-                FailedCheck::Block(FailedBlockCheck { block_id: 1, check_id: 1, rule: String::from("check if resource($resource_name), operation(\"read\"), right($resource_name, \"read\")") }),
+                FailedCheck::Block(FailedBlockCheck { block_id: 1, check_id: 0, rule: String::from("check if resource($resource), $resource.starts_with(\"/folder1/\")"), token_id: 0, reason: None }),
+                FailedCheck::Block(FailedBlockCheck { block_id: 1, check_id: 1, rule: String::from("check if resource($resource_name), operation(\"read\"), right($resource_name, \"read\")"), token_id: 0, reason: None }),
               ]})));
         }
     }
@@ -1021,10 +1624,31 @@ mod tests {
         let _serialized = biscuit2.to_vec().unwrap();
         //println!("biscuit2 serialized ({} bytes):\n{}", serialized.len(), serialized.to_hex(16));
 
-        let sealed = biscuit2.seal().unwrap().to_vec().unwrap();
+        let sealed = biscuit2.clone().seal().unwrap().to_vec().unwrap();
         //println!("biscuit2 sealed ({} bytes):\n{}", sealed.len(), sealed.to_hex(16));
 
         let biscuit3 = Biscuit::from(&sealed, &root.public()).unwrap();
+        assert!(biscuit3.is_sealed());
+        assert_eq!(
+            biscuit3.append(BlockBuilder::new()).unwrap_err(),
+            Token::AlreadySealed
+        );
+
+        let sealed_biscuit = SealedBiscuit::from(&sealed, root.public()).unwrap();
+        assert_eq!(sealed_biscuit.container_bytes(), biscuit3.container_bytes());
+        assert_eq!(
+            SealedBiscuit::from(biscuit2.to_vec().unwrap(), root.public()).unwrap_err(),
+            Token::NotSealed
+        );
+
+        {
+            let mut authorizer = Authorizer::new();
+            authorizer.add_fact("resource(\"/folder1/file1\")").unwrap();
+            authorizer.add_fact("operation(\"read\")").unwrap();
+            authorizer.allow().unwrap();
+
+            sealed_biscuit.authorize(&authorizer).unwrap();
+        }
 
         {
             let mut authorizer = biscuit3.authorizer().unwrap();
@@ -1079,7 +1703,9 @@ mod tests {
             Err(Token::FailedLogic(Logic::NoMatchingPolicy {
                 checks: vec![FailedCheck::Authorizer(FailedAuthorizerCheck {
                     check_id: 0,
-                    rule: String::from("check if right(\"file2\", \"write\")")
+                    rule: String::from("check if right(\"file2\", \"write\")"),
+                    name: None,
+                    reason: None,
                 }),]
             }))
         );
@@ -1213,6 +1839,8 @@ mod tests {
                         block_id: 0,
                         check_id: 0,
                         rule: String::from("check if resource(\"hello\")"),
+                        token_id: 0,
+                        reason: None,
                     }),]
                 }))
             );
@@ -1245,6 +1873,7 @@ mod tests {
                     block_id: 0,
                     check_id: 0,
                     rule: String::from("check if name($name)"),
+                    reason: None,
                 }),
             ])))
         );
@@ -1442,9 +2071,1153 @@ mod tests {
                         block_id: 0,
                         check_id: 0,
                         rule: String::from("check all fact($v), $v < 1"),
+                        token_id: 0,
+                        reason: None,
                     }),]
                 }))
             );
         }
     }
+
+    #[test]
+    fn block_bytes() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+
+        let mut block2 = BlockBuilder::new();
+        block2.add_check("check if resource(\"file1\")").unwrap();
+        let keypair2 = KeyPair::new_with_rng(&mut rng);
+        let biscuit2 = biscuit1.append_with_keypair(&keypair2, block2).unwrap();
+
+        let serialized = biscuit2.to_vec().unwrap();
+        assert_eq!(biscuit2.container_bytes(), &serialized[..]);
+
+        let parsed = Biscuit::from(&serialized, root.public()).unwrap();
+        assert_eq!(parsed.container_bytes(), biscuit2.container_bytes());
+
+        for i in 0..biscuit2.block_count() {
+            assert!(biscuit2.block_bytes(i).is_some());
+            assert_eq!(parsed.block_bytes(i), biscuit2.block_bytes(i));
+        }
+        assert_eq!(parsed.block_bytes(2), None);
+
+        let sealed = biscuit2.clone().seal().unwrap();
+        assert_eq!(sealed.container_bytes(), &sealed.to_vec().unwrap()[..]);
+        assert_eq!(sealed.block_bytes(1), biscuit2.block_bytes(1));
+    }
+
+    #[test]
+    fn unknown_predicates() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        builder
+            .add_code("user_role($role) <- role($role); check if rigth($r, \"read\")")
+            .unwrap();
+        assert_eq!(
+            builder.unknown_predicates(),
+            vec![UnknownPredicate {
+                check: "check if rigth($r, \"read\")".to_string(),
+                predicate: "rigth".to_string(),
+            }]
+        );
+
+        let mut strict_builder = builder.clone();
+        strict_builder.set_strict(true);
+        assert!(matches!(
+            strict_builder.build_with_rng(&root, default_symbol_table(), &mut rng),
+            Err(Token::UnknownPredicates(_))
+        ));
+
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+
+        let mut block2 = BlockBuilder::new();
+        block2
+            .add_check("check if resource($r), operation(\"read\"), right($r, \"read\")")
+            .unwrap();
+        block2.add_check("check if user_role(\"admin\")").unwrap();
+        block2
+            .add_check("check if group(\"a\"), team(\"b\")")
+            .unwrap();
+
+        assert_eq!(
+            biscuit1.unknown_predicates(&block2).unwrap(),
+            vec![
+                UnknownPredicate {
+                    check: "check if group(\"a\"), team(\"b\")".to_string(),
+                    predicate: "group".to_string(),
+                },
+                UnknownPredicate {
+                    check: "check if group(\"a\"), team(\"b\")".to_string(),
+                    predicate: "team".to_string(),
+                },
+            ]
+        );
+
+        // warnings do not prevent appending the block
+        biscuit1.append(block2.clone()).unwrap();
+
+        block2.set_strict(true);
+        match biscuit1.append(block2) {
+            Err(Token::UnknownPredicates(unknown)) => assert_eq!(unknown.len(), 2),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn digest() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+
+        let parsed1 = Biscuit::from(biscuit1.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(biscuit1.digest(), parsed1.digest());
+        assert_eq!(biscuit1, parsed1);
+
+        let reparsed1 = Biscuit::from(parsed1.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(parsed1.digest(), reparsed1.digest());
+
+        let mut block2 = BlockBuilder::new();
+        block2.add_check("check if resource(\"file1\")").unwrap();
+        let keypair2 = KeyPair::new_with_rng(&mut rng);
+        let biscuit2 = biscuit1.append_with_keypair(&keypair2, block2).unwrap();
+        assert_ne!(biscuit1.digest(), biscuit2.digest());
+        assert_ne!(biscuit1, biscuit2);
+
+        let sealed = biscuit2.clone().seal().unwrap();
+        assert_ne!(biscuit2.digest(), sealed.digest());
+    }
+
+    #[test]
+    fn attenuate() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        let serialized1 = biscuit1.to_vec().unwrap();
+
+        let biscuit2 = biscuit1
+            .attenuate_with_rng(&mut rng, |block| {
+                block.add_check("check if resource(\"file1\")")?;
+                block.set_context("attenuated".to_string());
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(biscuit1.block_count(), 1);
+        assert_eq!(biscuit1.to_vec().unwrap(), serialized1);
+        assert_eq!(biscuit2.block_count(), 2);
+        assert_eq!(
+            biscuit2.context(),
+            vec![None, Some("attenuated".to_string())]
+        );
+
+        let res = biscuit1.attenuate_with_rng(&mut rng, |block| block.add_check("check if"));
+        assert!(matches!(res, Err(Token::Language(_))));
+
+        // a sealed token parsed as a `Biscuit` still cannot be attenuated
+        let sealed = biscuit2.seal().unwrap().to_vec().unwrap();
+        let sealed = Biscuit::from(&sealed, root.public()).unwrap();
+        assert_eq!(
+            sealed.attenuate(|_| Ok(())).unwrap_err(),
+            Token::AlreadySealed
+        );
+    }
+
+    #[test]
+    fn custom_symbol_table() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut symbols = default_symbol_table();
+        symbols.insert("file1");
+        symbols.insert("file2");
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        builder.add_fact("right(\"file3\", \"read\")").unwrap();
+        let biscuit1 = builder
+            .build_with_rng(&root, symbols.clone(), &mut rng)
+            .unwrap();
+        let biscuit2 = biscuit1
+            .attenuate_with_rng(&mut rng, |block| {
+                block.add_check("check if resource(\"file2\")")
+            })
+            .unwrap();
+        let serialized = biscuit2.to_vec().unwrap();
+
+        let parsed = Biscuit::from_with_symbols(&serialized, root.public(), symbols).unwrap();
+        assert_eq!(
+            parsed.print_block_source(0).unwrap(),
+            biscuit2.print_block_source(0).unwrap()
+        );
+        assert_eq!(
+            parsed.print_block_source(1).unwrap(),
+            biscuit2.print_block_source(1).unwrap()
+        );
+
+        assert_eq!(
+            Biscuit::from(&serialized, root.public()).unwrap_err(),
+            Token::Format(Format::SymbolTableMismatch)
+        );
+
+        let mut other_symbols = default_symbol_table();
+        other_symbols.insert("file2");
+        other_symbols.insert("file1");
+        assert_eq!(
+            Biscuit::from_with_symbols(&serialized, root.public(), other_symbols).unwrap_err(),
+            Token::Format(Format::SymbolTableMismatch)
+        );
+
+        // tokens built with the default symbol table cannot be parsed with a custom one
+        let default_token = Biscuit::builder()
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        let mut custom = default_symbol_table();
+        custom.insert("file1");
+        assert_eq!(
+            Biscuit::from_with_symbols(&default_token.to_vec().unwrap(), root.public(), custom)
+                .unwrap_err(),
+            Token::Format(Format::SymbolTableMismatch)
+        );
+    }
+
+    #[test]
+    fn symbol_table_digest_is_signed() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut symbols = default_symbol_table();
+        symbols.insert("file1");
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        let token = builder
+            .build_with_rng(&root, symbols.clone(), &mut rng)
+            .unwrap();
+        let serialized = token.to_vec().unwrap();
+
+        let tamper = |digest: Option<Vec<u8>>| {
+            let mut container = schema::Biscuit::decode(&serialized[..]).unwrap();
+            let mut authority = schema::Block::decode(&container.authority.block[..]).unwrap();
+            assert!(authority.symbol_table_digest.is_some());
+            authority.symbol_table_digest = digest;
+            container.authority.block = authority.encode_to_vec();
+            container.encode_to_vec()
+        };
+
+        // stripping the digest to parse the token with the default symbol table
+        let stripped = tamper(None);
+        assert!(matches!(
+            Biscuit::from(&stripped, root.public()).unwrap_err(),
+            Token::Format(Format::Signature(_))
+        ));
+
+        let mut other_symbols = default_symbol_table();
+        other_symbols.insert("file2");
+        let changed = tamper(other_symbols.digest());
+        assert!(matches!(
+            Biscuit::from_with_symbols(&changed, root.public(), other_symbols).unwrap_err(),
+            Token::Format(Format::Signature(_))
+        ));
+
+        Biscuit::from_with_symbols(&serialized, root.public(), symbols).unwrap();
+    }
+
+    #[test]
+    fn invalid_predicate_names() {
+        use biscuit_parser::error::NameError;
+
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        // the builder methods validate names, so bypass them to build
+        // a malformed token
+        let mut token = |name: &str| {
+            let mut block = BlockBuilder::new();
+            block.facts.push(fact(name, &[string("file1")]));
+            let mut builder = Biscuit::builder();
+            builder.merge(block).unwrap();
+            builder
+                .build_with_rng(&root, default_symbol_table(), &mut rng)
+                .unwrap()
+                .to_vec()
+                .unwrap()
+        };
+
+        assert_eq!(
+            Biscuit::from(token(""), root.public()).unwrap_err(),
+            Token::Format(Format::InvalidName(NameError::Empty))
+        );
+        assert_eq!(
+            Biscuit::from(token(&"a".repeat(257)), root.public()).unwrap_err(),
+            Token::Format(Format::InvalidName(NameError::TooLong {
+                length: 257,
+                maximum: 256
+            }))
+        );
+        assert_eq!(
+            Biscuit::from(token("right\n"), root.public()).unwrap_err(),
+            Token::Format(Format::InvalidName(NameError::InvalidCharacter {
+                character: '\n',
+                position: 5
+            }))
+        );
+        assert!(Biscuit::from(token(&"a".repeat(256)), root.public()).is_ok());
+    }
+
+    #[test]
+    fn block_refs() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut builder = Biscuit::builder();
+        builder
+            .add_code("right(\"file1\", \"read\"); can_read($f) <- right($f, \"read\")")
+            .unwrap();
+        builder.set_context("authority".to_string());
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        let biscuit2 = biscuit1
+            .attenuate_with_rng(&mut rng, |block| {
+                block.add_code("check if resource(\"file1\"); check if operation(\"read\")")
+            })
+            .unwrap();
+
+        let parsed = Biscuit::from(biscuit2.to_vec().unwrap(), root.public()).unwrap();
+
+        let blocks = parsed.blocks().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(blocks.len(), 2);
+
+        assert_eq!(blocks[0].index(), 0);
+        assert_eq!(blocks[0].context(), Some("authority"));
+        assert_eq!(blocks[0].symbols(), vec!["file1", "can_read", "f"]);
+        let facts = blocks[0]
+            .facts()
+            .map(|f| f.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(facts, vec!["right(\"file1\", \"read\")"]);
+        let rules = blocks[0].rules().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].to_string(), "can_read($f) <- right($f, \"read\")");
+        assert_eq!(blocks[0].checks().count(), 0);
+
+        assert_eq!(blocks[1].index(), 1);
+        assert_eq!(blocks[1].context(), None);
+        assert_eq!(blocks[1].facts().count(), 0);
+        let checks = blocks[1]
+            .checks()
+            .map(|c| c.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            vec![
+                "check if resource(\"file1\")",
+                "check if operation(\"read\")"
+            ]
+        );
+        assert_eq!(
+            blocks[1].print_source(),
+            parsed.print_block_source(1).unwrap()
+        );
+    }
+
+    #[test]
+    fn expiration() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+        let date1 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let date2 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        assert_eq!(biscuit1.expiration(), None);
+
+        let biscuit2 = biscuit1
+            .attenuate_with_rng(&mut rng, |block| {
+                block.check_expiration_date(date1);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(biscuit2.expiration(), Some(date1));
+
+        let biscuit3 = biscuit2
+            .attenuate_with_rng(&mut rng, |block| {
+                block.add_check("check if time($t), resource(\"file1\")")?;
+                block.add_check(
+                    "check if time($t), 2020-09-13T12:26:40Z >= $t or time($t), $t < 2021-01-01T00:00:00Z",
+                )
+            })
+            .unwrap();
+        let parsed = Biscuit::from(biscuit3.to_vec().unwrap(), root.public()).unwrap();
+        assert_eq!(
+            parsed.expiration(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_609_459_200))
+        );
+
+        let biscuit4 = parsed
+            .attenuate_with_rng(&mut rng, |block| {
+                block.check_expiration_date(date2);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(biscuit4.expiration(), Some(date2));
+
+        // dates that do not fit in a SystemTime are skipped
+        let biscuit5 = biscuit4
+            .attenuate_with_rng(&mut rng, |block| {
+                let mut check: builder::Check =
+                    "check if time($t), $t <= 2030-01-01T00:00:00Z".try_into()?;
+                check.queries[0].expressions[0].ops[1] =
+                    builder::Op::Value(builder::Term::Date(u64::MAX));
+                block.add_check(check)
+            })
+            .unwrap();
+        assert_eq!(biscuit5.expiration(), Some(date2));
+    }
+
+    #[test]
+    fn rights() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut builder = Biscuit::builder();
+        builder
+            .add_code(
+                r#"right("file1", "read");
+                right("file2", "write");
+                right("file3", 1);
+                right("file4");
+                owner("alice", "file1");
+                "#,
+            )
+            .unwrap();
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        let biscuit2 = biscuit1
+            .attenuate_with_rng(&mut rng, |block| {
+                block.add_fact("right(\"file5\", \"read\")")
+            })
+            .unwrap();
+        let parsed = Biscuit::from(biscuit2.to_vec().unwrap(), root.public()).unwrap();
+
+        let rights = parsed.rights().unwrap();
+        assert_eq!(
+            rights.rights,
+            vec![
+                ("file1".to_string(), "read".to_string()),
+                ("file2".to_string(), "write".to_string()),
+            ]
+        );
+        assert_eq!(rights.warnings.len(), 2);
+        assert!(rights.warnings[0].starts_with("skipped fact right(\"file3\", 1)"));
+        assert!(rights.warnings[1].starts_with("skipped fact right(\"file4\")"));
+
+        let owners = parsed.authority_facts_named("owner").unwrap();
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].to_string(), "owner(\"alice\", \"file1\")");
+        assert!(parsed.authority_facts_named("unknown").unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(not(feature = "unsafe_debug"))]
+    fn debug_redacts_private_keys() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+        let secret = root.private().to_bytes_hex();
+
+        let keypair_debug = format!("{:?}", root);
+        assert!(!keypair_debug.contains(&secret));
+        assert!(keypair_debug.contains(&hex::encode(root.public().to_bytes())));
+        assert!(keypair_debug.contains("<private key redacted>"));
+        assert!(!format!("{:?}", root.private()).contains(&secret));
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        assert!(!format!("{:?}", builder).contains(&secret));
+
+        let biscuit = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        let next_secret = biscuit.container.proof.keypair().unwrap().private();
+        let biscuit_debug = format!("{:?}", biscuit);
+        assert!(!biscuit_debug.contains(&secret));
+        assert!(!biscuit_debug.contains(&next_secret.to_bytes_hex()));
+        // the serialized token contains the next private key
+        let raw_secret = format!("{:?}", next_secret.to_bytes());
+        assert!(!biscuit_debug.contains(raw_secret.trim_matches(|c| c == '[' || c == ']')));
+        assert!(!biscuit_debug.contains(&format!("{:?}", biscuit.container.bytes)));
+    }
+
+    #[test]
+    fn error_report() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        let keypair2 = KeyPair::new_with_rng(&mut rng);
+        let mut block2 = BlockBuilder::new();
+        block2.add_check("check if operation(\"read\")").unwrap();
+        let biscuit2 = biscuit1.append_with_keypair(&keypair2, block2).unwrap();
+
+        let mut authorizer = biscuit2.authorizer().unwrap();
+        authorizer.add_fact("operation(\"write\")").unwrap();
+        authorizer.allow().unwrap();
+        let report = authorizer.authorize().unwrap_err().to_report();
+
+        assert_eq!(report.category, ErrorCategory::Authorization);
+        assert_eq!(
+            report.failed_checks,
+            vec![FailedCheckReport {
+                block_id: Some(1),
+                check_id: 0,
+                rule: "check if operation(\"read\")".to_string(),
+            }]
+        );
+        assert!(report.message.starts_with("authorization failed: "));
+
+        let secrets = [
+            root.private().to_bytes_hex(),
+            keypair2.private().to_bytes_hex(),
+            biscuit2
+                .container
+                .proof
+                .keypair()
+                .unwrap()
+                .private()
+                .to_bytes_hex(),
+        ];
+        let printed = format!("{:?}", report);
+        for secret in secrets.iter() {
+            assert!(!printed.contains(secret.as_str()));
+        }
+
+        #[cfg(feature = "serde-error")]
+        {
+            let json = serde_json::to_value(&report).unwrap();
+            assert_eq!(json["category"], "Authorization");
+            assert_eq!(json["failed_checks"][0]["block_id"], 1);
+            let json = json.to_string();
+            for secret in secrets.iter() {
+                assert!(!json.contains(secret.as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_tokens() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        builder
+            .add_check("check if time($t), $t < 2030-01-01T00:00:00Z")
+            .unwrap();
+        builder.add_fact(fact("empty", &[string("")])).unwrap();
+        let biscuit1 = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        let mut block2 = BlockBuilder::new();
+        block2.add_check("check if operation(\"read\")").unwrap();
+        let keypair2 = KeyPair::new_with_rng(&mut rng);
+        let serialized = biscuit1
+            .append_with_keypair(&keypair2, block2)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+
+        let mut inputs = vec![vec![], vec![0], vec![0xff; 64]];
+        for i in 0..serialized.len() {
+            inputs.push(serialized[..i].to_vec());
+
+            let mut modified = serialized.clone();
+            modified[i] ^= 0xff;
+            inputs.push(modified);
+        }
+
+        for input in inputs {
+            let res = std::panic::catch_unwind(|| {
+                if let Ok(token) = Biscuit::from(&input, root.public()) {
+                    let mut authorizer = token.authorizer()?;
+                    authorizer.allow()?;
+                    authorizer.authorize()?;
+                    token.print();
+                }
+                if let Ok(token) = unverified::UnverifiedBiscuit::from(&input) {
+                    for i in 0..token.block_count() {
+                        let _ = token.print_block_source(i);
+                    }
+                }
+                let _ = Authorizer::from(&input);
+                Ok::<(), Token>(())
+            });
+            assert!(res.is_ok(), "panic on input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn invalid_expression_stack() {
+        use crate::format::schema;
+        use prost::Message;
+
+        // found by fuzzing: a correctly signed block with an expression that
+        // does not leave exactly one value on the stack
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+        let biscuit1 = Biscuit::builder()
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+
+        let block = schema::Block {
+            symbols: vec!["fuzz".to_string()],
+            context: None,
+            version: Some(MIN_SCHEMA_VERSION),
+            facts_v2: vec![],
+            rules_v2: vec![],
+            checks_v2: vec![schema::CheckV2 {
+                queries: vec![schema::RuleV2 {
+                    head: schema::PredicateV2 {
+                        name: 1024,
+                        terms: vec![],
+                    },
+                    body: vec![],
+                    expressions: vec![schema::ExpressionV2 {
+                        ops: vec![schema::Op {
+                            content: Some(schema::op::Content::Binary(schema::OpBinary {
+                                kind: schema::op_binary::Kind::And as i32,
+                            })),
+                        }],
+                    }],
+                    scope: vec![],
+                }],
+                kind: None,
+            }],
+            scope: vec![],
+            public_keys: vec![],
+            created_at: None,
+            symbol_table_digest: None,
+        };
+        let mut data = Vec::new();
+        block.encode(&mut data).unwrap();
+
+        let keypair2 = KeyPair::new_with_rng(&mut rng);
+        let serialized = biscuit1
+            .container
+            .append_serialized(&keypair2, data, None)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+
+        let token = Biscuit::from(&serialized, root.public()).unwrap();
+        assert!(token.print().contains("<invalid expression"));
+
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer.allow().unwrap();
+        assert!(authorizer.authorize().is_err());
+        assert!(authorizer.dump_code().contains("<invalid expression"));
+    }
+
+    #[test]
+    fn reader_writer() {
+        use std::io::Read;
+
+        // yields the data in small chunks, like a socket
+        struct Chunks<'a>(&'a [u8]);
+
+        impl Read for Chunks<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = self.0.len().min(buf.len()).min(7);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_right("file1", "read");
+        let token = builder
+            .build(&root)
+            .unwrap()
+            .attenuate(|block| block.add_check(r#"check if resource("file1")"#))
+            .unwrap();
+
+        let mut stream = vec![0xff; 3];
+        let written = token.to_writer(&mut stream).unwrap();
+        let bytes = token.to_vec().unwrap();
+        assert_eq!(written, 4 + bytes.len());
+        assert_eq!(&stream[3..7], &(bytes.len() as u32).to_be_bytes());
+        assert_eq!(&stream[7..], token.container_bytes());
+
+        // the data following the token is left in the reader
+        let second = Biscuit::builder().build(&root).unwrap();
+        second.to_writer(&mut stream).unwrap();
+        stream.extend_from_slice(b"trailing");
+        let mut reader = Chunks(&stream[3..]);
+        let parsed = Biscuit::from_reader(&mut reader, root.public()).unwrap();
+        assert_eq!(parsed, token);
+        let parsed = Biscuit::from_reader(&mut reader, root.public()).unwrap();
+        assert_eq!(parsed, second);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"trailing");
+
+        assert!(Biscuit::from_reader(Chunks(&stream), root.public()).is_err());
+        assert!(Biscuit::from_reader(Chunks(&stream[3..written]), root.public()).is_err());
+
+        let mut large = ((MAX_TOKEN_SIZE + 1) as u32).to_be_bytes().to_vec();
+        large.extend(vec![0; MAX_TOKEN_SIZE + 1]);
+        assert_eq!(
+            Biscuit::from_reader(&large[..], root.public()).unwrap_err(),
+            Token::Format(Format::DeserializationError(format!(
+                "deserialization error: the token is larger than {} bytes",
+                MAX_TOKEN_SIZE
+            )))
+        );
+    }
+
+    #[test]
+    fn verified_cache() {
+        use sha2::{Digest, Sha256};
+
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder.add_right("file1", "read");
+        let data = builder.build(&root).unwrap().to_vec().unwrap();
+
+        let token = Biscuit::from(&data, root.public()).unwrap();
+        let key: [u8; 32] = Sha256::digest(&data).into();
+        assert_eq!(token.cache_key(), key);
+        assert_eq!(Biscuit::cache_key_for(&data), key);
+
+        let assumed = Biscuit::assume_verified(&data).unwrap();
+        assert_eq!(assumed, token);
+        assert_eq!(assumed.cache_key(), key);
+        assert!(assumed.check_signatures_only(root.public()).is_ok());
+        assert!(assumed
+            .check_signatures_only(KeyPair::new().public())
+            .is_err());
+
+        // a forged token is parsed, but its signatures do not verify
+        let mut container = token.container.clone();
+        let mut block = schema::Block::decode(&container.authority.data[..]).unwrap();
+        block.symbols.push("forged".to_string());
+        container.authority.data = block.encode_to_vec();
+        let forged = container.to_vec().unwrap();
+        let assumed = Biscuit::assume_verified(&forged).unwrap();
+        assert_ne!(assumed.cache_key(), key);
+        assert_eq!(assumed.cache_key(), Biscuit::cache_key_for(&forged));
+        assert!(assumed.check_signatures_only(root.public()).is_err());
+        assert!(Biscuit::from(&forged, root.public()).is_err());
+    }
+
+    #[test]
+    fn append_preserves_block_bytes() {
+        use crate::format::schema;
+        use prost::Message;
+
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+        let biscuit1 = Biscuit::builder()
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+
+        // a valid block with its fields in a different order than the one
+        // used by our serializer
+        let block = schema::Block {
+            symbols: vec!["file1".to_string()],
+            context: None,
+            version: Some(MIN_SCHEMA_VERSION),
+            facts_v2: vec![],
+            rules_v2: vec![],
+            checks_v2: vec![],
+            scope: vec![],
+            public_keys: vec![],
+            created_at: None,
+            symbol_table_digest: None,
+        };
+        let mut data = Vec::new();
+        schema::Block {
+            symbols: vec![],
+            ..block.clone()
+        }
+        .encode(&mut data)
+        .unwrap();
+        schema::Block {
+            version: None,
+            ..block.clone()
+        }
+        .encode(&mut data)
+        .unwrap();
+        assert_eq!(schema::Block::decode(&data[..]).unwrap(), block);
+        assert_ne!(block.encode_to_vec(), data);
+
+        let keypair2 = KeyPair::new_with_rng(&mut rng);
+        let serialized = biscuit1
+            .container
+            .append_serialized(&keypair2, data.clone(), None)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+
+        let biscuit2 = Biscuit::from(&serialized, root.public()).unwrap();
+        let mut block3 = BlockBuilder::new();
+        block3.check_resource("file1");
+        let serialized = biscuit2.append(block3).unwrap().to_vec().unwrap();
+
+        let biscuit3 = Biscuit::from(&serialized, root.public()).unwrap();
+        assert_eq!(biscuit3.block_count(), 3);
+        assert_eq!(
+            biscuit3.container.authority.data,
+            biscuit1.container.authority.data
+        );
+        assert_eq!(biscuit3.container.blocks[0].data, data);
+    }
+
+    #[test]
+    fn empty_rule_body() {
+        use crate::format::schema;
+        use prost::Message;
+
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+        let biscuit1 = Biscuit::builder()
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+
+        let empty_query = schema::RuleV2 {
+            head: schema::PredicateV2 {
+                name: 1024,
+                terms: vec![],
+            },
+            body: vec![],
+            expressions: vec![],
+            scope: vec![],
+        };
+        let append = |block: schema::Block| {
+            let mut data = Vec::new();
+            block.encode(&mut data).unwrap();
+            let keypair2 = KeyPair::new();
+            let serialized = biscuit1
+                .container
+                .append_serialized(&keypair2, data, None)
+                .unwrap()
+                .to_vec()
+                .unwrap();
+            Biscuit::from(&serialized, root.public())
+        };
+        let block = schema::Block {
+            symbols: vec!["empty".to_string()],
+            context: None,
+            version: Some(MIN_SCHEMA_VERSION),
+            facts_v2: vec![],
+            rules_v2: vec![],
+            checks_v2: vec![schema::CheckV2 {
+                queries: vec![empty_query.clone()],
+                kind: None,
+            }],
+            scope: vec![],
+            public_keys: vec![],
+            created_at: None,
+            symbol_table_digest: None,
+        };
+
+        assert_eq!(
+            append(block.clone()).unwrap_err(),
+            Token::Format(Format::EmptyRuleBody("check 0".to_string()))
+        );
+        assert_eq!(
+            append(schema::Block {
+                rules_v2: vec![empty_query],
+                checks_v2: vec![],
+                ..block
+            })
+            .unwrap_err(),
+            Token::Format(Format::EmptyRuleBody("rule empty".to_string()))
+        );
+    }
+
+    #[test]
+    fn third_party_signed() {
+        let root = KeyPair::new();
+        let external = KeyPair::new();
+
+        let mut builder = Biscuit::builder();
+        builder.add_fact("right(\"read\")").unwrap();
+        let biscuit1 = builder.build(&root).unwrap();
+
+        let mut block = BlockBuilder::new();
+        block.add_fact("fraud_score(1)").unwrap();
+        let biscuit2 = biscuit1
+            .append_third_party_signed(&external, block)
+            .unwrap();
+        assert_eq!(
+            biscuit2.external_public_keys(),
+            vec![None, Some(external.public())]
+        );
+
+        let biscuit2 = Biscuit::from(biscuit2.to_vec().unwrap(), root.public()).unwrap();
+        let authorize = |check: &str| {
+            let mut authorizer = biscuit2.authorizer().unwrap();
+            let mut scope_params = HashMap::new();
+            scope_params.insert("external".to_string(), external.public());
+            authorizer
+                .add_code_with_params(check, HashMap::new(), scope_params)
+                .unwrap();
+            authorizer.add_policy("allow if true").unwrap();
+            authorizer.authorize().is_ok()
+        };
+        // the third-party facts are only visible when trusting its key
+        assert!(authorize(
+            "check if fraud_score($s), $s < 10 trusting {external};"
+        ));
+        assert!(!authorize("check if fraud_score($s), $s < 10;"));
+    }
+
+    #[test]
+    fn authority_rules_ignore_later_blocks() {
+        let root = KeyPair::new();
+        let mut builder = Biscuit::builder();
+        builder
+            .add_rule("can_read($file) <- right($file, \"read\")")
+            .unwrap();
+        builder.add_fact("right(\"file1\", \"read\")").unwrap();
+        let biscuit1 = builder.build(&root).unwrap();
+
+        // an attenuation block cannot feed facts to the authority rule
+        let biscuit2 = biscuit1
+            .attenuate(|block| block.add_fact("right(\"file2\", \"read\")"))
+            .unwrap();
+
+        let can_read = |file: &str| {
+            let mut authorizer = biscuit2.authorizer().unwrap();
+            authorizer
+                .add_code(format!("allow if can_read(\"{}\")", file))
+                .unwrap();
+            authorizer.authorize().is_ok()
+        };
+        assert!(can_read("file1"));
+        assert!(!can_read("file2"));
+
+        // the rule was not applied to the block's fact, even if a query
+        // trusting all blocks can see it
+        let mut authorizer = biscuit2.authorizer().unwrap();
+        authorizer.add_code("allow if true").unwrap();
+        authorizer.authorize().unwrap();
+        let facts: Vec<(String,)> = authorizer
+            .query_all("data($file) <- can_read($file)")
+            .unwrap();
+        assert_eq!(facts, vec![("file1".to_string(),)]);
+    }
+
+    #[test]
+    fn attenuable_template() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let root = KeyPair::new_with_rng(&mut rng);
+
+        let mut builder = Biscuit::builder();
+        builder.add_right("file1", "read");
+        builder.add_right("file2", "read");
+        let authority = builder
+            .build_with_rng(&root, default_symbol_table(), &mut rng)
+            .unwrap();
+        assert!(authority.is_attenuable());
+        let template = authority.to_vec().unwrap();
+
+        // another service parses the template and appends its block
+        let parsed = Biscuit::from_slice_attenuable(&template, root.public()).unwrap();
+        let keypair = KeyPair::new_with_rng(&mut rng);
+        let mut block = BlockBuilder::new();
+        block.check_resource("file1");
+        let remote = parsed
+            .append_with_keypair(&keypair, block.clone())
+            .unwrap()
+            .to_vec()
+            .unwrap();
+        let local = authority
+            .append_with_keypair(&keypair, block)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+        assert_eq!(remote, local);
+
+        let token = Biscuit::from(&remote, root.public()).unwrap();
+        let mut authorizer = token.authorizer().unwrap();
+        authorizer.add_fact(r#"resource("file1")"#).unwrap();
+        authorizer.allow().unwrap();
+        authorizer.authorize().unwrap();
+
+        let sealed = token.seal().unwrap().to_vec().unwrap();
+        assert!(!Biscuit::from(&sealed, root.public())
+            .unwrap()
+            .is_attenuable());
+        assert_eq!(
+            Biscuit::from_slice_attenuable(&sealed, root.public()).unwrap_err(),
+            Token::AppendOnSealed
+        );
+    }
+
+    #[test]
+    fn block_created_at() {
+        let root = KeyPair::new();
+        let created_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+        let before = SystemTime::now() - Duration::from_secs(1);
+        let biscuit1 = Biscuit::builder().build(&root).unwrap();
+        let mut block = BlockBuilder::new();
+        block.set_created_at(Some(created_at));
+        let biscuit2 = biscuit1.append(block).unwrap();
+        let mut block = BlockBuilder::new();
+        block.set_created_at(None);
+        let biscuit3 = biscuit2.append(block).unwrap();
+
+        let token = Biscuit::from(biscuit3.to_vec().unwrap(), root.public()).unwrap();
+        assert!(token.block_created_at(0).unwrap().unwrap() >= before);
+        assert_eq!(token.block_created_at(1).unwrap(), Some(created_at));
+        assert_eq!(token.block_created_at(2).unwrap(), None);
+
+        assert!(token.block_created_at(3).is_err());
+        assert_eq!(
+            token.blocks().nth(1).unwrap().unwrap().created_at(),
+            Some(created_at)
+        );
+        assert!(token.print().contains("created at: 2020-09-13T12:26:40Z"));
+
+        // a time out of the range of SystemTime is ignored
+        let mut overflow = token.clone();
+        overflow.blocks[1].created_at = Some(u64::MAX);
+        assert_eq!(overflow.block_created_at(2).unwrap(), None);
+        assert_eq!(
+            overflow.blocks().nth(2).unwrap().unwrap().created_at(),
+            None
+        );
+        assert!(overflow.print().contains("created at: "));
+
+        // the creation time is signed
+        let mut container = token.container.clone();
+        let mut block = schema::Block::decode(&container.blocks[0].data[..]).unwrap();
+        block.created_at = Some(1_700_000_000);
+        container.blocks[0].data = block.encode_to_vec();
+        let tampered = container.to_vec().unwrap();
+        assert!(Biscuit::from(&tampered, root.public()).is_err());
+    }
+
+    #[test]
+    fn print_block() {
+        let root = KeyPair::new();
+        let external = KeyPair::new();
+
+        let mut builder = Biscuit::builder();
+        builder
+            .add_code(r#"right("file1", "read"); can_read($f) <- right($f, "read");"#)
+            .unwrap();
+        builder.set_created_at(Some(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+        ));
+        let biscuit1 = builder.build(&root).unwrap();
+        let biscuit2 = biscuit1
+            .attenuate(|block| {
+                block.set_created_at(None);
+                block.add_check(r#"check if resource("file1")"#)
+            })
+            .unwrap();
+        let mut block = BlockBuilder::new();
+        block.add_fact(r#"department("sales")"#).unwrap();
+        let biscuit3 = biscuit2
+            .append_third_party_signed(&external, block)
+            .unwrap();
+
+        assert_eq!(
+            biscuit3.print_block(0).unwrap(),
+            r#"authority block:
+    symbols: [1024: "file1", 1025: "can_read", 1026: "f"]
+    version: 3
+    created at: 2020-09-13T12:26:40Z
+    context: ""
+    public keys: []
+    scopes: []
+    facts: [
+        right("file1", "read")
+    ]
+    rules: [
+        can_read($f) <- right($f, "read")
+    ]
+    checks: []
+"#
+        );
+        assert_eq!(
+            biscuit3.print_block(1).unwrap(),
+            r#"block 1:
+    symbols: []
+    version: 3
+    context: ""
+    public keys: []
+    scopes: []
+    facts: []
+    rules: []
+    checks: [
+        check if resource("file1")
+    ]
+"#
+        );
+        let block2 = biscuit3.print_block(2).unwrap();
+        assert!(block2.starts_with(
+            "block 2 (third party):\n    symbols: [1024: \"department\", 1025: \"sales\"]\n"
+        ));
+        assert!(block2.contains(&format!("external key: {}\n", external.public().print())));
+        assert_eq!(biscuit3.print_block(3), None);
+
+        let printed = biscuit3.print();
+        for i in 0..3 {
+            let block = biscuit3.print_block(i).unwrap();
+            let indented: Vec<_> = block.lines().map(|line| format!("    {}", line)).collect();
+            assert!(printed.contains(&indented.join("\n")));
+        }
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Biscuit>();
+        assert_send_sync::<unverified::UnverifiedBiscuit>();
+        assert_send_sync::<SealedBiscuit>();
+        assert_send_sync::<Block>();
+        assert_send_sync::<KeyPair>();
+        assert_send_sync::<PublicKey>();
+        assert_send_sync::<crate::PrivateKey>();
+        assert_send_sync::<Authorizer>();
+        assert_send_sync::<BiscuitBuilder>();
+        assert_send_sync::<BlockBuilder>();
+        assert_send_sync::<ThirdPartyRequest>();
+        assert_send_sync::<ThirdPartyBlock>();
+        assert_send_sync::<crate::error::Token>();
+    }
 }
@@ -0,0 +1,199 @@
+//! expected arity and term kinds of predicates
+//!
+//! a predicate used with different arities or term types in different blocks
+//! or services never matches, without error. A [`Schema`] lists the expected
+//! shape of some predicates, to detect those mismatches when building blocks
+//! (see [`BlockBuilder::with_schema`]) or when receiving tokens (see
+//! [`Schema::check_token`] and [`Authorizer::set_schema`](crate::Authorizer::set_schema)).
+use std::collections::HashMap;
+use std::fmt;
+
+use super::builder::{BlockBuilder, Check, Fact, Predicate, Rule, Term};
+use super::Biscuit;
+use crate::error::{self, SchemaMismatch, SchemaViolation};
+
+/// the kind of a term expected at a position of a predicate
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TermKind {
+    Integer,
+    String,
+    Date,
+    Bytes,
+    Bool,
+    Set,
+    /// any kind of term is accepted
+    Any,
+}
+
+impl TermKind {
+    /// kind of a term, `None` for variables and parameters
+    pub fn of(term: &Term) -> Option<TermKind> {
+        match term {
+            Term::Integer(_) => Some(TermKind::Integer),
+            Term::Str(_) => Some(TermKind::String),
+            Term::Date(_) => Some(TermKind::Date),
+            Term::Bytes(_) => Some(TermKind::Bytes),
+            Term::Bool(_) => Some(TermKind::Bool),
+            Term::Set(_) => Some(TermKind::Set),
+            Term::Variable(_) | Term::Parameter(_) => None,
+        }
+    }
+
+    fn accepts(&self, kind: TermKind) -> bool {
+        *self == TermKind::Any || *self == kind
+    }
+}
+
+impl fmt::Display for TermKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TermKind::Integer => "integer",
+            TermKind::String => "string",
+            TermKind::Date => "date",
+            TermKind::Bytes => "bytes",
+            TermKind::Bool => "bool",
+            TermKind::Set => "set",
+            TermKind::Any => "any",
+        };
+        f.write_str(name)
+    }
+}
+
+/// expected arity and term kinds of predicates, by name
+///
+/// predicates that are not in the schema are not checked
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schema {
+    predicates: HashMap<String, Vec<TermKind>>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    /// schema of the predicates created by the helpers of this library:
+    /// `right(string, string)`, `resource(string)`, `operation(string)`,
+    /// `time(date)`, `revocation_id(any)`, `client_ip(string)`, `nonce(bytes)`
+    /// and `issued_at(date)`
+    pub fn well_known() -> Self {
+        let mut schema = Schema::new();
+        schema.insert("right", &[TermKind::String, TermKind::String]);
+        schema.insert("resource", &[TermKind::String]);
+        schema.insert("operation", &[TermKind::String]);
+        schema.insert("time", &[TermKind::Date]);
+        // integers or UUIDs
+        schema.insert("revocation_id", &[TermKind::Any]);
+        schema.insert("client_ip", &[TermKind::String]);
+        schema.insert("nonce", &[TermKind::Bytes]);
+        schema.insert("issued_at", &[TermKind::Date]);
+        schema
+    }
+
+    /// sets the expected terms of a predicate, replacing the previous ones
+    pub fn insert(&mut self, name: &str, terms: &[TermKind]) {
+        self.predicates.insert(name.to_string(), terms.to_vec());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[TermKind]> {
+        self.predicates.get(name).map(|terms| terms.as_slice())
+    }
+
+    /// checks a predicate, variables and parameters match any kind of term
+    pub fn check_predicate(&self, predicate: &Predicate) -> Option<SchemaMismatch> {
+        let expected = self.get(&predicate.name)?;
+
+        if expected.len() != predicate.terms.len() {
+            return Some(SchemaMismatch::Arity {
+                expected: expected.len(),
+                actual: predicate.terms.len(),
+            });
+        }
+
+        expected
+            .iter()
+            .zip(predicate.terms.iter())
+            .enumerate()
+            .find_map(|(position, (expected, term))| match TermKind::of(term) {
+                Some(actual) if !expected.accepts(actual) => Some(SchemaMismatch::TermKind {
+                    position,
+                    expected: expected.to_string(),
+                    actual: actual.to_string(),
+                }),
+                _ => None,
+            })
+    }
+
+    /// checks the facts, rules and checks of a block
+    pub fn check_block(&self, block: &BlockBuilder) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+
+        for fact in &block.facts {
+            violations.extend(self.check_fact(fact));
+        }
+        for rule in &block.rules {
+            violations.extend(self.check_rule(rule));
+        }
+        for check in &block.checks {
+            violations.extend(self.check_check(check));
+        }
+
+        violations
+    }
+
+    pub(crate) fn check_fact(&self, fact: &Fact) -> Vec<SchemaViolation> {
+        self.check_element(&fact.to_string(), [&fact.predicate])
+    }
+
+    pub(crate) fn check_rule(&self, rule: &Rule) -> Vec<SchemaViolation> {
+        let predicates = std::iter::once(&rule.head).chain(rule.body.iter());
+        self.check_element(&rule.to_string(), predicates)
+    }
+
+    pub(crate) fn check_check(&self, check: &Check) -> Vec<SchemaViolation> {
+        let predicates = check.queries.iter().flat_map(|query| query.body.iter());
+        self.check_element(&check.to_string(), predicates)
+    }
+
+    /// checks every block of a token, the violations are usually logged as
+    /// warnings. They can be rejected with [`Authorizer::set_schema`](crate::Authorizer::set_schema)
+    pub fn check_token(&self, token: &Biscuit) -> Result<Vec<SchemaViolation>, error::Token> {
+        let mut violations = Vec::new();
+
+        for i in 0..token.block_count() {
+            let block = token.block(i)?;
+            let symbols = if i == 0 || block.external_key.is_none() {
+                &token.symbols
+            } else {
+                &block.symbols
+            };
+            let block = BlockBuilder::convert_from(&block, symbols)?;
+
+            violations.extend(self.check_block(&block).into_iter().map(|mut violation| {
+                violation.block_id = Some(i);
+                violation
+            }));
+        }
+
+        Ok(violations)
+    }
+
+    fn check_element<'a>(
+        &self,
+        element: &str,
+        predicates: impl IntoIterator<Item = &'a Predicate>,
+    ) -> Vec<SchemaViolation> {
+        predicates
+            .into_iter()
+            .filter_map(|predicate| {
+                self.check_predicate(predicate)
+                    .map(|mismatch| SchemaViolation {
+                        block_id: None,
+                        element: element.to_string(),
+                        predicate: predicate.name.clone(),
+                        mismatch,
+                    })
+            })
+            .collect()
+    }
+}
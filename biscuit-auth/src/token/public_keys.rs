@@ -1,4 +1,5 @@
 use std::collections::HashSet;
+use std::convert::TryFrom;
 
 use crate::{crypto::PublicKey, error};
 
@@ -66,7 +67,7 @@ impl PublicKeys {
     }
 
     pub fn get_key(&self, i: u64) -> Option<&PublicKey> {
-        self.keys.get(i as usize)
+        self.keys.get(usize::try_from(i).ok()?)
     }
 
     pub fn into_inner(self) -> Vec<PublicKey> {
@@ -0,0 +1,134 @@
+//! sealed tokens
+use std::convert::TryFrom;
+
+use std::time::SystemTime;
+
+use super::authorizer::Authorizer;
+use super::{Biscuit, BlockRef, RootKeyProvider};
+use crate::error;
+
+/// a Biscuit token that was sealed: no more blocks can be added to it
+///
+/// It is obtained from [`Biscuit::seal`] or by deserializing a sealed token
+/// with [`SealedBiscuit::from`]. Since it does not provide any attenuation
+/// method, a sealed token cannot be mistakenly treated as an attenuable one.
+///
+/// It does not give access to the underlying [`Biscuit`], only to read-only
+/// accessors. To authorize it with an existing authorizer, use
+/// [`SealedBiscuit::authorize`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SealedBiscuit {
+    inner: Biscuit,
+}
+
+impl SealedBiscuit {
+    /// deserializes a sealed token and validates the signature using the root public key
+    ///
+    /// this will fail if the token is not sealed
+    pub fn from<T, KP>(slice: T, key_provider: KP) -> Result<Self, error::Token>
+    where
+        T: AsRef<[u8]>,
+        KP: RootKeyProvider,
+    {
+        Biscuit::from(slice, key_provider).and_then(SealedBiscuit::try_from)
+    }
+
+    /// deserializes a sealed token and validates the signature using the root public key
+    ///
+    /// this will fail if the token is not sealed
+    pub fn from_base64<T, KP>(slice: T, key_provider: KP) -> Result<Self, error::Token>
+    where
+        T: AsRef<[u8]>,
+        KP: RootKeyProvider,
+    {
+        Biscuit::from_base64(slice, key_provider).and_then(SealedBiscuit::try_from)
+    }
+
+    /// serializes the token
+    pub fn to_vec(&self) -> Result<Vec<u8>, error::Token> {
+        self.inner.to_vec()
+    }
+
+    /// serializes the token and encode it to a (URL safe) base64 string
+    pub fn to_base64(&self) -> Result<String, error::Token> {
+        self.inner.to_base64()
+    }
+
+    /// creates a authorizer from this token
+    pub fn authorizer(&self) -> Result<Authorizer, error::Token> {
+        self.inner.authorizer()
+    }
+
+    /// runs authorization with the provided authorizer
+    pub fn authorize(&self, authorizer: &Authorizer) -> Result<usize, error::Token> {
+        self.inner.authorize(authorizer)
+    }
+
+    /// returns a list of revocation identifiers for each block, in order
+    pub fn revocation_identifiers(&self) -> Vec<Vec<u8>> {
+        self.inner.revocation_identifiers()
+    }
+
+    /// returns the number of blocks (at least 1)
+    pub fn block_count(&self) -> usize {
+        self.inner.block_count()
+    }
+
+    /// pretty printer for this token
+    pub fn print(&self) -> String {
+        self.inner.print()
+    }
+
+    /// prints the Datalog code of a block, see [`Biscuit::print_block_source`]
+    pub fn print_block_source(&self, index: usize) -> Result<String, error::Token> {
+        self.inner.print_block_source(index)
+    }
+
+    /// returns the context of each block, see [`Biscuit::context`]
+    pub fn context(&self) -> Vec<Option<String>> {
+        self.inner.context()
+    }
+
+    /// iterates over the blocks, see [`Biscuit::blocks`]
+    pub fn blocks(&self) -> impl Iterator<Item = Result<BlockRef<'_>, error::Token>> + '_ {
+        self.inner.blocks()
+    }
+
+    /// returns the earliest expiration date of the token, see [`Biscuit::expiration`]
+    pub fn expiration(&self) -> Option<SystemTime> {
+        self.inner.expiration()
+    }
+
+    /// returns the signed bytes of a block, see [`Biscuit::block_bytes`]
+    pub fn block_bytes(&self, index: usize) -> Option<&[u8]> {
+        self.inner.block_bytes(index)
+    }
+
+    /// returns the serialized form of the token, see [`Biscuit::container_bytes`]
+    pub fn container_bytes(&self) -> &[u8] {
+        self.inner.container_bytes()
+    }
+
+    /// returns a hash of the token, see [`Biscuit::digest`]
+    pub fn digest(&self) -> [u8; 32] {
+        self.inner.digest()
+    }
+}
+
+impl TryFrom<Biscuit> for SealedBiscuit {
+    type Error = error::Token;
+
+    fn try_from(token: Biscuit) -> Result<Self, Self::Error> {
+        if token.is_sealed() {
+            Ok(SealedBiscuit { inner: token })
+        } else {
+            Err(error::Token::NotSealed)
+        }
+    }
+}
+
+impl std::fmt::Display for SealedBiscuit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
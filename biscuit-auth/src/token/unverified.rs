@@ -9,6 +9,7 @@ use crate::{
     datalog::SymbolTable,
     error,
     format::{convert::proto_block_to_token_block, schema, SerializedBiscuit},
+    time::Instant,
     token::{ThirdPartyBlockContents, ThirdPartyRequest},
     KeyPair,
 };
@@ -53,7 +54,9 @@ impl UnverifiedBiscuit {
         F: Fn(Option<u32>) -> PublicKey,
     {
         let root = f(self.container.root_key_id);
+        let start = Instant::now();
         self.container.verify(&root)?;
+        let verification_time = start.elapsed();
 
         Ok(Biscuit {
             root_key_id: self.container.root_key_id,
@@ -62,6 +65,8 @@ impl UnverifiedBiscuit {
             symbols: self.symbols,
             public_key_to_block_id: self.public_key_to_block_id,
             container: self.container,
+            verification_time,
+            root_public_key: Some(root),
         })
     }
 
@@ -90,6 +95,7 @@ impl UnverifiedBiscuit {
     /// deserializes from raw bytes with a custom symbol table
     pub fn from_with_symbols(slice: &[u8], mut symbols: SymbolTable) -> Result<Self, error::Token> {
         let container = SerializedBiscuit::deserialize(slice)?;
+        container.check_symbol_table(&symbols)?;
 
         let (authority, blocks, public_key_to_block_id) = container.extract_blocks(&mut symbols)?;
 
@@ -338,7 +344,7 @@ impl UnverifiedBiscuit {
             self.container
                 .append_serialized(&next_keypair, payload, Some(external_signature))?;
 
-        let token_block = proto_block_to_token_block(&block, Some(external_key)).unwrap();
+        let token_block = proto_block_to_token_block(&block, Some(external_key))?;
         for key in &token_block.public_keys.keys {
             symbols.public_keys.insert_fallible(key)?;
         }
@@ -0,0 +1,122 @@
+//! JavaScript bindings, available with the `wasm` feature
+//!
+//! this is a thin layer over [`Biscuit`] using JS friendly types: keys are
+//! hex encoded strings, tokens are URL safe base64 strings, dates are
+//! milliseconds since the UNIX epoch (as returned by `Date.getTime()`),
+//! and errors are thrown as strings
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::builder::BlockBuilder;
+use crate::builder_ext::{AuthorizerExt, BuilderExt};
+use crate::{error, Authorizer, Biscuit, KeyPair, PrivateKey, PublicKey};
+
+/// a Biscuit token
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct JsBiscuit {
+    inner: Biscuit,
+}
+
+/// creates a token from Datalog code, signed with the hex encoded root private key
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn biscuit_from_code(root_private_key: &str, code: &str) -> Result<JsBiscuit, String> {
+    let private = PrivateKey::from_bytes_hex(root_private_key).map_err(to_js_error)?;
+    let root = KeyPair::from(&private);
+
+    let mut builder = Biscuit::builder();
+    builder.add_code(code).map_err(to_js_error)?;
+    let inner = builder.build(&root).map_err(to_js_error)?;
+
+    Ok(JsBiscuit { inner })
+}
+
+/// deserializes a base64 token and verifies its signature with the hex encoded root public key
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn biscuit_from_base64(data: &str, root_public_key: &str) -> Result<JsBiscuit, String> {
+    let root = PublicKey::from_bytes_hex(root_public_key).map_err(to_js_error)?;
+    let inner = Biscuit::from_base64(data, root).map_err(to_js_error)?;
+
+    Ok(JsBiscuit { inner })
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl JsBiscuit {
+    /// serializes the token to a URL safe base64 string
+    pub fn to_base64(&self) -> Result<String, String> {
+        self.inner.to_base64().map_err(to_js_error)
+    }
+
+    /// checks that the token allows accessing `resource` with `operation` at `time`
+    ///
+    /// the token's checks are verified, then the request is allowed
+    pub fn verify(&self, resource: &str, operation: &str, time: f64) -> Result<(), String> {
+        let mut authorizer = Authorizer::new();
+        authorizer.add_token(&self.inner).map_err(to_js_error)?;
+        authorizer.add_resource(resource);
+        authorizer.add_operation(operation);
+        authorizer.set_time_at(from_js_time(time));
+        authorizer.add_allow_all();
+
+        authorizer.authorize().map(|_| ()).map_err(to_js_error)
+    }
+
+    /// creates a new token with a block expiring at `expiration`
+    pub fn attenuate_with_expiration(&self, expiration: f64) -> Result<JsBiscuit, String> {
+        let mut block = BlockBuilder::new();
+        block.check_expiration_date(from_js_time(expiration));
+        let inner = self.inner.append(block).map_err(to_js_error)?;
+
+        Ok(JsBiscuit { inner })
+    }
+
+    /// pretty printer for this token
+    pub fn print(&self) -> String {
+        self.inner.print()
+    }
+}
+
+fn to_js_error<E: Into<error::Token>>(e: E) -> String {
+    e.into().to_report().message
+}
+
+/// converts from milliseconds since the UNIX epoch, clamping negative values
+fn from_js_time(millis: f64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0.0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_attenuate_verify() {
+        let root = KeyPair::new();
+        let token = biscuit_from_code(
+            &root.private().to_bytes_hex(),
+            r#"right("file1", "read"); check if resource($r), operation($op), right($r, $op)"#,
+        )
+        .unwrap();
+
+        let now = 1_600_000_000_000.0;
+        let attenuated = token
+            .attenuate_with_expiration(now + 60_000.0)
+            .unwrap()
+            .to_base64()
+            .unwrap();
+
+        let public = hex::encode(root.public().to_bytes());
+        let token = biscuit_from_base64(&attenuated, &public).unwrap();
+        token.verify("file1", "read", now).unwrap();
+        assert!(token.verify("file1", "write", now).is_err());
+        assert!(token.verify("file1", "read", now + 120_000.0).is_err());
+
+        assert!(biscuit_from_base64(
+            &attenuated,
+            &hex::encode(KeyPair::new().public().to_bytes())
+        )
+        .is_err());
+        assert!(biscuit_from_code("not a key", "").is_err());
+    }
+}
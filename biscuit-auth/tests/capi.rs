@@ -157,4 +157,44 @@ wrote 322 bytes
         .success()
         .stdout("key_pair creation error? (null)\n");
     }
+
+    #[test]
+    fn code_and_time() {
+        (assert_c! {
+            #include <stdio.h>
+            #include <string.h>
+            #include "biscuit_auth.h"
+
+            int main() {
+                char *seed = "abcdefghabcdefghabcdefghabcdefgh";
+
+                KeyPair * root_kp = key_pair_new((const uint8_t *) seed, strlen(seed));
+
+                BiscuitBuilder* b = biscuit_builder();
+                biscuit_builder_add_code(b, "right(\"file1\", \"read\"); check if time($t), $t < 2030-01-01T00:00:00Z");
+                Biscuit * biscuit = biscuit_builder_build(b, root_kp, (const uint8_t * ) seed, strlen(seed));
+
+                Authorizer * authorizer = biscuit_authorizer(biscuit);
+                authorizer_add_code(authorizer, "resource(\"file1\"); allow if resource($r), right($r, \"read\")");
+                authorizer_set_time_at(authorizer, 1600000000);
+                printf("authorized? %d\n", authorizer_authorize(authorizer));
+
+                Authorizer * late = biscuit_authorizer(biscuit);
+                authorizer_add_code(late, "resource(\"file1\"); allow if resource($r), right($r, \"read\")");
+                authorizer_set_time_at(late, 2000000000);
+                printf("late authorized? %d\n", authorizer_authorize(late));
+                printf("overflow? %d\n", authorizer_set_time_at(late, UINT64_MAX));
+
+                authorizer_free(late);
+                authorizer_free(authorizer);
+                biscuit_free(biscuit);
+                biscuit_builder_free(b);
+                key_pair_free(root_kp);
+
+                return 0;
+            }
+        })
+        .success()
+        .stdout("authorized? 1\nlate authorized? 0\noverflow? 0\n");
+    }
 }
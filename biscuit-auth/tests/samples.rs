@@ -0,0 +1,108 @@
+//! runs the conformance samples from the `samples` directory
+//!
+//! The samples are generated with `cargo run --example testcases --features serde-error -- ./samples --json > ./samples/samples.json`
+//! (and without `--json` for the README). Other implementations consume the same
+//! files, so the format of `samples.json` is stable:
+//!
+//! ```text
+//! {
+//!   "root_private_key": hex string,
+//!   "root_public_key": hex string,
+//!   "testcases": [
+//!     {
+//!       "title": string,
+//!       "filename": name of the serialized token, in the samples directory,
+//!       "token": [ { "symbols": [string], "public_keys": [string], "external_key": string | null, "code": string } ],
+//!       "validations": {
+//!         name: {
+//!           "world": { "facts": [string], "rules": [string], "checks": [string], "policies": [string] } | null,
+//!           "result": { "Ok": policy index } | { "Err": serialized error::Token },
+//!           "authorizer_code": string,
+//!           "revocation_ids": [hex string]
+//!         }
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! A validation loads the token with the root public key, creates an authorizer
+//! from `authorizer_code`, adds the token and runs the authorization. If the
+//! token cannot be loaded, `world` is null and the error is in `result`.
+#![cfg(feature = "serde-error")]
+extern crate biscuit_auth as biscuit;
+
+use biscuit::{Authorizer, AuthorizerLimits, Biscuit, PublicKey};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const SAMPLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/samples");
+
+#[test]
+fn samples() {
+    let descriptor = std::fs::read_to_string(format!("{}/samples.json", SAMPLES)).unwrap();
+    let descriptor: Value = serde_json::from_str(&descriptor).unwrap();
+
+    let root = PublicKey::from_bytes_hex(descriptor["root_public_key"].as_str().unwrap()).unwrap();
+
+    let mut failures = Vec::new();
+    let testcases = descriptor["testcases"].as_array().unwrap();
+    assert!(!testcases.is_empty());
+
+    for testcase in testcases {
+        let title = testcase["title"].as_str().unwrap();
+        let filename = testcase["filename"].as_str().unwrap();
+        let data = std::fs::read(format!("{}/{}", SAMPLES, filename)).unwrap();
+
+        for (name, validation) in testcase["validations"].as_object().unwrap() {
+            let (result, revocation_ids) = validate(root, &data, validation);
+
+            if result != validation["result"] {
+                failures.push(format!(
+                    "{} ({}) validation \"{}\": expected {}, got {}",
+                    title, filename, name, validation["result"], result
+                ));
+            }
+
+            if revocation_ids != validation["revocation_ids"] {
+                failures.push(format!(
+                    "{} ({}) validation \"{}\": expected revocation ids {}, got {}",
+                    title, filename, name, validation["revocation_ids"], revocation_ids
+                ));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+/// returns the serialized result and revocation ids, as found in `samples.json`
+fn validate(root: PublicKey, data: &[u8], validation: &Value) -> (Value, Value) {
+    let token = match Biscuit::from(data, root) {
+        Ok(token) => token,
+        Err(e) => return (json!({ "Err": e }), json!([])),
+    };
+
+    let revocation_ids: Vec<String> = token
+        .revocation_identifiers()
+        .iter()
+        .map(hex::encode)
+        .collect();
+
+    let mut authorizer = Authorizer::new();
+    authorizer
+        .add_code(validation["authorizer_code"].as_str().unwrap())
+        .unwrap();
+    // the samples are checked in debug builds too
+    authorizer.set_limits(AuthorizerLimits::default().with_max_time(Duration::from_secs(1)));
+
+    let result = match authorizer
+        .add_token(&token)
+        .and_then(|_| authorizer.authorize())
+    {
+        Ok(i) => json!({ "Ok": i }),
+        Err(e) => json!({ "Err": e }),
+    };
+
+    (result, json!(revocation_ids))
+}
@@ -4,6 +4,8 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
+use crate::error::NameError;
+
 #[cfg(feature = "datalog-macro")]
 use quote::{quote, ToTokens};
 
@@ -404,6 +406,7 @@ impl ToTokens for Check {
           ::biscuit_auth::builder::Check {
             queries: <[::biscuit_auth::builder::Rule]>::into_vec(Box::new([#(#queries),*])),
             kind: #kind,
+            name: None,
           }
         });
     }
@@ -464,6 +467,37 @@ impl ToTokens for Policy {
     }
 }
 
+/// maximum length in bytes of a predicate name
+pub const MAX_NAME_LENGTH: usize = 256;
+
+/// characters allowed in predicate names: ASCII letters and digits, `_` and `:`
+pub fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == ':'
+}
+
+/// checks that a predicate name is not empty, is at most `max_length` bytes
+/// long and only contains characters accepted by [`is_name_char`]
+///
+/// the parser applies the same rules with [`MAX_NAME_LENGTH`]
+pub fn validate_name(name: &str, max_length: usize) -> Result<(), NameError> {
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+    if name.len() > max_length {
+        return Err(NameError::TooLong {
+            length: name.len(),
+            maximum: max_length,
+        });
+    }
+    match name.char_indices().find(|(_, c)| !is_name_char(*c)) {
+        Some((position, character)) => Err(NameError::InvalidCharacter {
+            character,
+            position,
+        }),
+        None => Ok(()),
+    }
+}
+
 /// creates a new fact
 pub fn fact<I: AsRef<Term>>(name: &str, terms: &[I]) -> Fact {
     let pred = pred(name, terms);
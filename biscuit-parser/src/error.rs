@@ -2,6 +2,7 @@ use thiserror::Error;
 
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum LanguageError {
     #[error("datalog parsing error: {0:?}")]
     ParseError(ParseErrors),
@@ -10,16 +11,33 @@ pub enum LanguageError {
         missing_parameters: Vec<String>,
         unused_parameters: Vec<String>,
     },
+    #[error("invalid predicate name: {0}")]
+    InvalidName(NameError),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// reasons a predicate name is rejected, see [`validate_name`](crate::builder::validate_name)
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum NameError {
+    #[error("the name is empty")]
+    Empty,
+    #[error("the name is {length} bytes long, the maximum is {maximum}")]
+    TooLong { length: usize, maximum: usize },
+    #[error("invalid character {character:?} at byte {position}")]
+    InvalidCharacter { character: char, position: usize },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct ParseErrors {
     pub errors: Vec<ParseError>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-error", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct ParseError {
     pub input: String,
     pub message: Option<String>,
@@ -6,7 +6,7 @@ use nom::{
         complete::{char, digit1, multispace0 as space0},
         is_alphanumeric,
     },
-    combinator::{consumed, cut, eof, map, map_res, opt, recognize, value},
+    combinator::{consumed, cut, eof, map, map_res, opt, peek, recognize, value},
     error::{ErrorKind, FromExternalError, ParseError},
     multi::{many0, separated_list0, separated_list1},
     sequence::{delimited, pair, preceded, terminated, tuple},
@@ -30,14 +30,17 @@ pub fn fact(i: &str) -> IResult<&str, builder::Fact, Error> {
 
 pub fn fact_inner(i: &str) -> IResult<&str, builder::Fact, Error> {
     let (i, _) = space0(i)?;
-    let (i, fact_name) = name(i)?;
+    let (i, fact_name) = predicate_name(i)?;
 
     let (i, _) = space0(i)?;
     let (i, terms) = delimited(
         char('('),
-        cut(separated_list1(
-            preceded(space0, char(',')),
-            cut(term_in_fact),
+        alt((
+            value(Vec::new(), peek(preceded(space0, char(')')))),
+            cut(separated_list1(
+                preceded(space0, char(',')),
+                cut(term_in_fact),
+            )),
         )),
         preceded(space0, char(')')),
     )(i)?;
@@ -269,12 +272,15 @@ impl FromStr for builder::Predicate {
 
 fn predicate(i: &str) -> IResult<&str, builder::Predicate, Error> {
     let (i, _) = space0(i)?;
-    let (i, fact_name) = name(i)?;
+    let (i, fact_name) = predicate_name(i)?;
 
     let (i, _) = space0(i)?;
     let (i, terms) = delimited(
         char('('),
-        cut(separated_list1(preceded(space0, char(',')), cut(term))),
+        alt((
+            value(Vec::new(), peek(preceded(space0, char(')')))),
+            cut(separated_list1(preceded(space0, char(',')), cut(term))),
+        )),
         preceded(space0, char(')')),
     )(i)?;
 
@@ -289,12 +295,15 @@ fn predicate(i: &str) -> IResult<&str, builder::Predicate, Error> {
 
 fn rule_head(i: &str) -> IResult<&str, builder::Predicate, Error> {
     let (i, _) = space0(i)?;
-    let (i, fact_name) = name(i)?;
+    let (i, fact_name) = predicate_name(i)?;
 
     let (i, _) = space0(i)?;
     let (i, terms) = delimited(
         char('('),
-        cut(separated_list0(preceded(space0, char(',')), cut(term))),
+        alt((
+            value(Vec::new(), peek(preceded(space0, char(')')))),
+            cut(separated_list1(preceded(space0, char(',')), cut(term))),
+        )),
         preceded(space0, char(')')),
     )(i)?;
 
@@ -674,6 +683,23 @@ fn name(i: &str) -> IResult<&str, &str, Error> {
     reduce(take_while1(is_name_char), " ,:(\n;")(i)
 }
 
+fn predicate_name(i: &str) -> IResult<&str, &str, Error> {
+    let (remaining, predicate_name) = reduce(take_while1(builder::is_name_char), " ,:(\n;")(i)?;
+
+    if predicate_name.len() > builder::MAX_NAME_LENGTH {
+        return Err(nom::Err::Failure(Error {
+            input: predicate_name,
+            code: ErrorKind::Fail,
+            message: Some(format!(
+                "predicate names must be at most {} bytes long",
+                builder::MAX_NAME_LENGTH
+            )),
+        }));
+    }
+
+    Ok((remaining, predicate_name))
+}
+
 fn printable(i: &str) -> IResult<&str, &str, Error> {
     take_while1(|c: char| c != '\\' && c != '"')(i)
 }
@@ -1582,6 +1608,38 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn zero_arity() {
+        let no_terms: &[builder::Term] = &[];
+        assert_eq!(
+            super::fact("emergency( )"),
+            Ok(("", builder::fact("emergency", no_terms)))
+        );
+
+        let (_, rule) = super::rule("alert() <- emergency()").unwrap();
+        assert_eq!(rule.head, builder::pred("alert", no_terms));
+        assert_eq!(rule.body, vec![builder::pred("emergency", no_terms)]);
+        assert!(super::fact("emergency(,)").is_err());
+    }
+
+    #[test]
+    fn predicate_name() {
+        let long = "a".repeat(builder::MAX_NAME_LENGTH);
+        assert!(super::fact(&format!("{}(1)", long)).is_ok());
+
+        let too_long = format!("{}a", long);
+        assert!(builder::validate_name(&too_long, builder::MAX_NAME_LENGTH).is_err());
+        let input = format!("{}(1)", too_long);
+        assert_eq!(
+            super::fact(&input).unwrap_err(),
+            nom::Err::Failure(super::Error {
+                input: &too_long,
+                code: nom::error::ErrorKind::Fail,
+                message: Some("predicate names must be at most 256 bytes long".to_string()),
+            })
+        );
+    }
     /*
     #[test]
     fn rule() {